@@ -0,0 +1,98 @@
+//! Benchmarks for the core filters over several image sizes, run with `cargo bench`. Pass a name
+//! to only run matching benchmarks, e.g. `cargo bench -- median_filter`.
+//!
+//! Each benchmark is run repeatedly for roughly half a second and the minimum and median time
+//! per iteration are reported. Fast functions are run in batches per sample, so the time isn't
+//! dominated by reading the clock.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use ndarray::Array2;
+use rust_image_processing::pixel::{pixel_add, pixel_scale, pixel_shift, pixel_sub};
+use rust_image_processing::{adjust_brightness, adjust_contrast, apply_matrix, edge_detect, image_add, image_sub, linear_blend, median_filter, x_grad, y_grad, ImageBuffer};
+
+const SIZES: &[u32] = &[64, 256, 1024];
+const TARGET: Duration = Duration::from_millis(500);
+const SAMPLE: Duration = Duration::from_micros(100);
+
+struct Bencher {
+    filter: Option<String>,
+}
+
+impl Bencher {
+    fn run<T>(&self, name: &str, mut f: impl FnMut() -> T) {
+        if let Some(filter) = &self.filter {
+            if !name.contains(filter.as_str()) {
+                return;
+            }
+        }
+
+        let calibration_start = Instant::now();
+        black_box(f());
+        let once = calibration_start.elapsed().max(Duration::from_nanos(1));
+        let batch = (SAMPLE.as_nanos() / once.as_nanos()).max(1) as u32;
+
+        let mut samples = Vec::new();
+        let start = Instant::now();
+        while samples.len() < 3 || (start.elapsed() < TARGET && samples.len() < 1000) {
+            let sample_start = Instant::now();
+            for _ in 0..batch {
+                black_box(f());
+            }
+            samples.push(sample_start.elapsed() / batch);
+        }
+
+        samples.sort();
+        println!("{:<40} min {:>12.3?}   median {:>12.3?}   ({} runs)", name, samples[0], samples[samples.len() / 2], samples.len());
+    }
+}
+
+/// A deterministic noisy image, so results don't depend on image content on disk
+fn test_image(size: u32) -> ImageBuffer {
+    image::ImageBuffer::from_fn(size, size, |x, y| {
+        let hash = (x.wrapping_mul(73_856_093) ^ y.wrapping_mul(19_349_663)).wrapping_mul(2_654_435_761);
+        image::Rgba([(hash >> 8) as u8, (hash >> 16) as u8, (hash >> 24) as u8, 255])
+    })
+}
+
+fn box_kernel(size: usize) -> Array2<f32> {
+    Array2::from_elem((size, size), 1. / (size * size) as f32)
+}
+
+fn main() {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let bencher = Bencher { filter };
+
+    for &size in SIZES {
+        let input = test_image(size);
+        let other = test_image(size + 1);
+        let other = image::imageops::crop_imm(&other, 0, 0, size, size).to_image();
+
+        for kernel in [3, 5, 9] {
+            let matrix = box_kernel(kernel);
+            bencher.run(&format!("apply_matrix/{}x{}/{}", kernel, kernel, size), || apply_matrix(&input, matrix.clone()));
+        }
+
+        for window in [1, 2, 3] {
+            bencher.run(&format!("median_filter/{}/{}", window, size), || median_filter(&input, window));
+        }
+
+        bencher.run(&format!("x_grad/{}", size), || x_grad(&input));
+        bencher.run(&format!("y_grad/{}", size), || y_grad(&input));
+        bencher.run(&format!("edge_detect/{}", size), || edge_detect(&input));
+
+        bencher.run(&format!("image_add/{}", size), || image_add(&input, &other));
+        bencher.run(&format!("image_sub/{}", size), || image_sub(&input, &other));
+        bencher.run(&format!("linear_blend/{}", size), || linear_blend(&input, &other, 0.3));
+        bencher.run(&format!("adjust_brightness/{}", size), || adjust_brightness(&input, 40));
+        bencher.run(&format!("adjust_contrast/{}", size), || adjust_contrast(&input, 1.5));
+    }
+
+    let pixel_1 = image::Rgba([200, 100, 50, 255]);
+    let pixel_2 = image::Rgba([90, 180, 20, 255]);
+    bencher.run("pixel_add", || pixel_add(black_box(pixel_1), black_box(pixel_2)));
+    bencher.run("pixel_sub", || pixel_sub(black_box(pixel_1), black_box(pixel_2)));
+    bencher.run("pixel_shift", || pixel_shift(black_box(pixel_1), black_box(40)));
+    bencher.run("pixel_scale", || pixel_scale(black_box(pixel_1), black_box(1.5)));
+}
@@ -0,0 +1,82 @@
+//! Peak memory of each pipeline operation, run with `cargo bench --bench memory`, from which
+//! `memory::WORKING_COPIES` is set. Allocations are counted by a global allocator, and each
+//! operation's peak above what was allocated before it ran is reported in copies of the image.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ndarray::Array2;
+use rust_image_processing::greyscale::Greyscale;
+use rust_image_processing::memory::{image_bytes, WORKING_COPIES};
+use rust_image_processing::testing::test_image;
+use rust_image_processing::Pipeline;
+
+const SIZE: u32 = 512;
+
+/// The system allocator, keeping count of the bytes allocated now and at most since a reset
+struct Counting;
+
+static CURRENT: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let pointer = System.alloc(layout);
+        if !pointer.is_null() {
+            let current = CURRENT.fetch_add(layout.size(), Ordering::SeqCst) + layout.size();
+            PEAK.fetch_max(current, Ordering::SeqCst);
+        }
+        pointer
+    }
+
+    unsafe fn dealloc(&self, pointer: *mut u8, layout: Layout) {
+        System.dealloc(pointer, layout);
+        CURRENT.fetch_sub(layout.size(), Ordering::SeqCst);
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// Bytes allocated at the peak of a call, above those allocated before it
+fn peak_bytes<T>(f: impl FnOnce() -> T) -> usize {
+    let before = CURRENT.load(Ordering::SeqCst);
+    PEAK.store(before, Ordering::SeqCst);
+    drop(f());
+    PEAK.load(Ordering::SeqCst) - before
+}
+
+fn main() {
+    let filter = std::env::args().skip(1).find(|arg| !arg.starts_with("--"));
+    let input = test_image(SIZE, SIZE, 1);
+    let image = image_bytes(SIZE, SIZE) as f64;
+
+    let pipelines = [
+        ("adjust_brightness", Pipeline::new().adjust_brightness(40)),
+        ("adjust_contrast", Pipeline::new().adjust_contrast(1.5)),
+        ("adjust_gamma", Pipeline::new().adjust_gamma(2.2)),
+        ("contrast", Pipeline::new().contrast(1.5)),
+        ("greyscale", Pipeline::new().greyscale(Greyscale::Rec709)),
+        ("channel_mix", Pipeline::new().channel_mix([[0.5, 0.3, 0.2], [0.2, 0.6, 0.2], [0.1, 0.2, 0.7]])),
+        ("sharpen", Pipeline::new().sharpen(0.8)),
+        ("adaptive_sharpen", Pipeline::new().adaptive_sharpen(1.5, 1., 0.2)),
+        ("bilinear_filter", Pipeline::new().bilinear_filter()),
+        ("gaussian_blur", Pipeline::new().gaussian_blur()),
+        ("median_filter", Pipeline::new().median_filter(3)),
+        ("edge_detect", Pipeline::new().edge_detect()),
+        ("x_grad", Pipeline::new().x_grad()),
+        ("apply_matrix", Pipeline::new().apply_matrix(Array2::from_elem((9, 9), 1. / 81.))),
+        ("gaussian_blur+edge_detect+sharpen", Pipeline::new().gaussian_blur().edge_detect().sharpen(0.5)),
+    ];
+
+    let mut most: f64 = 0.;
+    for (name, pipeline) in &pipelines {
+        if filter.as_ref().is_some_and(|filter| !name.contains(filter.as_str())) {
+            continue;
+        }
+        let copies = peak_bytes(|| pipeline.run(&input)) as f64 / image;
+        most = most.max(copies);
+        println!("{:<40} peak {:>6.2} copies of the image", name, copies);
+    }
+    println!("{:<40} peak {:>6.2} copies, WORKING_COPIES is {}", "most", most, WORKING_COPIES);
+}
@@ -0,0 +1,47 @@
+/* C interface to rust_image_processing, built with `cargo build --release --features ffi`
+ * which produces librust_image_processing.so / .dylib / .dll in target/release.
+ *
+ * Functions returning int give 0 on success and -1 on failure, with the reason from
+ * rip_last_error(). A panic inside the library is caught and reported the same way rather than
+ * unwinding into the caller. Strings are utf-8 and nul terminated. */
+
+#ifndef RUST_IMAGE_PROCESSING_H
+#define RUST_IMAGE_PROCESSING_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct RipImage RipImage;
+
+/* Copy width * height * 4 bytes of row major rgba pixels into a new image, null on failure */
+RipImage *rip_image_from_rgba(const uint8_t *data, uint32_t width, uint32_t height);
+
+/* Release an image, null is ignored */
+void rip_image_free(RipImage *image);
+
+uint32_t rip_image_width(const RipImage *image);
+uint32_t rip_image_height(const RipImage *image);
+
+/* The image's rgba bytes, valid until the image is next modified or freed */
+const uint8_t *rip_image_data(const RipImage *image);
+size_t rip_image_data_len(const RipImage *image);
+
+/* Apply one operation by name, e.g. "median_filter", with its parameters as a json object
+ * such as "{\"window\": 2}", or null for operations without any */
+int rip_image_apply(RipImage *image, const char *name, const char *parameters);
+
+/* Run a json pipeline, in the same form as a pipeline file, over the image */
+int rip_image_run_pipeline(RipImage *image, const char *pipeline);
+
+/* The message of the last failure on this thread, owned by the library */
+const char *rip_last_error(void);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,189 @@
+"""Python bindings for rust_image_processing over its C interface, with numpy arrays in and out.
+
+This ctypes wrapper is a partial substitute for PyO3 bindings, which are still to do as PyO3
+can't be built offline. It's used from a source checkout rather than installed as a wheel.
+
+Build the shared library with `cargo build --release --features ffi`, then either put it next
+to this package or point RUST_IMAGE_PROCESSING_LIB at it.
+
+    import numpy as np
+    import rust_image_processing as rip
+
+    edges = rip.edge_detect(rip.gaussian_blur(image))
+    result = rip.run_pipeline(image, [{"op": "median_filter", "window": 2}, {"op": "sharpen", "value": 1.5}])
+
+Images are uint8 arrays of shape (height, width, 4) in rgba order; rgb and single channel
+arrays are widened to rgba with an opaque alpha. Results are always rgba.
+"""
+
+import ctypes
+import json
+import os
+import sys
+
+import numpy as np
+
+__all__ = [
+    "RustImageProcessingError",
+    "apply",
+    "run_pipeline",
+    "adjust_brightness",
+    "adjust_contrast",
+    "adjust_gamma",
+    "contrast",
+    "sharpen",
+    "bilinear_filter",
+    "gaussian_blur",
+    "median_filter",
+    "edge_detect",
+    "x_grad",
+    "y_grad",
+    "apply_matrix",
+]
+
+
+class RustImageProcessingError(Exception):
+    pass
+
+
+def _library_path():
+    if "RUST_IMAGE_PROCESSING_LIB" in os.environ:
+        return os.environ["RUST_IMAGE_PROCESSING_LIB"]
+
+    if sys.platform == "win32":
+        name = "rust_image_processing.dll"
+    elif sys.platform == "darwin":
+        name = "librust_image_processing.dylib"
+    else:
+        name = "librust_image_processing.so"
+
+    here = os.path.dirname(os.path.abspath(__file__))
+    for directory in (here, os.path.join(here, "..", "..", "target", "release")):
+        path = os.path.join(directory, name)
+        if os.path.exists(path):
+            return path
+
+    return name
+
+
+def _load():
+    lib = ctypes.CDLL(_library_path())
+
+    lib.rip_image_from_rgba.argtypes = [ctypes.c_void_p, ctypes.c_uint32, ctypes.c_uint32]
+    lib.rip_image_from_rgba.restype = ctypes.c_void_p
+    lib.rip_image_free.argtypes = [ctypes.c_void_p]
+    lib.rip_image_free.restype = None
+    lib.rip_image_width.argtypes = [ctypes.c_void_p]
+    lib.rip_image_width.restype = ctypes.c_uint32
+    lib.rip_image_height.argtypes = [ctypes.c_void_p]
+    lib.rip_image_height.restype = ctypes.c_uint32
+    lib.rip_image_data.argtypes = [ctypes.c_void_p]
+    lib.rip_image_data.restype = ctypes.POINTER(ctypes.c_uint8)
+    lib.rip_image_data_len.argtypes = [ctypes.c_void_p]
+    lib.rip_image_data_len.restype = ctypes.c_size_t
+    lib.rip_image_apply.argtypes = [ctypes.c_void_p, ctypes.c_char_p, ctypes.c_char_p]
+    lib.rip_image_apply.restype = ctypes.c_int
+    lib.rip_image_run_pipeline.argtypes = [ctypes.c_void_p, ctypes.c_char_p]
+    lib.rip_image_run_pipeline.restype = ctypes.c_int
+    lib.rip_last_error.argtypes = []
+    lib.rip_last_error.restype = ctypes.c_char_p
+
+    return lib
+
+
+_lib = _load()
+
+
+def _as_rgba(image):
+    image = np.asarray(image)
+    if image.dtype != np.uint8:
+        raise TypeError("expected a uint8 array, got {}".format(image.dtype))
+
+    if image.ndim == 2:
+        image = image[:, :, np.newaxis].repeat(3, axis=2)
+    if image.ndim != 3 or image.shape[2] not in (3, 4):
+        raise ValueError("expected an array of shape (height, width), (height, width, 3) or (height, width, 4)")
+    if image.shape[2] == 3:
+        alpha = np.full(image.shape[:2] + (1,), 255, dtype=np.uint8)
+        image = np.concatenate([image, alpha], axis=2)
+
+    return np.ascontiguousarray(image)
+
+
+def _process(image, call):
+    rgba = _as_rgba(image)
+    height, width = rgba.shape[:2]
+
+    handle = _lib.rip_image_from_rgba(rgba.ctypes.data, width, height)
+    if not handle:
+        raise RustImageProcessingError(_lib.rip_last_error().decode("utf-8"))
+    try:
+        if call(handle) != 0:
+            raise RustImageProcessingError(_lib.rip_last_error().decode("utf-8"))
+
+        data = np.ctypeslib.as_array(_lib.rip_image_data(handle), shape=(_lib.rip_image_data_len(handle),))
+        return data.reshape(_lib.rip_image_height(handle), _lib.rip_image_width(handle), 4).copy()
+    finally:
+        _lib.rip_image_free(handle)
+
+
+def apply(image, name, **parameters):
+    """Apply one operation by its pipeline name, e.g. apply(image, "median_filter", window=2)"""
+    encoded = json.dumps(parameters).encode("utf-8") if parameters else None
+    return _process(image, lambda handle: _lib.rip_image_apply(handle, name.encode("utf-8"), encoded))
+
+
+def run_pipeline(image, pipeline):
+    """Run a pipeline given as a list of steps, or its json, in the same form as a pipeline file"""
+    if not isinstance(pipeline, str):
+        pipeline = json.dumps(pipeline)
+    return _process(image, lambda handle: _lib.rip_image_run_pipeline(handle, pipeline.encode("utf-8")))
+
+
+def adjust_brightness(image, value):
+    return apply(image, "adjust_brightness", value=int(value))
+
+
+def adjust_contrast(image, value):
+    return apply(image, "adjust_contrast", value=float(value))
+
+
+def adjust_gamma(image, gamma):
+    return apply(image, "adjust_gamma", gamma=float(gamma))
+
+
+def contrast(image, value):
+    return apply(image, "contrast", value=float(value))
+
+
+def sharpen(image, value):
+    return apply(image, "sharpen", value=float(value))
+
+
+def bilinear_filter(image):
+    return apply(image, "bilinear_filter")
+
+
+def gaussian_blur(image):
+    return apply(image, "gaussian_blur")
+
+
+def median_filter(image, window):
+    return apply(image, "median_filter", window=int(window))
+
+
+def edge_detect(image):
+    return apply(image, "edge_detect")
+
+
+def x_grad(image):
+    return apply(image, "x_grad")
+
+
+def y_grad(image):
+    return apply(image, "y_grad")
+
+
+def apply_matrix(image, matrix):
+    """Convolve with a 2d kernel, given as nested lists or a 2d array"""
+    return apply(image, "apply_matrix", matrix=np.asarray(matrix, dtype=float).tolist())
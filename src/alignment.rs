@@ -0,0 +1,284 @@
+//! Aligning photos of the same scene taken a moment apart, e.g. the frames of a handheld burst,
+//! so they can be merged pixel for pixel. A shift is found by phase correlation, and can be
+//! refined to a homography from corners matched between the frames, which also follows the
+//! slight rotation and perspective change between handheld shots.
+
+use image::imageops::{resize, FilterType};
+use rand::Rng;
+
+use crate::features::harris_corners;
+use crate::fft::{fft2, Complex};
+use crate::plane::Plane;
+use crate::random::rng;
+use crate::warp::{warp, Homography};
+use crate::ImageBuffer;
+
+/// Photos are shrunk to at most this on their longest side for phase correlation
+const CORRELATION_SIZE: u32 = 512;
+
+/// Most corners matched between frames to fit a homography
+const MAX_CORRESPONDENCES: usize = 400;
+
+/// Half the side of the patches compared when matching corners
+const PATCH_RADIUS: i64 = 7;
+
+/// Furthest in pixels a corner is searched for from where the shift puts it
+const SEARCH_RADIUS: i64 = 6;
+
+/// Lowest normalised cross correlation between patches of a match
+const MIN_CORRELATION: f32 = 0.8;
+
+/// Rounds of RANSAC fitting a homography
+const RANSAC_ITERATIONS: usize = 500;
+
+/// Furthest in pixels a match may be from where a homography puts it to count as an inlier
+const INLIER_DISTANCE: f64 = 1.5;
+
+/// Fewest inliers a homography needs to be trusted over the shift
+const MIN_INLIERS: usize = 12;
+
+/// How frames are aligned
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Alignment {
+    /// Frames are already aligned, e.g. from a tripod
+    None,
+    /// A shift found by `phase_correlation`
+    Translation,
+    /// A homography fitted to matched corners, see `estimate_homography`
+    #[default]
+    Homography,
+}
+
+impl Alignment {
+    pub fn from_name(name: &str) -> Option<Alignment> {
+        match name {
+            "none" => Some(Alignment::None),
+            "translation" => Some(Alignment::Translation),
+            "homography" => Some(Alignment::Homography),
+            _ => None,
+        }
+    }
+
+    /// Move a frame onto a reference frame of the same scene
+    ///
+    /// # Arguments
+    ///
+    /// * `reference`: Frame to align to
+    /// * `frame`: Frame to move
+    /// * `seed`: Seed for the random sampling of homography fitting, see `random::rng`
+    ///
+    /// returns: ImageBuffer, the size of the reference, transparent where the frame doesn't cover
+    pub fn align(&self, reference: &ImageBuffer, frame: &ImageBuffer, seed: Option<u64>) -> ImageBuffer {
+        let (width, height) = reference.dimensions();
+        match self {
+            Alignment::None => frame.clone(),
+            Alignment::Translation => {
+                let (dx, dy) = phase_correlation(&Plane::from_luma(reference), &Plane::from_luma(frame));
+                warp(frame, width, height, |x, y| (x + dx, y + dy))
+            }
+            Alignment::Homography => match estimate_homography(reference, frame, seed) {
+                Some(homography) => warp(frame, width, height, |x, y| {
+                    let (u, v) = homography.apply(x as f64, y as f64);
+                    (u as f32, v as f32)
+                }),
+                None => Alignment::Translation.align(reference, frame, seed),
+            },
+        }
+    }
+}
+
+/// Find the shift between two views of the same scene by phase correlation: the peak of the
+/// inverse transform of their normalised cross power spectrum, which keeps only the phase, sits
+/// at the shift. Only shifts of up to half the image either way can be told apart.
+///
+/// # Arguments
+///
+/// * `reference`: Intensity of the first view
+/// * `moving`: Intensity of the second view, the size of the first
+///
+/// returns: (f32, f32), where a point of the reference is in the moving view less where it is
+/// in the reference, to a fraction of a pixel
+pub fn phase_correlation(reference: &Plane, moving: &Plane) -> (f32, f32) {
+    let longest = reference.width.max(reference.height).max(1);
+    let scale = (CORRELATION_SIZE as f32 / longest as f32).min(1.);
+    let (width, height) = (((reference.width as f32 * scale).round() as u32).max(1), ((reference.height as f32 * scale).round() as u32).max(1));
+    let (columns, rows) = (width.next_power_of_two() as usize, height.next_power_of_two() as usize);
+
+    let spectrum = |plane: &Plane| {
+        let small = shrink(plane, width, height);
+        let mean = small.data.iter().sum::<f32>() / small.data.len() as f32;
+        let mut values = vec![Complex::default(); columns * rows];
+        // A Hann window, so the image edges don't correlate as a strong cross at no shift
+        let window = |i: u32, n: u32| 0.5 - 0.5 * (std::f64::consts::TAU * (i as f64 + 0.5) / n as f64).cos();
+        for y in 0..height {
+            for x in 0..width {
+                let value = (small.get(x, y) - mean) as f64 * window(x, width) * window(y, height);
+                values[y as usize * columns + x as usize] = Complex::new(value, 0.);
+            }
+        }
+        fft2(&mut values, columns, rows, false);
+        values
+    };
+
+    let (a, b) = (spectrum(reference), spectrum(moving));
+    let mut cross: Vec<Complex> = a
+        .iter()
+        .zip(&b)
+        .map(|(&a, &b)| {
+            let product = b * a.conj();
+            product.scale(1. / product.norm().max(1e-12))
+        })
+        .collect();
+    fft2(&mut cross, columns, rows, true);
+
+    let surface: Vec<f64> = cross.iter().map(|c| c.re).collect();
+    let peak = (0..surface.len()).max_by(|&i, &j| surface[i].total_cmp(&surface[j])).unwrap_or(0);
+    let (px, py) = (peak % columns, peak / columns);
+
+    // A parabola through the peak and its neighbours on each axis, wrapping round
+    let at = |x: usize, y: usize| surface[(y % rows) * columns + x % columns];
+    let offset = |before: f64, centre: f64, after: f64| {
+        let curvature = before - 2. * centre + after;
+        if curvature < 0. {
+            (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.
+        }
+    };
+    let fx = px as f64 + offset(at(px + columns - 1, py), at(px, py), at(px + 1, py));
+    let fy = py as f64 + offset(at(px, py + rows - 1), at(px, py), at(px, py + 1));
+
+    let unwrap = |value: f64, size: usize| if value > size as f64 / 2. { value - size as f64 } else { value };
+    ((unwrap(fx, columns) / scale as f64) as f32, (unwrap(fy, rows) / scale as f64) as f32)
+}
+
+/// Fit the homography between two views of the same scene: corners of the reference are
+/// matched in the moving view near where `phase_correlation`'s shift puts them, and the
+/// homography is fitted to the matches by RANSAC, which ignores the mismatches and anything
+/// that moved between the views
+///
+/// # Arguments
+///
+/// * `reference`: First view
+/// * `moving`: Second view, the size of the first
+/// * `seed`: Seed for choosing the samples, see `random::rng`
+///
+/// returns: Option<Homography>, from reference positions to moving positions, or None without
+/// enough matches to trust
+pub fn estimate_homography(reference: &ImageBuffer, moving: &ImageBuffer, seed: Option<u64>) -> Option<Homography> {
+    let (reference_luma, moving_luma) = (Plane::from_luma(reference), Plane::from_luma(moving));
+    let (dx, dy) = phase_correlation(&reference_luma, &moving_luma);
+
+    let mut from = Vec::new();
+    let mut to = Vec::new();
+    for corner in harris_corners(reference, 0.01, 8).into_iter().take(MAX_CORRESPONDENCES) {
+        let (x, y) = (corner.x.round() as i64, corner.y.round() as i64);
+        let predicted = ((x as f32 + dx).round() as i64, (y as f32 + dy).round() as i64);
+        if let Some((mx, my)) = match_patch(&reference_luma, &moving_luma, (x, y), predicted) {
+            from.push((x as f64, y as f64));
+            to.push((mx, my));
+        }
+    }
+    if from.len() < MIN_INLIERS {
+        return None;
+    }
+
+    let inliers = |homography: &Homography| -> Vec<usize> {
+        (0..from.len())
+            .filter(|&i| {
+                let (u, v) = homography.apply(from[i].0, from[i].1);
+                (u - to[i].0).hypot(v - to[i].1) <= INLIER_DISTANCE
+            })
+            .collect()
+    };
+
+    let mut rng = rng(seed);
+    let mut best: Vec<usize> = Vec::new();
+    for _ in 0..RANSAC_ITERATIONS {
+        let sample: [usize; 4] = std::array::from_fn(|_| rng.gen_range(0..from.len()));
+        let (sample_from, sample_to): (Vec<_>, Vec<_>) = sample.iter().map(|&i| (from[i], to[i])).unzip();
+        if let Some(homography) = Homography::from_points(&sample_from, &sample_to) {
+            let found = inliers(&homography);
+            if found.len() > best.len() {
+                best = found;
+            }
+        }
+    }
+    if best.len() < MIN_INLIERS {
+        return None;
+    }
+
+    let (inlier_from, inlier_to): (Vec<_>, Vec<_>) = best.iter().map(|&i| (from[i], to[i])).unzip();
+    Homography::from_points(&inlier_from, &inlier_to)
+}
+
+/// Find a patch of the reference in the moving view, searching around a predicted position
+///
+/// returns: Option<(f64, f64)>, the position to a fraction of a pixel, or None if nothing there
+/// correlates well or the reference patch is flat
+fn match_patch(reference: &Plane, moving: &Plane, (x, y): (i64, i64), (px, py): (i64, i64)) -> Option<(f64, f64)> {
+    let fits = |plane: &Plane, x: i64, y: i64| {
+        x - PATCH_RADIUS >= 0 && y - PATCH_RADIUS >= 0 && x + PATCH_RADIUS < plane.width as i64 && y + PATCH_RADIUS < plane.height as i64
+    };
+    if !fits(reference, x, y) {
+        return None;
+    }
+
+    let patch = |plane: &Plane, cx: i64, cy: i64| -> Vec<f32> {
+        let mut values = Vec::with_capacity(((2 * PATCH_RADIUS + 1) * (2 * PATCH_RADIUS + 1)) as usize);
+        for dy in -PATCH_RADIUS..=PATCH_RADIUS {
+            for dx in -PATCH_RADIUS..=PATCH_RADIUS {
+                values.push(plane.get((cx + dx) as u32, (cy + dy) as u32));
+            }
+        }
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let norm = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>().sqrt();
+        values.iter().map(|v| (v - mean) / norm.max(1e-6)).collect()
+    };
+    let template = patch(reference, x, y);
+    if template.iter().all(|&v| v == 0.) {
+        return None;
+    }
+
+    let side = (2 * SEARCH_RADIUS + 1) as usize;
+    let mut scores = vec![f32::MIN; side * side];
+    for sy in -SEARCH_RADIUS..=SEARCH_RADIUS {
+        for sx in -SEARCH_RADIUS..=SEARCH_RADIUS {
+            let (cx, cy) = (px + sx, py + sy);
+            if fits(moving, cx, cy) {
+                let candidate = patch(moving, cx, cy);
+                scores[((sy + SEARCH_RADIUS) as usize) * side + (sx + SEARCH_RADIUS) as usize] = template.iter().zip(&candidate).map(|(a, b)| a * b).sum();
+            }
+        }
+    }
+
+    let best = (0..scores.len()).max_by(|&i, &j| scores[i].total_cmp(&scores[j]))?;
+    if scores[best] < MIN_CORRELATION {
+        return None;
+    }
+
+    let (bx, by) = (best % side, best / side);
+    let score = |x: usize, y: usize| scores[y * side + x];
+    let offset = |before: f32, centre: f32, after: f32| {
+        let curvature = before - 2. * centre + after;
+        if before > f32::MIN && after > f32::MIN && curvature < 0. {
+            (0.5 * (before - after) / curvature).clamp(-0.5, 0.5)
+        } else {
+            0.
+        }
+    };
+    let fx = if bx > 0 && bx + 1 < side { offset(score(bx - 1, by), score(bx, by), score(bx + 1, by)) } else { 0. };
+    let fy = if by > 0 && by + 1 < side { offset(score(bx, by - 1), score(bx, by), score(bx, by + 1)) } else { 0. };
+
+    Some(((px + bx as i64 - SEARCH_RADIUS) as f64 + fx as f64, (py + by as i64 - SEARCH_RADIUS) as f64 + fy as f64))
+}
+
+/// A plane shrunk to a size, averaging rather than skipping pixels
+fn shrink(plane: &Plane, width: u32, height: u32) -> Plane {
+    if (plane.width, plane.height) == (width, height) {
+        return plane.clone();
+    }
+
+    let image = plane.to_image();
+    Plane::from_luma(&resize(&image, width, height, FilterType::Triangle))
+}
@@ -0,0 +1,373 @@
+use std::borrow::Cow;
+
+use image::imageops::{crop_imm, replace, resize, FilterType};
+
+use crate::error::{Error, Result};
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+use crate::pixel::{pixel_add, pixel_scale, pixel_shift, pixel_sub};
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+use crate::simd;
+use crate::ImageBuffer;
+
+/// What the operations on two images do when the images differ in size
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SizePolicy {
+    /// Fail with `Error::DimensionMismatch`
+    #[default]
+    Strict,
+    /// Crop both to the top left of the width and height they share
+    CropToSmallest,
+    /// Resize the second image to the first
+    ResizeSecond,
+    /// Pad both at the right and bottom with transparent black to the largest width and height
+    Pad,
+}
+
+/// Bring two images to the same size by a policy, borrowing them when they already are
+///
+/// # Arguments
+///
+/// * `input_1`: First image
+/// * `input_2`: Second image
+/// * `policy`: What to do if they differ in size
+///
+/// returns: Result<(Cow<ImageBuffer>, Cow<ImageBuffer>)>, an error only for `SizePolicy::Strict`
+pub fn match_dimensions<'a>(input_1: &'a ImageBuffer, input_2: &'a ImageBuffer, policy: SizePolicy) -> Result<(Cow<'a, ImageBuffer>, Cow<'a, ImageBuffer>)> {
+    let ((width_1, height_1), (width_2, height_2)) = (input_1.dimensions(), input_2.dimensions());
+    if (width_1, height_1) == (width_2, height_2) {
+        return Ok((Cow::Borrowed(input_1), Cow::Borrowed(input_2)));
+    }
+
+    match policy {
+        SizePolicy::Strict => Err(Error::DimensionMismatch { expected: input_1.dimensions(), actual: input_2.dimensions() }),
+        SizePolicy::CropToSmallest => {
+            let (width, height) = (width_1.min(width_2), height_1.min(height_2));
+            let crop = |input: &ImageBuffer| crop_imm(input, 0, 0, width, height).to_image();
+            Ok((Cow::Owned(crop(input_1)), Cow::Owned(crop(input_2))))
+        }
+        SizePolicy::ResizeSecond => Ok((Cow::Borrowed(input_1), Cow::Owned(resize(input_2, width_1, height_1, FilterType::Triangle)))),
+        SizePolicy::Pad => {
+            let (width, height) = (width_1.max(width_2), height_1.max(height_2));
+            let pad = |input: &'a ImageBuffer| {
+                if input.dimensions() == (width, height) {
+                    return Cow::Borrowed(input);
+                }
+                let mut padded = ImageBuffer::new(width, height);
+                replace(&mut padded, input, 0, 0);
+                Cow::Owned(padded)
+            };
+            Ok((pad(input_1), pad(input_2)))
+        }
+    }
+}
+
+/// As `linear_blend`, but bringing images of different sizes to the same size by a policy
+/// first, rather than panicking
+pub fn linear_blend_with(input_1: &ImageBuffer, input_2: &ImageBuffer, value: f32, policy: SizePolicy) -> Result<ImageBuffer> {
+    let (input_1, input_2) = match_dimensions(input_1, input_2, policy)?;
+    Ok(linear_blend(&input_1, &input_2, value))
+}
+
+/// As `image_sub`, but bringing images of different sizes to the same size by a policy first,
+/// rather than panicking
+pub fn image_sub_with(input_1: &ImageBuffer, input_2: &ImageBuffer, policy: SizePolicy) -> Result<ImageBuffer> {
+    let (input_1, input_2) = match_dimensions(input_1, input_2, policy)?;
+    Ok(image_sub(&input_1, &input_2))
+}
+
+/// As `image_add`, but bringing images of different sizes to the same size by a policy first,
+/// rather than panicking
+pub fn image_add_with(input_1: &ImageBuffer, input_2: &ImageBuffer, policy: SizePolicy) -> Result<ImageBuffer> {
+    let (input_1, input_2) = match_dimensions(input_1, input_2, policy)?;
+    Ok(image_add(&input_1, &input_2))
+}
+
+/// Blend two images of the same size, `value` of the way from the first to the second. Panics
+/// if the sizes differ; `linear_blend_with` handles those.
+pub fn linear_blend(input_1: &ImageBuffer, input_2: &ImageBuffer, value: f32) -> ImageBuffer {
+    let (input_x, input_y) = input_1.dimensions();
+    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+
+    linear_blend_into(input_1, input_2, value, &mut output);
+
+    output
+}
+
+/// As `linear_blend`, but writing into an existing buffer the same size as `input_1`
+pub fn linear_blend_into(input_1: &ImageBuffer, input_2: &ImageBuffer, value: f32, output: &mut ImageBuffer) {
+    assert_input_dimensions(input_1, input_2);
+    assert_output_dimensions(input_1, output);
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    simd::blend(input_1, input_2, value, output);
+
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let scaled_1 = pixel_scale(*input_1.get_pixel(x,y), 1. - value);
+        let scaled_2 = pixel_scale(*input_2.get_pixel(x,y), value);
+
+        *pixel = pixel_add(scaled_1, scaled_2);
+    }
+}
+
+/// As `linear_blend`, but overwriting `input_1` with the result
+pub fn linear_blend_in_place(input_1: &mut ImageBuffer, input_2: &ImageBuffer, value: f32) {
+    assert_input_dimensions(input_1, input_2);
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    simd::blend_in_place(input_1, input_2, value);
+
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    for (x, y, pixel) in input_1.enumerate_pixels_mut() {
+        let scaled_1 = pixel_scale(*pixel, 1. - value);
+        let scaled_2 = pixel_scale(*input_2.get_pixel(x,y), value);
+
+        *pixel = pixel_add(scaled_1, scaled_2);
+    }
+}
+
+/// Subtract the second of two images of the same size from the first. Panics if the sizes
+/// differ; `image_sub_with` handles those.
+pub fn image_sub(input_1: &ImageBuffer, input_2: &ImageBuffer) -> ImageBuffer {
+    let (input_x, input_y) = input_1.dimensions();
+    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+
+    image_sub_into(input_1, input_2, &mut output);
+
+    output
+}
+
+/// As `image_sub`, but writing into an existing buffer the same size as `input_1`
+pub fn image_sub_into(input_1: &ImageBuffer, input_2: &ImageBuffer, output: &mut ImageBuffer) {
+    assert_input_dimensions(input_1, input_2);
+    assert_output_dimensions(input_1, output);
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    simd::sub(input_1, input_2, output);
+
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let image_1 = *input_1.get_pixel(x,y);
+        let image_2 = *input_2.get_pixel(x,y);
+
+        *pixel = pixel_sub(image_1, image_2);
+    }
+}
+
+/// As `image_sub`, but overwriting `input_1` with the result
+pub fn image_sub_in_place(input_1: &mut ImageBuffer, input_2: &ImageBuffer) {
+    assert_input_dimensions(input_1, input_2);
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    simd::sub_in_place(input_1, input_2);
+
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    for (x, y, pixel) in input_1.enumerate_pixels_mut() {
+        *pixel = pixel_sub(*pixel, *input_2.get_pixel(x,y));
+    }
+}
+
+/// Add two images of the same size. Panics if the sizes differ; `image_add_with` handles those.
+pub fn image_add(input_1: &ImageBuffer, input_2: &ImageBuffer) -> ImageBuffer {
+    let (input_x, input_y) = input_1.dimensions();
+    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+
+    image_add_into(input_1, input_2, &mut output);
+
+    output
+}
+
+/// As `image_add`, but writing into an existing buffer the same size as `input_1`
+pub fn image_add_into(input_1: &ImageBuffer, input_2: &ImageBuffer, output: &mut ImageBuffer) {
+    assert_input_dimensions(input_1, input_2);
+    assert_output_dimensions(input_1, output);
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    simd::add(input_1, input_2, output);
+
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let image_1 = *input_1.get_pixel(x,y);
+        let image_2 = *input_2.get_pixel(x,y);
+
+        *pixel = pixel_add(image_1, image_2);
+    }
+}
+
+/// As `image_add`, but overwriting `input_1` with the result
+pub fn image_add_in_place(input_1: &mut ImageBuffer, input_2: &ImageBuffer) {
+    assert_input_dimensions(input_1, input_2);
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    simd::add_in_place(input_1, input_2);
+
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    for (x, y, pixel) in input_1.enumerate_pixels_mut() {
+        *pixel = pixel_add(*pixel, *input_2.get_pixel(x,y));
+    }
+}
+
+/// For each pixel, p, of an image, adjust brightness by output of:
+///     p + value
+///
+/// # Arguments
+///
+/// * `input`: Image buffer
+/// * `value`: Brightness addition value
+///
+/// returns: ImageBuffer
+pub fn adjust_brightness(input: &ImageBuffer, value: i32) -> ImageBuffer {
+    let (input_x, input_y) = input.dimensions();
+
+    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+
+    adjust_brightness_into(input, value, &mut output);
+
+    output
+}
+
+/// As `adjust_brightness`, but writing into an existing buffer the same size as `input`
+pub fn adjust_brightness_into(input: &ImageBuffer, value: i32, output: &mut ImageBuffer) {
+    assert_output_dimensions(input, output);
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    simd::shift(input, value, output);
+
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    for(x, y, pixel) in output.enumerate_pixels_mut() {
+        *pixel = pixel_shift(*input.get_pixel(x, y), value);
+    }
+}
+
+/// As `adjust_brightness`, but modifying the image itself
+pub fn adjust_brightness_in_place(input: &mut ImageBuffer, value: i32) {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    simd::shift_in_place(input, value);
+
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    for pixel in input.pixels_mut() {
+        *pixel = pixel_shift(*pixel, value);
+    }
+}
+
+/// For each pixel, p, of an image, adjust contrast by output of:
+///     p * value
+///
+/// # Arguments
+///
+/// * `input`: Image buffer
+/// * `value`: Contrast scale value
+///
+/// returns: ImageBuffer
+pub fn adjust_contrast(input: &ImageBuffer, value: f32) -> ImageBuffer {
+    let (input_x, input_y) = input.dimensions();
+
+    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+
+    adjust_contrast_into(input, value, &mut output);
+
+    output
+}
+
+/// As `adjust_contrast`, but writing into an existing buffer the same size as `input`
+pub fn adjust_contrast_into(input: &ImageBuffer, value: f32, output: &mut ImageBuffer) {
+    assert_output_dimensions(input, output);
+
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    simd::scale(input, value, output);
+
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    for(x, y, pixel) in output.enumerate_pixels_mut() {
+        *pixel = pixel_scale(*input.get_pixel(x, y), value);
+    }
+}
+
+/// As `adjust_contrast`, but modifying the image itself
+pub fn adjust_contrast_in_place(input: &mut ImageBuffer, value: f32) {
+    #[cfg(all(feature = "simd", target_arch = "x86_64"))]
+    simd::scale_in_place(input, value);
+
+    #[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+    for pixel in input.pixels_mut() {
+        *pixel = pixel_scale(*pixel, value);
+    }
+}
+
+/// For each pixel, p, of an image, apply gamma correction by output of:
+///     255 * (p / 255)^(1 / gamma)
+///
+/// # Arguments
+///
+/// * `input`: Image buffer
+/// * `gamma`: Gamma value, above 1 brightens and below 1 darkens the image
+///
+/// returns: ImageBuffer
+pub fn adjust_gamma(input: &ImageBuffer, gamma: f32) -> ImageBuffer {
+    apply_lut(input, &gamma_lut(gamma))
+}
+
+/// As `adjust_gamma`, but writing into an existing buffer the same size as `input`
+pub fn adjust_gamma_into(input: &ImageBuffer, gamma: f32, output: &mut ImageBuffer) {
+    apply_lut_into(input, &gamma_lut(gamma), output);
+}
+
+/// As `adjust_gamma`, but modifying the image itself
+pub fn adjust_gamma_in_place(input: &mut ImageBuffer, gamma: f32) {
+    apply_lut_in_place(input, &gamma_lut(gamma));
+}
+
+/// Map every r,g,b value of an image through a lookup table, leaving alpha untouched
+///
+/// # Arguments
+///
+/// * `input`: Image buffer
+/// * `table`: Output value for each of the 256 input values
+///
+/// returns: ImageBuffer
+pub fn apply_lut(input: &ImageBuffer, table: &[u8; 256]) -> ImageBuffer {
+    let (input_x, input_y) = input.dimensions();
+
+    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+
+    apply_lut_into(input, table, &mut output);
+
+    output
+}
+
+/// As `apply_lut`, but writing into an existing buffer the same size as `input`
+pub fn apply_lut_into(input: &ImageBuffer, table: &[u8; 256], output: &mut ImageBuffer) {
+    assert_output_dimensions(input, output);
+
+    for(x, y, pixel) in output.enumerate_pixels_mut() {
+        let input_pixel = input.get_pixel(x, y);
+        *pixel = image::Rgba([table[input_pixel[0] as usize], table[input_pixel[1] as usize], table[input_pixel[2] as usize], input_pixel[3]]);
+    }
+}
+
+/// As `apply_lut`, but modifying the image itself
+pub fn apply_lut_in_place(input: &mut ImageBuffer, table: &[u8; 256]) {
+    for pixel in input.pixels_mut() {
+        *pixel = image::Rgba([table[pixel[0] as usize], table[pixel[1] as usize], table[pixel[2] as usize], pixel[3]]);
+    }
+}
+
+pub(crate) fn gamma_lut(gamma: f32) -> [u8; 256] {
+    let mut table = [0; 256];
+    for (i, value) in table.iter_mut().enumerate() {
+        *value = (255. * (i as f32 / 255.).powf(1. / gamma)).round().clamp(0., 255.) as u8;
+    }
+
+    table
+}
+
+/// The `_into` variants write over every pixel of `output`, so it must already be the size of
+/// the result
+pub(crate) fn assert_output_dimensions(input: &ImageBuffer, output: &ImageBuffer) {
+    assert_eq!(input.dimensions(), output.dimensions(), "output buffer must be the same size as the input");
+}
+
+/// Pixels of the second image are read at the first's positions, so a smaller second image
+/// would run off its edge and a larger one be silently cropped
+fn assert_input_dimensions(input_1: &ImageBuffer, input_2: &ImageBuffer) {
+    assert_eq!(input_1.dimensions(), input_2.dimensions(), "images must be the same size, see SizePolicy for other sizes");
+}
@@ -0,0 +1,292 @@
+//! Finding QR codes and 1D barcodes in photos, without decoding them. Each is returned as the
+//! quadrilateral around it, clockwise from the top left, which `document::rectify_page` can
+//! square up for a decoder.
+
+use image::imageops::{resize, FilterType};
+
+use crate::document::{adaptive_threshold, order_corners};
+use crate::error::Result;
+use crate::features::structure_tensor;
+use crate::plane::Plane;
+use crate::records::{number, Record};
+use crate::segmentation::connected_components;
+use crate::value::Value;
+use crate::ImageBuffer;
+
+/// Photos are shrunk to at most this on their longest side to look for codes
+const DETECTION_SIZE: u32 = 1024;
+
+/// Candidate finder pattern centres closer than this many modules are taken as the same pattern
+const FINDER_MERGE_DISTANCE: f64 = 2.;
+
+/// Rows and columns through a finder pattern that must match for it to count
+const MIN_FINDER_HITS: u32 = 2;
+
+/// Structure tensor coherence above which an area counts as parallel lines
+const BARCODE_COHERENCE: f32 = 0.7;
+
+/// Least area of a barcode, as a fraction of the photo
+const MIN_BARCODE_AREA: f64 = 0.002;
+
+/// Fewest changes between bar and space along the middle of a barcode
+const MIN_BARCODE_EDGES: usize = 16;
+
+/// One of the three nested squares in the corners of a QR code
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FinderPattern {
+    pub x: f64,
+    pub y: f64,
+    /// Width in pixels of one module, the code's smallest square
+    pub module_size: f64,
+}
+
+impl Record for FinderPattern {
+    const NAME: &'static str = "finder_pattern";
+
+    fn to_value(&self) -> Value {
+        Value::Table(vec![
+            ("x".to_string(), Value::Number(self.x)),
+            ("y".to_string(), Value::Number(self.y)),
+            ("module_size".to_string(), Value::Number(self.module_size)),
+        ])
+    }
+
+    fn from_value(value: &Value) -> Result<FinderPattern> {
+        Ok(FinderPattern { x: number(value, Self::NAME, "x")?, y: number(value, Self::NAME, "y")?, module_size: number(value, Self::NAME, "module_size")? })
+    }
+}
+
+/// Find QR code finder patterns, the squares with 1:1:3:1:1 dark, light, dark, light, dark
+/// runs through their centre across and down
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to search, dark codes on light
+///
+/// returns: Vec<FinderPattern>
+pub fn find_qr_finders(input: &ImageBuffer) -> Vec<FinderPattern> {
+    let (binary, scale) = binarise(input);
+    let (width, height) = (binary.width, binary.height);
+    let dark = |x: u32, y: u32| binary.get(x, y) < 0.5;
+
+    // Each matching run across a row, checked down its column and across again
+    let mut candidates: Vec<(FinderPattern, u32)> = Vec::new();
+    for y in 0..height {
+        let runs = runs((0..width).map(|x| dark(x, y)));
+        for window in runs.windows(5) {
+            if !window[0].0 || !ratios_match(&window.iter().map(|r| r.2).collect::<Vec<_>>()) {
+                continue;
+            }
+            let x = window[2].1 as f64 + window[2].2 as f64 / 2.;
+            let Some((cy, vertical)) = cross_check((0..height).map(|y| dark(x as u32, y)), y as f64) else {
+                continue;
+            };
+            let Some((cx, horizontal)) = cross_check((0..width).map(|x| dark(x, cy as u32)), x) else {
+                continue;
+            };
+            let module_size = (vertical + horizontal) / 14.;
+            if (vertical - horizontal).abs() > 0.4 * vertical.max(horizontal) {
+                continue;
+            }
+
+            match candidates.iter_mut().find(|(f, _)| (f.x - cx).hypot(f.y - cy) < FINDER_MERGE_DISTANCE * f.module_size.max(module_size)) {
+                Some((finder, hits)) => {
+                    let n = *hits as f64;
+                    *finder = FinderPattern {
+                        x: (finder.x * n + cx) / (n + 1.),
+                        y: (finder.y * n + cy) / (n + 1.),
+                        module_size: (finder.module_size * n + module_size) / (n + 1.),
+                    };
+                    *hits += 1;
+                }
+                None => candidates.push((FinderPattern { x: cx, y: cy, module_size }, 1)),
+            }
+        }
+    }
+
+    candidates
+        .into_iter()
+        .filter(|&(_, hits)| hits >= MIN_FINDER_HITS)
+        .map(|(f, _)| FinderPattern { x: (f.x + 0.5) / scale - 0.5, y: (f.y + 0.5) / scale - 0.5, module_size: f.module_size / scale })
+        .collect()
+}
+
+/// Find QR codes, as three finder patterns of a similar size making a right angled isosceles
+/// triangle. The fourth corner is taken as completing the parallelogram, so under strong
+/// perspective the quadrilateral is only approximate there.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to search, dark codes on light
+///
+/// returns: Vec<[(f64, f64); 4]>, the corners of each code clockwise from the top left
+pub fn find_qr_codes(input: &ImageBuffer) -> Vec<[(f64, f64); 4]> {
+    let finders = find_qr_finders(input);
+    let distance = |a: &FinderPattern, b: &FinderPattern| (a.x - b.x).hypot(a.y - b.y);
+
+    // Every fitting triple, scored by how far it is from a perfect right isosceles triangle
+    let mut triples = Vec::new();
+    for a in 0..finders.len() {
+        for b in a + 1..finders.len() {
+            for c in b + 1..finders.len() {
+                let sizes = [finders[a].module_size, finders[b].module_size, finders[c].module_size];
+                if sizes.iter().cloned().fold(0., f64::max) > 1.4 * sizes.iter().cloned().fold(f64::INFINITY, f64::min) {
+                    continue;
+                }
+
+                // The corner finder is the one opposite the longest side
+                let mut sides = [(distance(&finders[b], &finders[c]), a, b, c), (distance(&finders[a], &finders[c]), b, a, c), (distance(&finders[a], &finders[b]), c, a, b)];
+                sides.sort_by(|x, y| y.0.total_cmp(&x.0));
+                let (hypotenuse, corner, first, second) = sides[0];
+                let (leg_1, leg_2) = (sides[1].0, sides[2].0);
+                let error = (leg_1 / leg_2 - 1.).abs() + (hypotenuse / (leg_1.hypot(leg_2)) - 1.).abs();
+                if leg_1 / leg_2 < 1.3 && error < 0.2 && leg_2 > 10. * sizes[0] {
+                    triples.push((error, corner, first, second));
+                }
+            }
+        }
+    }
+    triples.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut used = vec![false; finders.len()];
+    let mut codes = Vec::new();
+    for (_, corner, first, second) in triples {
+        if used[corner] || used[first] || used[second] {
+            continue;
+        }
+        used[corner] = true;
+        used[first] = true;
+        used[second] = true;
+
+        let (a, mut b, mut c) = (finders[corner], finders[first], finders[second]);
+        // With y down, the top right finder is clockwise from the bottom left one about the corner
+        if (b.x - a.x) * (c.y - a.y) - (b.y - a.y) * (c.x - a.x) < 0. {
+            std::mem::swap(&mut b, &mut c);
+        }
+        let module = (a.module_size + b.module_size + c.module_size) / 3.;
+        let unit = |from: &FinderPattern, to: &FinderPattern| {
+            let length = distance(from, to).max(1e-9);
+            ((to.x - from.x) / length * 3.5 * module, (to.y - from.y) / length * 3.5 * module)
+        };
+        let (across, down) = (unit(&a, &b), unit(&a, &c));
+        let d = (b.x + c.x - a.x, b.y + c.y - a.y);
+
+        codes.push(order_corners([
+            (a.x - across.0 - down.0, a.y - across.1 - down.1),
+            (b.x + across.0 - down.0, b.y + across.1 - down.1),
+            (d.0 + across.0 + down.0, d.1 + across.1 + down.1),
+            (c.x - across.0 + down.0, c.y - across.1 + down.1),
+        ]));
+    }
+    codes
+}
+
+/// Find 1D barcodes, as areas of strong, parallel edges with many changes between bar and
+/// space across them. Other regular stripes, like railings, can be found too.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to search
+///
+/// returns: Vec<[(f64, f64); 4]>, the corners of each barcode clockwise from the top left
+pub fn find_barcodes(input: &ImageBuffer) -> Vec<[(f64, f64); 4]> {
+    let scale = (DETECTION_SIZE as f64 / 2. / input.width().max(input.height()) as f64).min(1.);
+    let (width, height) = (((input.width() as f64 * scale).round() as u32).max(1), ((input.height() as f64 * scale).round() as u32).max(1));
+    let luma = Plane::from_luma(&resize(input, width, height, FilterType::Triangle));
+
+    let tensor = structure_tensor(&luma, 0.5, 4.);
+    let (coherence, orientation) = (tensor.coherence(), tensor.orientation());
+    let energy = tensor.xx.zip(&tensor.yy, |a, b| a + b);
+    let mean_energy = energy.data.iter().sum::<f32>() / energy.data.len().max(1) as f32;
+
+    let mask = coherence.zip(&energy, |c, e| if c > BARCODE_COHERENCE && e > 4. * mean_energy { 1. } else { 0. });
+    let components = connected_components(&mask);
+    let mut pixels = vec![Vec::new(); components.count as usize];
+    for y in 0..height {
+        for x in 0..width {
+            pixels[components.get(x, y) as usize].push((x as f64, y as f64, orientation.get(x, y) as f64));
+        }
+    }
+
+    let min_area = MIN_BARCODE_AREA * width as f64 * height as f64;
+    let mut barcodes = Vec::new();
+    for region in pixels.iter().skip(1).filter(|p| p.len() as f64 >= min_area) {
+        // Mean gradient direction, across the bars, averaging doubled angles as it's an axis
+        let (sin, cos) = region.iter().fold((0., 0.), |(s, c), p| (s + (2. * p.2).sin(), c + (2. * p.2).cos()));
+        let angle = 0.5 * sin.atan2(cos);
+        let (across, along) = ((angle.cos(), angle.sin()), (-angle.sin(), angle.cos()));
+
+        let project = |axis: (f64, f64)| {
+            let values = region.iter().map(|p| p.0 * axis.0 + p.1 * axis.1);
+            (values.clone().fold(f64::INFINITY, f64::min), values.fold(f64::NEG_INFINITY, f64::max))
+        };
+        let ((a0, a1), (b0, b1)) = (project(across), project(along));
+
+        // Count edges along the middle, thresholding at the mean luma along it
+        let middle = (b0 + b1) / 2.;
+        let samples: Vec<f32> = (a0.floor() as i64..=a1.ceil() as i64)
+            .map(|t| luma.sample((t as f64 * across.0 + middle * along.0) as f32, (t as f64 * across.1 + middle * along.1) as f32))
+            .collect();
+        let mean = samples.iter().sum::<f32>() / samples.len().max(1) as f32;
+        let edges = samples.windows(2).filter(|w| (w[0] < mean) != (w[1] < mean)).count();
+        if edges < MIN_BARCODE_EDGES {
+            continue;
+        }
+
+        let corner = |a: f64, b: f64| ((a * across.0 + b * along.0 + 0.5) / scale - 0.5, (a * across.1 + b * along.1 + 0.5) / scale - 0.5);
+        barcodes.push(order_corners([corner(a0, b0), corner(a1, b0), corner(a1, b1), corner(a0, b1)]));
+    }
+    barcodes
+}
+
+/// Black and white plane of the input at detection size, and the scale it was shrunk by
+fn binarise(input: &ImageBuffer) -> (Plane, f64) {
+    let scale = (DETECTION_SIZE as f64 / input.width().max(input.height()) as f64).min(1.);
+    let (width, height) = (((input.width() as f64 * scale).round() as u32).max(1), ((input.height() as f64 * scale).round() as u32).max(1));
+    let small = resize(input, width, height, FilterType::Triangle);
+    // A wide neighbourhood, so the middle of a finder pattern's centre square stays dark
+    let binary = adaptive_threshold(&small, width.max(height) / 16, 0.);
+    (Plane::from_luma(&binary).map(|v| v / 255.), scale)
+}
+
+/// Runs of equal values, as (value, start, length)
+fn runs(values: impl Iterator<Item = bool>) -> Vec<(bool, u32, u32)> {
+    let mut runs: Vec<(bool, u32, u32)> = Vec::new();
+    for (i, value) in values.enumerate() {
+        match runs.last_mut() {
+            Some(run) if run.0 == value => run.2 += 1,
+            _ => runs.push((value, i as u32, 1)),
+        }
+    }
+    runs
+}
+
+/// Whether five run lengths are close to 1:1:3:1:1
+fn ratios_match(counts: &[u32]) -> bool {
+    let total: u32 = counts.iter().sum();
+    if total < 7 {
+        return false;
+    }
+    let module = total as f64 / 7.;
+    let tolerance = module / 2.;
+    counts.iter().zip([1., 1., 3., 1., 1.]).all(|(&count, expected)| (count as f64 - expected * module).abs() < expected * tolerance)
+}
+
+/// Check a line through a candidate centre for the finder pattern's runs, with the centre run
+/// holding the candidate
+///
+/// returns: Option<(f64, f64)>, the centre of the pattern along the line and its total width
+fn cross_check(values: impl Iterator<Item = bool>, centre: f64) -> Option<(f64, f64)> {
+    let runs = runs(values);
+    let middle = runs.iter().position(|r| (r.1 as f64) <= centre && centre < (r.1 + r.2) as f64)?;
+    if middle < 2 || middle + 2 >= runs.len() || !runs[middle].0 {
+        return None;
+    }
+
+    let window = &runs[middle - 2..=middle + 2];
+    if !ratios_match(&window.iter().map(|r| r.2).collect::<Vec<_>>()) {
+        return None;
+    }
+    let total: u32 = window.iter().map(|r| r.2).sum();
+    Some((runs[middle].1 as f64 + runs[middle].2 as f64 / 2., total as f64))
+}
@@ -0,0 +1,178 @@
+//! Running a pipeline over many images at once
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use image::ImageFormat;
+use log::{debug, info};
+
+use crate::error::Result;
+use crate::io::{open_image_with_profile, save_image, OutputFormat, OutputOptions};
+use crate::memory::{image_bytes, MemoryBudget};
+use crate::pipeline::Pipeline;
+use crate::progress::Progress;
+use crate::timing::timed;
+use crate::ImageBuffer;
+
+/// List the files in a directory that have an image file extension, sorted by name
+pub fn image_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        #[cfg(feature = "raw")]
+        let is_image = ImageFormat::from_path(&path).is_ok() || crate::raw::is_raw_path(&path);
+        #[cfg(not(feature = "raw"))]
+        let is_image = ImageFormat::from_path(&path).is_ok();
+        if path.is_file() && is_image {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    Ok(files)
+}
+
+/// Run a pipeline over each input image, saving each result (named after the input) in the
+/// output directory, as a png unless the output options choose another format
+///
+/// # Arguments
+///
+/// * `pipeline`: Pipeline to run
+/// * `inputs`: Images to process
+/// * `output_dir`: Directory to write the results to, created if missing
+/// * `options`: How to encode the results
+/// * `progress`: Progress over the whole batch, with each image an equal share
+///
+/// returns: Result<Vec<PathBuf>>, the paths written
+pub fn run_batch(pipeline: &Pipeline, inputs: &[PathBuf], output_dir: &Path, options: &OutputOptions, progress: &Progress) -> Result<Vec<PathBuf>> {
+    process_batch(inputs, output_dir, options, progress, |input, image_progress| pipeline.run_with_progress(input, image_progress))
+}
+
+/// As `run_batch`, with any processing of one image in place of a pipeline, given each image
+/// and its share of the progress
+pub fn process_batch<F>(inputs: &[PathBuf], output_dir: &Path, options: &OutputOptions, progress: &Progress, process: F) -> Result<Vec<PathBuf>>
+where
+    F: Fn(&ImageBuffer, &Progress) -> Result<ImageBuffer>,
+{
+    fs::create_dir_all(output_dir)?;
+
+    let count = inputs.len() as f32;
+    let mut outputs = Vec::with_capacity(inputs.len());
+
+    for (i, input_path) in inputs.iter().enumerate() {
+        let image_progress = progress.part(i as f32 / count, (i + 1) as f32 / count);
+        outputs.push(process_one(input_path, output_dir, options, &image_progress, &process)?);
+    }
+
+    Ok(outputs)
+}
+
+/// As `run_batch`, within a memory budget. As many images are processed at once as fit whole
+/// in the budget, up to one per core, and any image too large for its share is run in tiles
+/// sized to fit. The first error stops the batch.
+///
+/// # Arguments
+///
+/// * `pipeline`: Pipeline to run
+/// * `inputs`: Images to process
+/// * `output_dir`: Directory to write the results to, created if missing
+/// * `options`: How to encode the results
+/// * `budget`: Memory the whole batch may use
+/// * `progress`: Progress over the whole batch, reported as each image completes
+///
+/// returns: Result<Vec<PathBuf>>, the paths written, in the order of the inputs
+pub fn run_batch_with_budget(pipeline: &Pipeline, inputs: &[PathBuf], output_dir: &Path, options: &OutputOptions, budget: MemoryBudget, progress: &Progress) -> Result<Vec<PathBuf>> {
+    process_batch_with_budget(inputs, output_dir, options, budget, pipeline.halo(), progress, |input, tile_size, image_progress| match tile_size {
+        Some(tile_size) => pipeline.run_tiled_with_progress(input, tile_size, image_progress),
+        None => pipeline.run_with_progress(input, image_progress),
+    })
+}
+
+/// As `run_batch_with_budget`, with any processing of one image in place of a pipeline, given
+/// each image, the tile size to run it in if it doesn't fit whole, and its progress
+pub fn process_batch_with_budget<F>(inputs: &[PathBuf], output_dir: &Path, options: &OutputOptions, budget: MemoryBudget, halo: u32, progress: &Progress, process: F) -> Result<Vec<PathBuf>>
+where
+    F: Fn(&ImageBuffer, Option<u32>, &Progress) -> Result<ImageBuffer> + Sync,
+{
+    fs::create_dir_all(output_dir)?;
+
+    let workers = concurrent_images(inputs, budget);
+    let share = budget.share(workers);
+    info!("Processing {} images, {} at a time within {} bytes", inputs.len(), workers, budget.bytes());
+
+    let process_within = |input: &ImageBuffer, image_progress: &Progress| {
+        let tile_size = share.tile_size(input.width(), input.height(), halo);
+        if let Some(tile_size) = tile_size {
+            debug!("Running a {}x{} image in {} pixel tiles", input.width(), input.height(), tile_size);
+        }
+        process(input, tile_size, image_progress)
+    };
+
+    if workers == 1 {
+        return process_batch(inputs, output_dir, options, progress, process_within);
+    }
+
+    let next = AtomicUsize::new(0);
+    let completed = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results = Mutex::new(Vec::with_capacity(inputs.len()));
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while !failed.load(Ordering::Relaxed) {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(input_path) = inputs.get(i) else { break };
+
+                    let result = process_one(input_path, output_dir, options, &progress.cancellation_only(), &process_within);
+                    failed.fetch_or(result.is_err(), Ordering::Relaxed);
+                    progress.report((completed.fetch_add(1, Ordering::Relaxed) + 1) as f32 / inputs.len() as f32);
+                    results.lock().unwrap().push((i, result));
+                }
+            });
+        }
+    });
+
+    let mut results = results.into_inner().unwrap();
+    results.sort_by_key(|(i, _)| *i);
+    results.into_iter().map(|(_, result)| result).collect()
+}
+
+/// How many images of a batch fit in the budget at once, judged by the largest (read from the
+/// file headers) and limited to one per core. If any image's size can't be read without
+/// decoding it, they're run one at a time.
+fn concurrent_images(inputs: &[PathBuf], budget: MemoryBudget) -> usize {
+    let cores = thread::available_parallelism().map_or(1, |cores| cores.get()).min(inputs.len());
+    if cores <= 1 {
+        return 1;
+    }
+
+    let sizes: Option<Vec<(u32, u32)>> = inputs.iter().map(|path| image::image_dimensions(path).ok()).collect();
+    match sizes.and_then(|sizes| sizes.into_iter().max_by_key(|&(width, height)| image_bytes(width, height))) {
+        Some((width, height)) => budget.concurrent_images(width, height, cores),
+        None => 1,
+    }
+}
+
+/// Open, process and save one image of a batch, naming the result after the input
+fn process_one<F>(input_path: &Path, output_dir: &Path, options: &OutputOptions, progress: &Progress, process: &F) -> Result<PathBuf>
+where
+    F: Fn(&ImageBuffer, &Progress) -> Result<ImageBuffer>,
+{
+    progress.check()?;
+
+    info!("Processing {}", input_path.display());
+    let input = open_image_with_profile(input_path, options.profile())?;
+    let output = timed(&input_path.display().to_string(), || process(&input, progress))?;
+
+    let extension = options.format().unwrap_or(OutputFormat::Png).extension();
+    let stem = input_path.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    let output_path = output_dir.join(format!("{}.{}", stem, extension));
+    save_image(&output, &output_path, options, Some(input_path))?;
+
+    Ok(output_path)
+}
@@ -0,0 +1,590 @@
+//! Camera calibration from photos of a checkerboard: finding the board's inner corners in each
+//! photo, then estimating the camera's focal length, principal point and lens distortion from
+//! them by Zhang's method, refined by Levenberg–Marquardt. The result is saved as json and
+//! turned into the `LensDistortion` that `warp::undistort` corrects.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::features::harris_response;
+use crate::linalg::{cross, invert3, norm, null_vector, solve, transform3};
+use crate::plane::Plane;
+use crate::suppression::local_maxima;
+use crate::value::{json, Value};
+use crate::warp::{Homography, LensDistortion};
+use crate::ImageBuffer;
+
+/// Radius of the circle sampled around a candidate corner to check it's where four squares meet
+const JUNCTION_RADIUS: f32 = 5.;
+const JUNCTION_SAMPLES: usize = 32;
+/// Half width of the window gradients are taken from when refining corners to sub pixel
+const REFINE_RADIUS: i64 = 4;
+const REFINE_ITERATIONS: usize = 10;
+const MAX_ITERATIONS: usize = 100;
+
+/// A grid position and the image position it was found at
+type Correspondence = ((f64, f64), (f64, f64));
+
+/// What a calibration found, in pixels of images the size it was calibrated at
+#[derive(Clone, Debug, PartialEq)]
+pub struct CameraCalibration {
+    pub width: u32,
+    pub height: u32,
+    /// Horizontal and vertical focal length
+    pub focal_length: (f32, f32),
+    /// Principal point, where the optical axis meets the image
+    pub centre: (f32, f32),
+    pub k1: f32,
+    pub k2: f32,
+    pub p1: f32,
+    pub p2: f32,
+    /// Root mean square distance between the found corners and where the calibrated model puts them
+    pub rms_error: f32,
+}
+
+impl CameraCalibration {
+    /// The lens model for `warp::undistort`, scaled for images of a different size to the
+    /// calibration's, such as downscaled copies
+    pub fn lens_distortion(&self, width: u32, height: u32) -> LensDistortion {
+        let (sx, sy) = (width as f32 / self.width as f32, height as f32 / self.height as f32);
+
+        LensDistortion::new(self.k1, self.k2, self.p1, self.p2)
+            // Scaling about pixel corners rather than centres
+            .with_centre((self.centre.0 + 0.5) * sx - 0.5, (self.centre.1 + 0.5) * sy - 0.5)
+            .with_focal_length(self.focal_length.0 * sx, self.focal_length.1 * sy)
+    }
+
+    pub fn to_value(&self) -> Value {
+        let number = |v: f32| Value::Number(v as f64);
+        let pair = |(a, b): (f32, f32)| Value::Array(vec![number(a), number(b)]);
+
+        Value::Table(vec![
+            ("width".to_string(), Value::Number(self.width as f64)),
+            ("height".to_string(), Value::Number(self.height as f64)),
+            ("focal_length".to_string(), pair(self.focal_length)),
+            ("centre".to_string(), pair(self.centre)),
+            (
+                "distortion".to_string(),
+                Value::Table(vec![
+                    ("k1".to_string(), number(self.k1)),
+                    ("k2".to_string(), number(self.k2)),
+                    ("p1".to_string(), number(self.p1)),
+                    ("p2".to_string(), number(self.p2)),
+                ]),
+            ),
+            ("rms_error".to_string(), number(self.rms_error)),
+        ])
+    }
+
+    pub fn from_value(value: &Value) -> Result<CameraCalibration> {
+        let number = |value: &Value, key: &str| match value.get(key) {
+            Some(v) => v.as_f64().map(|v| v as f32).ok_or_else(|| invalid(key)),
+            None => Err(missing(key)),
+        };
+        let pair = |key: &str| match value.get(key).and_then(|v| v.as_array()) {
+            Some([a, b]) => Ok((a.as_f64().ok_or_else(|| invalid(key))? as f32, b.as_f64().ok_or_else(|| invalid(key))? as f32)),
+            Some(_) => Err(invalid(key)),
+            None => Err(missing(key)),
+        };
+        let distortion = value.get("distortion").ok_or_else(|| missing("distortion"))?;
+
+        Ok(CameraCalibration {
+            width: number(value, "width")? as u32,
+            height: number(value, "height")? as u32,
+            focal_length: pair("focal_length")?,
+            centre: pair("centre")?,
+            k1: number(distortion, "k1")?,
+            k2: number(distortion, "k2")?,
+            p1: number(distortion, "p1")?,
+            p2: number(distortion, "p2")?,
+            rms_error: number(value, "rms_error").unwrap_or(0.),
+        })
+    }
+
+    /// Write the calibration as a json file
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        Ok(fs::write(path, json::to_string(&self.to_value()))?)
+    }
+
+    /// Read a calibration json file written by `save`
+    pub fn load(path: impl AsRef<Path>) -> Result<CameraCalibration> {
+        CameraCalibration::from_value(&json::parse(&fs::read_to_string(path)?)?)
+    }
+}
+
+fn missing(parameter: &str) -> Error {
+    Error::MissingParameter { operation: "calibration".to_string(), parameter: parameter.to_string() }
+}
+
+fn invalid(parameter: &str) -> Error {
+    Error::InvalidParameter { operation: "calibration".to_string(), parameter: parameter.to_string() }
+}
+
+/// Find the inner corners of a checkerboard, where four squares meet
+///
+/// # Arguments
+///
+/// * `input`: Photo of the board, which should fill a good part of the frame
+/// * `columns`: Inner corners along each row of the board, one less than its squares
+/// * `rows`: Inner corners down each column of the board
+///
+/// returns: Option<Vec<(f32, f32)>>, the `columns * rows` corners a row at a time to sub pixel
+/// accuracy, or None if the whole board wasn't found
+pub fn find_checkerboard(input: &ImageBuffer, columns: usize, rows: usize) -> Option<Vec<(f32, f32)>> {
+    let expected = columns * rows;
+    if columns < 2 || rows < 2 {
+        return None;
+    }
+
+    let luma = Plane::from_luma(input).gaussian_blur(1.);
+    let response = harris_response(&luma, 1.5, 0.04);
+    let gradients = luma.gradients();
+    let candidates: Vec<(f32, f32)> = local_maxima(&response, 0.01, JUNCTION_RADIUS as u32)
+        .into_iter()
+        .map(|peak| refine_corner(&gradients, (peak.x as f32, peak.y as f32)))
+        .filter(|&point| is_junction(&luma, point))
+        .take(expected * 3)
+        .collect();
+    if candidates.len() < expected {
+        return None;
+    }
+
+    let hull = convex_hull(&candidates);
+    let quad = largest_quadrilateral(&hull)?;
+
+    // The hull's corners are the board's outer corners, but which side runs along the rows
+    // isn't known, so try both and keep whichever explains every corner
+    let labellings = [
+        [(0, 0), (columns - 1, 0), (columns - 1, rows - 1), (0, rows - 1)],
+        [(0, 0), (0, rows - 1), (columns - 1, rows - 1), (columns - 1, 0)],
+    ];
+    labellings
+        .iter()
+        .filter_map(|labels| match_grid(&candidates, &quad, labels, columns, rows))
+        .next()
+}
+
+/// Whether the intensity around a point alternates light, dark, light, dark, as around a
+/// checkerboard corner, with opposite sides mostly alike
+fn is_junction(luma: &Plane, (x, y): (f32, f32)) -> bool {
+    let samples: Vec<f32> = (0..JUNCTION_SAMPLES)
+        .map(|i| {
+            let angle = i as f32 / JUNCTION_SAMPLES as f32 * std::f32::consts::TAU;
+            luma.sample(x + JUNCTION_RADIUS * angle.cos(), y + JUNCTION_RADIUS * angle.sin())
+        })
+        .collect();
+
+    let (min, max) = samples.iter().fold((f32::MAX, f32::MIN), |(min, max), &v| (min.min(v), max.max(v)));
+    if max - min < 30. {
+        return false;
+    }
+
+    let middle = (min + max) / 2.;
+    let light: Vec<bool> = samples.iter().map(|&v| v > middle).collect();
+    let changes = (0..JUNCTION_SAMPLES).filter(|&i| light[i] != light[(i + 1) % JUNCTION_SAMPLES]).count();
+    let opposite_alike = (0..JUNCTION_SAMPLES / 2).filter(|&i| light[i] == light[i + JUNCTION_SAMPLES / 2]).count();
+
+    // Allowing for the point being a little off the corner
+    changes == 4 && opposite_alike * 8 >= JUNCTION_SAMPLES / 2 * 5
+}
+
+/// Move a corner to the point every nearby gradient is perpendicular to the direction from,
+/// as edges leading into a corner all point at it
+fn refine_corner((dx, dy): &(Plane, Plane), (mut x, mut y): (f32, f32)) -> (f32, f32) {
+    for _ in 0..REFINE_ITERATIONS {
+        let (mut a, mut b, mut c, mut bx, mut by) = (0f64, 0f64, 0f64, 0f64, 0f64);
+        for oy in -REFINE_RADIUS..=REFINE_RADIUS {
+            for ox in -REFINE_RADIUS..=REFINE_RADIUS {
+                let (px, py) = (x.round() as i64 + ox, y.round() as i64 + oy);
+                let (gx, gy) = (dx.get_clamped(px, py) as f64, dy.get_clamped(px, py) as f64);
+                let weight = (-((ox * ox + oy * oy) as f64) / (REFINE_RADIUS * REFINE_RADIUS) as f64).exp();
+                let (gxx, gxy, gyy) = (gx * gx * weight, gx * gy * weight, gy * gy * weight);
+                a += gxx;
+                b += gxy;
+                c += gyy;
+                bx += gxx * px as f64 + gxy * py as f64;
+                by += gxy * px as f64 + gyy * py as f64;
+            }
+        }
+
+        let determinant = a * c - b * b;
+        if determinant.abs() < 1e-9 {
+            break;
+        }
+        let (nx, ny) = (((c * bx - b * by) / determinant) as f32, ((a * by - b * bx) / determinant) as f32);
+        // Refinement shouldn't wander off to another corner
+        if (nx - x).hypot(ny - y) > REFINE_RADIUS as f32 {
+            break;
+        }
+        let moved = (nx - x).hypot(ny - y);
+        (x, y) = (nx, ny);
+        if moved < 0.01 {
+            break;
+        }
+    }
+
+    (x, y)
+}
+
+/// Convex hull by Andrew's monotone chain, anticlockwise without collinear points
+fn convex_hull(points: &[(f32, f32)]) -> Vec<(f32, f32)> {
+    let mut sorted = points.to_vec();
+    sorted.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    let turn = |o: (f32, f32), a: (f32, f32), b: (f32, f32)| (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0);
+
+    let mut hull: Vec<(f32, f32)> = Vec::new();
+    for pass in 0..2 {
+        let start = hull.len();
+        let points: Box<dyn Iterator<Item = &(f32, f32)>> = if pass == 0 { Box::new(sorted.iter()) } else { Box::new(sorted.iter().rev()) };
+        for &point in points {
+            while hull.len() >= start + 2 && turn(hull[hull.len() - 2], hull[hull.len() - 1], point) <= 0. {
+                hull.pop();
+            }
+            hull.push(point);
+        }
+        hull.pop();
+    }
+
+    hull
+}
+
+/// The four hull points enclosing the most area, in hull order
+fn largest_quadrilateral(hull: &[(f32, f32)]) -> Option<[(f32, f32); 4]> {
+    let area = |q: [(f32, f32); 4]| (0..4).map(|i| q[i].0 * q[(i + 1) % 4].1 - q[(i + 1) % 4].0 * q[i].1).sum::<f32>().abs();
+    let n = hull.len();
+
+    let mut best = None;
+    let mut best_area = 0.;
+    for a in 0..n {
+        for b in a + 1..n {
+            for c in b + 1..n {
+                for d in c + 1..n {
+                    let quad = [hull[a], hull[b], hull[c], hull[d]];
+                    if area(quad) > best_area {
+                        best_area = area(quad);
+                        best = Some(quad);
+                    }
+                }
+            }
+        }
+    }
+
+    best
+}
+
+/// Assign candidates to grid positions, growing out from the board's outer corners. Each
+/// position is predicted by an affine fit to the nearby positions already matched, so the
+/// prediction follows the board as lens distortion bends it.
+fn match_grid(candidates: &[(f32, f32)], quad: &[(f32, f32); 4], labels: &[(usize, usize); 4], columns: usize, rows: usize) -> Option<Vec<(f32, f32)>> {
+    let grid: Vec<(f64, f64)> = labels.iter().map(|&(i, j)| (i as f64, j as f64)).collect();
+    let image: Vec<(f64, f64)> = quad.iter().map(|&(x, y)| (x as f64, y as f64)).collect();
+    let homography = Homography::from_points(&grid, &image)?;
+
+    let mut matched: Vec<Option<(f32, f32)>> = vec![None; columns * rows];
+    let mut used = vec![false; candidates.len()];
+    for (&(i, j), corner) in labels.iter().zip(quad) {
+        let k = candidates.iter().position(|c| c == corner)?;
+        matched[j * columns + i] = Some(*corner);
+        used[k] = true;
+    }
+
+    loop {
+        let mut progress = false;
+
+        for j in 0..rows {
+            for i in 0..columns {
+                if matched[j * columns + i].is_some() {
+                    continue;
+                }
+
+                let nearby: Vec<Correspondence> = (j.saturating_sub(2)..(j + 3).min(rows))
+                    .flat_map(|nj| (i.saturating_sub(2)..(i + 3).min(columns)).map(move |ni| (ni, nj)))
+                    .filter_map(|(ni, nj)| matched[nj * columns + ni].map(|(x, y)| ((ni as f64, nj as f64), (x as f64, y as f64))))
+                    .collect();
+                let adjacent = nearby.iter().any(|&((ni, nj), _)| (ni - i as f64).abs() <= 1. && (nj - j as f64).abs() <= 1.);
+                if !adjacent {
+                    continue;
+                }
+
+                let (predicted, spacing) = match affine_fit(&nearby) {
+                    Some(affine) => {
+                        let at = |x: f64, y: f64| (affine[0] * x + affine[1] * y + affine[2], affine[3] * x + affine[4] * y + affine[5]);
+                        let spacing = affine[0].hypot(affine[3]).min(affine[1].hypot(affine[4]));
+                        (at(i as f64, j as f64), spacing)
+                    }
+                    None => {
+                        let predicted = homography.apply(i as f64, j as f64);
+                        let beside = homography.apply(i as f64 + 1., j as f64);
+                        let below = homography.apply(i as f64, j as f64 + 1.);
+                        (predicted, (beside.0 - predicted.0).hypot(beside.1 - predicted.1).min((below.0 - predicted.0).hypot(below.1 - predicted.1)))
+                    }
+                };
+
+                // Corners are accepted within a third of the local square size of the prediction
+                let nearest = candidates
+                    .iter()
+                    .enumerate()
+                    .filter(|&(k, _)| !used[k])
+                    .map(|(k, &(x, y))| (k, (x as f64 - predicted.0).hypot(y as f64 - predicted.1)))
+                    .filter(|&(_, distance)| distance < spacing / 3.)
+                    .min_by(|a, b| a.1.total_cmp(&b.1));
+                if let Some((k, _)) = nearest {
+                    matched[j * columns + i] = Some(candidates[k]);
+                    used[k] = true;
+                    progress = true;
+                }
+            }
+        }
+
+        if !progress {
+            break;
+        }
+    }
+
+    matched.into_iter().collect()
+}
+
+/// Least squares affine map `[a b c; d e f]` from grid to image positions, None unless the grid
+/// positions span both directions
+fn affine_fit(pairs: &[Correspondence]) -> Option<[f64; 6]> {
+    let mut normal = vec![vec![0.; 3]; 3];
+    let (mut bx, mut by) = (vec![0.; 3], vec![0.; 3]);
+    for &((gx, gy), (x, y)) in pairs {
+        let row = [gx, gy, 1.];
+        for a in 0..3 {
+            for b in 0..3 {
+                normal[a][b] += row[a] * row[b];
+            }
+            bx[a] += row[a] * x;
+            by[a] += row[a] * y;
+        }
+    }
+
+    let determinant = normal[0][0] * normal[1][1] - normal[0][1] * normal[1][0];
+    if pairs.len() < 3 || determinant.abs() < 1e-9 {
+        return None;
+    }
+    let (x, y) = (solve(&normal, &bx)?, solve(&normal, &by)?);
+    Some([x[0], x[1], x[2], y[0], y[1], y[2]])
+}
+
+/// Estimate a camera's intrinsics and lens distortion from the checkerboard corners found in
+/// several photos of the board, at different angles
+///
+/// # Arguments
+///
+/// * `views`: The corners of each photo, from `find_checkerboard`
+/// * `columns`, `rows`: The board's inner corners
+/// * `width`, `height`: Size of the photos
+///
+/// returns: Result<CameraCalibration>
+pub fn calibrate(views: &[Vec<(f32, f32)>], columns: usize, rows: usize, width: u32, height: u32) -> Result<CameraCalibration> {
+    if views.len() < 2 {
+        return Err(Error::Calibration(format!("needs at least 2 views of the board, got {}", views.len())));
+    }
+    if let Some(view) = views.iter().find(|view| view.len() != columns * rows) {
+        return Err(Error::Calibration(format!("a view has {} corners rather than {}", view.len(), columns * rows)));
+    }
+
+    // The board is the z = 0 plane, in units of squares
+    let board: Vec<(f64, f64)> = (0..columns * rows).map(|k| ((k % columns) as f64, (k / columns) as f64)).collect();
+    let views: Vec<Vec<(f64, f64)>> = views.iter().map(|view| view.iter().map(|&(x, y)| (x as f64, y as f64)).collect()).collect();
+
+    let homographies = views
+        .iter()
+        .map(|view| Homography::from_points(&board, view))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| Error::Calibration("a view's corners are degenerate".to_string()))?;
+
+    let camera = initial_intrinsics(&homographies).ok_or_else(|| Error::Calibration("views are too alike to calibrate from, vary the board's angle".to_string()))?;
+    let mut parameters = vec![camera[0], camera[1], camera[2], camera[3], 0., 0., 0., 0.];
+    for homography in &homographies {
+        parameters.extend(initial_pose(&camera, homography));
+    }
+
+    let parameters = refine(parameters, &board, &views);
+    let residuals = reprojection_residuals(&parameters, &board, &views);
+    let rms_error = (residuals.iter().map(|r| r * r).sum::<f64>() / (residuals.len() / 2) as f64).sqrt();
+
+    Ok(CameraCalibration {
+        width,
+        height,
+        focal_length: (parameters[0] as f32, parameters[1] as f32),
+        centre: (parameters[2] as f32, parameters[3] as f32),
+        k1: parameters[4] as f32,
+        k2: parameters[5] as f32,
+        p1: parameters[6] as f32,
+        p2: parameters[7] as f32,
+        rms_error: rms_error as f32,
+    })
+}
+
+/// Zhang's closed form focal length and principal point, with no skew, from the constraints
+/// each homography puts on the image of the absolute conic
+fn initial_intrinsics(homographies: &[Homography]) -> Option<[f64; 4]> {
+    let v = |h: &Homography, i: usize, j: usize| {
+        let (hi, hj) = ([0, 1, 2].map(|r| h.matrix[r][i]), [0, 1, 2].map(|r| h.matrix[r][j]));
+        [hi[0] * hj[0], hi[0] * hj[1] + hi[1] * hj[0], hi[1] * hj[1], hi[2] * hj[0] + hi[0] * hj[2], hi[2] * hj[1] + hi[1] * hj[2], hi[2] * hj[2]]
+    };
+
+    let mut rows = Vec::new();
+    for h in homographies {
+        // Homographies are scaled to unit size so every view weighs the same
+        let scale = h.matrix.iter().flatten().map(|v| v * v).sum::<f64>().sqrt();
+        let h = Homography { matrix: h.matrix.map(|row| row.map(|v| v / scale)) };
+        rows.push(v(&h, 0, 1).to_vec());
+        let (v11, v22) = (v(&h, 0, 0), v(&h, 1, 1));
+        rows.push((0..6).map(|k| v11[k] - v22[k]).collect());
+    }
+    // Zero skew, which otherwise needs a third view to pin down
+    rows.push(vec![0., 1., 0., 0., 0., 0.]);
+
+    let b = null_vector(&rows);
+    let b = if b[0] < 0. { b.iter().map(|v| -v).collect() } else { b };
+    let (b11, b12, b22, b13, b23, b33) = (b[0], b[1], b[2], b[3], b[4], b[5]);
+
+    let denominator = b11 * b22 - b12 * b12;
+    let cy = (b12 * b13 - b11 * b23) / denominator;
+    let lambda = b33 - (b13 * b13 + cy * (b12 * b13 - b11 * b23)) / b11;
+    let fx = (lambda / b11).sqrt();
+    let fy = (lambda * b11 / denominator).sqrt();
+    let cx = -b13 * fx * fx / lambda;
+
+    [fx, fy, cx, cy].iter().all(|v| v.is_finite()).then_some([fx, fy, cx, cy])
+}
+
+/// The board's rotation, as a rotation vector, and translation in a view, from its homography
+fn initial_pose(camera: &[f64; 4], homography: &Homography) -> [f64; 6] {
+    let k = [[camera[0], 0., camera[2]], [0., camera[1], camera[3]], [0., 0., 1.]];
+    let k_inverse = invert3(&k).unwrap_or(k);
+    let column = |i: usize| transform3(&k_inverse, [0, 1, 2].map(|r| homography.matrix[r][i]));
+    let (h1, h2, h3) = (column(0), column(1), column(2));
+
+    // The board must be in front of the camera
+    let mut lambda = 1. / norm(&h1);
+    if h3[2] * lambda < 0. {
+        lambda = -lambda;
+    }
+    let r1 = h1.map(|v| v * lambda);
+    let r2 = h2.map(|v| v * lambda);
+    let t = h3.map(|v| v * lambda);
+
+    // Noise leaves the columns slightly off orthonormal, so square them up
+    let r1 = r1.map(|v| v / norm(&r1));
+    let dot = r1[0] * r2[0] + r1[1] * r2[1] + r1[2] * r2[2];
+    let r2 = [0, 1, 2].map(|i| r2[i] - dot * r1[i]);
+    let r2 = r2.map(|v| v / norm(&r2));
+    let r3 = cross(r1, r2);
+
+    let rotation = [[r1[0], r2[0], r3[0]], [r1[1], r2[1], r3[1]], [r1[2], r2[2], r3[2]]];
+    let r = rotation_vector(&rotation);
+    [r[0], r[1], r[2], t[0], t[1], t[2]]
+}
+
+fn rotation_vector(r: &[[f64; 3]; 3]) -> [f64; 3] {
+    let cos = ((r[0][0] + r[1][1] + r[2][2] - 1.) / 2.).clamp(-1., 1.);
+    let angle = cos.acos();
+    let axis = [r[2][1] - r[1][2], r[0][2] - r[2][0], r[1][0] - r[0][1]];
+    let sin = norm(&axis) / 2.;
+
+    if sin < 1e-9 {
+        return [0.; 3];
+    }
+    axis.map(|v| v / (2. * sin) * angle)
+}
+
+/// Rodrigues' formula
+fn rotation_matrix(v: &[f64]) -> [[f64; 3]; 3] {
+    let angle = norm(v);
+    if angle < 1e-12 {
+        return [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]];
+    }
+
+    let (x, y, z) = (v[0] / angle, v[1] / angle, v[2] / angle);
+    let (s, c) = angle.sin_cos();
+    let t = 1. - c;
+    [
+        [t * x * x + c, t * x * y - s * z, t * x * z + s * y],
+        [t * x * y + s * z, t * y * y + c, t * y * z - s * x],
+        [t * x * z - s * y, t * y * z + s * x, t * z * z + c],
+    ]
+}
+
+/// Differences between the found corners and their projections, x and y for every corner of
+/// every view, for parameters `fx, fy, cx, cy, k1, k2, p1, p2` then six pose values per view
+fn reprojection_residuals(parameters: &[f64], board: &[(f64, f64)], views: &[Vec<(f64, f64)>]) -> Vec<f64> {
+    let [fx, fy, cx, cy, k1, k2, p1, p2] = parameters[..8].try_into().unwrap();
+    let mut residuals = Vec::with_capacity(board.len() * views.len() * 2);
+
+    for (view, pose) in views.iter().zip(parameters[8..].chunks_exact(6)) {
+        let rotation = rotation_matrix(&pose[..3]);
+        for (&(bx, by), &(u, v)) in board.iter().zip(view) {
+            let [x, y, z] = transform3(&rotation, [bx, by, 0.]);
+            let (x, y) = ((x + pose[3]) / (z + pose[5]), (y + pose[4]) / (z + pose[5]));
+
+            let r2 = x * x + y * y;
+            let radial = 1. + k1 * r2 + k2 * r2 * r2;
+            let xd = x * radial + 2. * p1 * x * y + p2 * (r2 + 2. * x * x);
+            let yd = y * radial + p1 * (r2 + 2. * y * y) + 2. * p2 * x * y;
+
+            residuals.push(fx * xd + cx - u);
+            residuals.push(fy * yd + cy - v);
+        }
+    }
+
+    residuals
+}
+
+/// Levenberg–Marquardt on the reprojection error, with a forward difference jacobian
+fn refine(mut parameters: Vec<f64>, board: &[(f64, f64)], views: &[Vec<(f64, f64)>]) -> Vec<f64> {
+    let cost = |parameters: &[f64]| reprojection_residuals(parameters, board, views).iter().map(|r| r * r).sum::<f64>();
+    let mut damping = 1e-3;
+    let mut current = cost(&parameters);
+
+    for _ in 0..MAX_ITERATIONS {
+        let residuals = reprojection_residuals(&parameters, board, views);
+        let jacobian: Vec<Vec<f64>> = (0..parameters.len())
+            .map(|p| {
+                let step = 1e-6 * parameters[p].abs().max(1e-2);
+                let mut moved = parameters.clone();
+                moved[p] += step;
+                reprojection_residuals(&moved, board, views).iter().zip(&residuals).map(|(a, b)| (a - b) / step).collect()
+            })
+            .collect();
+
+        let n = parameters.len();
+        let gradient: Vec<f64> = (0..n).map(|i| jacobian[i].iter().zip(&residuals).map(|(j, r)| j * r).sum()).collect();
+        let normal: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| jacobian[i].iter().zip(&jacobian[j]).map(|(a, b)| a * b).sum()).collect()).collect();
+
+        let mut improved = false;
+        while damping < 1e12 {
+            let mut damped = normal.clone();
+            for (i, row) in damped.iter_mut().enumerate() {
+                row[i] += damping * normal[i][i].max(1e-12);
+            }
+
+            let Some(delta) = solve(&damped, &gradient.iter().map(|g| -g).collect::<Vec<_>>()) else {
+                damping *= 10.;
+                continue;
+            };
+            let candidate: Vec<f64> = parameters.iter().zip(&delta).map(|(p, d)| p + d).collect();
+            let candidate_cost = cost(&candidate);
+            if candidate_cost < current {
+                let relative = (current - candidate_cost) / current.max(1e-300);
+                parameters = candidate;
+                current = candidate_cost;
+                damping = (damping / 10.).max(1e-12);
+                improved = relative > 1e-12;
+                break;
+            }
+            damping *= 10.;
+        }
+
+        if !improved {
+            break;
+        }
+    }
+
+    parameters
+}
@@ -0,0 +1,390 @@
+//! Layered composition: a canvas of layers drawn bottom to top, each an image, an adjustment
+//! (a pipeline run on everything beneath it) or a line of text, placed at a position with an
+//! opacity, a blend mode and optionally a mask. Canvases are built in code or read from a json,
+//! toml or yaml file, e.g. a photo, a contrast adjustment, a watermark and a caption:
+//!
+//! ```toml
+//! [[layers]]
+//! image = "photo.jpg"
+//!
+//! [[layers]]
+//! pipeline = [{ op = "contrast", value = 2.0 }]
+//! opacity = 0.5
+//!
+//! [[layers]]
+//! image = "logo.png"
+//! x = 20
+//! y = 20
+//! opacity = 0.6
+//! blend = "screen"
+//!
+//! [[layers]]
+//! text = "Summer 2024"
+//! x = 20
+//! y = 400
+//! colour = "#ffffff"
+//! scale = 3
+//! ```
+
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::io::open_image;
+use crate::pipeline::{Pipeline, PipelineFormat};
+use crate::pixel::parse_colour;
+use crate::plane::Plane;
+use crate::text::{draw_text, text_size};
+use crate::value::{json, toml, yaml, Value};
+use crate::ImageBuffer;
+
+/// Furthest a layer read from a file may be placed from the canvas, in pixels, each way
+const MAX_POSITION: f64 = 1e9;
+
+/// Largest text scale read from a file, far past any canvas, so sizes stay in range
+const MAX_TEXT_SCALE: f64 = 1e6;
+
+/// How a layer's colours combine with the colours beneath it, before its opacity is applied
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BlendMode {
+    /// The layer's colour
+    #[default]
+    Normal,
+    /// The product, darkening everywhere but under white
+    Multiply,
+    /// The inverse of the product of the inverses, lightening everywhere but under black
+    Screen,
+    /// Multiply in the shadows beneath and screen in the highlights, adding contrast
+    Overlay,
+    /// The darker of the two
+    Darken,
+    /// The lighter of the two
+    Lighten,
+    /// The sum, clipped to white
+    Add,
+    /// The absolute difference, black where they match
+    Difference,
+}
+
+impl BlendMode {
+    pub fn from_name(name: &str) -> Option<BlendMode> {
+        match name {
+            "normal" => Some(BlendMode::Normal),
+            "multiply" => Some(BlendMode::Multiply),
+            "screen" => Some(BlendMode::Screen),
+            "overlay" => Some(BlendMode::Overlay),
+            "darken" => Some(BlendMode::Darken),
+            "lighten" => Some(BlendMode::Lighten),
+            "add" => Some(BlendMode::Add),
+            "difference" => Some(BlendMode::Difference),
+            _ => None,
+        }
+    }
+
+    /// Blend one channel of the layer over the channel beneath, both from 0 to 1
+    ///
+    /// # Arguments
+    ///
+    /// * `below`: Value beneath the layer
+    /// * `layer`: Value of the layer
+    ///
+    /// returns: f32, from 0 to 1
+    pub fn blend(&self, below: f32, layer: f32) -> f32 {
+        match self {
+            BlendMode::Normal => layer,
+            BlendMode::Multiply => below * layer,
+            BlendMode::Screen => below + layer - below * layer,
+            BlendMode::Overlay => {
+                if below <= 0.5 {
+                    2. * below * layer
+                } else {
+                    1. - 2. * (1. - below) * (1. - layer)
+                }
+            }
+            BlendMode::Darken => below.min(layer),
+            BlendMode::Lighten => below.max(layer),
+            BlendMode::Add => (below + layer).min(1.),
+            BlendMode::Difference => (below - layer).abs(),
+        }
+    }
+}
+
+/// What a layer draws
+#[derive(Clone, Debug, PartialEq)]
+enum Content {
+    Image(ImageBuffer),
+    Adjustment(Pipeline),
+    Text { text: String, colour: image::Rgba<u8>, scale: u32 },
+}
+
+/// One layer of a canvas
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    content: Content,
+    x: i64,
+    y: i64,
+    opacity: f32,
+    blend_mode: BlendMode,
+    mask: Option<Plane>,
+}
+
+impl Layer {
+    fn new(content: Content) -> Layer {
+        Layer { content, x: 0, y: 0, opacity: 1., blend_mode: BlendMode::Normal, mask: None }
+    }
+
+    /// A layer of an image, drawn with its own transparency
+    pub fn image(image: ImageBuffer) -> Layer {
+        Layer::new(Content::Image(image))
+    }
+
+    /// An adjustment layer, covering the canvas with what the pipeline makes of the layers
+    /// beneath it. Its position is ignored.
+    pub fn adjustment(pipeline: Pipeline) -> Layer {
+        Layer::new(Content::Adjustment(pipeline))
+    }
+
+    /// A line of text in the built in bitmap font, `scale` image pixels to each font pixel
+    pub fn text(text: impl Into<String>, colour: image::Rgba<u8>, scale: u32) -> Layer {
+        Layer::new(Content::Text { text: text.into(), colour, scale: scale.max(1) })
+    }
+
+    /// Position of the layer's top left corner on the canvas, which may be off it
+    pub fn with_position(mut self, x: i64, y: i64) -> Layer {
+        self.x = x;
+        self.y = y;
+        self
+    }
+
+    /// Opacity from 0, invisible, to 1
+    pub fn with_opacity(mut self, opacity: f32) -> Layer {
+        self.opacity = opacity.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Layer {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    /// Mask from 0, hiding the layer, to 1, showing it, the size of the layer (of the canvas for
+    /// an adjustment)
+    pub fn with_mask(mut self, mask: Plane) -> Layer {
+        self.mask = Some(mask);
+        self
+    }
+
+    /// Build a layer from a canvas file entry, a table holding one of `image` (a path),
+    /// `pipeline` (a list of steps as in pipeline files) or `text`, with optional `x`, `y`,
+    /// `opacity`, `blend`, `mask` (the path of a grey image) and for text `colour` and `scale`
+    ///
+    /// # Arguments
+    ///
+    /// * `entry`: Table describing the layer
+    /// * `base_dir`: Directory relative paths are read from
+    ///
+    /// returns: Result<Layer>
+    pub fn from_value(entry: &Value, base_dir: &Path) -> Result<Layer> {
+        let mut layer = match (entry.get("image"), entry.get("pipeline"), entry.get("text")) {
+            (Some(path), None, None) => Layer::image(open_image(base_dir.join(path.as_str().ok_or_else(|| invalid("image"))?))?),
+            (None, Some(steps), None) => Layer::adjustment(Pipeline::from_value(steps)?),
+            (None, None, Some(text)) => {
+                let colour = match entry.get("colour") {
+                    Some(colour) => colour.as_str().and_then(parse_colour).ok_or_else(|| invalid("colour"))?,
+                    None => image::Rgba([255, 255, 255, 255]),
+                };
+                let scale = optional_number(entry, "scale")?.unwrap_or(1.);
+                Layer::text(text.as_str().ok_or_else(|| invalid("text"))?, colour, scale.clamp(1., MAX_TEXT_SCALE) as u32)
+            }
+            (None, None, None) => return Err(Error::MissingParameter { operation: "layer".to_string(), parameter: "image".to_string() }),
+            _ => return Err(invalid("image")),
+        };
+
+        let position = |value: f64| value.round().clamp(-MAX_POSITION, MAX_POSITION) as i64;
+        let (x, y) = (optional_number(entry, "x")?.unwrap_or(0.), optional_number(entry, "y")?.unwrap_or(0.));
+        layer = layer.with_position(position(x), position(y)).with_opacity(optional_number(entry, "opacity")?.unwrap_or(1.) as f32);
+        if let Some(name) = entry.get("blend") {
+            layer = layer.with_blend_mode(name.as_str().and_then(BlendMode::from_name).ok_or_else(|| invalid("blend"))?);
+        }
+        if let Some(path) = entry.get("mask") {
+            let mask = open_image(base_dir.join(path.as_str().ok_or_else(|| invalid("mask"))?))?;
+            layer = layer.with_mask(Plane::from_luma(&mask).map(|v| v / 255.));
+        }
+
+        Ok(layer)
+    }
+}
+
+/// Layers composited in order onto a background
+#[derive(Clone, Debug, PartialEq)]
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    background: image::Rgba<u8>,
+    layers: Vec<Layer>,
+}
+
+impl Canvas {
+    /// An empty canvas of a size, transparent until layers are added
+    pub fn new(width: u32, height: u32) -> Canvas {
+        Canvas { width, height, background: image::Rgba([0, 0, 0, 0]), layers: Vec::new() }
+    }
+
+    pub fn with_background(mut self, background: image::Rgba<u8>) -> Canvas {
+        self.background = background;
+        self
+    }
+
+    /// Add a layer on top of those already added
+    pub fn with_layer(mut self, layer: Layer) -> Canvas {
+        self.layers.push(layer);
+        self
+    }
+
+    pub fn layers(&self) -> &[Layer] {
+        &self.layers
+    }
+
+    /// Read a canvas file, with the format picked from the file extension and layer paths
+    /// relative to the file
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path to a .json, .toml, .yaml or .yml file
+    ///
+    /// returns: Result<Canvas>
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Canvas> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)?;
+        let document = match PipelineFormat::from_path(path)? {
+            PipelineFormat::Json => json::parse(&source)?,
+            PipelineFormat::Toml => toml::parse(&source)?,
+            PipelineFormat::Yaml => yaml::parse(&source)?,
+        };
+
+        Canvas::from_value(&document, path.parent().unwrap_or(Path::new(".")))
+    }
+
+    /// Build a canvas from a table with its layers under `layers`, bottom first, its size under
+    /// `width` and `height` (by default the size of the first image layer) and optionally a
+    /// `background` colour
+    ///
+    /// # Arguments
+    ///
+    /// * `document`: Table describing the canvas
+    /// * `base_dir`: Directory relative paths are read from
+    ///
+    /// returns: Result<Canvas>
+    pub fn from_value(document: &Value, base_dir: &Path) -> Result<Canvas> {
+        let layers = match document.get("layers") {
+            Some(Value::Array(entries)) => entries.iter().map(|entry| Layer::from_value(entry, base_dir)).collect::<Result<Vec<_>>>()?,
+            Some(_) => return Err(invalid("layers")),
+            None => Vec::new(),
+        };
+
+        let first_image = layers.iter().find_map(|layer| match &layer.content {
+            Content::Image(image) => Some(image.dimensions()),
+            _ => None,
+        });
+        let width = match optional_number(document, "width")? {
+            Some(width) => width as u32,
+            None => first_image.ok_or_else(|| Error::MissingParameter { operation: "canvas".to_string(), parameter: "width".to_string() })?.0,
+        };
+        let height = match optional_number(document, "height")? {
+            Some(height) => height as u32,
+            None => first_image.ok_or_else(|| Error::MissingParameter { operation: "canvas".to_string(), parameter: "height".to_string() })?.1,
+        };
+
+        let mut canvas = Canvas::new(width, height);
+        if let Some(colour) = document.get("background") {
+            canvas = canvas.with_background(colour.as_str().and_then(parse_colour).ok_or_else(|| invalid("background"))?);
+        }
+        canvas.layers = layers;
+
+        Ok(canvas)
+    }
+
+    /// Composite the layers bottom to top
+    ///
+    /// returns: Result<ImageBuffer>, an error if a layer's mask isn't the size of the layer
+    pub fn render(&self) -> Result<ImageBuffer> {
+        let mut output = ImageBuffer::from_pixel(self.width, self.height, self.background);
+
+        for layer in &self.layers {
+            // The image drawn, where, the layer's full size and where in it the image starts
+            let (source, (x, y), size, offset) = match &layer.content {
+                Content::Image(image) => (Cow::Borrowed(image), (layer.x, layer.y), image.dimensions(), (0, 0)),
+                Content::Adjustment(pipeline) => (Cow::Owned(pipeline.run(&output)), (0, 0), output.dimensions(), (0, 0)),
+                Content::Text { text, colour, scale } => {
+                    // Only the part of the text on the canvas is drawn, however large the text
+                    let (width, height) = text_size(text, *scale);
+                    let visible = |position: i64, size: u32, canvas: u32| {
+                        let start = position.clamp(0, canvas as i64);
+                        (start, position.saturating_add(size as i64).clamp(start, canvas as i64))
+                    };
+                    let ((left, right), (top, bottom)) = (visible(layer.x, width, self.width), visible(layer.y, height, self.height));
+                    let offset = |start: i64, position: i64| start.saturating_sub(position).min(u32::MAX as i64) as u32;
+
+                    let mut image = ImageBuffer::new((right - left) as u32, (bottom - top) as u32);
+                    draw_text(&mut image, layer.x - left, layer.y - top, text, *colour, *scale);
+                    (Cow::Owned(image), (left, top), (width, height), (offset(left, layer.x), offset(top, layer.y)))
+                }
+            };
+
+            if let Some(mask) = &layer.mask {
+                if (mask.width, mask.height) != size {
+                    return Err(Error::DimensionMismatch { expected: size, actual: (mask.width, mask.height) });
+                }
+            }
+            composite(&mut output, &source, x, y, offset, layer);
+        }
+
+        Ok(output)
+    }
+}
+
+/// Draw a layer's image over the canvas at a position, mixing its colour with the canvas by the
+/// blend mode where the canvas is opaque, then compositing it over by its alpha, opacity and mask.
+/// `mask_offset` is where the image starts within the layer's mask, for layers drawn clipped.
+fn composite(output: &mut ImageBuffer, source: &ImageBuffer, x: i64, y: i64, mask_offset: (u32, u32), layer: &Layer) {
+    let (width, height) = (output.width() as i64, output.height() as i64);
+
+    for sy in 0..source.height() {
+        for sx in 0..source.width() {
+            let (ox, oy) = (x.saturating_add(sx as i64), y.saturating_add(sy as i64));
+            if ox < 0 || oy < 0 || ox >= width || oy >= height {
+                continue;
+            }
+
+            let coverage = layer.mask.as_ref().map_or(1., |mask| mask.get(sx + mask_offset.0, sy + mask_offset.1).clamp(0., 1.));
+            let layer_pixel = source.get_pixel(sx, sy);
+            let layer_alpha = layer_pixel[3] as f32 / 255. * layer.opacity * coverage;
+            if layer_alpha <= 0. {
+                continue;
+            }
+
+            let below = output.get_pixel_mut(ox as u32, oy as u32);
+            let below_alpha = below[3] as f32 / 255.;
+            let alpha = layer_alpha + below_alpha * (1. - layer_alpha);
+            for channel in 0..3 {
+                let (under, over) = (below[channel] as f32 / 255., layer_pixel[channel] as f32 / 255.);
+                let mixed = (1. - below_alpha) * over + below_alpha * layer.blend_mode.blend(under, over);
+                let value = (mixed * layer_alpha + under * below_alpha * (1. - layer_alpha)) / alpha;
+                below[channel] = (value * 255.).round().clamp(0., 255.) as u8;
+            }
+            below[3] = (alpha * 255.).round().clamp(0., 255.) as u8;
+        }
+    }
+}
+
+fn invalid(parameter: &str) -> Error {
+    Error::InvalidParameter { operation: "layer".to_string(), parameter: parameter.to_string() }
+}
+
+fn optional_number(entry: &Value, parameter: &str) -> Result<Option<f64>> {
+    match entry.get(parameter) {
+        Some(value) => value.as_f64().map(Some).ok_or_else(|| invalid(parameter)),
+        None => Ok(None),
+    }
+}
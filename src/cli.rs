@@ -0,0 +1,59 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// Command line arguments split into the subcommand, its `--name value` options and `--name`
+/// flags
+pub struct Args {
+    pub command: Option<String>,
+    options: HashMap<String, String>,
+    flags: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct ArgsError(pub String);
+
+impl fmt::Display for ArgsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for ArgsError {}
+
+impl Args {
+    /// Parse arguments (without the program name), `flag_names` lists the options that take no
+    /// value
+    pub fn parse(args: impl IntoIterator<Item = String>, flag_names: &[&str]) -> Result<Args, ArgsError> {
+        let mut args = args.into_iter();
+        let command = args.next();
+        let mut options = HashMap::new();
+        let mut flags = Vec::new();
+
+        while let Some(arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if flag_names.contains(&name) => flags.push(name.to_string()),
+                Some(name) => match args.next() {
+                    Some(value) => {
+                        options.insert(name.to_string(), value);
+                    }
+                    None => return Err(ArgsError(format!("missing value for --{}", name))),
+                },
+                None => return Err(ArgsError(format!("unexpected argument '{}'", arg))),
+            }
+        }
+
+        Ok(Args { command, options, flags })
+    }
+
+    pub fn option(&self, name: &str) -> Option<&str> {
+        self.options.get(name).map(|s| s.as_str())
+    }
+
+    pub fn flag(&self, name: &str) -> bool {
+        self.flags.iter().any(|flag| flag == name)
+    }
+
+    pub fn required(&self, name: &str) -> Result<&str, ArgsError> {
+        self.option(name).ok_or_else(|| ArgsError(format!("missing required option --{}", name)))
+    }
+}
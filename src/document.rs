@@ -0,0 +1,390 @@
+//! Cleaning up photos of documents into flat black and white scans: finding the page, squaring
+//! it up and binarising the text
+
+use image::imageops::{resize, FilterType};
+use log::warn;
+
+use crate::plane::Plane;
+use crate::segmentation::connected_components;
+use crate::shape::convex_hull;
+use crate::warp::{rotate, warp_perspective, Homography};
+use crate::ImageBuffer;
+
+/// Photos are shrunk to at most this on their longest side to find the page
+const DETECTION_SIZE: u32 = 512;
+
+/// Fraction of each side at either end left out when fitting lines to the page outline, where
+/// the corners are rounded off
+const SIDE_TRIM: f64 = 0.1;
+
+/// Furthest in pixels at detection size an outline pixel can be from a side to be fitted to it
+const SIDE_DISTANCE: f64 = 4.;
+
+/// Fraction trimmed off each edge of a found page, where slivers of the background would
+/// otherwise show as black lines
+const PAGE_MARGIN: f64 = 0.01;
+
+/// Largest skew in degrees either way `estimate_skew` looks for
+const MAX_SKEW: f32 = 15.;
+
+/// Least fraction of the photo the page must cover to be taken as found
+const MIN_PAGE_AREA: f64 = 0.2;
+
+/// A point on a line and the line's direction
+type Line = ((f64, f64), (f64, f64));
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScanOptions {
+    detect_page: bool,
+    threshold_radius: u32,
+    threshold_offset: f32,
+    despeckle_size: u32,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions { detect_page: true, threshold_radius: 15, threshold_offset: 10., despeckle_size: 8 }
+    }
+}
+
+impl ScanOptions {
+    /// Look for the page and square it up, true by default. Without it the whole photo is
+    /// binarised as it is.
+    pub fn with_page_detection(mut self, detect_page: bool) -> ScanOptions {
+        self.detect_page = detect_page;
+        self
+    }
+
+    /// Radius in pixels of the neighbourhood each pixel is compared with, 15 by default, a little
+    /// more than the thickness of the text
+    pub fn with_threshold_radius(mut self, radius: u32) -> ScanOptions {
+        self.threshold_radius = radius;
+        self
+    }
+
+    /// How much darker in luma than its neighbourhood a pixel must be to turn black, 10 by default
+    pub fn with_threshold_offset(mut self, offset: f32) -> ScanOptions {
+        self.threshold_offset = offset;
+        self
+    }
+
+    /// Black specks up to this many pixels are removed, 8 by default, 0 to keep everything
+    pub fn with_despeckle_size(mut self, size: u32) -> ScanOptions {
+        self.despeckle_size = size;
+        self
+    }
+}
+
+/// Turn a photo of a document into a clean black and white scan: find the page, correct the
+/// perspective to square it up and trim its edges, then threshold adaptively and remove specks.
+/// If the page can't be found the whole photo is used.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer of a photo of a page, on a darker background
+/// * `options`: Page detection, threshold and despeckle settings
+///
+/// returns: ImageBuffer
+pub fn scan(input: &ImageBuffer, options: &ScanOptions) -> ImageBuffer {
+    let page = if options.detect_page {
+        match find_page(input) {
+            Some(corners) => {
+                let page = rectify_page(input, &corners);
+                let (dx, dy) = ((page.width() as f64 * PAGE_MARGIN).round() as u32, (page.height() as f64 * PAGE_MARGIN).round() as u32);
+                image::imageops::crop_imm(&page, dx, dy, page.width() - 2 * dx, page.height() - 2 * dy).to_image()
+            }
+            None => {
+                warn!("No page found, using the whole image");
+                input.clone()
+            }
+        }
+    } else {
+        input.clone()
+    };
+
+    let binary = adaptive_threshold(&page, options.threshold_radius, options.threshold_offset);
+    despeckle(&binary, options.despeckle_size)
+}
+
+/// Find the corners of a page in a photo, taking the page as the largest bright area and its
+/// outline as the quadrilateral best fitting the area's convex hull
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer of a photo of a page, on a darker background
+///
+/// returns: Option<[(f64, f64); 4]>, the corners clockwise from the top left, or None if no
+/// bright area covers a fifth of the photo
+pub fn find_page(input: &ImageBuffer) -> Option<[(f64, f64); 4]> {
+    let scale = (DETECTION_SIZE as f64 / input.width().max(input.height()) as f64).min(1.);
+    let (width, height) = (((input.width() as f64 * scale).round() as u32).max(1), ((input.height() as f64 * scale).round() as u32).max(1));
+    let luma = Plane::from_luma(&resize(input, width, height, FilterType::Triangle)).gaussian_blur(1.);
+
+    let threshold = otsu_threshold(&luma);
+    let components = connected_components(&luma.map(|v| if v > threshold { 1. } else { 0. }));
+    let sizes = components.sizes();
+    let page = (1..components.count).max_by_key(|&label| sizes[label as usize])?;
+
+    // Only the ends of each row can be on the hull
+    let mut ends = Vec::new();
+    for y in 0..height {
+        let row = (0..width).filter(|&x| components.get(x, y) == page);
+        if let (Some(first), Some(last)) = (row.clone().next(), row.max()) {
+            ends.push((first as f64, y as f64));
+            ends.push((last as f64, y as f64));
+        }
+    }
+
+    let mut corners = convex_hull(ends);
+    while corners.len() > 4 {
+        // Drop the corner cutting off the least area, until a quadrilateral is left
+        let n = corners.len();
+        let cut = |i: usize| triangle_area(corners[(i + n - 1) % n], corners[i], corners[(i + 1) % n]);
+        let least = (0..n).min_by(|&a, &b| cut(a).total_cmp(&cut(b)))?;
+        corners.remove(least);
+    }
+    if corners.len() < 4 {
+        return None;
+    }
+
+    let area = triangle_area(corners[0], corners[1], corners[2]) + triangle_area(corners[0], corners[2], corners[3]);
+    if area < MIN_PAGE_AREA * width as f64 * height as f64 {
+        return None;
+    }
+
+    let boundary: Vec<(f64, f64)> = (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .filter(|&(x, y)| {
+            let inside = |dx: i64, dy: i64| {
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                nx >= 0 && ny >= 0 && nx < width as i64 && ny < height as i64 && components.get(nx as u32, ny as u32) == page
+            };
+            inside(0, 0) && !(inside(-1, 0) && inside(1, 0) && inside(0, -1) && inside(0, 1))
+        })
+        .map(|(x, y)| (x as f64, y as f64))
+        .collect();
+
+    let full_size = |(x, y): (f64, f64)| ((x + 0.5) / scale - 0.5, (y + 0.5) / scale - 0.5);
+    Some(order_corners(refine_corners(&corners, &boundary).map(full_size)))
+}
+
+/// Put the corners of a convex quadrilateral in order clockwise from the top left, the one
+/// nearest the image's top left corner
+pub(crate) fn order_corners(mut corners: [(f64, f64); 4]) -> [(f64, f64); 4] {
+    // Angles about the centre run clockwise with y down
+    let (cx, cy) = (corners.iter().map(|c| c.0).sum::<f64>() / 4., corners.iter().map(|c| c.1).sum::<f64>() / 4.);
+    corners.sort_by(|a, b| (a.1 - cy).atan2(a.0 - cx).total_cmp(&(b.1 - cy).atan2(b.0 - cx)));
+    let top_left = (0..4).min_by(|&a, &b| (corners[a].0 + corners[a].1).total_cmp(&(corners[b].0 + corners[b].1))).unwrap_or(0);
+    corners.rotate_left(top_left);
+    corners
+}
+
+/// Move the corners of a quadrilateral to where lines fitted along the middle of each side
+/// meet, as the blurred outline rounds the corners off and cutting the hull down to four
+/// points leaves them short of the true corners
+fn refine_corners(corners: &[(f64, f64)], boundary: &[(f64, f64)]) -> [(f64, f64); 4] {
+    let sides: Vec<Option<Line>> = (0..4)
+        .map(|i| {
+            let (a, b) = (corners[i], corners[(i + 1) % 4]);
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            let length_squared = dx * dx + dy * dy;
+            let near: Vec<(f64, f64)> = boundary
+                .iter()
+                .copied()
+                .filter(|p| {
+                    let t = ((p.0 - a.0) * dx + (p.1 - a.1) * dy) / length_squared;
+                    let distance = ((p.0 - a.0) * dy - (p.1 - a.1) * dx).abs() / length_squared.sqrt();
+                    (SIDE_TRIM..=1. - SIDE_TRIM).contains(&t) && distance <= SIDE_DISTANCE
+                })
+                .collect();
+            fit_line(&near)
+        })
+        .collect();
+
+    std::array::from_fn(|i| match (sides[(i + 3) % 4], sides[i]) {
+        (Some(before), Some(after)) => intersect(before, after).unwrap_or(corners[i]),
+        _ => corners[i],
+    })
+}
+
+/// Total least squares line through points
+fn fit_line(points: &[(f64, f64)]) -> Option<Line> {
+    if points.len() < 2 {
+        return None;
+    }
+    let count = points.len() as f64;
+    let (cx, cy) = (points.iter().map(|p| p.0).sum::<f64>() / count, points.iter().map(|p| p.1).sum::<f64>() / count);
+    let (xx, xy, yy) = points.iter().fold((0., 0., 0.), |(xx, xy, yy), p| (xx + (p.0 - cx).powi(2), xy + (p.0 - cx) * (p.1 - cy), yy + (p.1 - cy).powi(2)));
+    let angle = 0.5 * (2. * xy).atan2(xx - yy);
+    Some(((cx, cy), (angle.cos(), angle.sin())))
+}
+
+fn intersect((p, d): Line, (q, e): Line) -> Option<(f64, f64)> {
+    let denominator = d.0 * e.1 - d.1 * e.0;
+    if denominator.abs() < 1e-9 {
+        return None;
+    }
+    let t = ((q.0 - p.0) * e.1 - (q.1 - p.1) * e.0) / denominator;
+    Some((p.0 + t * d.0, p.1 + t * d.1))
+}
+
+/// Square up a page by perspective correction, to the length of its longer top or bottom edge
+/// and longer left or right edge
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer of a photo of a page
+/// * `corners`: Corners of the page clockwise from the top left, as from `find_page`
+///
+/// returns: ImageBuffer
+pub fn rectify_page(input: &ImageBuffer, corners: &[(f64, f64); 4]) -> ImageBuffer {
+    let length = |a: (f64, f64), b: (f64, f64)| (a.0 - b.0).hypot(a.1 - b.1);
+    let width = length(corners[0], corners[1]).max(length(corners[3], corners[2])).round().max(1.);
+    let height = length(corners[0], corners[3]).max(length(corners[1], corners[2])).round().max(1.);
+
+    let page = [(0., 0.), (width - 1., 0.), (width - 1., height - 1.), (0., height - 1.)];
+    match Homography::from_points(corners, &page) {
+        Some(homography) => warp_perspective(input, &homography, width as u32, height as u32),
+        None => input.clone(),
+    }
+}
+
+/// Estimate how far the lines of text in a scan are turned from level, by projection profiles:
+/// the dark pixels are summed along rows at each angle tried, and the angle where the sums vary
+/// most, with rows of text and the gaps between them lined up, is the skew. Angles are tried in
+/// half degree steps up to 15 degrees either way, then refined in twentieths around the best.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer of a scan of text, dark on light
+///
+/// returns: f32, the skew in degrees, positive when lines run down to the right
+pub fn estimate_skew(input: &ImageBuffer) -> f32 {
+    let scale = (DETECTION_SIZE as f32 * 2. / input.width().max(input.height()) as f32).min(1.);
+    let (width, height) = (((input.width() as f32 * scale).round() as u32).max(1), ((input.height() as f32 * scale).round() as u32).max(1));
+    let binary = adaptive_threshold(&resize(input, width, height, FilterType::Triangle), 15, 10.);
+    let dark: Vec<(f32, f32)> = binary.enumerate_pixels().filter(|(_, _, p)| p[0] == 0).map(|(x, y, _)| (x as f32, y as f32)).collect();
+    if dark.is_empty() {
+        return 0.;
+    }
+
+    let best = |angles: Vec<f32>| {
+        angles.into_iter().map(|degrees| (degrees, profile_spread(&dark, width + height, degrees))).max_by(|a, b| a.1.total_cmp(&b.1)).map_or(0., |(degrees, _)| degrees)
+    };
+    let coarse = best((-30..=30).map(|i| i as f32 * MAX_SKEW / 30.).collect());
+    best((-10..=10).map(|i| coarse + i as f32 * 0.05).collect())
+}
+
+/// How much the projection profile of points along lines turned by an angle varies, as the sum
+/// of squares of its rows, which ranks the variances as every angle has the same total. Rows run
+/// along lines where y cos - x sin is constant.
+fn profile_spread(points: &[(f32, f32)], size: u32, degrees: f32) -> f64 {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let mut rows = vec![0u64; size as usize * 2 + 1];
+    for &(x, y) in points {
+        rows[(y * cos - x * sin + size as f32).round() as usize] += 1;
+    }
+    rows.iter().map(|&n| (n * n) as f64).sum()
+}
+
+/// Level the lines of text of a scan, rotating by the skew from `estimate_skew`. Corners
+/// uncovered by the rotation are filled with the median colour of the scan's edges.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer of a scan of text, dark on light
+///
+/// returns: ImageBuffer
+pub fn deskew(input: &ImageBuffer) -> ImageBuffer {
+    let skew = estimate_skew(input);
+    let (width, height) = input.dimensions();
+    if skew == 0. || width == 0 || height == 0 {
+        return input.clone();
+    }
+
+    let mut edge: Vec<image::Rgba<u8>> = (0..width).flat_map(|x| [*input.get_pixel(x, 0), *input.get_pixel(x, height - 1)]).chain((0..height).flat_map(|y| [*input.get_pixel(0, y), *input.get_pixel(width - 1, y)])).collect();
+    edge.sort_by_key(|p| p[0] as u32 + p[1] as u32 + p[2] as u32);
+    let fill = edge[edge.len() / 2];
+
+    let mut output = rotate(input, -skew);
+    for pixel in output.pixels_mut() {
+        if pixel[3] == 0 {
+            *pixel = fill;
+        }
+    }
+    output
+}
+
+/// Threshold each pixel against the mean luma of its neighbourhood, so shadows and uneven
+/// lighting across a page don't turn areas black the way a single threshold would
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to threshold
+/// * `radius`: Radius in pixels of the neighbourhood, e.g. 15
+/// * `offset`: How much darker in luma than its neighbourhood a pixel must be to turn black, e.g. 10
+///
+/// returns: ImageBuffer, black and white
+pub fn adaptive_threshold(input: &ImageBuffer, radius: u32, offset: f32) -> ImageBuffer {
+    let luma = Plane::from_luma(input);
+    let mean = luma.box_blur(radius);
+    luma.zip(&mean, |value, mean| if value < mean - offset { 0. } else { 255. }).to_image()
+}
+
+/// Remove small specks of black from a black and white image, turning them white
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer, black and white
+/// * `max_size`: Largest speck to remove in pixels
+///
+/// returns: ImageBuffer
+pub fn despeckle(input: &ImageBuffer, max_size: u32) -> ImageBuffer {
+    let components = connected_components(&Plane::from_luma(input).map(|v| if v < 128. { 1. } else { 0. }));
+    let sizes = components.sizes();
+
+    let mut output = input.clone();
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let label = components.get(x, y);
+        if label != 0 && sizes[label as usize] <= max_size as u64 {
+            *pixel = image::Rgba([255, 255, 255, pixel[3]]);
+        }
+    }
+    output
+}
+
+/// Threshold splitting a plane's values into the two classes with the least variance within
+/// them (Otsu, 1979)
+fn otsu_threshold(plane: &Plane) -> f32 {
+    let mut histogram = [0u64; 256];
+    for &v in &plane.data {
+        histogram[v.clamp(0., 255.) as usize] += 1;
+    }
+
+    let total = plane.data.len() as f64;
+    let sum: f64 = histogram.iter().enumerate().map(|(v, &n)| v as f64 * n as f64).sum();
+    let (mut below, mut below_sum) = (0., 0.);
+    // A plane of one value has no split, and nothing is above it
+    let (mut best, mut best_variance) = (255, 0.);
+
+    for (v, &n) in histogram.iter().enumerate() {
+        below += n as f64;
+        below_sum += v as f64 * n as f64;
+        let above = total - below;
+        if below == 0. || above == 0. {
+            continue;
+        }
+
+        let difference = below_sum / below - (sum - below_sum) / above;
+        let variance = below * above * difference * difference;
+        if variance > best_variance {
+            (best, best_variance) = (v, variance);
+        }
+    }
+
+    best as f32
+}
+
+fn triangle_area(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)).abs() / 2.
+}
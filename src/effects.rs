@@ -0,0 +1,136 @@
+//! Glitch art effects, imitating broken video signals and damaged files. The random ones take a
+//! seed and always give the same result for the same seed, so a piece can be reproduced.
+
+use image::codecs::jpeg::JpegEncoder;
+use rand::Rng;
+
+use crate::error::Result;
+use crate::random::rng;
+use crate::ImageBuffer;
+
+/// Longest run of rows `scanline_displacement` moves together
+const MAX_BAND_HEIGHT: u32 = 24;
+
+/// Shift the red, green and blue channels by separate offsets, splitting edges into coloured
+/// fringes. Pixels shifted in from beyond the edge repeat the edge.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to shift
+/// * `offsets`: Horizontal and vertical offset in pixels of each of r, g, b, e.g.
+///   `[(-6, 0), (0, 0), (6, 2)]`
+///
+/// returns: ImageBuffer
+pub fn channel_shift(input: &ImageBuffer, offsets: [(i32, i32); 3]) -> ImageBuffer {
+    let (width, height) = input.dimensions();
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let mut pixel = *input.get_pixel(x, y);
+        for (c, (dx, dy)) in offsets.iter().enumerate() {
+            let source_x = (x as i64 - *dx as i64).clamp(0, width as i64 - 1) as u32;
+            let source_y = (y as i64 - *dy as i64).clamp(0, height as i64 - 1) as u32;
+            pixel[c] = input.get_pixel(source_x, source_y)[c];
+        }
+        pixel
+    })
+}
+
+/// Slide random bands of rows sideways, wrapping around, like a video signal losing sync
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to displace
+/// * `max_offset`: Furthest a band moves in pixels, either way
+/// * `probability`: Chance from 0 to 1 of a band starting at each row
+/// * `seed`: Seed for the random bands
+///
+/// returns: ImageBuffer
+pub fn scanline_displacement(input: &ImageBuffer, max_offset: u32, probability: f32, seed: u64) -> ImageBuffer {
+    let (width, height) = input.dimensions();
+    let mut rng = rng(Some(seed));
+    let mut output = input.clone();
+
+    let mut y = 0;
+    while y < height {
+        if !rng.gen_bool(probability.clamp(0., 1.) as f64) {
+            y += 1;
+            continue;
+        }
+
+        let band = rng.gen_range(1..=MAX_BAND_HEIGHT).min(height - y);
+        let offset = rng.gen_range(-(max_offset as i64)..=max_offset as i64);
+        for row in y..y + band {
+            for x in 0..width {
+                let source_x = (x as i64 - offset).rem_euclid(width as i64) as u32;
+                output.put_pixel(x, row, *input.get_pixel(source_x, row));
+            }
+        }
+        y += band;
+    }
+
+    output
+}
+
+/// Swap random pairs of square blocks, like a file with corrupted chunks. Only whole blocks
+/// move, so a part block on the right or bottom edge stays put.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to shuffle
+/// * `block_size`: Width and height of the blocks in pixels
+/// * `fraction`: Fraction of the blocks to swap, from 0 to 1
+/// * `seed`: Seed for which blocks are swapped
+///
+/// returns: ImageBuffer
+pub fn block_shuffle(input: &ImageBuffer, block_size: u32, fraction: f32, seed: u64) -> ImageBuffer {
+    let block_size = block_size.max(1);
+    let (columns, rows) = (input.width() / block_size, input.height() / block_size);
+    let blocks = columns * rows;
+    let mut output = input.clone();
+    if blocks < 2 {
+        return output;
+    }
+
+    let mut rng = rng(Some(seed));
+    let swaps = (blocks as f32 * fraction.clamp(0., 1.) / 2.).round() as u32;
+    for _ in 0..swaps {
+        let (a, b) = (rng.gen_range(0..blocks), rng.gen_range(0..blocks));
+        let (ax, ay) = (a % columns * block_size, a / columns * block_size);
+        let (bx, by) = (b % columns * block_size, b / columns * block_size);
+
+        for dy in 0..block_size {
+            for dx in 0..block_size {
+                let (pixel_a, pixel_b) = (*output.get_pixel(ax + dx, ay + dy), *output.get_pixel(bx + dx, by + dy));
+                output.put_pixel(ax + dx, ay + dy, pixel_b);
+                output.put_pixel(bx + dx, by + dy, pixel_a);
+            }
+        }
+    }
+
+    output
+}
+
+/// Add the blocking and ringing of heavy jpeg compression, by compressing the image and
+/// decoding it again, as many times as asked since artifacts build up with each generation.
+/// Alpha is kept from the input, as jpeg has none.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to degrade
+/// * `quality`: Jpeg quality from 1 to 100, lower is blockier, e.g. 5
+/// * `generations`: Times to compress, at least one
+///
+/// returns: Result<ImageBuffer>
+pub fn jpeg_artifacts(input: &ImageBuffer, quality: u8, generations: u32) -> Result<ImageBuffer> {
+    let mut rgb = image::DynamicImage::ImageRgba8(input.clone()).to_rgb8();
+
+    for _ in 0..generations.max(1) {
+        let mut encoded = Vec::new();
+        JpegEncoder::new_with_quality(&mut encoded, quality.clamp(1, 100)).encode_image(&rgb)?;
+        rgb = image::load_from_memory(&encoded)?.to_rgb8();
+    }
+
+    Ok(ImageBuffer::from_fn(input.width(), input.height(), |x, y| {
+        let [r, g, b] = rgb.get_pixel(x, y).0;
+        image::Rgba([r, g, b, input.get_pixel(x, y)[3]])
+    }))
+}
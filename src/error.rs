@@ -0,0 +1,89 @@
+use std::fmt;
+
+/// Errors raised when reading pipeline descriptions or reading and writing images
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Image(image::ImageError),
+    Codec(String),
+    Parse { line: usize, message: String },
+    UnsupportedFormat(String),
+    UnknownOperation(String),
+    MissingParameter { operation: String, parameter: String },
+    InvalidParameter { operation: String, parameter: String },
+    Cancelled,
+    BufferSize { expected: usize, actual: usize },
+    DimensionMismatch { expected: (u32, u32), actual: (u32, u32) },
+    Http(String),
+    Calibration(String),
+    Golden(String),
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "io error: {}", err),
+            Error::Image(err) => write!(f, "image error: {}", err),
+            Error::Codec(message) => write!(f, "codec error: {}", message),
+            Error::Parse { line, message } => write!(f, "parse error on line {}: {}", line, message),
+            Error::UnsupportedFormat(format) => write!(f, "unsupported format: {}", format),
+            Error::UnknownOperation(name) => write!(f, "unknown operation: {}", name),
+            Error::MissingParameter { operation, parameter } => {
+                write!(f, "operation '{}' is missing parameter '{}'", operation, parameter)
+            }
+            Error::InvalidParameter { operation, parameter } => {
+                write!(f, "operation '{}' has an invalid value for parameter '{}'", operation, parameter)
+            }
+            Error::Cancelled => write!(f, "cancelled"),
+            Error::BufferSize { expected, actual } => {
+                write!(f, "expected a buffer of {} bytes but got {}", expected, actual)
+            }
+            Error::DimensionMismatch { expected, actual } => {
+                write!(f, "expected an image of {}x{} but got {}x{}", expected.0, expected.1, actual.0, actual.1)
+            }
+            Error::Http(message) => write!(f, "http error: {}", message),
+            Error::Calibration(message) => write!(f, "calibration failed: {}", message),
+            Error::Golden(message) => write!(f, "golden image check failed: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            Error::Image(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+impl From<image::ImageError> for Error {
+    fn from(err: image::ImageError) -> Self {
+        Error::Image(err)
+    }
+}
+
+impl From<png::DecodingError> for Error {
+    fn from(err: png::DecodingError) -> Self {
+        Error::Codec(err.to_string())
+    }
+}
+
+impl From<png::EncodingError> for Error {
+    fn from(err: png::EncodingError) -> Self {
+        Error::Codec(err.to_string())
+    }
+}
+
+pub(crate) fn parse_error(line: usize, message: impl Into<String>) -> Error {
+    Error::Parse { line, message: message.into() }
+}
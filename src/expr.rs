@@ -0,0 +1,453 @@
+//! Pixel expressions: arithmetic over one or more images written as text, e.g. `0.5*a + 0.5*b`
+//! or `clamp(a - b + 128)`, generalising `image_add`, `image_sub` and `linear_blend`. The
+//! expression is compiled once to a small stack program and run for each red, green and blue
+//! value, with `a` standing for the first image's value, `b` the second's and so on up to `h`.
+//!
+//! The language has numbers, `+ - * / %`, `^` for powers, comparisons `< <= > >= == !=` giving
+//! 1 or 0, parentheses, the variables `x`, `y`, `width`, `height` and `channel` (0 for red, 1
+//! green, 2 blue), and the functions `clamp(v)` (to 0 to 255) or `clamp(v, low, high)`, `min`,
+//! `max`, `abs`, `sqrt`, `pow`, `exp`, `log`, `sin`, `cos`, `floor`, `round` and
+//! `if(condition, then, else)`. The result is rounded and clamped to 0 to 255, and the alpha
+//! taken from the first image.
+
+use crate::error::{Error, Result};
+use crate::ImageBuffer;
+
+/// Deepest nesting of brackets, calls and negations accepted, so a long run of `(` or `-` is a
+/// parse error rather than a stack overflow
+const MAX_DEPTH: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Function {
+    Clamp,
+    Min,
+    Max,
+    Abs,
+    Sqrt,
+    Pow,
+    Exp,
+    Log,
+    Sin,
+    Cos,
+    Floor,
+    Round,
+    If,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Function> {
+        match name {
+            "clamp" => Some(Function::Clamp),
+            "min" => Some(Function::Min),
+            "max" => Some(Function::Max),
+            "abs" => Some(Function::Abs),
+            "sqrt" => Some(Function::Sqrt),
+            "pow" => Some(Function::Pow),
+            "exp" => Some(Function::Exp),
+            "log" => Some(Function::Log),
+            "sin" => Some(Function::Sin),
+            "cos" => Some(Function::Cos),
+            "floor" => Some(Function::Floor),
+            "round" => Some(Function::Round),
+            "if" => Some(Function::If),
+            _ => None,
+        }
+    }
+
+    /// Whether the function takes this many arguments
+    fn accepts(&self, count: usize) -> bool {
+        match self {
+            Function::Clamp => count == 1 || count == 3,
+            Function::Min | Function::Max => count >= 2,
+            Function::Pow => count == 2,
+            Function::If => count == 3,
+            _ => count == 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Instruction {
+    Constant(f32),
+    Input(usize),
+    X,
+    Y,
+    Width,
+    Height,
+    Channel,
+    Negate,
+    Binary(char),
+    Compare(&'static str),
+    Call(Function, usize),
+}
+
+/// Values of an expression's variables at one pixel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Variables<'a> {
+    /// Value of each input image, `a` first
+    pub inputs: &'a [f32],
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub channel: usize,
+}
+
+/// A compiled pixel expression
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expression {
+    program: Vec<Instruction>,
+    inputs: usize,
+}
+
+impl Expression {
+    /// Compile an expression
+    ///
+    /// # Arguments
+    ///
+    /// * `source`: The expression, e.g. `clamp(a - b + 128)`
+    ///
+    /// returns: Result<Expression>, a parse error giving the column of any mistake
+    pub fn parse(source: &str) -> Result<Expression> {
+        let mut parser = Parser { tokens: tokenise(source)?, position: 0, program: Vec::new(), inputs: 0, depth: 0 };
+        parser.comparison()?;
+        if let Some(token) = parser.tokens.get(parser.position) {
+            return Err(parse_error(token.column, &format!("unexpected '{}'", token.text)));
+        }
+
+        Ok(Expression { program: parser.program, inputs: parser.inputs })
+    }
+
+    /// The number of input images the expression needs, one more than the highest it refers to
+    pub fn inputs(&self) -> usize {
+        self.inputs
+    }
+
+    /// Evaluate the expression at one pixel, unrounded and unclamped
+    ///
+    /// # Arguments
+    ///
+    /// * `variables`: Values of the variables, with at least `inputs()` input values
+    ///
+    /// returns: f32
+    pub fn evaluate(&self, variables: &Variables) -> f32 {
+        self.run(variables, &mut Vec::with_capacity(self.program.len()))
+    }
+
+    /// Evaluate the expression over images of the same size, for each of their red, green and
+    /// blue values
+    ///
+    /// # Arguments
+    ///
+    /// * `images`: Input images, `a` first, at least as many as the expression refers to
+    ///
+    /// returns: Result<ImageBuffer>, an error if there are too few images or they differ in size
+    pub fn apply(&self, images: &[ImageBuffer]) -> Result<ImageBuffer> {
+        if images.len() < self.inputs.max(1) {
+            let missing = (b'a' + images.len() as u8) as char;
+            return Err(Error::MissingParameter { operation: "expr".to_string(), parameter: missing.to_string() });
+        }
+        let (width, height) = images[0].dimensions();
+        if let Some(image) = images.iter().find(|image| image.dimensions() != (width, height)) {
+            return Err(Error::DimensionMismatch { expected: (width, height), actual: image.dimensions() });
+        }
+
+        let mut stack = Vec::with_capacity(self.program.len());
+        let mut inputs = vec![0.; images.len()];
+        let mut output = images[0].clone();
+        for (x, y, pixel) in output.enumerate_pixels_mut() {
+            for channel in 0..3 {
+                for (value, image) in inputs.iter_mut().zip(images) {
+                    *value = image.get_pixel(x, y)[channel] as f32;
+                }
+                let variables = Variables { inputs: &inputs, x, y, width, height, channel };
+                pixel[channel] = self.run(&variables, &mut stack).round().clamp(0., 255.) as u8;
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn run(&self, variables: &Variables, stack: &mut Vec<f32>) -> f32 {
+        stack.clear();
+        for instruction in &self.program {
+            let value = match *instruction {
+                Instruction::Constant(value) => value,
+                Instruction::Input(index) => variables.inputs.get(index).copied().unwrap_or(0.),
+                Instruction::X => variables.x as f32,
+                Instruction::Y => variables.y as f32,
+                Instruction::Width => variables.width as f32,
+                Instruction::Height => variables.height as f32,
+                Instruction::Channel => variables.channel as f32,
+                Instruction::Negate => -pop(stack),
+                Instruction::Binary(operator) => {
+                    let (left, right) = pop_pair(stack);
+                    match operator {
+                        '+' => left + right,
+                        '-' => left - right,
+                        '*' => left * right,
+                        '/' => left / right,
+                        '%' => left % right,
+                        _ => left.powf(right),
+                    }
+                }
+                Instruction::Compare(operator) => {
+                    let (left, right) = pop_pair(stack);
+                    let result = match operator {
+                        "<" => left < right,
+                        "<=" => left <= right,
+                        ">" => left > right,
+                        ">=" => left >= right,
+                        "==" => left == right,
+                        _ => left != right,
+                    };
+                    if result { 1. } else { 0. }
+                }
+                Instruction::Call(function, count) => {
+                    let start = stack.len().saturating_sub(count);
+                    let value = call(function, &stack[start..]);
+                    stack.truncate(start);
+                    value
+                }
+            };
+            stack.push(value);
+        }
+        pop(stack)
+    }
+}
+
+/// Evaluate an expression over images, see `Expression`
+///
+/// # Arguments
+///
+/// * `source`: The expression, e.g. `0.5*a + 0.5*b`
+/// * `images`: Input images, `a` first, all the same size
+///
+/// returns: Result<ImageBuffer>
+pub fn evaluate_images(source: &str, images: &[ImageBuffer]) -> Result<ImageBuffer> {
+    Expression::parse(source)?.apply(images)
+}
+
+fn pop(stack: &mut Vec<f32>) -> f32 {
+    stack.pop().unwrap_or(0.)
+}
+
+fn pop_pair(stack: &mut Vec<f32>) -> (f32, f32) {
+    let right = pop(stack);
+    (pop(stack), right)
+}
+
+fn call(function: Function, arguments: &[f32]) -> f32 {
+    let first = arguments.first().copied().unwrap_or(0.);
+    match function {
+        Function::Clamp if arguments.len() == 3 => first.clamp(arguments[1].min(arguments[2]), arguments[2].max(arguments[1])),
+        Function::Clamp => first.clamp(0., 255.),
+        Function::Min => arguments.iter().copied().fold(f32::INFINITY, f32::min),
+        Function::Max => arguments.iter().copied().fold(f32::NEG_INFINITY, f32::max),
+        Function::Abs => first.abs(),
+        Function::Sqrt => first.sqrt(),
+        Function::Pow => first.powf(arguments[1]),
+        Function::Exp => first.exp(),
+        Function::Log => first.ln(),
+        Function::Sin => first.sin(),
+        Function::Cos => first.cos(),
+        Function::Floor => first.floor(),
+        Function::Round => first.round(),
+        Function::If => if first != 0. { arguments[1] } else { arguments[2] },
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Token {
+    text: String,
+    column: usize,
+}
+
+/// Split an expression into numbers, names and operators
+fn tokenise(source: &str) -> Result<Vec<Token>> {
+    let characters: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < characters.len() {
+        let c = characters[i];
+        let start = i;
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        } else if c.is_ascii_digit() || c == '.' {
+            while i < characters.len() && (characters[i].is_ascii_digit() || characters[i] == '.') {
+                i += 1;
+            }
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            while i < characters.len() && (characters[i].is_ascii_alphanumeric() || characters[i] == '_') {
+                i += 1;
+            }
+        } else if "<>=!".contains(c) && characters.get(i + 1) == Some(&'=') {
+            i += 2;
+        } else if "+-*/%^()<>,".contains(c) {
+            i += 1;
+        } else {
+            return Err(parse_error(start + 1, &format!("unexpected '{}'", c)));
+        }
+        tokens.push(Token { text: characters[start..i].iter().collect(), column: start + 1 });
+    }
+
+    Ok(tokens)
+}
+
+fn parse_error(column: usize, message: &str) -> Error {
+    Error::Parse { line: 1, message: format!("column {}: {}", column, message) }
+}
+
+/// Recursive descent parser writing the program in postfix order as it goes
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    program: Vec<Instruction>,
+    inputs: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&str> {
+        self.tokens.get(self.position).map(|token| token.text.as_str())
+    }
+
+    fn column(&self) -> usize {
+        self.tokens.get(self.position).map_or_else(|| self.tokens.last().map_or(1, |token| token.column + token.text.len()), |token| token.column)
+    }
+
+    fn expect(&mut self, text: &str) -> Result<()> {
+        if self.peek() == Some(text) {
+            self.position += 1;
+            Ok(())
+        } else {
+            Err(parse_error(self.column(), &format!("expected '{}'", text)))
+        }
+    }
+
+    fn comparison(&mut self) -> Result<()> {
+        self.additive()?;
+        if let Some(operator) = self.peek().and_then(|text| ["<", "<=", ">", ">=", "==", "!="].into_iter().find(|&operator| operator == text)) {
+            self.position += 1;
+            self.additive()?;
+            self.program.push(Instruction::Compare(operator));
+        }
+        Ok(())
+    }
+
+    fn additive(&mut self) -> Result<()> {
+        self.term()?;
+        while let Some(operator @ ("+" | "-")) = self.peek() {
+            let operator = operator.chars().next().unwrap_or('+');
+            self.position += 1;
+            self.term()?;
+            self.program.push(Instruction::Binary(operator));
+        }
+        Ok(())
+    }
+
+    fn term(&mut self) -> Result<()> {
+        self.unary()?;
+        while let Some(operator @ ("*" | "/" | "%")) = self.peek() {
+            let operator = operator.chars().next().unwrap_or('*');
+            self.position += 1;
+            self.unary()?;
+            self.program.push(Instruction::Binary(operator));
+        }
+        Ok(())
+    }
+
+    /// Every nested bracket, call and negation comes through here, so the depth is kept here
+    fn unary(&mut self) -> Result<()> {
+        if self.depth >= MAX_DEPTH {
+            return Err(parse_error(self.column(), &format!("nested deeper than {} levels", MAX_DEPTH)));
+        }
+
+        self.depth += 1;
+        let result = if self.peek() == Some("-") {
+            self.position += 1;
+            self.unary().map(|()| self.program.push(Instruction::Negate))
+        } else {
+            self.power()
+        };
+        self.depth -= 1;
+
+        result
+    }
+
+    /// A primary raised to a power, right associative and binding tighter than negation on its
+    /// left, so `-a^2` is `-(a^2)`
+    fn power(&mut self) -> Result<()> {
+        self.primary()?;
+        if self.peek() == Some("^") {
+            self.position += 1;
+            self.unary()?;
+            self.program.push(Instruction::Binary('^'));
+        }
+        Ok(())
+    }
+
+    fn primary(&mut self) -> Result<()> {
+        let column = self.column();
+        let Some(token) = self.tokens.get(self.position).map(|token| token.text.clone()) else {
+            return Err(parse_error(column, "unexpected end of expression"));
+        };
+        self.position += 1;
+
+        if token == "(" {
+            self.comparison()?;
+            return self.expect(")");
+        }
+        if token.starts_with(|c: char| c.is_ascii_digit() || c == '.') {
+            let value = token.parse().map_err(|_| parse_error(column, &format!("invalid number '{}'", token)))?;
+            self.program.push(Instruction::Constant(value));
+            return Ok(());
+        }
+        if self.peek() == Some("(") {
+            return self.call(&token, column);
+        }
+
+        let instruction = match token.as_str() {
+            "x" => Instruction::X,
+            "y" => Instruction::Y,
+            "width" => Instruction::Width,
+            "height" => Instruction::Height,
+            "channel" => Instruction::Channel,
+            name if name.len() == 1 && ('a'..='h').contains(&name.chars().next().unwrap_or(' ')) => {
+                let index = (name.as_bytes()[0] - b'a') as usize;
+                self.inputs = self.inputs.max(index + 1);
+                Instruction::Input(index)
+            }
+            name if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') => return Err(parse_error(column, &format!("unknown variable '{}'", name))),
+            _ => return Err(parse_error(column, &format!("unexpected '{}'", token))),
+        };
+        self.program.push(instruction);
+        Ok(())
+    }
+
+    fn call(&mut self, name: &str, column: usize) -> Result<()> {
+        let function = Function::from_name(name).ok_or_else(|| parse_error(column, &format!("unknown function '{}'", name)))?;
+        self.expect("(")?;
+
+        let mut count = 0;
+        if self.peek() != Some(")") {
+            loop {
+                self.comparison()?;
+                count += 1;
+                if self.peek() != Some(",") {
+                    break;
+                }
+                self.position += 1;
+            }
+        }
+        self.expect(")")?;
+
+        if !function.accepts(count) {
+            return Err(parse_error(column, &format!("wrong number of arguments to '{}'", name)));
+        }
+        self.program.push(Instruction::Call(function, count));
+        Ok(())
+    }
+}
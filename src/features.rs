@@ -0,0 +1,159 @@
+//! Feature detection: distinctive points, such as corners, that can be found again in other
+//! views of a scene
+
+use crate::error::Result;
+use crate::plane::Plane;
+use crate::records::{number, Record};
+use crate::suppression::local_maxima;
+use crate::value::Value;
+use crate::ImageBuffer;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Corner {
+    pub x: f32,
+    pub y: f32,
+    /// Strength of the corner, higher is more distinctive
+    pub response: f32,
+}
+
+impl Record for Corner {
+    const NAME: &'static str = "corner";
+
+    fn to_value(&self) -> Value {
+        Value::Table(vec![
+            ("x".to_string(), Value::Number(self.x as f64)),
+            ("y".to_string(), Value::Number(self.y as f64)),
+            ("response".to_string(), Value::Number(self.response as f64)),
+        ])
+    }
+
+    fn from_value(value: &Value) -> Result<Corner> {
+        Ok(Corner {
+            x: number(value, Self::NAME, "x")? as f32,
+            y: number(value, Self::NAME, "y")? as f32,
+            response: number(value, Self::NAME, "response")? as f32,
+        })
+    }
+}
+
+/// The structure tensor of a plane at every pixel: the gaussian weighted sums of the products of
+/// its gradients, `[[xx, xy], [xy, yy]]`. Its eigenvectors give the dominant local orientation
+/// and its eigenvalues how strongly the neighbourhood is oriented: both small on flat areas,
+/// one large along edges and both large at corners.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StructureTensor {
+    pub xx: Plane,
+    pub xy: Plane,
+    pub yy: Plane,
+}
+
+impl StructureTensor {
+    /// The larger and smaller eigenvalues at each pixel
+    pub fn eigenvalues(&self) -> (Plane, Plane) {
+        let mut larger = Plane::new(self.xx.width, self.xx.height);
+        let mut smaller = Plane::new(self.xx.width, self.xx.height);
+
+        for i in 0..self.xx.data.len() {
+            let (a, b, c) = (self.xx.data[i], self.yy.data[i], self.xy.data[i]);
+            let (mean, spread) = ((a + b) / 2., ((a - b) * (a - b) / 4. + c * c).sqrt());
+            larger.data[i] = mean + spread;
+            smaller.data[i] = mean - spread;
+        }
+
+        (larger, smaller)
+    }
+
+    /// Angle in radians of the eigenvector of the larger eigenvalue, across edges in the
+    /// direction the brightness changes fastest, anticlockwise from the x axis with y down
+    pub fn orientation(&self) -> Plane {
+        let mut orientation = Plane::new(self.xx.width, self.xx.height);
+        for (i, value) in orientation.data.iter_mut().enumerate() {
+            *value = 0.5 * (2. * self.xy.data[i]).atan2(self.xx.data[i] - self.yy.data[i]);
+        }
+        orientation
+    }
+
+    /// How strongly each neighbourhood has a single orientation, `((l1 - l2) / (l1 + l2))^2`
+    /// from 0 where it has none, on flat areas and corners, to 1 along straight edges
+    pub fn coherence(&self) -> Plane {
+        let (larger, smaller) = self.eigenvalues();
+        larger.zip(&smaller, |l1, l2| if l1 + l2 > f32::EPSILON { ((l1 - l2) / (l1 + l2)).powi(2) } else { 0. })
+    }
+}
+
+/// Compute the structure tensor of a plane
+///
+/// # Arguments
+///
+/// * `input`: Intensity to analyse
+/// * `sigma`: Standard deviation of a blur before taking gradients, to ignore noise finer than
+///   this, 0 for none
+/// * `rho`: Standard deviation of the window the gradient products are summed over, larger
+///   averages the orientation over a wider area
+///
+/// returns: StructureTensor
+pub fn structure_tensor(input: &Plane, sigma: f32, rho: f32) -> StructureTensor {
+    let (dx, dy) = input.gaussian_blur(sigma).gradients();
+    StructureTensor {
+        xx: dx.zip(&dx, |a, b| a * b).gaussian_blur(rho),
+        xy: dx.zip(&dy, |a, b| a * b).gaussian_blur(rho),
+        yy: dy.zip(&dy, |a, b| a * b).gaussian_blur(rho),
+    }
+}
+
+/// Harris corner response `det(M) - k trace(M)^2` of the structure tensor `M`. Corners are
+/// strongly positive, edges negative and flat areas near zero.
+///
+/// # Arguments
+///
+/// * `input`: Intensity to find corners in
+/// * `sigma`: Standard deviation of the window the gradients are summed over
+/// * `k`: Sensitivity, usually from 0.04 to 0.06, lower finds more corners
+///
+/// returns: Plane
+pub fn harris_response(input: &Plane, sigma: f32, k: f32) -> Plane {
+    let tensor = structure_tensor(input, 0., sigma);
+
+    let mut response = Plane::new(input.width, input.height);
+    for (i, value) in response.data.iter_mut().enumerate() {
+        let (a, b, c) = (tensor.xx.data[i], tensor.yy.data[i], tensor.xy.data[i]);
+        *value = a * b - c * c - k * (a + b) * (a + b);
+    }
+
+    response
+}
+
+/// Find corners by the Harris detector, keeping the local maxima of the response above a
+/// fraction of the strongest, at least `min_distance` apart and strongest first
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to find corners in
+/// * `threshold`: Fraction of the strongest response a corner must reach, e.g. 0.01
+/// * `min_distance`: Radius in pixels a corner must be the strongest response within
+///
+/// returns: Vec<Corner>
+pub fn harris_corners(input: &ImageBuffer, threshold: f32, min_distance: u32) -> Vec<Corner> {
+    let response = harris_response(&Plane::from_luma(input), 1.5, 0.04);
+    local_maxima(&response, threshold, min_distance)
+        .into_iter()
+        .map(|peak| {
+            let mut corner = Corner { x: peak.x as f32, y: peak.y as f32, response: peak.value };
+            refine_peak(&response, &mut corner);
+            corner
+        })
+        .collect()
+}
+
+/// Move a maximum to the peak of a parabola fitted through it and its neighbours on each axis
+fn refine_peak(plane: &Plane, corner: &mut Corner) {
+    let (x, y) = (corner.x as i64, corner.y as i64);
+    let offset = |before: f32, centre: f32, after: f32| {
+        let curvature = before - 2. * centre + after;
+        if curvature < 0. { ((before - after) / (2. * curvature)).clamp(-0.5, 0.5) } else { 0. }
+    };
+
+    let centre = plane.get_clamped(x, y);
+    corner.x += offset(plane.get_clamped(x - 1, y), centre, plane.get_clamped(x + 1, y));
+    corner.y += offset(plane.get_clamped(x, y - 1), centre, plane.get_clamped(x, y + 1));
+}
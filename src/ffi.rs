@@ -0,0 +1,190 @@
+//! C ABI for embedding the library in C, C++ or Swift, enabled with the `ffi` feature. The
+//! matching declarations are in `include/rust_image_processing.h`.
+//!
+//! Images are opaque `RipImage` handles created from rgba bytes and released with
+//! `rip_image_free`. Functions returning `int` give 0 on success and -1 on failure, with the
+//! reason from `rip_last_error`, panics included.
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, c_int, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+use crate::pipeline::{Operation, Pipeline, PipelineFormat};
+use crate::value::{json, Value};
+use crate::ImageBuffer;
+
+pub struct RipImage {
+    buffer: ImageBuffer,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<CString> = RefCell::new(CString::default());
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', "")).unwrap();
+    LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+}
+
+/// Run a function body, turning an error or a panic into -1 and the last error, as a panic
+/// unwinding out of an `extern "C"` fn would abort the host process
+fn status(body: impl FnOnce() -> std::result::Result<(), String>) -> c_int {
+    match catch_unwind(AssertUnwindSafe(body)) {
+        Ok(Ok(())) => 0,
+        Ok(Err(message)) => {
+            set_last_error(message);
+            -1
+        }
+        Err(payload) => {
+            set_last_error(format!("panicked: {}", panic_message(payload.as_ref())));
+            -1
+        }
+    }
+}
+
+/// The message a panic was raised with, if it was given as a string
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+        (Some(message), _) => message,
+        (_, Some(message)) => message,
+        _ => "unknown panic",
+    }
+}
+
+/// Bytes of a `width` by `height` rgba image, or None if that overflows `usize`
+fn rgba_len(width: u32, height: u32) -> Option<usize> {
+    (width as usize).checked_mul(height as usize)?.checked_mul(4)
+}
+
+unsafe fn c_str<'a>(value: *const c_char, name: &str) -> std::result::Result<&'a str, String> {
+    if value.is_null() {
+        return Err(format!("{} is null", name));
+    }
+    CStr::from_ptr(value).to_str().map_err(|err| format!("{} is not utf-8: {}", name, err))
+}
+
+/// Copy `width * height * 4` bytes of row major rgba pixels into a new image, returns null on
+/// failure
+///
+/// # Safety
+///
+/// `data` must point to at least `width * height * 4` readable bytes
+#[no_mangle]
+pub unsafe extern "C" fn rip_image_from_rgba(data: *const u8, width: u32, height: u32) -> *mut RipImage {
+    if data.is_null() {
+        set_last_error("data is null".to_string());
+        return ptr::null_mut();
+    }
+
+    let Some(len) = rgba_len(width, height) else {
+        set_last_error(format!("a {}x{} image is too large", width, height));
+        return ptr::null_mut();
+    };
+
+    let data = slice::from_raw_parts(data, len);
+    match image::ImageBuffer::from_raw(width, height, data.to_vec()) {
+        Some(buffer) => Box::into_raw(Box::new(RipImage { buffer })),
+        None => {
+            set_last_error(format!("a {}x{} image is too large", width, height));
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Release an image, null is ignored
+///
+/// # Safety
+///
+/// `image` must come from `rip_image_from_rgba` and not be used afterwards
+#[no_mangle]
+pub unsafe extern "C" fn rip_image_free(image: *mut RipImage) {
+    if !image.is_null() {
+        drop(Box::from_raw(image));
+    }
+}
+
+/// # Safety
+///
+/// `image` must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn rip_image_width(image: *const RipImage) -> u32 {
+    (&*image).buffer.width()
+}
+
+/// # Safety
+///
+/// `image` must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn rip_image_height(image: *const RipImage) -> u32 {
+    (&*image).buffer.height()
+}
+
+/// The image's rgba bytes, valid until the image is next modified or freed
+///
+/// # Safety
+///
+/// `image` must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn rip_image_data(image: *const RipImage) -> *const u8 {
+    (&*image).buffer.as_ptr()
+}
+
+/// # Safety
+///
+/// `image` must be a live handle
+#[no_mangle]
+pub unsafe extern "C" fn rip_image_data_len(image: *const RipImage) -> usize {
+    (&*image).buffer.len()
+}
+
+/// Apply a single operation by name, e.g. `"median_filter"`, with its parameters as a json
+/// object such as `{"window": 2}`, or null for operations without any
+///
+/// # Safety
+///
+/// `image` must be a live handle and the strings nul terminated or null
+#[no_mangle]
+pub unsafe extern "C" fn rip_image_apply(image: *mut RipImage, name: *const c_char, parameters: *const c_char) -> c_int {
+    status(|| {
+        let name = c_str(name, "name")?;
+        let mut step = match parameters.is_null() {
+            true => Vec::new(),
+            false => match json::parse(c_str(parameters, "parameters")?).map_err(|err| err.to_string())? {
+                Value::Table(entries) => entries,
+                _ => return Err("parameters must be a json object".to_string()),
+            },
+        };
+        step.push(("op".to_string(), Value::String(name.to_string())));
+
+        let operation = Operation::from_value(&Value::Table(step)).map_err(|err| err.to_string())?;
+        let image = &mut *image;
+        image.buffer = operation.apply(&image.buffer);
+
+        Ok(())
+    })
+}
+
+/// Run a json pipeline, in the same form as a pipeline file, over the image
+///
+/// # Safety
+///
+/// `image` must be a live handle and `pipeline` nul terminated
+#[no_mangle]
+pub unsafe extern "C" fn rip_image_run_pipeline(image: *mut RipImage, pipeline: *const c_char) -> c_int {
+    status(|| {
+        let pipeline = Pipeline::parse(c_str(pipeline, "pipeline")?, PipelineFormat::Json).map_err(|err| err.to_string())?;
+        let image = &mut *image;
+        image.buffer = pipeline.run(&image.buffer);
+
+        Ok(())
+    })
+}
+
+/// The message of the last failure on this thread, owned by the library
+#[no_mangle]
+pub extern "C" fn rip_last_error() -> *const c_char {
+    LAST_ERROR.with(|last_error| last_error.borrow().as_ptr())
+}
@@ -0,0 +1,123 @@
+//! Fast fourier transforms of power of two sizes, for the frequency domain analysis a few of the
+//! modules need (saliency, alignment)
+
+use std::ops::{Add, Mul, Sub};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub(crate) struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Complex {
+        Complex { re, im }
+    }
+
+    /// `e^(i angle)`
+    pub fn from_angle(angle: f64) -> Complex {
+        Complex::new(angle.cos(), angle.sin())
+    }
+
+    pub fn norm(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    pub fn scale(self, factor: f64) -> Complex {
+        Complex::new(self.re * factor, self.im * factor)
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, other: Complex) -> Complex {
+        Complex::new(self.re + other.re, self.im + other.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, other: Complex) -> Complex {
+        Complex::new(self.re - other.re, self.im - other.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, other: Complex) -> Complex {
+        Complex::new(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+    }
+}
+
+/// In place radix 2 transform, the inverse scaled by `1 / n` so the two round trip
+///
+/// Panics if the length isn't a power of two.
+pub(crate) fn fft(values: &mut [Complex], inverse: bool) {
+    let n = values.len();
+    assert!(n.is_power_of_two(), "fft length must be a power of two, got {}", n);
+    if n == 1 {
+        return;
+    }
+
+    // Bit reversed order, so the butterflies below work on neighbouring runs
+    let bits = n.trailing_zeros();
+    for i in 0..n {
+        let j = i.reverse_bits() >> (usize::BITS - bits);
+        if j > i {
+            values.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1. } else { -1. };
+    let mut size = 2;
+    while size <= n {
+        let step = Complex::from_angle(sign * 2. * std::f64::consts::PI / size as f64);
+        for run in values.chunks_mut(size) {
+            let (low, high) = run.split_at_mut(size / 2);
+            let mut twiddle = Complex::new(1., 0.);
+            for (a, b) in low.iter_mut().zip(high.iter_mut()) {
+                let t = twiddle * *b;
+                (*a, *b) = (*a + t, *a - t);
+                twiddle = twiddle * step;
+            }
+        }
+        size *= 2;
+    }
+
+    if inverse {
+        for value in values.iter_mut() {
+            *value = value.scale(1. / n as f64);
+        }
+    }
+}
+
+/// In place transform of a row major grid, as transforms of the rows then the columns
+pub(crate) fn fft2(values: &mut [Complex], width: usize, height: usize, inverse: bool) {
+    assert_eq!(values.len(), width * height, "grid must be width by height");
+
+    for row in values.chunks_mut(width) {
+        fft(row, inverse);
+    }
+
+    let mut column = vec![Complex::default(); height];
+    for x in 0..width {
+        for (y, value) in column.iter_mut().enumerate() {
+            *value = values[y * width + x];
+        }
+        fft(&mut column, inverse);
+        for (y, value) in column.iter().enumerate() {
+            values[y * width + x] = *value;
+        }
+    }
+}
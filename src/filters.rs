@@ -0,0 +1,458 @@
+use image::imageops::contrast;
+use log::debug;
+use ndarray::{array, Array2};
+
+use crate::arithmetic::{assert_output_dimensions, image_add, image_sub};
+use crate::error::Result;
+use crate::features::structure_tensor;
+use crate::plane::Plane;
+use crate::progress::Progress;
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+use crate::simd::convolve_pixel;
+use crate::ImageBuffer;
+
+/// Smallest sigma `bilateral_filter` uses, small enough that its weights pick out the centre
+const MIN_SIGMA: f32 = 0.01;
+
+/// Edge detection by the following process:
+///     1) increase image contrast
+///     2) Sharpen image
+///     3) Compute x and y gradients
+///     4) Add x and y gradients
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to edge detect
+///
+/// returns: ImageBuffer
+pub fn edge_detect(input: &ImageBuffer) -> ImageBuffer {
+    let cleaned = contrast(input, 2.);
+    let sharpened = sharpen(&cleaned, 10.);
+
+    let gradient_x = x_grad(&sharpened);
+    let gradient_y = y_grad(&sharpened);
+
+    image_add(&gradient_x, &gradient_y)
+}
+
+pub fn x_grad(input: &ImageBuffer) -> ImageBuffer {
+    let matrix = array![
+        [-1., 1.]
+    ];
+
+    apply_matrix(input, matrix)
+}
+
+pub fn y_grad(input: &ImageBuffer) -> ImageBuffer {
+    let matrix = array![
+        [1.],
+        [-1.],
+    ];
+
+    apply_matrix(input, matrix)
+}
+
+/// Perform sharpening of an image by:
+///     1) Perform a bilinear blur filter
+///     2) Subtract the Image - Bilinear Output, to get the 'detail' of the image
+///     3) Add specified multiple of detail back to original image
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to sharpen
+/// * `value`: Multiple of detail to add on
+///
+/// returns: ImageBuffer
+pub fn sharpen(input: &ImageBuffer, value: f32) -> ImageBuffer {
+    let filtered = bilinear_filter(input);
+
+    let detail = image_sub(input, &filtered);
+    let detail = contrast(&detail, value);
+
+    image_add(input, &detail)
+}
+
+/// Sharpen with the gain on the detail adapted pixel by pixel, as the sharpening panels of photo
+/// editors do. The detail is the luma less its gaussian blur, added back to all three channels so
+/// colours don't fringe. Its gain is cut where the detail is no stronger than the image's noise,
+/// estimated from the median detail, so flat areas and grain aren't amplified; `masking` limits
+/// it further to the stronger edges; and the result is kept within the range of each pixel's
+/// neighbours so edges are steepened without overshooting into halos.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to sharpen
+/// * `amount`: Multiple of detail to add on, e.g. 1
+/// * `radius`: Standard deviation in pixels of the blur the detail is taken from, larger
+///   sharpens coarser detail, e.g. 1
+/// * `masking`: From 0, sharpening everywhere, to 1, sharpening only the strongest edges
+///
+/// returns: ImageBuffer
+pub fn adaptive_sharpen(input: &ImageBuffer, amount: f32, radius: f32, masking: f32) -> ImageBuffer {
+    let luma = Plane::from_luma(input);
+    let blurred = luma.gaussian_blur(radius.max(0.1));
+    let detail = luma.zip(&blurred, |value, blurred| value - blurred);
+
+    // Noise from the median absolute detail, and the detail's energy around each pixel
+    let noise = percentile(&mut detail.data.iter().map(|v| v.abs()).collect::<Vec<_>>(), 0.5) / 0.6745;
+    let energy = detail.map(|v| v * v).gaussian_blur(radius.max(1.));
+
+    // Edge strength from 0 to 1, relative to the strong edges of the image
+    let (dx, dy) = blurred.gradients();
+    let edges = dx.zip(&dy, |x, y| (x * x + y * y).sqrt());
+    let strong = percentile(&mut edges.data.clone(), 0.98);
+    let masking = masking.clamp(0., 1.);
+
+    let (width, height) = input.dimensions();
+    let mut output = input.clone();
+    for y in 0..height {
+        for x in 0..width {
+            let i = (y * width + x) as usize;
+            let wiener = ((energy.data[i] - noise * noise) / energy.data[i].max(1e-6)).clamp(0., 1.);
+            let strength = if strong > 0. { (edges.data[i] / strong).min(1.) } else { 0. };
+            let mask = if masking > 0. { smoothstep(masking * 0.5, masking, strength) } else { 1. };
+
+            // Keep within the neighbours' range, which sharpening an edge needn't leave
+            let (low, high) = (-1..=1).flat_map(|dy| (-1..=1).map(move |dx| (dx, dy))).fold((f32::MAX, f32::MIN), |(low, high), (dx, dy)| {
+                let value = luma.get_clamped(x as i64 + dx, y as i64 + dy);
+                (low.min(value), high.max(value))
+            });
+            let sharpened = (luma.data[i] + amount * wiener * mask * detail.data[i]).clamp(low, high);
+
+            let pixel = output.get_pixel_mut(x, y);
+            for channel in 0..3 {
+                pixel[channel] = (pixel[channel] as f32 + sharpened - luma.data[i]).round().clamp(0., 255.) as u8;
+            }
+        }
+    }
+
+    output
+}
+
+/// The value a fraction of the way through the values in order, reordering them
+fn percentile(values: &mut [f32], fraction: f32) -> f32 {
+    if values.is_empty() {
+        return 0.;
+    }
+    let index = ((values.len() - 1) as f32 * fraction.clamp(0., 1.)).round() as usize;
+    *values.select_nth_unstable_by(index, f32::total_cmp).1
+}
+
+/// 0 below `low`, 1 above `high` and a smooth step between
+fn smoothstep(low: f32, high: f32, value: f32) -> f32 {
+    let t = ((value - low) / (high - low).max(1e-6)).clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
+
+pub fn bilinear_filter(input: &ImageBuffer) -> ImageBuffer {
+    let bilinear = array![
+        [1./16., 2./16., 1./16.],
+        [2./16., 4./16., 2./16.],
+        [1./16., 2./16., 1./16.]
+    ];
+
+    apply_matrix(input, bilinear)
+}
+
+pub fn gaussian_blur(input: &ImageBuffer) -> ImageBuffer {
+    let gaussian = array![
+        [1./256., 4./256., 6./256., 4./256., 1./256.],
+        [4./256., 16./256., 24./256., 16./256., 4./256.],
+        [6./256., 24./256., 36./256., 24./256., 6./256.],
+        [4./256., 16./256., 24./256., 16./256., 4./256.],
+        [1./256., 4./256., 6./256., 4./256., 1./256.],
+    ];
+
+    apply_matrix(input, gaussian)
+}
+
+/// Apply a matrix to the input image, pixel by pixel where:
+///     g(i, j) = sum f(i + k, j + l)h(k, l)
+/// Where:
+///     g is new pixel of image
+///     f is current pixel of image
+///     h is the matrix
+///     k, l range over the dimensions of the matrix
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to apply matrix to
+/// * `matrix`: Matrix to apply
+///
+/// returns: ImageBuffer
+pub fn apply_matrix(input: &ImageBuffer, matrix: Array2<f32>) -> ImageBuffer {
+    let (input_x, input_y) = input.dimensions();
+    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+
+    apply_matrix_into(input, &matrix, &mut output);
+
+    output
+}
+
+/// As `apply_matrix`, but writing into an existing buffer the same size as `input`. The output
+/// can't be the input itself, as later pixels read from earlier ones.
+pub fn apply_matrix_into(input: &ImageBuffer, matrix: &Array2<f32>, output: &mut ImageBuffer) {
+    apply_matrix_with_progress(input, matrix, output, &Progress::none()).unwrap();
+}
+
+/// As `apply_matrix_into`, reporting progress after each row and stopping early if cancelled
+pub fn apply_matrix_with_progress(input: &ImageBuffer, matrix: &Array2<f32>, output: &mut ImageBuffer, progress: &Progress) -> Result<()> {
+    assert_output_dimensions(input, output);
+
+    let input_y = input.height();
+    let (matrix_x, matrix_y) = (matrix.shape()[0], matrix.shape()[1]);
+
+    debug!("Applying matrix of size: {}, {}", matrix_x, matrix_y);
+
+    for (y, row) in output.enumerate_rows_mut() {
+        progress.check()?;
+
+        for (x, _, pixel) in row {
+            let total = convolve_pixel(input, matrix, x, y);
+
+            let r = total[0].clamp(0, 255) as u8;
+            let g = total[1].clamp(0, 255) as u8;
+            let b = total[2].clamp(0, 255) as u8;
+
+            *pixel = image::Rgba([r, g, b, 255]);
+        }
+
+        progress.report((y + 1) as f32 / input_y as f32);
+    }
+
+    Ok(())
+}
+
+/// The convolution sum for one output pixel of `apply_matrix`
+#[cfg(not(all(feature = "simd", target_arch = "x86_64")))]
+fn convolve_pixel(input: &ImageBuffer, matrix: &Array2<f32>, x: u32, y: u32) -> [i32; 3] {
+    let (input_x, input_y) = input.dimensions();
+    let (matrix_x, matrix_y) = (matrix.shape()[0], matrix.shape()[1]);
+
+    // Do all maths as integers then only truncate to [0, 255] right at the end
+    let mut pixels_to_sum = Vec::new();
+
+    for i in 0..matrix_x {
+        for j in 0..matrix_y {
+            let x_curr = (x as i32) + (i as i32);
+            let y_curr = (y as i32) + (j as i32);
+
+            let x_curr = x_curr.clamp(0, input_x as i32 - 1);
+            let y_curr = y_curr.clamp(0, input_y as i32 - 1);
+
+            let input_curr = input.get_pixel(x_curr as u32, y_curr as u32);
+            let matrix_curr = matrix[[i, j]];
+
+            let prod = vec![((input_curr[0] as f32) * matrix_curr) as i32, ((input_curr[1] as f32) * matrix_curr) as i32, ((input_curr[2] as f32) * matrix_curr) as i32];
+
+            pixels_to_sum.push(prod);
+        }
+    }
+
+    let mut total: Vec<i32> = vec![0, 0, 0];
+
+    for pixel_to_sum in pixels_to_sum {
+        total = vec![total[0] + pixel_to_sum[0], total[1] + pixel_to_sum[1], total[2] + pixel_to_sum[2]];
+    }
+
+    [total[0], total[1], total[2]]
+}
+
+/// Replace each pixel's r,g,b values with the medians over the square of pixels within
+/// `window` of it, removing speckle noise while keeping edges
+pub fn median_filter(input: &ImageBuffer, window: u32) -> ImageBuffer {
+    let (input_x, input_y) = input.dimensions();
+    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+
+    median_filter_into(input, window, &mut output);
+
+    output
+}
+
+/// As `median_filter`, but writing into an existing buffer the same size as `input`
+pub fn median_filter_into(input: &ImageBuffer, window: u32, output: &mut ImageBuffer) {
+    median_filter_with_progress(input, window, output, &Progress::none()).unwrap();
+}
+
+/// As `median_filter_into`, reporting progress after each row and stopping early if cancelled
+pub fn median_filter_with_progress(input: &ImageBuffer, window: u32, output: &mut ImageBuffer, progress: &Progress) -> Result<()> {
+    assert_output_dimensions(input, output);
+
+    let (input_x, input_y) = input.dimensions();
+    // Offsets into the window are signed, and `window + 1` must not overflow
+    let window = window.min(i32::MAX as u32 - 1) as i32;
+
+    for (y, row) in output.enumerate_rows_mut() {
+        progress.check()?;
+
+        for (x, _, pixel) in row {
+            let mut r_vals = Vec::new();
+            let mut g_vals = Vec::new();
+            let mut b_vals = Vec::new();
+
+            for i in -window..(window+1) {
+                for j in -window..(window+1) {
+                    let x_curr = (x as i32) + i;
+                    let y_curr = (y as i32) + j;
+
+                    let x_curr = x_curr.clamp(0, input_x as i32 - 1);
+                    let y_curr = y_curr.clamp(0, input_y as i32 - 1);
+
+                    let pixel_curr = input.get_pixel(x_curr as u32, y_curr as u32);
+                    r_vals.push(pixel_curr[0]);
+                    g_vals.push(pixel_curr[1]);
+                    b_vals.push(pixel_curr[2]);
+                }
+            }
+
+            r_vals.sort();
+            g_vals.sort();
+            b_vals.sort();
+
+            let r_median = median(&r_vals);
+            let g_median = median(&g_vals);
+            let b_median = median(&b_vals);
+
+            let input_pixel = input.get_pixel(x, y);
+
+            *pixel = image::Rgba([r_median, g_median, b_median, input_pixel[3]])
+        }
+
+        progress.report((y + 1) as f32 / input_y as f32);
+    }
+
+    Ok(())
+}
+
+fn median(numbers: &[u8]) -> u8 {
+    let mid = numbers.len() / 2;
+
+    numbers[mid]
+}
+
+/// Edge preserving smoothing, averaging each pixel with its neighbours weighted both by how near
+/// they are and by how similar their colour is, so flat areas are smoothed but edges between
+/// different colours are kept
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to smooth
+/// * `sigma_space`: Standard deviation of the spatial weight in pixels, the window reaching
+///   twice this
+/// * `sigma_colour`: Standard deviation of the colour weight, over the r,g,b distance from 0
+///   to 255, smaller keeps weaker edges
+///
+/// Both sigmas are raised to at least 0.01, where the filter leaves the image unchanged, as the
+/// weights are undefined at 0.
+///
+/// returns: ImageBuffer
+pub fn bilateral_filter(input: &ImageBuffer, sigma_space: f32, sigma_colour: f32) -> ImageBuffer {
+    let (width, height) = input.dimensions();
+    let (sigma_space, sigma_colour) = (sigma_space.max(MIN_SIGMA), sigma_colour.max(MIN_SIGMA));
+    let radius = (sigma_space * 2.).ceil().max(1.) as i32;
+
+    let spatial: Vec<f32> = (-radius..=radius)
+        .flat_map(|dy| (-radius..=radius).map(move |dx| (-((dx * dx + dy * dy) as f32) / (2. * sigma_space * sigma_space)).exp()))
+        .collect();
+    // Colour weights by squared distance, which is at most 3 * 255^2
+    let colour: Vec<f32> = (0..=3 * 255 * 255).map(|d| (-(d as f32) / (2. * sigma_colour * sigma_colour)).exp()).collect();
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let centre = input.get_pixel(x, y);
+        let mut total = [0f32; 3];
+        let mut weights = 0.;
+
+        for dy in -radius..=radius {
+            for dx in -radius..=radius {
+                let x_curr = (x as i32 + dx).clamp(0, width as i32 - 1) as u32;
+                let y_curr = (y as i32 + dy).clamp(0, height as i32 - 1) as u32;
+                let pixel = input.get_pixel(x_curr, y_curr);
+
+                let distance: i32 = (0..3).map(|c| (pixel[c] as i32 - centre[c] as i32).pow(2)).sum();
+                let weight = spatial[((dy + radius) * (2 * radius + 1) + dx + radius) as usize] * colour[distance as usize];
+                for (sum, &value) in total.iter_mut().zip(pixel.0.iter()) {
+                    *sum += weight * value as f32;
+                }
+                weights += weight;
+            }
+        }
+
+        let [r, g, b] = total.map(|sum| (sum / weights).round() as u8);
+        image::Rgba([r, g, b, centre[3]])
+    })
+}
+
+/// Coherence enhancing shock filter (Weickert, 2003), a crisper alternative to `sharpen` that
+/// steepens edges into clean steps rather than adding halos. Each step dilates pixels on the
+/// bright side of an edge and erodes those on the dark side, the side told by the second
+/// derivative across the edge, taken along the structure tensor's dominant orientation so
+/// lines and flow-like texture are followed rather than broken into blobs.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to sharpen
+/// * `sigma`: Blur before finding the edges, larger ignores finer detail and noise, e.g. 1.5
+/// * `rho`: Window the edge orientation is averaged over, e.g. 4
+/// * `iterations`: Number of steps, more crisper, e.g. 10
+///
+/// returns: ImageBuffer
+pub fn shock_filter(input: &ImageBuffer, sigma: f32, rho: f32, iterations: u32) -> ImageBuffer {
+    // Steps over half a pixel aren't stable
+    const TIME_STEP: f32 = 0.5;
+
+    let mut channels: Vec<Plane> = (0..3).map(|c| Plane::from_channel(input, c)).collect();
+
+    for _ in 0..iterations {
+        let luma = Plane { data: (0..channels[0].data.len()).map(|i| 0.299 * channels[0].data[i] + 0.587 * channels[1].data[i] + 0.114 * channels[2].data[i]).collect(), ..channels[0] };
+        let smoothed = luma.gaussian_blur(sigma);
+        let orientation = structure_tensor(&smoothed, 0., rho).orientation();
+
+        // Dilate where the second derivative across the edge is negative, erode where positive
+        let mut direction = Plane::new(luma.width, luma.height);
+        for y in 0..luma.height as i64 {
+            for x in 0..luma.width as i64 {
+                let at = |dx: i64, dy: i64| smoothed.get_clamped(x + dx, y + dy);
+                let xx = at(1, 0) - 2. * at(0, 0) + at(-1, 0);
+                let yy = at(0, 1) - 2. * at(0, 0) + at(0, -1);
+                let xy = (at(1, 1) - at(1, -1) - at(-1, 1) + at(-1, -1)) / 4.;
+                let (s, c) = orientation.get(x as u32, y as u32).sin_cos();
+                let across = c * c * xx + 2. * c * s * xy + s * s * yy;
+                direction.set(x as u32, y as u32, -across.signum() * (across.abs() > 1e-6) as u8 as f32);
+            }
+        }
+
+        for channel in channels.iter_mut() {
+            *channel = shock_step(channel, &direction, TIME_STEP);
+        }
+    }
+
+    ImageBuffer::from_fn(input.width(), input.height(), |x, y| {
+        let [r, g, b] = [0, 1, 2].map(|c| channels[c].get(x, y).round().clamp(0., 255.) as u8);
+        image::Rgba([r, g, b, input.get_pixel(x, y)[3]])
+    })
+}
+
+/// One step of `u += dt direction |grad u|`, with the gradient taken upwind so dilation and
+/// erosion don't overshoot (Osher and Rudin)
+fn shock_step(plane: &Plane, direction: &Plane, time_step: f32) -> Plane {
+    let mut output = Plane::new(plane.width, plane.height);
+
+    for y in 0..plane.height as i64 {
+        for x in 0..plane.width as i64 {
+            let centre = plane.get_clamped(x, y);
+            let (back_x, forward_x) = (centre - plane.get_clamped(x - 1, y), plane.get_clamped(x + 1, y) - centre);
+            let (back_y, forward_y) = (centre - plane.get_clamped(x, y - 1), plane.get_clamped(x, y + 1) - centre);
+
+            // Dilating takes from the brighter neighbours, eroding from the darker
+            let d = direction.get(x as u32, y as u32);
+            let magnitude = if d > 0. {
+                (back_x.min(0.).powi(2) + forward_x.max(0.).powi(2) + back_y.min(0.).powi(2) + forward_y.max(0.).powi(2)).sqrt()
+            } else {
+                (back_x.max(0.).powi(2) + forward_x.min(0.).powi(2) + back_y.max(0.).powi(2) + forward_y.min(0.).powi(2)).sqrt()
+            };
+            output.set(x as u32, y as u32, centre + time_step * d * magnitude);
+        }
+    }
+
+    output
+}
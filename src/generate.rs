@@ -0,0 +1,278 @@
+//! Procedurally generated images, for tests, examples and generative art without needing image
+//! files: flat colours, gradients, checkerboards, noise and fractals, and standard test charts
+//! with known ground truth for measuring sharpening, resizing and deblurring
+
+use rand::seq::SliceRandom;
+
+use crate::random::rng;
+use crate::ImageBuffer;
+
+type Colour = image::Rgba<u8>;
+
+/// An image of one colour
+pub fn solid(width: u32, height: u32, colour: Colour) -> ImageBuffer {
+    ImageBuffer::from_pixel(width, height, colour)
+}
+
+/// A gradient from one colour to another along a direction, reaching from corner to corner
+///
+/// # Arguments
+///
+/// * `width`: Width of the image
+/// * `height`: Height of the image
+/// * `from`: Colour at the start
+/// * `to`: Colour at the end
+/// * `angle`: Direction of the gradient in degrees, anticlockwise from left to right, 90
+///   running bottom to top
+///
+/// returns: ImageBuffer
+pub fn linear_gradient(width: u32, height: u32, from: Colour, to: Colour, angle: f32) -> ImageBuffer {
+    // Rows run down the image, so up is negative y
+    let (dx, dy) = (angle.to_radians().cos(), -angle.to_radians().sin());
+    let corners = [(0., 0.), (width as f32, 0.), (0., height as f32), (width as f32, height as f32)];
+    let projections = corners.map(|(x, y)| x * dx + y * dy);
+    let start = projections.iter().copied().fold(f32::MAX, f32::min);
+    let end = projections.iter().copied().fold(f32::MIN, f32::max);
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let projection = (x as f32 + 0.5) * dx + (y as f32 + 0.5) * dy;
+        mix(from, to, (projection - start) / (end - start).max(f32::EPSILON))
+    })
+}
+
+/// A gradient from one colour at the centre to another at the corners
+pub fn radial_gradient(width: u32, height: u32, inner: Colour, outer: Colour) -> ImageBuffer {
+    let (cx, cy) = (width as f32 / 2., height as f32 / 2.);
+    let radius = cx.hypot(cy).max(f32::EPSILON);
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        mix(inner, outer, (x as f32 + 0.5 - cx).hypot(y as f32 + 0.5 - cy) / radius)
+    })
+}
+
+/// A checkerboard of squares alternating between two colours, the first in the top left
+pub fn checkerboard(width: u32, height: u32, square_size: u32, first: Colour, second: Colour) -> ImageBuffer {
+    let square_size = square_size.max(1);
+    ImageBuffer::from_fn(width, height, |x, y| if (x / square_size + y / square_size).is_multiple_of(2) { first } else { second })
+}
+
+/// Grey Perlin noise, summed over octaves of doubling frequency and halving amplitude for the
+/// cloudy look of fractal noise
+///
+/// # Arguments
+///
+/// * `width`: Width of the image
+/// * `height`: Height of the image
+/// * `scale`: Size in pixels of the coarsest octave's features
+/// * `octaves`: Number of octaves, one for smooth blobs, more adding finer detail
+/// * `seed`: Seed for the noise, the same seed giving the same image
+///
+/// returns: ImageBuffer
+pub fn perlin_noise(width: u32, height: u32, scale: f32, octaves: u32, seed: u64) -> ImageBuffer {
+    let noise = Perlin::new(seed);
+    let octaves = octaves.max(1);
+    // The largest the sum can reach, to scale it back into -1 to 1
+    let total: f32 = (0..octaves).map(|octave| 0.5f32.powi(octave as i32)).sum();
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let value: f32 = (0..octaves)
+            .map(|octave| {
+                let frequency = 2f32.powi(octave as i32) / scale.max(f32::EPSILON);
+                0.5f32.powi(octave as i32) * noise.at(x as f32 * frequency, y as f32 * frequency)
+            })
+            .sum();
+        let grey = ((value / total * 0.5 + 0.5) * 255.).round().clamp(0., 255.) as u8;
+        image::Rgba([grey, grey, grey, 255])
+    })
+}
+
+/// The Mandelbrot set, points inside in black and those outside coloured by how quickly they
+/// escape, smoothly so there are no bands
+///
+/// # Arguments
+///
+/// * `width`: Width of the image
+/// * `height`: Height of the image
+/// * `centre`: Point of the complex plane at the centre of the image, (-0.5, 0) for the whole set
+/// * `span`: Width of the complex plane the image covers, 3 for the whole set
+/// * `max_iterations`: Iterations before a point is taken as inside, more shows finer detail
+///   when zoomed in
+///
+/// returns: ImageBuffer
+pub fn mandelbrot(width: u32, height: u32, centre: (f64, f64), span: f64, max_iterations: u32) -> ImageBuffer {
+    let step = span / width as f64;
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let c_re = centre.0 + (x as f64 + 0.5 - width as f64 / 2.) * step;
+        let c_im = centre.1 - (y as f64 + 0.5 - height as f64 / 2.) * step;
+
+        let (mut re, mut im) = (0f64, 0f64);
+        for iteration in 0..max_iterations {
+            if re * re + im * im > 256. {
+                // Fractional escape count, continuous across the boundaries between counts
+                let smooth = iteration as f64 + 1. - (re * re + im * im).ln().ln() / std::f64::consts::LN_2;
+                return palette(smooth / 32.);
+            }
+            (re, im) = (re * re - im * im + c_re, 2. * re * im + c_im);
+        }
+
+        image::Rgba([0, 0, 0, 255])
+    })
+}
+
+/// A Siemens star, black and white sectors meeting at the centre, for measuring resolution in
+/// every direction at once: the radius at which the sectors blur together gives the resolution.
+/// Sectors are antialiased by supersampling, so the chart is accurate down to single pixels.
+///
+/// # Arguments
+///
+/// * `size`: Width and height of the square image
+/// * `spokes`: Number of black sectors, e.g. 36
+///
+/// returns: ImageBuffer
+pub fn siemens_star(size: u32, spokes: u32) -> ImageBuffer {
+    let centre = size as f32 / 2.;
+    let radius = centre * 0.95;
+    let spokes = spokes.max(1) as f32;
+
+    supersampled(size, size, |x, y| {
+        let (dx, dy) = (x - centre, y - centre);
+        if dx.hypot(dy) > radius {
+            return 1.;
+        }
+        let sector = (dy.atan2(dx) / std::f32::consts::TAU * spokes * 2.).rem_euclid(2.);
+        if sector < 1. { 0. } else { 1. }
+    })
+}
+
+/// A straight edge between dark and light through the centre, tilted from vertical, for
+/// measuring sharpness as in ISO 12233: the tilt means the rows sample the edge at different
+/// sub pixel offsets, which together give its profile finely
+///
+/// # Arguments
+///
+/// * `width`: Width of the image
+/// * `height`: Height of the image
+/// * `angle`: Tilt from vertical in degrees, usually about 5
+/// * `dark`: Grey level on the left, e.g. 64 for the usual low contrast chart
+/// * `light`: Grey level on the right, e.g. 192
+///
+/// returns: ImageBuffer
+pub fn slanted_edge(width: u32, height: u32, angle: f32, dark: u8, light: u8) -> ImageBuffer {
+    let (cx, cy) = (width as f32 / 2., height as f32 / 2.);
+    let (sin, cos) = angle.to_radians().sin_cos();
+    let coverage = supersampled(width, height, |x, y| if (x - cx) * cos + (y - cy) * sin > 0. { 1. } else { 0. });
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let t = coverage.get_pixel(x, y)[0] as f32 / 255.;
+        let grey = (dark as f32 * (1. - t) + light as f32 * t).round() as u8;
+        image::Rgba([grey, grey, grey, 255])
+    })
+}
+
+/// Colour bars at 75% intensity as in the SMPTE and EBU charts: white, yellow, cyan, green,
+/// magenta, red and blue, for checking colour handling and channel order
+pub fn colour_bars(width: u32, height: u32) -> ImageBuffer {
+    const BARS: [[u8; 3]; 7] = [[191, 191, 191], [191, 191, 0], [0, 191, 191], [0, 191, 0], [191, 0, 191], [191, 0, 0], [0, 0, 191]];
+
+    ImageBuffer::from_fn(width, height, |x, _| {
+        let [r, g, b] = BARS[(x as usize * BARS.len() / width.max(1) as usize).min(BARS.len() - 1)];
+        image::Rgba([r, g, b, 255])
+    })
+}
+
+/// A resolution wedge, black and white horizontal lines converging from left to right, their
+/// period shrinking steadily so the point where they merge gives the vertical resolution
+///
+/// # Arguments
+///
+/// * `width`: Width of the image
+/// * `height`: Height of the image
+/// * `max_period`: Period of a black and white line pair in pixels on the left
+/// * `min_period`: Period on the right, 2 being the finest a pixel grid can show
+///
+/// returns: ImageBuffer
+pub fn resolution_wedge(width: u32, height: u32, max_period: f32, min_period: f32) -> ImageBuffer {
+    let centre = height as f32 / 2.;
+
+    supersampled(width, height, |x, y| {
+        let t = x / width as f32;
+        let period = max_period * (1. - t) + min_period * t;
+        if ((y - centre) / period).rem_euclid(1.) < 0.5 { 0. } else { 1. }
+    })
+}
+
+/// A grey image of a shape given by its brightness from 0 to 1 at points in the plane, averaged
+/// over a grid of samples in each pixel so edges are antialiased
+fn supersampled<F>(width: u32, height: u32, brightness: F) -> ImageBuffer
+where
+    F: Fn(f32, f32) -> f32,
+{
+    const SAMPLES: u32 = 8;
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let total: f32 = (0..SAMPLES * SAMPLES)
+            .map(|i| {
+                let (sx, sy) = ((i % SAMPLES) as f32 + 0.5, (i / SAMPLES) as f32 + 0.5);
+                brightness(x as f32 + sx / SAMPLES as f32, y as f32 + sy / SAMPLES as f32)
+            })
+            .sum();
+        let grey = (total / (SAMPLES * SAMPLES) as f32 * 255.).round() as u8;
+        image::Rgba([grey, grey, grey, 255])
+    })
+}
+
+/// A cycle of colours through cosines offset per channel
+fn palette(t: f64) -> Colour {
+    let channel = |offset: f64| ((0.5 + 0.5 * (std::f64::consts::TAU * (t + offset)).cos()) * 255.).round() as u8;
+    image::Rgba([channel(0.), channel(0.33), channel(0.67), 255])
+}
+
+fn mix(from: Colour, to: Colour, t: f32) -> Colour {
+    let t = t.clamp(0., 1.);
+    image::Rgba(std::array::from_fn(|c| (from[c] as f32 * (1. - t) + to[c] as f32 * t).round() as u8))
+}
+
+/// Classic gradient noise, from a seeded permutation of the lattice
+struct Perlin {
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    fn new(seed: u64) -> Perlin {
+        let mut values: Vec<u8> = (0..=255).collect();
+        values.shuffle(&mut rng(Some(seed)));
+        Perlin { permutation: std::array::from_fn(|i| values[i % 256]) }
+    }
+
+    /// Noise at a point, within -1 to 1 and zero on the lattice
+    fn at(&self, x: f32, y: f32) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (xi, yi) = ((x0 as i64).rem_euclid(256) as usize, (y0 as i64).rem_euclid(256) as usize);
+
+        let hash = |i: usize, j: usize| self.permutation[self.permutation[i] as usize + j];
+        // Dot product of the distance to a lattice point with one of eight gradients
+        let gradient = |hash: u8, dx: f32, dy: f32| match hash % 8 {
+            0 => dx + dy,
+            1 => dx - dy,
+            2 => -dx + dy,
+            3 => -dx - dy,
+            4 => dx * std::f32::consts::SQRT_2,
+            5 => -dx * std::f32::consts::SQRT_2,
+            6 => dy * std::f32::consts::SQRT_2,
+            _ => -dy * std::f32::consts::SQRT_2,
+        };
+        let fade = |t: f32| t * t * t * (t * (t * 6. - 15.) + 10.);
+
+        let (u, v) = (fade(fx), fade(fy));
+        let top = lerp(gradient(hash(xi, yi), fx, fy), gradient(hash(xi + 1, yi), fx - 1., fy), u);
+        let bottom = lerp(gradient(hash(xi, yi + 1), fx, fy - 1.), gradient(hash(xi + 1, yi + 1), fx - 1., fy - 1.), u);
+        // Gradients of length root two keep this within -1 to 1
+        lerp(top, bottom, v)
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + (b - a) * t
+}
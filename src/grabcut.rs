@@ -0,0 +1,230 @@
+//! Foreground extraction by GrabCut (Rother, Kolmogorov and Blake, 2004), cutting a subject out
+//! of a photo from a rough rectangle around it or a few brush strokes. Foreground and background
+//! colours are each modelled by a mixture of gaussians, pixels are labelled by a minimum graph
+//! cut trading off how well each model fits against the contrast between neighbours, and the
+//! models are refitted to the new labels, a few times over.
+
+use crate::linalg::{determinant3, invert3};
+use crate::maxflow::Graph;
+use crate::plane::Plane;
+use crate::rect::Rect;
+use crate::ImageBuffer;
+
+/// Gaussians in each colour mixture
+const COMPONENTS: usize = 5;
+
+/// Weight of the smoothness cost against the colour cost, the value from the paper
+const GAMMA: f64 = 50.;
+
+/// Rounds of k-means starting each mixture
+const KMEANS_ITERATIONS: usize = 5;
+
+/// Added to the diagonal of each covariance, so a component of one flat colour stays invertible
+const COVARIANCE_FLOOR: f64 = 0.01;
+
+/// Seed mask values up to this are certainly background
+pub const SURE_BACKGROUND: f32 = 0.25;
+
+/// Seed mask values from this are certainly foreground
+pub const SURE_FOREGROUND: f32 = 0.75;
+
+/// Cut the subject inside a rectangle out of an image. Everything outside the rectangle is
+/// background and everything inside starts as foreground, so the rectangle should hold the
+/// whole subject with a little room around it.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to cut from
+/// * `rect`: Rectangle around the subject
+/// * `iterations`: Rounds of cutting and refitting the colour models, e.g. 5
+///
+/// returns: Plane, an alpha matte of 1 for foreground and 0 for background
+pub fn grab_cut(input: &ImageBuffer, rect: Rect, iterations: u32) -> Plane {
+    let mut seeds = Plane::new(input.width(), input.height());
+    if let Some(rect) = rect.intersect(&Rect::new(0, 0, input.width(), input.height())) {
+        for y in rect.y..rect.bottom() {
+            for x in rect.x..rect.right() {
+                seeds.set(x, y, 0.5);
+            }
+        }
+    }
+    grab_cut_mask(input, &seeds, iterations)
+}
+
+/// Cut a subject out of an image from a seed mask, e.g. brush strokes painted over the
+/// image. Values up to `SURE_BACKGROUND` (black strokes) are kept as background and values from
+/// `SURE_FOREGROUND` (white strokes) as foreground. Values between are left to the cut, starting
+/// as foreground from 0.5 and background below, so paint the rest mid grey to start it in the
+/// foreground or darker grey to start it in the background.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to cut from
+/// * `seeds`: Seed mask from 0 to 1, the size of the input
+/// * `iterations`: Rounds of cutting and refitting the colour models, e.g. 5
+///
+/// returns: Plane, an alpha matte of 1 for foreground and 0 for background
+pub fn grab_cut_mask(input: &ImageBuffer, seeds: &Plane, iterations: u32) -> Plane {
+    let (width, height) = (input.width() as usize, input.height() as usize);
+    let colours: Vec<[f64; 3]> = input.pixels().map(|p| [p[0] as f64, p[1] as f64, p[2] as f64]).collect();
+    let mut foreground: Vec<bool> = seeds.data.iter().map(|&v| v >= 0.5).collect();
+
+    let neighbours = neighbour_weights(&colours, width, height);
+    // More than the cost of cutting every link around a pixel, so seeds are never relabelled
+    let sure = 1. + 8. * GAMMA;
+
+    for _ in 0..iterations.max(1) {
+        let (foreground_colours, background_colours): (Vec<_>, Vec<_>) = colours.iter().zip(&foreground).partition(|(_, &f)| f);
+        if foreground_colours.is_empty() || background_colours.is_empty() {
+            break;
+        }
+        let foreground_model = Mixture::fit(&foreground_colours.into_iter().map(|(c, _)| *c).collect::<Vec<_>>());
+        let background_model = Mixture::fit(&background_colours.into_iter().map(|(c, _)| *c).collect::<Vec<_>>());
+
+        let mut graph = Graph::new(width * height);
+        for (i, (colour, &seed)) in colours.iter().zip(&seeds.data).enumerate() {
+            let (source, sink) = if seed <= SURE_BACKGROUND {
+                (0., sure)
+            } else if seed >= SURE_FOREGROUND {
+                (sure, 0.)
+            } else {
+                // Cutting from the source labels background, so it costs the background fit
+                (background_model.cost(colour), foreground_model.cost(colour))
+            };
+            graph.add_terminals(i, source, sink);
+        }
+        for &(a, b, weight) in &neighbours {
+            graph.add_edge(a, b, weight, weight);
+        }
+
+        graph.max_flow();
+        let labels: Vec<bool> = (0..width * height).map(|i| graph.is_source_side(i)).collect();
+        if labels == foreground {
+            break;
+        }
+        foreground = labels;
+    }
+
+    Plane { width: input.width(), height: input.height(), data: foreground.iter().map(|&f| if f { 1. } else { 0. }).collect() }
+}
+
+/// Set an image's alpha from a matte, multiplying any alpha it already has, to cut out the
+/// subject for compositing
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to cut from
+/// * `matte`: Alpha matte from 0 to 1, the size of the input
+///
+/// returns: ImageBuffer
+pub fn apply_matte(input: &ImageBuffer, matte: &Plane) -> ImageBuffer {
+    ImageBuffer::from_fn(input.width(), input.height(), |x, y| {
+        let mut pixel = *input.get_pixel(x, y);
+        pixel[3] = (pixel[3] as f32 * matte.get(x, y).clamp(0., 1.)).round() as u8;
+        pixel
+    })
+}
+
+/// Links between each pixel and its eight neighbours, weighted to be cheap to cut across strong
+/// colour edges, with the contrast scale set from the image's mean neighbour difference
+fn neighbour_weights(colours: &[[f64; 3]], width: usize, height: usize) -> Vec<(usize, usize, f64)> {
+    let mut pairs = Vec::with_capacity(width * height * 4);
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            // Right, down right, down and down left, so each pair is visited once
+            for (dx, dy) in [(1, 0), (1, 1), (0, 1), (-1, 1)] {
+                let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                if nx >= 0 && (nx as usize) < width && (ny as usize) < height {
+                    let distance = ((dx * dx + dy * dy) as f64).sqrt();
+                    pairs.push((i, ny as usize * width + nx as usize, distance));
+                }
+            }
+        }
+    }
+
+    let difference = |a: usize, b: usize| (0..3).map(|c| (colours[a][c] - colours[b][c]).powi(2)).sum::<f64>();
+    let mean_difference = pairs.iter().map(|&(a, b, _)| difference(a, b)).sum::<f64>() / pairs.len().max(1) as f64;
+    let beta = if mean_difference > 0. { 1. / (2. * mean_difference) } else { 0. };
+
+    pairs.into_iter().map(|(a, b, distance)| (a, b, GAMMA / distance * (-beta * difference(a, b)).exp())).collect()
+}
+
+struct Gaussian {
+    mean: [f64; 3],
+    inverse: [[f64; 3]; 3],
+    /// Weight over the square root of the covariance determinant, the scale of the density
+    scale: f64,
+}
+
+impl Gaussian {
+    /// Density at a colour, leaving out the constant shared by every gaussian
+    fn density(&self, colour: &[f64; 3]) -> f64 {
+        let d = [colour[0] - self.mean[0], colour[1] - self.mean[1], colour[2] - self.mean[2]];
+        let distance: f64 = (0..3).map(|i| d[i] * (0..3).map(|j| self.inverse[i][j] * d[j]).sum::<f64>()).sum();
+        self.scale * (-0.5 * distance).exp()
+    }
+}
+
+/// A gaussian mixture over r,g,b colours
+struct Mixture {
+    components: Vec<Gaussian>,
+}
+
+impl Mixture {
+    /// Fit a mixture to colours, clustering them by k-means from centres spread through their
+    /// luma order, so the same colours always give the same mixture
+    fn fit(colours: &[[f64; 3]]) -> Mixture {
+        let mut order: Vec<usize> = (0..colours.len()).collect();
+        let luma = |c: &[f64; 3]| 0.299 * c[0] + 0.587 * c[1] + 0.114 * c[2];
+        order.sort_by(|&a, &b| luma(&colours[a]).total_cmp(&luma(&colours[b])));
+        let mut centres: Vec<[f64; 3]> = (0..COMPONENTS).map(|k| colours[order[(2 * k + 1) * colours.len() / (2 * COMPONENTS)]]).collect();
+
+        let mut assignments = vec![0; colours.len()];
+        for _ in 0..KMEANS_ITERATIONS {
+            for (assignment, colour) in assignments.iter_mut().zip(colours) {
+                let distance = |centre: &[f64; 3]| (0..3).map(|c| (colour[c] - centre[c]).powi(2)).sum::<f64>();
+                *assignment = (0..centres.len()).min_by(|&a, &b| distance(&centres[a]).total_cmp(&distance(&centres[b]))).unwrap_or(0);
+            }
+
+            let mut totals = vec![([0.; 3], 0usize); centres.len()];
+            for (&assignment, colour) in assignments.iter().zip(colours) {
+                let (total, count) = &mut totals[assignment];
+                *total = [total[0] + colour[0], total[1] + colour[1], total[2] + colour[2]];
+                *count += 1;
+            }
+            for (centre, (total, count)) in centres.iter_mut().zip(totals) {
+                if count > 0 {
+                    *centre = total.map(|t| t / count as f64);
+                }
+            }
+        }
+
+        let components = (0..centres.len())
+            .filter_map(|k| {
+                let members: Vec<&[f64; 3]> = assignments.iter().zip(colours).filter(|(&a, _)| a == k).map(|(_, c)| c).collect();
+                if members.is_empty() {
+                    return None;
+                }
+
+                let count = members.len() as f64;
+                let mean: [f64; 3] = std::array::from_fn(|c| members.iter().map(|m| m[c]).sum::<f64>() / count);
+                let mut covariance: [[f64; 3]; 3] = std::array::from_fn(|i| std::array::from_fn(|j| members.iter().map(|m| (m[i] - mean[i]) * (m[j] - mean[j])).sum::<f64>() / count));
+                for (i, row) in covariance.iter_mut().enumerate() {
+                    row[i] += COVARIANCE_FLOOR;
+                }
+
+                let weight = count / colours.len() as f64;
+                Some(Gaussian { mean, inverse: invert3(&covariance)?, scale: weight / determinant3(&covariance).sqrt() })
+            })
+            .collect();
+
+        Mixture { components }
+    }
+
+    /// Cost of a colour belonging to this mixture, its negative log likelihood
+    fn cost(&self, colour: &[f64; 3]) -> f64 {
+        let density: f64 = self.components.iter().map(|g| g.density(colour)).sum();
+        -density.max(f64::MIN_POSITIVE).ln()
+    }
+}
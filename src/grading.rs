@@ -0,0 +1,161 @@
+//! Colour grading by tonal range: split toning tints the shadows, midtones and highlights
+//! separately, each with its own lift and gain, blended by masks on the luma which fall off
+//! smoothly so there are no bands where one range hands over to the next.
+
+use crate::ImageBuffer;
+
+/// Largest tint, at a strength of 1, as a fraction of the range
+const TINT_SCALE: f32 = 0.25;
+
+/// Adjustment to one tonal range
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneRange {
+    hue: f32,
+    strength: f32,
+    lift: f32,
+    gain: f32,
+}
+
+impl Default for ToneRange {
+    fn default() -> ToneRange {
+        ToneRange { hue: 0., strength: 0., lift: 0., gain: 1. }
+    }
+}
+
+impl ToneRange {
+    pub fn new() -> ToneRange {
+        ToneRange::default()
+    }
+
+    /// Tint towards a hue, in degrees from red through green at 120 and blue at 240, by a
+    /// strength from 0 to 1. Tints shift the colour without changing the luma.
+    pub fn with_tint(mut self, hue: f32, strength: f32) -> ToneRange {
+        self.hue = hue.rem_euclid(360.);
+        self.strength = strength.clamp(0., 1.);
+        self
+    }
+
+    /// Raise the blacks towards white by a fraction, or lower them below black if negative
+    pub fn with_lift(mut self, lift: f32) -> ToneRange {
+        self.lift = lift.clamp(-1., 1.);
+        self
+    }
+
+    /// Scale the values, brightening above 1 and darkening below
+    pub fn with_gain(mut self, gain: f32) -> ToneRange {
+        self.gain = gain.max(0.);
+        self
+    }
+
+    /// What the range does to a value from 0 to 1 of a channel, before masking
+    fn adjust(&self, value: f32, tint: f32) -> f32 {
+        let gained = value * self.gain;
+        gained + self.lift * (1. - gained) + tint
+    }
+
+    /// Offset of each channel towards the hue, summing to no change of luma
+    fn tint(&self) -> [f32; 3] {
+        let colour = hue_colour(self.hue);
+        let luma = 0.299 * colour[0] + 0.587 * colour[1] + 0.114 * colour[2];
+        colour.map(|c| (c - luma) * self.strength * TINT_SCALE)
+    }
+}
+
+/// Split toning of an image's shadows, midtones and highlights
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct SplitToning {
+    shadows: ToneRange,
+    midtones: ToneRange,
+    highlights: ToneRange,
+    balance: f32,
+}
+
+impl SplitToning {
+    pub fn new() -> SplitToning {
+        SplitToning::default()
+    }
+
+    pub fn with_shadows(mut self, shadows: ToneRange) -> SplitToning {
+        self.shadows = shadows;
+        self
+    }
+
+    pub fn with_midtones(mut self, midtones: ToneRange) -> SplitToning {
+        self.midtones = midtones;
+        self
+    }
+
+    pub fn with_highlights(mut self, highlights: ToneRange) -> SplitToning {
+        self.highlights = highlights;
+        self
+    }
+
+    /// Move the luma where shadows turn to highlights, from -1 (darker, more highlights) to 1
+    /// (lighter, more shadows)
+    pub fn with_balance(mut self, balance: f32) -> SplitToning {
+        self.balance = balance.clamp(-1., 1.);
+        self
+    }
+
+    /// Weights of the shadows, midtones and highlights at a luma from 0 to 1, summing to 1
+    pub fn weights(&self, luma: f32) -> [f32; 3] {
+        let pivot = (0.5 + 0.45 * self.balance).clamp(0.05, 0.95);
+        let shadows = 1. - smoothstep(0., pivot, luma);
+        let highlights = smoothstep(pivot, 1., luma);
+        [shadows, 1. - shadows - highlights, highlights]
+    }
+
+    /// Apply the toning to an image, keeping its alpha
+    ///
+    /// # Arguments
+    ///
+    /// * `input`: ImageBuffer to tone
+    ///
+    /// returns: ImageBuffer
+    pub fn apply(&self, input: &ImageBuffer) -> ImageBuffer {
+        let ranges = [self.shadows, self.midtones, self.highlights];
+        let tints = ranges.map(|range| range.tint());
+
+        let mut output = input.clone();
+        for pixel in output.pixels_mut() {
+            let rgb = [pixel[0], pixel[1], pixel[2]].map(|v| v as f32 / 255.);
+            let weights = self.weights(0.299 * rgb[0] + 0.587 * rgb[1] + 0.114 * rgb[2]);
+            for channel in 0..3 {
+                let value: f32 = (0..3).map(|r| weights[r] * ranges[r].adjust(rgb[channel], tints[r][channel])).sum();
+                pixel[channel] = (value * 255.).round().clamp(0., 255.) as u8;
+            }
+        }
+        output
+    }
+}
+
+/// Tone an image's shadows and highlights towards two hues, e.g. teal (180) and orange (30)
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to tone
+/// * `shadow_hue`: Hue of the shadows in degrees
+/// * `highlight_hue`: Hue of the highlights in degrees
+/// * `strength`: Strength of both tints, from 0 to 1
+///
+/// returns: ImageBuffer
+pub fn split_tone(input: &ImageBuffer, shadow_hue: f32, highlight_hue: f32, strength: f32) -> ImageBuffer {
+    SplitToning::new()
+        .with_shadows(ToneRange::new().with_tint(shadow_hue, strength))
+        .with_highlights(ToneRange::new().with_tint(highlight_hue, strength))
+        .apply(input)
+}
+
+/// The fully saturated colour of a hue in degrees, channels from 0 to 1
+fn hue_colour(hue: f32) -> [f32; 3] {
+    [0., 120., 240.].map(|centre: f32| {
+        let distance = ((hue - centre).rem_euclid(360.)).min((centre - hue).rem_euclid(360.));
+        (2. - distance / 60.).clamp(0., 1.)
+    })
+}
+
+/// 0 at `low`, 1 at `high` and a smooth step between
+fn smoothstep(low: f32, high: f32, value: f32) -> f32 {
+    let t = ((value - low) / (high - low)).clamp(0., 1.);
+    t * t * (3. - 2. * t)
+}
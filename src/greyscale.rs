@@ -0,0 +1,87 @@
+//! Greyscale conversion by a choice of formula, as no one mix of the channels suits every image:
+//! the luma weightings of Rec. 601 and Rec. 709 keep the tones the eye sees, desaturation keeps
+//! the lightness of each colour, a single channel acts like a coloured filter on black and white
+//! film, and a channel mixer takes any weights. The mixer also mixes colour to colour.
+
+use crate::ImageBuffer;
+
+/// How `greyscale` turns colour into grey
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub enum Greyscale {
+    /// Luma weighted for standard definition video, `0.299 r + 0.587 g + 0.114 b`, as used
+    /// by the analysis in `plane::Plane::from_luma`
+    Rec601,
+    /// Luma weighted for HD video and sRGB's primaries, `0.2126 r + 0.7152 g + 0.0722 b`
+    #[default]
+    Rec709,
+    /// The midpoint of the largest and smallest channels, the lightness of HSL
+    Desaturate,
+    /// One channel alone, 0 red, 1 green or 2 blue; red darkens skies as a red filter would
+    Channel(usize),
+    /// Weights of red, green and blue, keeping the brightness if they sum to 1
+    Mix([f32; 3]),
+}
+
+impl Greyscale {
+    pub fn from_name(name: &str) -> Option<Greyscale> {
+        match name {
+            "rec601" => Some(Greyscale::Rec601),
+            "rec709" => Some(Greyscale::Rec709),
+            "desaturate" => Some(Greyscale::Desaturate),
+            "red" => Some(Greyscale::Channel(0)),
+            "green" => Some(Greyscale::Channel(1)),
+            "blue" => Some(Greyscale::Channel(2)),
+            _ => None,
+        }
+    }
+
+    /// The grey of one pixel's red, green and blue, unrounded
+    pub fn grey(&self, pixel: image::Rgba<u8>) -> f32 {
+        let [r, g, b] = [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32];
+        match self {
+            Greyscale::Rec601 => 0.299 * r + 0.587 * g + 0.114 * b,
+            Greyscale::Rec709 => 0.2126 * r + 0.7152 * g + 0.0722 * b,
+            Greyscale::Desaturate => (r.max(g).max(b) + r.min(g).min(b)) / 2.,
+            Greyscale::Channel(channel) => pixel[(*channel).min(2)] as f32,
+            Greyscale::Mix(weights) => weights[0] * r + weights[1] * g + weights[2] * b,
+        }
+    }
+}
+
+/// Convert an image to grey, keeping it as rgba with equal channels and its alpha
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to convert
+/// * `conversion`: Formula for the grey
+///
+/// returns: ImageBuffer
+pub fn greyscale(input: &ImageBuffer, conversion: Greyscale) -> ImageBuffer {
+    let mut output = input.clone();
+    for pixel in output.pixels_mut() {
+        let grey = conversion.grey(*pixel).round().clamp(0., 255.) as u8;
+        *pixel = image::Rgba([grey, grey, grey, pixel[3]]);
+    }
+    output
+}
+
+/// Mix the channels of an image, each output channel a weighted sum of the input's red, green
+/// and blue, e.g. rows of `[1, 0, 0]`, `[0, 1, 0]`, `[0, 0, 1]` leave it unchanged, and three
+/// equal rows make it grey. Alpha is kept.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to mix
+/// * `matrix`: Weights of red, green and blue for each output channel, red first
+///
+/// returns: ImageBuffer
+pub fn channel_mix(input: &ImageBuffer, matrix: [[f32; 3]; 3]) -> ImageBuffer {
+    let mut output = input.clone();
+    for pixel in output.pixels_mut() {
+        let [r, g, b] = [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32];
+        for (channel, weights) in matrix.iter().enumerate() {
+            pixel[channel] = (weights[0] * r + weights[1] * g + weights[2] * b).round().clamp(0., 255.) as u8;
+        }
+    }
+    output
+}
@@ -0,0 +1,123 @@
+//! A minimal http/1.1 client for `io::from_url`, fetching whole responses over plain tcp
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use crate::error::{Error, Result};
+
+const MAX_REDIRECTS: usize = 5;
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fetch the body of a url, following redirects
+pub(crate) fn get(url: &str) -> Result<Vec<u8>> {
+    let mut url = url.to_string();
+
+    for _ in 0..=MAX_REDIRECTS {
+        match request(&url)? {
+            Response::Body(body) => return Ok(body),
+            Response::Redirect(location) => {
+                url = match location.starts_with('/') {
+                    true => format!("http://{}{}", split_url(&url)?.0, location),
+                    false => location,
+                };
+            }
+        }
+    }
+
+    Err(Error::Http(format!("more than {} redirects", MAX_REDIRECTS)))
+}
+
+enum Response {
+    Body(Vec<u8>),
+    Redirect(String),
+}
+
+/// Split a url into its `host[:port]` and path
+fn split_url(url: &str) -> Result<(&str, &str)> {
+    let rest = match url.strip_prefix("http://") {
+        Some(rest) => rest,
+        None if url.starts_with("https://") => return Err(Error::Http("https urls aren't supported".to_string())),
+        None => return Err(Error::Http(format!("not an http url: {}", url))),
+    };
+
+    Ok(match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    })
+}
+
+fn request(url: &str) -> Result<Response> {
+    let (authority, path) = split_url(url)?;
+    let address = match authority.contains(':') {
+        true => authority.to_string(),
+        false => format!("{}:80", authority),
+    };
+
+    let mut stream = TcpStream::connect(address)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    write!(stream, "GET {} HTTP/1.1\r\nHost: {}\r\nAccept: image/*\r\nConnection: close\r\n\r\n", path, authority)?;
+
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status: u16 = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|status| status.parse().ok())
+        .ok_or_else(|| Error::Http(format!("malformed status line: {}", line.trim_end())))?;
+
+    let mut length = None;
+    let mut chunked = false;
+    let mut location = None;
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => length = value.parse::<usize>().ok(),
+                "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+                "location" => location = Some(value.to_string()),
+                _ => {}
+            }
+        }
+    }
+
+    match (status, location) {
+        (200..=299, _) => {}
+        (300..=399, Some(location)) => return Ok(Response::Redirect(location)),
+        (status, _) => return Err(Error::Http(format!("{} returned status {}", url, status))),
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            line.clear();
+            reader.read_line(&mut line)?;
+            let size = usize::from_str_radix(line.trim_end().split(';').next().unwrap_or(""), 16)
+                .map_err(|_| Error::Http("malformed chunk size".to_string()))?;
+            if size == 0 {
+                break;
+            }
+
+            let start = body.len();
+            body.resize(start + size, 0);
+            reader.read_exact(&mut body[start..])?;
+            // The line break closing the chunk
+            line.clear();
+            reader.read_line(&mut line)?;
+        }
+    } else if let Some(length) = length {
+        body.resize(length, 0);
+        reader.read_exact(&mut body)?;
+    } else {
+        reader.read_to_end(&mut body)?;
+    }
+
+    Ok(Response::Body(body))
+}
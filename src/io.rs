@@ -0,0 +1,379 @@
+use std::fs;
+use std::io::{Cursor, Read};
+use std::path::Path;
+
+use image::codecs::bmp::BmpEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::tiff::TiffEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::imageops::{flip_horizontal, flip_vertical, rotate180, rotate270, rotate90};
+use image::{ColorType, DynamicImage, GenericImageView};
+use log::{debug, warn};
+
+use crate::error::{Error, Result};
+use crate::metadata::exif::Orientation;
+use crate::metadata::Metadata;
+use crate::ImageBuffer;
+
+pub fn load_image(path: String) -> ImageBuffer {
+    open_image(path).unwrap()
+}
+
+/// What happens to the colours of an image with an embedded colour profile
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProfilePolicy {
+    /// Convert them to sRGB when loading, which the filters and most displays assume
+    ConvertToSrgb,
+    /// Leave them as they are, embedding the profile again in the output
+    Keep,
+}
+
+/// Load an image file as rgba, turned upright according to its exif orientation and converted
+/// to sRGB from any embedded colour profile
+pub fn open_image(path: impl AsRef<Path>) -> Result<ImageBuffer> {
+    open_image_with_profile(path, ProfilePolicy::ConvertToSrgb)
+}
+
+/// Load an image file as rgba, turned upright according to its exif orientation. With the
+/// `raw` feature, dng files are developed with the default `raw::DevelopOptions`.
+///
+/// # Arguments
+///
+/// * `path`: Image file
+/// * `profile`: Whether to convert colours from an embedded colour profile to sRGB
+///
+/// returns: Result<ImageBuffer>
+pub fn open_image_with_profile(path: impl AsRef<Path>, profile: ProfilePolicy) -> Result<ImageBuffer> {
+    #[cfg(feature = "raw")]
+    if crate::raw::is_raw_path(path.as_ref()) {
+        return crate::raw::open_raw(path);
+    }
+
+    from_bytes_with_profile(&fs::read(path)?, profile)
+}
+
+/// Decode an image held in memory, in any supported format, like `open_image`
+pub fn from_bytes(data: &[u8]) -> Result<ImageBuffer> {
+    from_bytes_with_profile(data, ProfilePolicy::ConvertToSrgb)
+}
+
+/// Decode an image read to its end from a reader, like `open_image`
+pub fn from_reader(mut reader: impl Read) -> Result<ImageBuffer> {
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+
+    from_bytes(&data)
+}
+
+/// Download and decode an image, like `open_image`. Only plain `http://` urls are supported,
+/// following up to five redirects.
+#[cfg(feature = "url")]
+pub fn from_url(url: &str) -> Result<ImageBuffer> {
+    from_bytes(&crate::http::get(url)?)
+}
+
+/// Decode an image held in memory, like `open_image_with_profile`
+pub fn from_bytes_with_profile(data: &[u8], profile: ProfilePolicy) -> Result<ImageBuffer> {
+    let input_raw = image::load_from_memory(data)?;
+
+    let (input_x, input_y) = input_raw.dimensions();
+    let mut input: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+    for(x, y, pixel) in input.enumerate_pixels_mut() {
+        *pixel = input_raw.get_pixel(x, y);
+    }
+
+    let metadata = Metadata::from_bytes(data);
+    let mut input = apply_orientation(input, metadata.orientation());
+    if profile == ProfilePolicy::ConvertToSrgb {
+        convert_profile(&mut input, &metadata);
+    }
+
+    Ok(input)
+}
+
+/// Convert an image to sRGB from the colour profile in its metadata, if it has one
+fn convert_profile(image: &mut ImageBuffer, metadata: &Metadata) {
+    let profile = match (&metadata.icc_profile, metadata.parse_icc_profile()) {
+        (None, _) => return,
+        (Some(_), Some(profile)) => profile,
+        (Some(_), None) => return warn!("Couldn't read the image's colour profile, using its colours as they are"),
+    };
+    let name = profile.description.as_deref().unwrap_or("unnamed");
+
+    if profile.is_srgb() {
+        return;
+    }
+    if profile.convert_to_srgb(image) {
+        debug!("Converted colours from the '{}' profile to sRGB", name);
+    } else {
+        warn!("Can't convert colours from the '{}' profile, using them as they are", name);
+    }
+}
+
+/// Turn an image's pixels so it displays upright
+///
+/// # Arguments
+///
+/// * `image`: Image as stored
+/// * `orientation`: How the stored image should be turned, usually from its exif data
+///
+/// returns: ImageBuffer
+pub fn apply_orientation(image: ImageBuffer, orientation: Orientation) -> ImageBuffer {
+    match orientation {
+        Orientation::Normal => image,
+        Orientation::FlipHorizontal => flip_horizontal(&image),
+        Orientation::Rotate180 => rotate180(&image),
+        Orientation::FlipVertical => flip_vertical(&image),
+        Orientation::Transpose => flip_horizontal(&rotate90(&image)),
+        Orientation::Rotate90 => rotate90(&image),
+        Orientation::Transverse => flip_horizontal(&rotate270(&image)),
+        Orientation::Rotate270 => rotate270(&image),
+    }
+}
+
+/// File formats results can be written in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputFormat {
+    Png,
+    Jpeg,
+    /// Lossless webp
+    WebP,
+    Tiff,
+    Bmp,
+}
+
+impl OutputFormat {
+    pub fn from_name(name: &str) -> Option<OutputFormat> {
+        match name.to_ascii_lowercase().as_str() {
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg),
+            "webp" => Some(OutputFormat::WebP),
+            "tif" | "tiff" => Some(OutputFormat::Tiff),
+            "bmp" => Some(OutputFormat::Bmp),
+            _ => None,
+        }
+    }
+
+    /// The format matching a path's extension
+    pub fn from_path(path: &Path) -> Result<OutputFormat> {
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or("");
+        OutputFormat::from_name(extension).ok_or_else(|| Error::UnsupportedFormat(path.display().to_string()))
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg => "jpg",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Tiff => "tiff",
+            OutputFormat::Bmp => "bmp",
+        }
+    }
+}
+
+/// Png compression effort, trading file size against encoding time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PngCompression {
+    Fast,
+    Default,
+    Best,
+}
+
+impl PngCompression {
+    pub fn from_name(name: &str) -> Option<PngCompression> {
+        match name {
+            "fast" => Some(PngCompression::Fast),
+            "default" => Some(PngCompression::Default),
+            "best" => Some(PngCompression::Best),
+            _ => None,
+        }
+    }
+}
+
+/// Whether a processed image keeps the exif, colour profile and text of the file it came from.
+/// Only png and jpeg output can carry metadata, it's dropped from other formats.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetadataPolicy {
+    Strip,
+    Preserve,
+}
+
+/// How results are encoded, built up from the defaults of a format taken from the file
+/// extension, jpeg quality 90, default png compression and stripped metadata
+#[derive(Clone, Debug)]
+pub struct OutputOptions {
+    format: Option<OutputFormat>,
+    jpeg_quality: u8,
+    png_compression: PngCompression,
+    metadata: MetadataPolicy,
+    profile: ProfilePolicy,
+}
+
+impl Default for OutputOptions {
+    fn default() -> OutputOptions {
+        OutputOptions {
+            format: None,
+            jpeg_quality: 90,
+            png_compression: PngCompression::Default,
+            metadata: MetadataPolicy::Strip,
+            profile: ProfilePolicy::ConvertToSrgb,
+        }
+    }
+}
+
+impl OutputOptions {
+    pub fn new() -> OutputOptions {
+        OutputOptions::default()
+    }
+
+    /// Write this format whatever the output path's extension
+    pub fn with_format(mut self, format: OutputFormat) -> OutputOptions {
+        self.format = Some(format);
+        self
+    }
+
+    /// Jpeg quality from 1 to 100
+    pub fn with_jpeg_quality(mut self, quality: u8) -> OutputOptions {
+        self.jpeg_quality = quality.clamp(1, 100);
+        self
+    }
+
+    pub fn with_png_compression(mut self, compression: PngCompression) -> OutputOptions {
+        self.png_compression = compression;
+        self
+    }
+
+    pub fn with_metadata(mut self, metadata: MetadataPolicy) -> OutputOptions {
+        self.metadata = metadata;
+        self
+    }
+
+    /// How the source was loaded: `Keep` embeds its colour profile in the output, even when
+    /// other metadata is stripped, while `ConvertToSrgb` never copies it as the colours no longer
+    /// match it
+    pub fn with_profile(mut self, profile: ProfilePolicy) -> OutputOptions {
+        self.profile = profile;
+        self
+    }
+
+    pub fn format(&self) -> Option<OutputFormat> {
+        self.format
+    }
+
+    pub fn metadata(&self) -> MetadataPolicy {
+        self.metadata
+    }
+
+    pub fn profile(&self) -> ProfilePolicy {
+        self.profile
+    }
+
+    /// The format to write to a path, the chosen one or else the one matching its extension
+    pub fn format_for(&self, path: &Path) -> Result<OutputFormat> {
+        match self.format {
+            Some(format) => Ok(format),
+            None => OutputFormat::from_path(path),
+        }
+    }
+}
+
+/// Save an image with the given encoding options
+///
+/// # Arguments
+///
+/// * `image`: Image to save
+/// * `path`: File to write
+/// * `options`: Format and encoding settings
+/// * `source`: File the image was processed from, loaded with `open_image_with_profile` and the
+///   options' profile policy, whose metadata is copied when the options preserve it
+///
+/// returns: Result<()>
+pub fn save_image(image: &ImageBuffer, path: impl AsRef<Path>, options: &OutputOptions, source: Option<&Path>) -> Result<()> {
+    let path = path.as_ref();
+    let format = options.format_for(path)?;
+
+    let metadata = match source {
+        Some(source) if options.metadata == MetadataPolicy::Preserve || options.profile == ProfilePolicy::Keep => {
+            let mut metadata = Metadata::read(source)?;
+            // Sources are loaded upright, so the orientation no longer applies
+            metadata.mark_upright();
+            if options.metadata == MetadataPolicy::Strip {
+                metadata = Metadata { icc_profile: metadata.icc_profile, ..Metadata::default() };
+            }
+            if options.profile == ProfilePolicy::ConvertToSrgb {
+                metadata.icc_profile = None;
+            }
+            Some(metadata)
+        }
+        _ => None,
+    };
+
+    let encoded = encode_image(image, format, options, metadata.as_ref())?;
+    fs::write(path, encoded)?;
+
+    Ok(())
+}
+
+/// Encode an image to bytes in the given format with the default options, the in memory
+/// counterpart of `ImageBuffer::save`
+pub fn to_bytes(image: &ImageBuffer, format: OutputFormat) -> Result<Vec<u8>> {
+    encode_image(image, format, &OutputOptions::default(), None)
+}
+
+/// Encode an image to bytes in the given format
+///
+/// # Arguments
+///
+/// * `image`: Image to encode
+/// * `format`: Format to encode as, ignoring any format set in the options
+/// * `options`: Encoding settings
+/// * `metadata`: Metadata to embed, dropped with a warning for formats that can't carry it
+///
+/// returns: Result<Vec<u8>>
+pub fn encode_image(image: &ImageBuffer, format: OutputFormat, options: &OutputOptions, metadata: Option<&Metadata>) -> Result<Vec<u8>> {
+    let mut encoded = Vec::new();
+    let metadata = metadata.filter(|metadata| !metadata.is_empty());
+
+    match format {
+        OutputFormat::Png => {
+            let mut info = png::Info::with_size(image.width(), image.height());
+            info.color_type = png::ColorType::Rgba;
+            info.bit_depth = png::BitDepth::Eight;
+            info.compression = match options.png_compression {
+                PngCompression::Fast => png::Compression::Fast,
+                PngCompression::Default => png::Compression::Default,
+                PngCompression::Best => png::Compression::Best,
+            };
+            if let Some(metadata) = metadata {
+                metadata.apply_to_png(&mut info);
+            }
+
+            let mut writer = png::Encoder::with_info(&mut encoded, info)?.write_header()?;
+            writer.write_image_data(image.as_raw())?;
+            writer.finish()?;
+        }
+        OutputFormat::Jpeg => {
+            // Jpeg has no alpha channel
+            let rgb = DynamicImage::ImageRgba8(image.clone()).into_rgb8();
+            JpegEncoder::new_with_quality(&mut encoded, options.jpeg_quality).encode_image(&rgb)?;
+
+            if let Some(metadata) = metadata {
+                encoded = metadata.insert_into_jpeg(&encoded);
+            }
+        }
+        OutputFormat::WebP | OutputFormat::Tiff | OutputFormat::Bmp => {
+            if metadata.is_some() {
+                warn!("Metadata can't be written to {} files, dropping it", format.extension());
+            }
+
+            let mut cursor = Cursor::new(&mut encoded);
+            match format {
+                OutputFormat::WebP => WebPEncoder::new_lossless(&mut cursor).encode(image.as_raw(), image.width(), image.height(), ColorType::Rgba8)?,
+                OutputFormat::Tiff => TiffEncoder::new(&mut cursor).encode(image.as_raw(), image.width(), image.height(), ColorType::Rgba8)?,
+                _ => BmpEncoder::new(&mut cursor).encode(image.as_raw(), image.width(), image.height(), ColorType::Rgba8)?,
+            }
+        }
+    }
+
+    Ok(encoded)
+}
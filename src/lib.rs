@@ -0,0 +1,74 @@
+//! Implementing functions from 'Computer Vision: Algorithms and Applications'
+
+pub mod alignment;
+pub mod arithmetic;
+pub mod barcode;
+pub mod batch;
+pub mod calibration;
+pub mod canvas;
+pub mod document;
+pub mod effects;
+pub mod error;
+pub mod expr;
+pub mod features;
+mod fft;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod filters;
+pub mod generate;
+pub mod grading;
+pub mod grabcut;
+pub mod greyscale;
+#[cfg(feature = "url")]
+mod http;
+pub mod io;
+mod linalg;
+pub mod matting;
+mod maxflow;
+pub mod memory;
+pub mod metadata;
+pub mod montage;
+pub mod pipeline;
+pub mod pixel;
+pub mod plane;
+pub mod progress;
+pub mod pyramid;
+pub mod quality;
+pub mod random;
+#[cfg(feature = "raw")]
+pub mod raw;
+pub mod rect;
+pub mod records;
+pub mod redact;
+pub mod saliency;
+pub mod segmentation;
+pub mod shape;
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+pub mod simd;
+pub mod stacking;
+pub mod stats;
+pub mod stereo;
+pub mod stylize;
+pub mod superres;
+pub mod suppression;
+pub mod testing;
+pub mod text;
+pub mod thumbnail;
+pub mod tiling;
+mod timing;
+pub mod value;
+pub mod vignette;
+pub mod warp;
+#[cfg(target_arch = "wasm32")]
+pub mod wasm;
+
+pub use arithmetic::{adjust_brightness, adjust_contrast, adjust_gamma, apply_lut, image_add, image_sub, linear_blend};
+pub use error::{Error, Result};
+pub use filters::{adaptive_sharpen, apply_matrix, bilateral_filter, bilinear_filter, edge_detect, gaussian_blur, median_filter, sharpen, x_grad, y_grad};
+pub use io::{load_image, save_image, OutputFormat, OutputOptions};
+pub use memory::MemoryBudget;
+pub use pipeline::{Operation, Pipeline};
+pub use progress::{CancellationToken, Progress};
+pub use rect::Rect;
+
+pub type ImageBuffer = image::ImageBuffer<image::Rgba<u8>, Vec<u8>>;
@@ -0,0 +1,148 @@
+//! The small dense linear algebra the geometry code needs: solving systems, symmetric eigen
+//! decompositions and least squares null vectors, over row major `Vec<Vec<f64>>` matrices
+
+/// Solve `a x = b` by gaussian elimination with partial pivoting
+///
+/// returns: Option<Vec<f64>>, None if `a` is singular
+pub(crate) fn solve(a: &[Vec<f64>], b: &[f64]) -> Option<Vec<f64>> {
+    let n = b.len();
+    let mut m: Vec<Vec<f64>> = a.iter().zip(b).map(|(row, &b)| row.iter().copied().chain([b]).collect()).collect();
+
+    for column in 0..n {
+        let pivot = (column..n).max_by(|&i, &j| m[i][column].abs().total_cmp(&m[j][column].abs()))?;
+        if m[pivot][column].abs() < 1e-12 {
+            return None;
+        }
+        m.swap(column, pivot);
+
+        let (upper, lower) = m.split_at_mut(column + 1);
+        let pivot_row = &upper[column];
+        for row in lower.iter_mut() {
+            let factor = row[column] / pivot_row[column];
+            for (value, pivot) in row[column..].iter_mut().zip(&pivot_row[column..]) {
+                *value -= factor * pivot;
+            }
+        }
+    }
+
+    let mut x = vec![0.; n];
+    for row in (0..n).rev() {
+        let sum: f64 = (row + 1..n).map(|k| m[row][k] * x[k]).sum();
+        x[row] = (m[row][n] - sum) / m[row][row];
+    }
+
+    Some(x)
+}
+
+/// Eigen decomposition of a symmetric matrix by cyclic Jacobi rotations
+///
+/// returns: (Vec<f64>, Vec<Vec<f64>>), the eigenvalues in ascending order and the matching unit
+/// eigenvectors
+pub(crate) fn symmetric_eigen(a: &[Vec<f64>]) -> (Vec<f64>, Vec<Vec<f64>>) {
+    let n = a.len();
+    let mut a = a.to_vec();
+    let mut v: Vec<Vec<f64>> = (0..n).map(|i| (0..n).map(|j| if i == j { 1. } else { 0. }).collect()).collect();
+
+    let total: f64 = a.iter().flatten().map(|x| x * x).sum();
+    for _ in 0..100 {
+        let off_diagonal: f64 = (0..n).flat_map(|i| (0..n).filter(move |&j| j != i).map(move |j| (i, j))).map(|(i, j)| a[i][j] * a[i][j]).sum();
+        if off_diagonal <= 1e-24 * total {
+            break;
+        }
+
+        for p in 0..n {
+            for q in p + 1..n {
+                if a[p][q].abs() < 1e-300 {
+                    continue;
+                }
+
+                let theta = (a[q][q] - a[p][p]) / (2. * a[p][q]);
+                let t = theta.signum() / (theta.abs() + (theta * theta + 1.).sqrt());
+                let c = 1. / (t * t + 1.).sqrt();
+                let s = t * c;
+
+                for row in a.iter_mut() {
+                    let (akp, akq) = (row[p], row[q]);
+                    row[p] = c * akp - s * akq;
+                    row[q] = s * akp + c * akq;
+                }
+                let (before, after) = a.split_at_mut(q);
+                for (apk, aqk) in before[p].iter_mut().zip(after[0].iter_mut()) {
+                    let (x, y) = (*apk, *aqk);
+                    *apk = c * x - s * y;
+                    *aqk = s * x + c * y;
+                }
+                for row in v.iter_mut() {
+                    let (vkp, vkq) = (row[p], row[q]);
+                    row[p] = c * vkp - s * vkq;
+                    row[q] = s * vkp + c * vkq;
+                }
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = (0..n).collect();
+    order.sort_by(|&i, &j| a[i][i].total_cmp(&a[j][j]));
+
+    let values = order.iter().map(|&i| a[i][i]).collect();
+    let vectors = order.iter().map(|&i| v.iter().map(|row| row[i]).collect()).collect();
+    (values, vectors)
+}
+
+/// The unit vector `x` minimising `|a x|`, the eigenvector of `aᵀa` with the smallest eigenvalue
+pub(crate) fn null_vector(a: &[Vec<f64>]) -> Vec<f64> {
+    let (_, mut vectors) = symmetric_eigen(&transpose_product(a));
+    vectors.swap_remove(0)
+}
+
+/// `aᵀa`
+pub(crate) fn transpose_product(a: &[Vec<f64>]) -> Vec<Vec<f64>> {
+    let n = a.first().map(|row| row.len()).unwrap_or(0);
+    let mut product = vec![vec![0.; n]; n];
+
+    for row in a {
+        for i in 0..n {
+            for j in 0..n {
+                product[i][j] += row[i] * row[j];
+            }
+        }
+    }
+
+    product
+}
+
+pub(crate) fn multiply3(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+pub(crate) fn transform3(a: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|i| a[i][0] * v[0] + a[i][1] * v[1] + a[i][2] * v[2])
+}
+
+pub(crate) fn determinant3(a: &[[f64; 3]; 3]) -> f64 {
+    a[0][0] * (a[1][1] * a[2][2] - a[1][2] * a[2][1]) - a[0][1] * (a[1][0] * a[2][2] - a[1][2] * a[2][0]) + a[0][2] * (a[1][0] * a[2][1] - a[1][1] * a[2][0])
+}
+
+pub(crate) fn invert3(a: &[[f64; 3]; 3]) -> Option<[[f64; 3]; 3]> {
+    let cofactor = |i: usize, j: usize| {
+        let (r0, r1) = ((i + 1) % 3, (i + 2) % 3);
+        let (c0, c1) = ((j + 1) % 3, (j + 2) % 3);
+        a[r0][c0] * a[r1][c1] - a[r0][c1] * a[r1][c0]
+    };
+
+    let determinant = (0..3).map(|j| a[0][j] * cofactor(0, j)).sum::<f64>();
+    if determinant.abs() < 1e-15 {
+        return None;
+    }
+
+    // The inverse is the transposed cofactor matrix over the determinant
+    Some(std::array::from_fn(|i| std::array::from_fn(|j| cofactor(j, i) / determinant)))
+}
+
+pub(crate) fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[1] * b[2] - a[2] * b[1], a[2] * b[0] - a[0] * b[2], a[0] * b[1] - a[1] * b[0]]
+}
+
+pub(crate) fn norm(v: &[f64]) -> f64 {
+    v.iter().map(|x| x * x).sum::<f64>().sqrt()
+}
@@ -0,0 +1,41 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+/// Writes log records to stderr, info messages as they are and anything else prefixed with its
+/// level
+struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        match record.level() {
+            Level::Info => eprintln!("{}", record.args()),
+            level => eprintln!("[{}] {}", level.to_string().to_lowercase(), record.args()),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: StderrLogger = StderrLogger;
+
+/// Install the logger, `--quiet` keeps only warnings and errors while `--verbose` adds debug
+/// output such as per operation timings
+pub fn init(verbose: bool, quiet: bool) {
+    let level = if quiet {
+        LevelFilter::Warn
+    } else if verbose {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    };
+
+    log::set_logger(&LOGGER).expect("logger should only be installed once");
+    log::set_max_level(level);
+}
@@ -1,13 +1,186 @@
-use image::GenericImageView;
-use std::cmp;
-use image::imageops::contrast;
-use ndarray::{array, Array2};
+mod cli;
+mod logger;
+mod preview;
+mod server;
+
+use std::error::Error;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
 
-type ImageBuffer = image::ImageBuffer<image::Rgba<u8>, Vec<u8>>;
+use image::imageops::contrast;
+use log::{info, warn, LevelFilter};
+use rust_image_processing::alignment::Alignment;
+use rust_image_processing::barcode::{find_barcodes, find_qr_codes};
+use rust_image_processing::batch::{image_files, process_batch, run_batch, run_batch_with_budget};
+use rust_image_processing::calibration::{self, find_checkerboard, CameraCalibration};
+use rust_image_processing::canvas::Canvas;
+use rust_image_processing::document::{self, estimate_skew, rectify_page, ScanOptions};
+use rust_image_processing::expr::Expression;
+use rust_image_processing::grabcut::{apply_matte, grab_cut, grab_cut_mask};
+use rust_image_processing::io::{open_image, open_image_with_profile, MetadataPolicy, PngCompression, ProfilePolicy};
+use rust_image_processing::matting::{closed_form_matte, trimap_from_matte};
+use rust_image_processing::montage::{self, Comparison, Montage};
+use rust_image_processing::pixel;
+use rust_image_processing::plane::Plane;
+use rust_image_processing::quality::quality;
+use rust_image_processing::random;
+use rust_image_processing::records::Record;
+use rust_image_processing::redact::{redact_mask, Redaction};
+use rust_image_processing::saliency::Saliency;
+use rust_image_processing::segmentation::{connected_components, mean_shift_filter, mean_shift_segmentation};
+use rust_image_processing::shape::{describe_regions, thin};
+use rust_image_processing::stacking::{self, MergeMethod, StackOptions};
+use rust_image_processing::stats::{stats, CHANNEL_NAMES};
+use rust_image_processing::stereo::{self, MatchCost, StereoOptions};
+use rust_image_processing::superres::{self, SuperResMethod};
+use rust_image_processing::thumbnail;
+use rust_image_processing::value::{json, Value};
+use rust_image_processing::warp::{self, PolarTransform};
+use rust_image_processing::{adjust_brightness, edge_detect, load_image, save_image, OutputFormat, ImageBuffer, MemoryBudget, OutputOptions, Pipeline, Progress, Rect};
+
+use cli::Args;
+use preview::Protocol;
+use server::ServerConfig;
+
+const FLAGS: &[&str] = &["verbose", "quiet", "keep-profile", "json", "matte", "log", "inverse", "no-captions"];
+
+const USAGE: &str = "usage:
+    clionRustTest                                  run the example images
+    clionRustTest run --pipeline <file> --input <image> [--output <image>]
+                      [--tile-size <pixels> | --strip-height <rows> | --max-memory <size>]
+                      [output options]
+    clionRustTest batch --pipeline <file> --input-dir <dir> --output-dir <dir>
+                        [--max-memory <size>] [output options]
+    clionRustTest watch --pipeline <file> --input <image> [--output <image>] [--interval <ms>]
+                        [output options]
+    clionRustTest preview --input <image> [--pipeline <file>] [--width <columns>]
+                          [--protocol blocks|kitty|sixel]
+    clionRustTest serve [--address <host:port>] [--max-connections <n>]
+                        [--max-body-bytes <bytes>] [--timeout <seconds>]
+    clionRustTest calibrate --images <dir> --board <columns>x<rows> [--output <file>]
+    clionRustTest undistort --input <image> --calibration <file> [--output <image>]
+                            [output options]
+    clionRustTest disparity --left <image> --right <image> [--output <image>]
+                            [--max-disparity <pixels>] [--block-size <pixels>] [--cost sad|census]
+                            [output options]
+    clionRustTest polar --input <image> [--output <image>] [--log] [--inverse] [--centre <x,y>]
+                        [--radius <pixels>] [--width <pixels>] [--height <pixels>] [output options]
+    clionRustTest superres --input <image> [--output <image>] [--factor <times>]
+                           [--method back-projection|lanczos] [output options]
+    clionRustTest thumbnail --input <image> --width <pixels> --height <pixels> [--output <image>]
+                            [--saliency edges|spectral] [output options]
+    clionRustTest saliency --input <image> [--output <image>] [--saliency edges|spectral]
+                           [output options]
+    clionRustTest redact (--input <image> [--output <image>] | --input-dir <dir> --output-dir <dir>)
+                         (--regions <x,y,width,height;...> | --mask <image>)
+                         [--style pixelate|blur|fill] [--size <pixels>] [output options]
+    clionRustTest segment --input <image> [--output <image>] [--mode segment|filter]
+                          [--spatial-bandwidth <pixels>] [--range-bandwidth <distance>]
+                          [--min-region <pixels>] [output options]
+    clionRustTest cutout --input <image> (--rect <x,y,width,height> | --seeds <image>) [--output <image>]
+                         [--iterations <n>] [--feather <pixels>] [--matte] [output options]
+    clionRustTest matte --input <image> --trimap <image> [--output <image>] [--iterations <n>]
+                        [--matte] [output options]
+    clionRustTest scan --input <image> [--output <image>] [--corners <x,y;x,y;x,y;x,y>]
+                       [--radius <pixels>] [--offset <luma>] [--despeckle <pixels>] [output options]
+    clionRustTest deskew --input <image> [--output <image>] [output options]
+    clionRustTest stack --input-dir <dir> --output <image> [--align none|translation|homography]
+                        [--method mean|median|sigma-clip] [--sigma <deviations>] [output options]
+    clionRustTest focus-stack --input-dir <dir> --output <image> [--align none|translation|homography]
+                              [output options]
+    clionRustTest montage (--inputs <image,image,...> | --input-dir <dir>) --output <image>
+                          [--columns <count>] [--cell-size <width>x<height>] [--padding <pixels>]
+                          [--background <#rrggbb>] [--no-captions] [output options]
+    clionRustTest compare-visual --before <image> --after <image> --output <image>
+                                 [--mode side-by-side|split|checkerboard] [--position <fraction>]
+                                 [--size <pixels>] [output options]
+    clionRustTest expr --expression <expression> --inputs <image,image,...> --output <image>
+                       [output options]
+    clionRustTest compose --canvas <file> --output <image> [output options]
+    clionRustTest codes --input <image> [--crop-dir <dir>] [--json] [output options]
+    clionRustTest shapes --input <mask> [--min-area <pixels>] [--skeleton <path>] [--json] [output options]
+    clionRustTest info --input <image> [--json]
+    clionRustTest rank --input-dir <dir> [--min-score <0-1>] [--min-sharpness <variance>] [--json]
+
+output options:
+    --format png|jpeg|webp|tiff|bmp                format to write, by default from the output extension
+    --quality <1-100>                              jpeg quality, 90 by default
+    --compression fast|default|best                png compression effort
+    --metadata strip|preserve                      keep the input's exif, colour profile and text
+    --keep-profile                                 keep colours in the input's colour profile rather
+                                                   than converting to sRGB, embedding it in the output
+
+options:
+    --verbose                                      log debug output, including operation timings
+    --quiet                                        only log warnings and errors
+    --seed <n>                                     seed every random choice, for reproducible output";
 
 // Implementing functions from 'Computer Vision: Algorithms and Applications'
 fn main() {
-    let input1 = load_image("./images/benWindsorCodeIcon.jpg".to_string());
+    let args = match Args::parse(std::env::args().skip(1), FLAGS) {
+        Ok(args) => args,
+        Err(err) => exit_with_usage(&err),
+    };
+
+    logger::init(args.flag("verbose"), args.flag("quiet"));
+    match args.option("seed").map(str::parse) {
+        Some(Ok(seed)) => random::set_fixed_seed(Some(seed)),
+        Some(Err(_)) => exit_with_usage(&"--seed must be a whole number"),
+        None => {}
+    }
+
+    let result = match args.command.as_deref() {
+        None => {
+            examples();
+            Ok(())
+        }
+        Some("run") => run(&args),
+        Some("batch") => batch(&args),
+        Some("watch") => watch(&args),
+        Some("preview") => preview(&args),
+        Some("serve") => serve(&args),
+        Some("calibrate") => calibrate(&args),
+        Some("undistort") => undistort(&args),
+        Some("disparity") => disparity(&args),
+        Some("polar") => polar(&args),
+        Some("superres") => superres(&args),
+        Some("thumbnail") => thumbnail(&args),
+        Some("saliency") => saliency(&args),
+        Some("redact") => redact(&args),
+        Some("segment") => segment(&args),
+        Some("cutout") => cutout(&args),
+        Some("matte") => matte(&args),
+        Some("scan") => scan(&args),
+        Some("deskew") => deskew(&args),
+        Some("stack") => stack(&args),
+        Some("focus-stack") => focus_stack(&args),
+        Some("montage") => montage(&args),
+        Some("compare-visual") => compare_visual(&args),
+        Some("expr") => expr(&args),
+        Some("compose") => compose(&args),
+        Some("codes") => codes(&args),
+        Some("shapes") => shapes(&args),
+        Some("info") => info(&args),
+        Some("rank") => rank(&args),
+        Some(command) => exit_with_usage(&format!("unknown command '{}'", command)),
+    };
+
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn exit_with_usage(message: &dyn std::fmt::Display) -> ! {
+    eprintln!("error: {}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn examples() {
+    let _input1 = load_image("./images/benWindsorCodeIcon.jpg".to_string());
     let input2 = load_image("./images/houseTest.jpg".to_string());
 
     let edges = edge_detect(&input2);
@@ -20,357 +193,842 @@ fn main() {
     brightness_enhanced.save("./images/brightnessEnhanced.png").unwrap();
 }
 
+/// Run a pipeline file over an image, writing `<input>_processed.png` unless an output is given.
+/// Large images can be processed in tiles, given or chosen to fit a memory budget, or streamed
+/// through in strips of rows.
+fn run(args: &Args) -> Result<(), Box<dyn Error>> {
+    let pipeline = Pipeline::from_file(args.required("pipeline")?)?;
+    let input_path = Path::new(args.required("input")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+
+    let progress = progress_bar();
+
+    if let Some(strip_height) = args.option("strip-height") {
+        if options.format_for(&output_path)? != OutputFormat::Png || options.metadata() == MetadataPolicy::Preserve || args.flag("keep-profile") {
+            return Err("--strip-height only writes sRGB png files without metadata".into());
+        }
+        let strip_height: u32 = strip_height.parse()?;
+        if strip_height == 0 {
+            return Err("--strip-height must be at least 1".into());
+        }
+        pipeline.run_file_streaming(input_path, &output_path, strip_height, &progress)?;
+    } else {
+        let tile_size: Option<u32> = args.option("tile-size").map(str::parse).transpose()?;
+        if tile_size == Some(0) {
+            return Err("--tile-size must be at least 1".into());
+        }
+
+        let input = open_image_with_profile(input_path, options.profile())?;
+        let output = match (tile_size, memory_budget(args)?) {
+            (Some(tile_size), _) => pipeline.run_tiled_with_progress(&input, tile_size, &progress)?,
+            (None, Some(budget)) => pipeline.run_with_budget(&input, budget, &progress)?,
+            (None, None) => pipeline.run_with_progress(&input, &progress)?,
+        };
+        save_image(&output, &output_path, &options, Some(input_path))?;
+    }
+    info!("Wrote {}", output_path.display());
+
+    Ok(())
+}
 
-/// Edge detection by the following process:
-///     1) increase image contrast
-///     2) Sharpen image
-///     3) Compute x and y gradients
-///     4) Add x and y gradients
-///
-/// # Arguments
-///
-/// * `input`: ImageBuffer to edge detect
-///
-/// returns: ImageBuffer
-fn edge_detect(input: &ImageBuffer) -> ImageBuffer {
-    let cleaned = contrast(input, 2.);
-    let sharpened = sharpen(&cleaned, 10.);
+/// Run a pipeline file over every image in a directory, several at once if given a memory budget
+fn batch(args: &Args) -> Result<(), Box<dyn Error>> {
+    let pipeline = Pipeline::from_file(args.required("pipeline")?)?;
+    let inputs = image_files(Path::new(args.required("input-dir")?))?;
+    let output_dir = Path::new(args.required("output-dir")?);
+    let options = output_options(args)?;
 
-    let gradient_x = x_grad(&sharpened);
-    let gradient_y = y_grad(&sharpened);
+    let outputs = match memory_budget(args)? {
+        Some(budget) => run_batch_with_budget(&pipeline, &inputs, output_dir, &options, budget, &progress_bar())?,
+        None => run_batch(&pipeline, &inputs, output_dir, &options, &progress_bar())?,
+    };
+    info!("Wrote {} images to {}", outputs.len(), output_dir.display());
 
-    image_add(&gradient_x, &gradient_y)
+    Ok(())
 }
 
-fn x_grad(input: &ImageBuffer) -> ImageBuffer {
-    let matrix = array![
-        [-1., 1.]
-    ];
+/// Re-run a pipeline file over an image whenever either changes on disk, polling their
+/// modification times. Failures are logged and the files watched again, so a half written
+/// pipeline doesn't end the session.
+fn watch(args: &Args) -> Result<(), Box<dyn Error>> {
+    let pipeline_path = Path::new(args.required("pipeline")?);
+    let input_path = Path::new(args.required("input")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+    let interval = Duration::from_millis(parse_option(args, "interval", 250)?);
+
+    info!("Watching {} and {}, press ctrl-c to stop", pipeline_path.display(), input_path.display());
+
+    let mut last_modified = None;
+    loop {
+        let modified = (modified_time(pipeline_path), modified_time(input_path));
+        if last_modified != Some(modified) {
+            last_modified = Some(modified);
+
+            let start = Instant::now();
+            match process_once(pipeline_path, input_path, &output_path, &options) {
+                Ok(()) => info!("Wrote {} in {:.2?}", output_path.display(), start.elapsed()),
+                Err(err) => warn!("{}", err),
+            }
+        }
 
-    apply_matrix(input, matrix)
+        thread::sleep(interval);
+    }
 }
 
-fn y_grad(input: &ImageBuffer) -> ImageBuffer {
-    let matrix = array![
-        [1.],
-        [-1.],
-    ];
+fn process_once(pipeline_path: &Path, input_path: &Path, output_path: &Path, options: &OutputOptions) -> Result<(), Box<dyn Error>> {
+    let pipeline = Pipeline::from_file(pipeline_path)?;
+    let input = open_image_with_profile(input_path, options.profile())?;
+    save_image(&pipeline.run(&input), output_path, options, Some(input_path))?;
+
+    Ok(())
+}
 
-    apply_matrix(input, matrix)
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
 }
 
-/// Perform sharpening of an image by:
-///     1) Perform a bilinear blur filter
-///     2) Subtract the Image - Bilinear Output, to get the 'detail' of the image
-///     3) Add specified multiple of detail back to original image
-///
-/// # Arguments
-///
-/// * `input`: ImageBuffer to sharpen
-/// * `value`: Multiple of detail to add on
-///
-/// returns: ImageBuffer
-fn sharpen(input: &ImageBuffer, value: f32) -> ImageBuffer {
-    let filtered = bilinear_filter(input);
+/// Draw an image in the terminal, after running a pipeline over it if one is given. The width
+/// defaults to the terminal's.
+fn preview(args: &Args) -> Result<(), Box<dyn Error>> {
+    let mut image = open_image(args.required("input")?)?;
+    if let Some(pipeline) = args.option("pipeline") {
+        image = Pipeline::from_file(pipeline)?.run(&image);
+    }
+
+    let columns = match args.option("width") {
+        Some(width) => width.parse()?,
+        None => crossterm::terminal::size().map(|(columns, _)| columns as u32).unwrap_or(80),
+    };
+    let protocol = match args.option("protocol") {
+        Some(name) => Protocol::from_name(name).ok_or_else(|| format!("unknown protocol '{}'", name))?,
+        None => Protocol::Blocks,
+    };
 
-    let detail = image_sub(input, &filtered);
-    let detail = contrast(&detail, value);
+    std::io::stdout().write_all(preview::render(&image, columns, protocol).as_bytes())?;
 
-    image_add(input, &detail)
+    Ok(())
 }
 
-fn bilinear_filter(input: &ImageBuffer) -> ImageBuffer {
-    let bilinear = array![
-        [1./16., 2./16., 1./16.],
-        [2./16., 4./16., 2./16.],
-        [1./16., 2./16., 1./16.]
-    ];
+/// Serve pipelines over http, see `server::serve`
+fn serve(args: &Args) -> Result<(), Box<dyn Error>> {
+    let config = ServerConfig {
+        max_connections: parse_option(args, "max-connections", 8)?,
+        max_body_bytes: parse_option(args, "max-body-bytes", 32 * 1024 * 1024)?,
+        timeout: Duration::from_secs(parse_option(args, "timeout", 30)?),
+    };
+    server::serve(args.option("address").unwrap_or("127.0.0.1:8080"), config)?;
 
-    apply_matrix(input, bilinear)
+    Ok(())
 }
 
-fn gaussian_blur(input: &ImageBuffer) -> ImageBuffer {
-    let gaussian = array![
-        [1./256., 4./256., 6./256., 4./256., 1./256.],
-        [4./256., 16./256., 24./256., 16./256., 4./256.],
-        [6./256., 24./256., 36./256., 24./256., 6./256.],
-        [4./256., 16./256., 24./256., 16./256., 4./256.],
-        [1./256., 4./256., 6./256., 4./256., 1./256.],
-    ];
+/// Calibrate a camera from a directory of photos of a checkerboard, given the board's inner
+/// corners, writing `calibration.json` unless an output is given
+fn calibrate(args: &Args) -> Result<(), Box<dyn Error>> {
+    let board = args.required("board")?;
+    let (columns, rows) = board
+        .split_once('x')
+        .and_then(|(columns, rows)| Some((columns.parse().ok()?, rows.parse().ok()?)))
+        .ok_or_else(|| format!("--board should be <columns>x<rows>, got '{}'", board))?;
+
+    let mut views = Vec::new();
+    let mut size = (0, 0);
+    for path in image_files(Path::new(args.required("images")?))? {
+        let image = open_image(&path)?;
+        size = image.dimensions();
+        match find_checkerboard(&image, columns, rows) {
+            Some(corners) => views.push(corners),
+            None => warn!("No {}x{} board found in {}", columns, rows, path.display()),
+        }
+    }
+
+    let calibration = calibration::calibrate(&views, columns, rows, size.0, size.1)?;
+    let output = args.option("output").unwrap_or("calibration.json");
+    calibration.save(output)?;
+    info!("Calibrated from {} views with an error of {:.3} pixels, wrote {}", views.len(), calibration.rms_error, output);
 
-    apply_matrix(input, gaussian)
+    Ok(())
 }
 
-/// Apply a matrix to the input image, pixel by pixel where:
-///     g(i, j) = sum f(i + k, j + l)h(k, l)
-/// Where:
-///     g is new pixel of image
-///     f is current pixel of image
-///     h is the matrix
-///     k, l range over the dimensions of the matrix
-///
-/// # Arguments
-///
-/// * `input`: ImageBuffer to apply matrix to
-/// * `matrix`: Matrix to apply
-///
-/// returns: ImageBuffer
-fn apply_matrix(input: &ImageBuffer, matrix: Array2<f32>) -> ImageBuffer {
-    let (input_x, input_y) = input.dimensions();
-    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+/// Correct an image's lens distortion with a calibration file written by `calibrate`
+fn undistort(args: &Args) -> Result<(), Box<dyn Error>> {
+    let calibration = CameraCalibration::load(args.required("calibration")?)?;
+    let input_path = Path::new(args.required("input")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+
+    let input = open_image_with_profile(input_path, options.profile())?;
+    let lens = calibration.lens_distortion(input.width(), input.height());
+    save_image(&warp::undistort(&input, &lens), &output_path, &options, Some(input_path))?;
+    info!("Wrote {}", output_path.display());
+
+    Ok(())
+}
 
-    let (matrix_x, matrix_y) = (matrix.shape().get(0).unwrap(), matrix.shape().get(1).unwrap());
+/// Unroll an image into its polar or log-polar form about a centre, or with `--inverse` roll
+/// one back up, by default keeping the input's size
+fn polar(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input_path = Path::new(args.required("input")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+
+    let input = open_image_with_profile(input_path, options.profile())?;
+    let width = parse_option(args, "width", input.width())?;
+    let height = parse_option(args, "height", input.height())?;
+
+    let mut transform = PolarTransform::new().with_log_scale(args.flag("log"));
+    if let Some(centre) = args.option("centre") {
+        match centre.split(',').map(|v| v.trim().parse::<f32>()).collect::<Result<Vec<_>, _>>() {
+            Ok(values) if values.len() == 2 => transform = transform.with_centre(values[0], values[1]),
+            _ => return Err(format!("--centre must be x,y but was '{}'", centre).into()),
+        }
+    }
+    if let Some(radius) = args.option("radius") {
+        transform = transform.with_max_radius(radius.parse().map_err(|_| format!("--radius must be a number but was '{}'", radius))?);
+    }
 
-    println!("Applying matrix of size: {}, {}", matrix_x, matrix_y);
-
-    for (x, y, pixel) in output.enumerate_pixels_mut() {
+    let output = if args.flag("inverse") { transform.from_polar(&input, width, height) } else { transform.to_polar(&input, width, height) };
+    save_image(&output, &output_path, &options, Some(input_path))?;
+    info!("Wrote {}", output_path.display());
 
-        // Do all maths as integers then only truncate to [0, 255] right at the end
-        let mut pixels_to_sum = Vec::new();
+    Ok(())
+}
 
-        for i in 0..*matrix_x {
-            for j in 0..*matrix_y {
-                let x_curr = (x as i32) + (i as i32);
-                let y_curr = (y as i32) + (j as i32);
+/// Match a rectified stereo pair and write the disparity map in colour, near in red and far in
+/// blue, next to the left image unless an output is given
+fn disparity(args: &Args) -> Result<(), Box<dyn Error>> {
+    let left_path = Path::new(args.required("left")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(left_path, &options),
+    };
+
+    let max_disparity = parse_option(args, "max-disparity", 64)?;
+    let block_size: u32 = parse_option(args, "block-size", 7)?;
+    let cost = match args.option("cost") {
+        Some(name) => MatchCost::from_name(name).ok_or_else(|| format!("unknown cost '{}'", name))?,
+        None => MatchCost::Census,
+    };
+    let stereo_options = StereoOptions::new().with_max_disparity(max_disparity).with_block_radius(block_size / 2).with_cost(cost);
+
+    let left = open_image(left_path)?;
+    let right = open_image(args.required("right")?)?;
+    if left.dimensions() != right.dimensions() {
+        return Err(format!("left is {:?} but right is {:?}, stereo images must be the same size", left.dimensions(), right.dimensions()).into());
+    }
 
-                let x_curr = cmp::min(input_x as i32 - 1, cmp::max(0, x_curr));
-                let y_curr = cmp::min(input_y as i32 - 1, cmp::max(0, y_curr));
+    let disparities = stereo::disparity_map(&left, &right, &stereo_options);
+    save_image(&stereo::colorize_disparity(&disparities, max_disparity as f32), &output_path, &options, None)?;
+    info!("Wrote {}", output_path.display());
 
-                let input_curr = input.get_pixel(x_curr as u32, y_curr as u32);
-                let matrix_curr = matrix[[i, j]];
+    Ok(())
+}
 
-                let prod = vec![((input_curr[0] as f32) * matrix_curr) as i32, ((input_curr[1] as f32) * matrix_curr) as i32, ((input_curr[2] as f32) * matrix_curr) as i32];
+/// Upscale an image by a whole factor, recovering more detail than resizing
+fn superres(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input_path = Path::new(args.required("input")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+
+    let factor: u32 = parse_option(args, "factor", 2)?;
+    let method = match args.option("method") {
+        Some(name) => SuperResMethod::from_name(name).ok_or_else(|| format!("unknown method '{}'", name))?,
+        None => SuperResMethod::BackProjection,
+    };
+
+    let input = open_image_with_profile(input_path, options.profile())?;
+    save_image(&superres::superres(&input, factor, method)?, &output_path, &options, Some(input_path))?;
+    info!("Wrote {}", output_path.display());
+
+    Ok(())
+}
 
-                pixels_to_sum.push(prod);
-            }
-        }
+/// Make a thumbnail of exactly the given size, cropped around the most salient part of the image
+fn thumbnail(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input_path = Path::new(args.required("input")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+
+    let width: u32 = args.required("width")?.parse()?;
+    let height: u32 = args.required("height")?.parse()?;
+    if width == 0 || height == 0 {
+        return Err("--width and --height must be at least 1".into());
+    }
+
+    let input = open_image_with_profile(input_path, options.profile())?;
+    save_image(&thumbnail::thumbnail(&input, width, height, saliency_option(args)?), &output_path, &options, Some(input_path))?;
+    info!("Wrote {}", output_path.display());
 
-        let mut total: Vec<i32> = vec![0, 0, 0];
+    Ok(())
+}
+
+/// Write an image's saliency map in grey, white where it's most salient
+fn saliency(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input_path = Path::new(args.required("input")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+
+    let input = open_image(input_path)?;
+    let map = saliency_option(args)?.map(&input);
+    save_image(&map.map(|v| v * 255.).to_image(), &output_path, &options, None)?;
+    info!("Wrote {}", output_path.display());
+
+    Ok(())
+}
 
-        for pixel_to_sum in pixels_to_sum {
-            total = vec![total[0] + pixel_to_sum[0], total[1] + pixel_to_sum[1], total[2] + pixel_to_sum[2]];
+/// Segment an image by mean shift, writing each region in its mean colour, or just filter it
+fn segment(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input_path = Path::new(args.required("input")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+    let spatial_bandwidth = parse_option(args, "spatial-bandwidth", 8)?;
+    let range_bandwidth = parse_option(args, "range-bandwidth", 16.)?;
+
+    let input = open_image_with_profile(input_path, options.profile())?;
+    let output = match args.option("mode").unwrap_or("segment") {
+        "segment" => {
+            let segmentation = mean_shift_segmentation(&input, spatial_bandwidth, range_bandwidth, parse_option(args, "min-region", 50)?);
+            info!("Found {} regions", segmentation.count);
+            segmentation.mean_colours(&input)
         }
+        "filter" => mean_shift_filter(&input, spatial_bandwidth, range_bandwidth),
+        mode => return Err(format!("unknown mode '{}'", mode).into()),
+    };
+    save_image(&output, &output_path, &options, Some(input_path))?;
+    info!("Wrote {}", output_path.display());
 
-        let r = cmp::min(255, cmp::max(0, total[0])) as u8;
-        let g = cmp::min(255, cmp::max(0, total[1])) as u8;
-        let b = cmp::min(255, cmp::max(0, total[2])) as u8;
+    Ok(())
+}
+
+/// Cut the subject out of a photo by GrabCut, from a rectangle around it or a seed mask of
+/// strokes, writing it with a transparent background or just the matte
+fn cutout(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input_path = Path::new(args.required("input")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+    let iterations = parse_option(args, "iterations", 5)?;
+
+    let input = open_image_with_profile(input_path, options.profile())?;
+    let matte = match (args.option("rect"), args.option("seeds")) {
+        (Some(rect), None) => match parse_regions(rect)?[..] {
+            [rect] => grab_cut(&input, rect, iterations),
+            _ => return Err("--rect takes one rectangle".into()),
+        },
+        (None, Some(seeds)) => {
+            let seeds = Plane::from_luma(&open_image(seeds)?).map(|v| v / 255.);
+            if (seeds.width, seeds.height) != input.dimensions() {
+                return Err(format!("the seeds are {}x{} but the input is {}x{}", seeds.width, seeds.height, input.width(), input.height()).into());
+            }
+            grab_cut_mask(&input, &seeds, iterations)
+        }
+        _ => return Err("one of --rect or --seeds is required".into()),
+    };
+    let matte = match args.option("feather") {
+        Some(band) => closed_form_matte(&input, &trimap_from_matte(&matte, band.parse()?), 500),
+        None => matte,
+    };
+
+    let output = if args.flag("matte") { matte.map(|v| v * 255.).to_image() } else { apply_matte(&input, &matte) };
+    save_image(&output, &output_path, &options, Some(input_path))?;
+    info!("Wrote {}", output_path.display());
+
+    Ok(())
+}
 
-        *pixel = image::Rgba([r, g, b, 255]);
+/// Pull a soft matte from an image and a trimap, writing the cut out or just the matte
+fn matte(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input_path = Path::new(args.required("input")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+
+    let input = open_image_with_profile(input_path, options.profile())?;
+    let trimap = Plane::from_luma(&open_image(args.required("trimap")?)?).map(|v| v / 255.);
+    if (trimap.width, trimap.height) != input.dimensions() {
+        return Err(format!("the trimap is {}x{} but the input is {}x{}", trimap.width, trimap.height, input.width(), input.height()).into());
     }
+    let matte = closed_form_matte(&input, &trimap, parse_option(args, "iterations", 500)?);
+
+    let output = if args.flag("matte") { matte.map(|v| v * 255.).to_image() } else { apply_matte(&input, &matte) };
+    save_image(&output, &output_path, &options, Some(input_path))?;
+    info!("Wrote {}", output_path.display());
 
-    output
+    Ok(())
 }
 
-fn median_filter(input: &ImageBuffer, window: i32) -> ImageBuffer {
-    let (input_x, input_y) = input.dimensions();
-    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+/// Clean up a photo of a document into a black and white scan, finding the page or taking its
+/// corners clockwise from the top left
+fn scan(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input_path = Path::new(args.required("input")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+    let mut scan_options = ScanOptions::default()
+        .with_threshold_radius(parse_option(args, "radius", 15)?)
+        .with_threshold_offset(parse_option(args, "offset", 10.)?)
+        .with_despeckle_size(parse_option(args, "despeckle", 8)?);
+
+    let mut input = open_image_with_profile(input_path, options.profile())?;
+    if let Some(corners) = args.option("corners") {
+        let points: Vec<(f64, f64)> = corners
+            .split(';')
+            .map(|point| match point.split(',').map(|v| v.trim().parse()).collect::<Result<Vec<f64>, _>>()?[..] {
+                [x, y] => Ok((x, y)),
+                _ => Err(format!("corners should be x,y, got '{}'", point).into()),
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+        let corners: [(f64, f64); 4] = points.try_into().map_err(|_| "--corners takes four corners")?;
+        input = rectify_page(&input, &corners);
+        scan_options = scan_options.with_page_detection(false);
+    }
 
-    for (x, y, pixel) in output.enumerate_pixels_mut() {
-        let mut r_vals = Vec::new();
-        let mut g_vals = Vec::new();
-        let mut b_vals = Vec::new();
+    save_image(&document::scan(&input, &scan_options), &output_path, &options, Some(input_path))?;
+    info!("Wrote {}", output_path.display());
 
-        for i in (-1*window)..(window+1) {
-            for j in (-1*window)..(window+1) {
-                let x_curr = (x as i32) + i;
-                let y_curr = (y as i32) + j;
+    Ok(())
+}
 
-                let x_curr = cmp::min(input_x as i32 - 1, cmp::max(0, x_curr));
-                let y_curr = cmp::min(input_y as i32 - 1, cmp::max(0, y_curr));
+/// Level the lines of text of a scan
+fn deskew(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input_path = Path::new(args.required("input")?);
+    let options = output_options(args)?;
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+
+    let input = open_image_with_profile(input_path, options.profile())?;
+    info!("Skewed by {:.2} degrees", estimate_skew(&input));
+    save_image(&document::deskew(&input), &output_path, &options, Some(input_path))?;
+    info!("Wrote {}", output_path.display());
+
+    Ok(())
+}
 
-                let pixel_curr = input.get_pixel(x_curr as u32, y_curr as u32);
-                r_vals.push(pixel_curr[0]);
-                g_vals.push(pixel_curr[1]);
-                b_vals.push(pixel_curr[2]);
+/// Hide rectangles or a masked area of an image, or of each image in a directory, e.g. faces or
+/// number plates in frames from a fixed camera
+fn redact(args: &Args) -> Result<(), Box<dyn Error>> {
+    let size = parse_option(args, "size", 16)?;
+    let style = args.option("style").unwrap_or("pixelate");
+    let redaction = Redaction::from_name(style, size).ok_or_else(|| format!("unknown style '{}'", style))?;
+    let options = output_options(args)?;
+
+    let mask = match args.option("mask") {
+        Some(path) => Some(Plane::from_luma(&open_image(path)?).map(|v| v / 255.)),
+        None => None,
+    };
+    let regions = match (args.option("regions"), &mask) {
+        (Some(regions), _) => parse_regions(regions)?,
+        (None, Some(_)) => Vec::new(),
+        (None, None) => return Err("one of --regions or --mask is required".into()),
+    };
+
+    let process = |input: &ImageBuffer| -> rust_image_processing::Result<ImageBuffer> {
+        match &mask {
+            Some(mask) if (mask.width, mask.height) != input.dimensions() => {
+                warn!("The mask is {}x{} but the image is {}x{}", mask.width, mask.height, input.width(), input.height());
+                Err(rust_image_processing::Error::InvalidParameter { operation: "redact".to_string(), parameter: "mask".to_string() })
             }
+            Some(mask) => Ok(redact_mask(input, mask, redaction)),
+            None => Ok(rust_image_processing::redact::redact(input, &regions, redaction)),
         }
+    };
 
-        r_vals.sort();
-        g_vals.sort();
-        b_vals.sort();
+    if let Some(input_dir) = args.option("input-dir") {
+        let output_dir = Path::new(args.required("output-dir")?);
+        let outputs = process_batch(&image_files(Path::new(input_dir))?, output_dir, &options, &progress_bar(), |input, _| process(input))?;
+        info!("Wrote {} images to {}", outputs.len(), output_dir.display());
+        return Ok(());
+    }
+
+    let input_path = Path::new(args.required("input")?);
+    let output_path = match args.option("output") {
+        Some(output) => PathBuf::from(output),
+        None => default_output_path(input_path, &options),
+    };
+    let input = open_image_with_profile(input_path, options.profile())?;
+    save_image(&process(&input)?, &output_path, &options, Some(input_path))?;
+    info!("Wrote {}", output_path.display());
 
-        let r_median = median(&r_vals);
-        let g_median = median(&g_vals);
-        let b_median = median(&b_vals);
+    Ok(())
+}
 
-        let input_pixel = input.get_pixel(x, y);
+/// Print an image's dimensions, format and per channel statistics, as a table or as json
+fn info(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input_path = Path::new(args.required("input")?);
+    let format = image::io::Reader::open(input_path)?.with_guessed_format()?.format();
+    let format = format.map(|f| format!("{:?}", f).to_lowercase()).unwrap_or_else(|| "unknown".to_string());
+    let stats = stats(&open_image(input_path)?);
+
+    if args.flag("json") {
+        let info = Value::Table(vec![
+            ("path".to_string(), Value::String(input_path.display().to_string())),
+            ("format".to_string(), Value::String(format)),
+            ("stats".to_string(), stats.to_value()),
+        ]);
+        println!("{}", json::to_string(&info));
+        return Ok(());
+    }
 
-        *pixel = image::Rgba([r_median, g_median, b_median, input_pixel[3]])
+    println!("{}: {}x{} {}", input_path.display(), stats.width, stats.height, format);
+    println!("{:<8}{:>9}{:>9}{:>6}{:>6}{:>8}{:>9}", "channel", "mean", "std dev", "min", "max", "median", "entropy");
+    for (name, channel) in CHANNEL_NAMES.iter().zip(&stats.channels) {
+        println!(
+            "{:<8}{:>9.2}{:>9.2}{:>6}{:>6}{:>8}{:>9.3}",
+            name, channel.mean, channel.std_dev, channel.min, channel.max, channel.median, channel.entropy
+        );
     }
 
-    output
+    Ok(())
 }
 
-fn linear_blend(input_1: &ImageBuffer, input_2: &ImageBuffer, value: f32) -> ImageBuffer {
-    let (input_x, input_y) = input_1.dimensions();
-    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+/// Stack a directory of frames of the same scene into one with less noise, aligned to the first
+/// in name order
+fn stack(args: &Args) -> Result<(), Box<dyn Error>> {
+    let options = output_options(args)?;
+    let output_path = Path::new(args.required("output")?);
+    let mut stack_options = StackOptions::new().with_clip_sigma(parse_option(args, "sigma", 2.5)?);
+    if let Some(name) = args.option("align") {
+        stack_options = stack_options.with_alignment(Alignment::from_name(name).ok_or_else(|| format!("unknown alignment '{}'", name))?);
+    }
+    if let Some(name) = args.option("method") {
+        stack_options = stack_options.with_merge_method(MergeMethod::from_name(name).ok_or_else(|| format!("unknown method '{}'", name))?);
+    }
 
-    for (x, y, pixel) in output.enumerate_pixels_mut() {
-        let scaled_1 = pixel_scale(*input_1.get_pixel(x,y), (1 as f32) - value);
-        let scaled_2 = pixel_scale(*input_2.get_pixel(x,y), value);
+    let paths = image_files(Path::new(args.required("input-dir")?))?;
+    let frames = paths.iter().map(|path| open_image_with_profile(path, options.profile())).collect::<Result<Vec<_>, _>>()?;
+    info!("Stacking {} frames", frames.len());
+    save_image(&stacking::stack(&frames, &stack_options)?, output_path, &options, paths.first().map(|path| path.as_path()))?;
+    info!("Wrote {}", output_path.display());
 
-        *pixel = pixel_add(scaled_1, scaled_2);
-    }
+    Ok(())
+}
 
-    output
+/// Focus stack a directory of frames focused at different distances into one sharp throughout,
+/// aligned to the first in name order
+fn focus_stack(args: &Args) -> Result<(), Box<dyn Error>> {
+    let options = output_options(args)?;
+    let output_path = Path::new(args.required("output")?);
+    let alignment = match args.option("align") {
+        Some(name) => Alignment::from_name(name).ok_or_else(|| format!("unknown alignment '{}'", name))?,
+        None => Alignment::Homography,
+    };
+
+    let paths = image_files(Path::new(args.required("input-dir")?))?;
+    let frames = paths.iter().map(|path| open_image_with_profile(path, options.profile())).collect::<Result<Vec<_>, _>>()?;
+    info!("Focus stacking {} frames", frames.len());
+    save_image(&stacking::focus_stack(&frames, alignment, None)?, output_path, &options, paths.first().map(|path| path.as_path()))?;
+    info!("Wrote {}", output_path.display());
+
+    Ok(())
 }
 
-fn image_sub(input_1: &ImageBuffer, input_2: &ImageBuffer) -> ImageBuffer {
-    let (input_x, input_y) = input_1.dimensions();
-    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+/// Tile images into a grid captioned with their file names, given as a list or a directory
+fn montage(args: &Args) -> Result<(), Box<dyn Error>> {
+    let options = output_options(args)?;
+    let output_path = Path::new(args.required("output")?);
+    let paths: Vec<PathBuf> = match (args.option("inputs"), args.option("input-dir")) {
+        (Some(inputs), _) => inputs.split(',').map(|path| PathBuf::from(path.trim())).collect(),
+        (None, Some(dir)) => image_files(Path::new(dir))?,
+        (None, None) => return Err("missing required option --inputs or --input-dir".into()),
+    };
+
+    let mut montage = Montage::new().with_padding(parse_option(args, "padding", 8)?).with_captions(!args.flag("no-captions"));
+    if let Some(columns) = args.option("columns") {
+        montage = montage.with_columns(columns.parse()?);
+    }
+    if let Some(size) = args.option("cell-size") {
+        let (width, height) = size.split_once('x').ok_or_else(|| format!("cell size should be <width>x<height>, got '{}'", size))?;
+        montage = montage.with_cell_size(width.parse()?, height.parse()?);
+    }
+    if let Some(colour) = args.option("background") {
+        montage = montage.with_background(parse_colour(colour)?);
+    }
 
-    for (x, y, pixel) in output.enumerate_pixels_mut() {
-        let image_1 = *input_1.get_pixel(x,y);
-        let image_2 = *input_2.get_pixel(x,y);
+    let images = paths.iter().map(|path| open_image_with_profile(path, options.profile())).collect::<Result<Vec<_>, _>>()?;
+    let names: Vec<String> = paths.iter().map(|path| path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default()).collect();
+    let captions: Vec<&str> = names.iter().map(|name| name.as_str()).collect();
+    save_image(&montage.compose(&images, &captions), output_path, &options, None)?;
+    info!("Wrote {} images to {}", images.len(), output_path.display());
 
-        *pixel = pixel_sub(image_1, image_2);
-    }
+    Ok(())
+}
 
-    output
+/// Put a before and after image together side by side, split down a line or interleaved in a
+/// checkerboard
+fn compare_visual(args: &Args) -> Result<(), Box<dyn Error>> {
+    let options = output_options(args)?;
+    let output_path = Path::new(args.required("output")?);
+    let before_path = Path::new(args.required("before")?);
+    let comparison = match args.option("mode").unwrap_or("side-by-side") {
+        "split" => Comparison::Split(parse_option(args, "position", 0.5)?),
+        "checkerboard" => Comparison::Checkerboard(parse_option(args, "size", 32)?),
+        name => Comparison::from_name(name).ok_or_else(|| format!("unknown mode '{}'", name))?,
+    };
+
+    let before = open_image_with_profile(before_path, options.profile())?;
+    let after = open_image_with_profile(Path::new(args.required("after")?), options.profile())?;
+    save_image(&montage::compare_visual(&before, &after, comparison), output_path, &options, Some(before_path))?;
+    info!("Wrote {}", output_path.display());
+
+    Ok(())
 }
 
-fn image_add(input_1: &ImageBuffer, input_2: &ImageBuffer) -> ImageBuffer {
-    let (input_x, input_y) = input_1.dimensions();
-    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+/// Evaluate a pixel expression over images, `a` the first, `b` the second and so on
+fn expr(args: &Args) -> Result<(), Box<dyn Error>> {
+    let options = output_options(args)?;
+    let output_path = Path::new(args.required("output")?);
+    let expression = Expression::parse(args.required("expression")?)?;
 
-    for (x, y, pixel) in output.enumerate_pixels_mut() {
-        let image_1 = *input_1.get_pixel(x,y);
-        let image_2 = *input_2.get_pixel(x,y);
+    let paths: Vec<PathBuf> = args.required("inputs")?.split(',').map(|path| PathBuf::from(path.trim())).collect();
+    let images = paths.iter().map(|path| open_image_with_profile(path, options.profile())).collect::<Result<Vec<_>, _>>()?;
+    save_image(&expression.apply(&images)?, output_path, &options, paths.first().map(|path| path.as_path()))?;
+    info!("Wrote {}", output_path.display());
 
-        *pixel = pixel_add(image_1, image_2);
-    }
+    Ok(())
+}
 
-    output
+/// Composite the layers of a canvas file, images, adjustments and text, into one image
+fn compose(args: &Args) -> Result<(), Box<dyn Error>> {
+    let options = output_options(args)?;
+    let output_path = Path::new(args.required("output")?);
+
+    let canvas = Canvas::from_file(args.required("canvas")?)?;
+    info!("Compositing {} layers", canvas.layers().len());
+    save_image(&canvas.render()?, output_path, &options, None)?;
+    info!("Wrote {}", output_path.display());
+
+    Ok(())
 }
 
-/// For each pixel, p, of an image, adjust brightness by output of:
-///     p + value
-///
-/// # Arguments
-///
-/// * `input`: Image buffer
-/// * `value`: Brightness addition value
-///
-/// returns: ImageBuffer
-fn adjust_brightness(input: &ImageBuffer, value: i32) -> ImageBuffer {
-    let (input_x, input_y) = input.dimensions();
+/// List the QR codes and barcodes found in a photo, as their corners clockwise from the top left,
+/// and write each squared up to a directory for a decoder
+fn codes(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input_path = Path::new(args.required("input")?);
+    let input = open_image(input_path)?;
+    let found: Vec<(&str, [(f64, f64); 4])> =
+        find_qr_codes(&input).into_iter().map(|corners| ("qr", corners)).chain(find_barcodes(&input).into_iter().map(|corners| ("barcode", corners))).collect();
+
+    if let Some(dir) = args.option("crop-dir") {
+        let options = output_options(args)?;
+        let stem = input_path.file_stem().and_then(|s| s.to_str()).unwrap_or("code");
+        std::fs::create_dir_all(dir)?;
+        for (i, (kind, corners)) in found.iter().enumerate() {
+            let path = Path::new(dir).join(format!("{}-{}-{}.{}", stem, kind, i, options.format().unwrap_or(OutputFormat::Png).extension()));
+            save_image(&rectify_page(&input, corners), &path, &options, None)?;
+            info!("Wrote {}", path.display());
+        }
+    }
 
-    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+    if args.flag("json") {
+        let entries = found
+            .iter()
+            .map(|(kind, corners)| {
+                let corners = corners.iter().map(|&(x, y)| Value::Array(vec![Value::Number(x), Value::Number(y)])).collect();
+                Value::Table(vec![("kind".to_string(), Value::String(kind.to_string())), ("corners".to_string(), Value::Array(corners))])
+            })
+            .collect();
+        println!("{}", json::to_string(&Value::Array(entries)));
+        return Ok(());
+    }
 
-    for(x, y, pixel) in output.enumerate_pixels_mut() {
-        *pixel = pixel_shift(*input.get_pixel(x, y), value);
+    for (kind, corners) in &found {
+        let corners: Vec<String> = corners.iter().map(|(x, y)| format!("{:.1},{:.1}", x, y)).collect();
+        println!("{:<8} {}", kind, corners.join(";"));
     }
 
-    output
+    Ok(())
 }
 
-/// For each pixel, p, of an image, adjust contrast by output of:
-///     p * value
-///
-/// # Arguments
-///
-/// * `input`: Image buffer
-/// * `value`: Contrast scale value
-///
-/// returns: ImageBuffer
-fn adjust_contrast(input: &ImageBuffer, value: f32) -> ImageBuffer {
-    let (input_x, input_y) = input.dimensions();
+/// Describe the shape of each white part of a mask, e.g. from `cutout --matte`, and optionally
+/// write the mask thinned to its skeleton
+fn shapes(args: &Args) -> Result<(), Box<dyn Error>> {
+    let input_path = Path::new(args.required("input")?);
+    let min_area = parse_option(args, "min-area", 1.)?;
+    let mask = Plane::from_luma(&open_image(input_path)?).map(|v| v / 255.);
+
+    if let Some(skeleton) = args.option("skeleton") {
+        let options = output_options(args)?;
+        save_image(&thin(&mask).map(|v| v * 255.).to_image(), Path::new(skeleton), &options, None)?;
+        info!("Wrote {}", skeleton);
+    }
 
-    let mut output: image::ImageBuffer<image::Rgba<u8>, _> = image::ImageBuffer::new(input_x, input_y);
+    let shapes: Vec<_> = describe_regions(&connected_components(&mask)).into_iter().filter(|shape| shape.area >= min_area).collect();
 
-    for(x, y, pixel) in output.enumerate_pixels_mut() {
-        *pixel = pixel_scale(*input.get_pixel(x, y), value);
+    if args.flag("json") {
+        println!("{}", json::to_string(&Value::Array(shapes.iter().map(|shape| shape.to_value()).collect())));
+        return Ok(());
     }
 
-    output
+    println!("{:>6}{:>9}{:>11}{:>13}{:>13}{:>10}  bounding box", "label", "area", "perimeter", "circularity", "eccentricity", "solidity");
+    for shape in &shapes {
+        let rect = shape.bounding_box;
+        println!(
+            "{:>6}{:>9}{:>11.1}{:>13.3}{:>13.3}{:>10.3}  {},{},{},{}",
+            shape.label, shape.area, shape.perimeter, shape.circularity, shape.eccentricity, shape.solidity, rect.x, rect.y, rect.width, rect.height
+        );
+    }
+
+    Ok(())
 }
 
-fn pixel_sub(pixel_1: image::Rgba<u8>, pixel_2: image::Rgba<u8>) -> image::Rgba<u8> {
-    image::Rgba([
-        safe_add(pixel_1[0], -1 * (pixel_2[0] as i32)),
-        safe_add(pixel_1[1], -1 * (pixel_2[1] as i32)),
-        safe_add(pixel_1[2], -1 * (pixel_2[2] as i32)),
-        pixel_1[3]
-    ])
+/// Score every photo in a directory and print them best first, leaving out any below the
+/// minimums, e.g. to find the sharpest of a burst or cull the blurred shots
+fn rank(args: &Args) -> Result<(), Box<dyn Error>> {
+    let min_score = parse_option(args, "min-score", 0.)?;
+    let min_sharpness = parse_option(args, "min-sharpness", 0.)?;
+
+    let mut ranked = Vec::new();
+    for path in image_files(Path::new(args.required("input-dir")?))? {
+        let quality = quality(&open_image(&path)?);
+        if quality.score >= min_score && quality.sharpness >= min_sharpness {
+            ranked.push((path, quality));
+        }
+    }
+    ranked.sort_by(|(_, a), (_, b)| b.score.total_cmp(&a.score));
+
+    if args.flag("json") {
+        let entries = ranked
+            .iter()
+            .map(|(path, quality)| Value::Table(vec![("path".to_string(), Value::String(path.display().to_string())), ("quality".to_string(), quality.to_value())]))
+            .collect();
+        println!("{}", json::to_string(&Value::Array(entries)));
+        return Ok(());
+    }
+
+    println!("{:>6}{:>11}{:>7}{:>9}{:>9}  path", "score", "sharpness", "luma", "black", "white");
+    for (path, quality) in &ranked {
+        let exposure = quality.exposure;
+        println!(
+            "{:>6.3}{:>11.1}{:>7.1}{:>8.1}%{:>8.1}%  {}",
+            quality.score, quality.sharpness, exposure.mean, exposure.shadows_clipped * 100., exposure.highlights_clipped * 100., path.display()
+        );
+    }
+
+    Ok(())
 }
 
-fn pixel_add(pixel_1: image::Rgba<u8>, pixel_2: image::Rgba<u8>) -> image::Rgba<u8> {
-    image::Rgba([
-        safe_add(pixel_1[0], pixel_2[0] as i32),
-        safe_add(pixel_1[1], pixel_2[1] as i32),
-        safe_add(pixel_1[2], pixel_2[2] as i32),
-        pixel_1[3]
-    ])
+/// Parse rectangles written `x,y,width,height` separated by `;`
+fn parse_regions(regions: &str) -> Result<Vec<Rect>, Box<dyn Error>> {
+    regions
+        .split(';')
+        .map(|region| {
+            let values: Vec<u32> = region.split(',').map(|v| v.trim().parse()).collect::<Result<_, _>>()
+                .map_err(|_| format!("regions should be x,y,width,height, got '{}'", region))?;
+            match values[..] {
+                [x, y, width, height] => Ok(Rect::new(x, y, width, height)),
+                _ => Err(format!("regions should be x,y,width,height, got '{}'", region).into()),
+            }
+        })
+        .collect()
 }
 
-/// Shift a pixels r,g,b values by a constant value (positive or negative)
-///
-/// # Arguments
-///
-/// * `pixel`: Single  pixel of an image
-/// * `value`: Positive or negative value to shift the pixel by
-///
-/// returns: rgba pixel
-fn pixel_shift(pixel: image::Rgba<u8>, value: i32) -> image::Rgba<u8> {
-    image::Rgba([safe_add(pixel[0], value), safe_add(pixel[1], value), safe_add(pixel[2], value), pixel[3]])
+/// Parse a colour written `#rrggbb` or `#rrggbbaa`
+fn parse_colour(colour: &str) -> Result<image::Rgba<u8>, Box<dyn Error>> {
+    Ok(pixel::parse_colour(colour).ok_or_else(|| format!("colours should be #rrggbb or #rrggbbaa, got '{}'", colour))?)
 }
 
-/// Scales (multiplies) a pixels r,g,b values by a constant value
-///
-/// # Arguments
-///
-/// * `pixel`: Single  pixel of an image
-/// * `value`: Scale factor of the pixel
-///
-/// returns: rgba pixel
-fn pixel_scale(pixel: image::Rgba<u8>, value: f32) -> image::Rgba<u8> {
-    image::Rgba([safe_mult(pixel[0], value), safe_mult(pixel[1], value), safe_mult(pixel[2], value), pixel[3]])
+fn saliency_option(args: &Args) -> Result<Saliency, Box<dyn Error>> {
+    match args.option("saliency") {
+        Some(name) => Ok(Saliency::from_name(name).ok_or_else(|| format!("unknown saliency '{}'", name))?),
+        None => Ok(Saliency::SpectralResidual),
+    }
 }
 
-/// Given a u8 and an integer, perform addition in the i32 space but then clamp back to a u8
-///
-/// # Arguments
-///
-/// * `a`: u8 value
-/// * `b`: i32 value
-///
-/// returns: u8
-fn safe_add(a: u8, b: i32) -> u8 {
-    let c = (a as i32) + b;
-    let scaled = cmp::min(255, cmp::max(0, c));
+fn parse_option<T: std::str::FromStr>(args: &Args, name: &str, default: T) -> Result<T, Box<dyn Error>>
+where
+    T::Err: Error + 'static,
+{
+    match args.option(name) {
+        Some(value) => Ok(value.parse()?),
+        None => Ok(default),
+    }
+}
 
-    scaled as u8
+/// The `--max-memory` budget, a size such as `512M` or `2G`
+fn memory_budget(args: &Args) -> Result<Option<MemoryBudget>, Box<dyn Error>> {
+    match args.option("max-memory") {
+        Some(size) => Ok(Some(MemoryBudget::parse(size).ok_or_else(|| format!("invalid memory size '{}'", size))?)),
+        None => Ok(None),
+    }
 }
 
-/// Given a u8 and a float, multiply as floats, round to i32 then clamb pack to to a u8
-///
-/// # Arguments
-///
-/// * `a`: u8 value
-/// * `b`: f32 value
-///
-/// returns: u8
-fn safe_mult(a: u8, b: f32) -> u8 {
-    let c = ((a as f32) * b) as i32;
-    let scaled = cmp::min(255, cmp::max(0, c));
+/// Progress shown as a percentage on stderr, rewritten in place as work continues. It's left
+/// out when quiet, and when verbose as it would be broken up by the debug output.
+fn progress_bar() -> Progress {
+    if log::max_level() != LevelFilter::Info {
+        return Progress::none();
+    }
 
-    scaled as u8
+    Progress::none().with_callback(|fraction| {
+        eprint!("\r{:5.1}%", fraction * 100.);
+        if fraction >= 1. {
+            eprintln!();
+        }
+        std::io::stderr().flush().ok();
+    })
 }
 
-fn load_image(path: String) -> ImageBuffer {
-    let input_raw = image::open(path).unwrap();
+/// Encoding settings from the output option flags
+fn output_options(args: &Args) -> Result<OutputOptions, Box<dyn Error>> {
+    let mut options = OutputOptions::new();
 
-    let (input_x, input_y) = input_raw.dimensions();
-    let mut input: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
-    for(x, y, pixel) in input.enumerate_pixels_mut() {
-        *pixel = input_raw.get_pixel(x, y);
-    }
-
-    input
-}
+    if let Some(name) = args.option("format") {
+        options = options.with_format(OutputFormat::from_name(name).ok_or_else(|| format!("unknown format '{}'", name))?);
+    }
+    if let Some(quality) = args.option("quality") {
+        options = options.with_jpeg_quality(quality.parse()?);
+    }
+    if let Some(name) = args.option("compression") {
+        options = options.with_png_compression(PngCompression::from_name(name).ok_or_else(|| format!("unknown compression '{}'", name))?);
+    }
+    match args.option("metadata") {
+        Some("preserve") => options = options.with_metadata(MetadataPolicy::Preserve),
+        Some("strip") | None => {}
+        Some(name) => return Err(format!("unknown metadata policy '{}'", name).into()),
+    }
+    if args.flag("keep-profile") {
+        options = options.with_profile(ProfilePolicy::Keep);
+    }
 
-fn median(numbers: &Vec<u8>) -> u8 {
-    let mid = numbers.len() / 2;
+    Ok(options)
+}
 
-    numbers[mid]
-}
\ No newline at end of file
+fn default_output_path(input: &Path, options: &OutputOptions) -> PathBuf {
+    let stem = input.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    let extension = options.format().unwrap_or(OutputFormat::Png).extension();
+    input.with_file_name(format!("{}_processed.{}", stem, extension))
+}
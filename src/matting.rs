@@ -0,0 +1,141 @@
+//! Soft alpha mattes, for cutting out hair, fur and blurred edges that a hard cut would chop.
+//! Uses closed form matting (Levin, Lischinski and Weiss, 2008): within each small window
+//! alpha is assumed to be a linear function of colour, which gives a quadratic cost over the
+//! whole matte that's minimised, with the known pixels of a trimap held fixed, by conjugate
+//! gradients.
+
+use crate::grabcut::{SURE_BACKGROUND, SURE_FOREGROUND};
+use crate::linalg::invert3;
+use crate::plane::Plane;
+use crate::ImageBuffer;
+
+/// Regularisation of each window's colour covariance, in colours scaled to 0 to 1. Larger
+/// values give smoother mattes.
+const REGULARISATION: f64 = 1e-7;
+
+/// Weight holding the trimap's known pixels to their values
+const CONSTRAINT_WEIGHT: f64 = 100.;
+
+/// Conjugate gradients stop once the residual has shrunk by this much
+const TOLERANCE: f64 = 1e-10;
+
+/// A 3x3 window with unknown pixels, and the local colour model alpha is fitted to in it
+struct Window {
+    pixels: [usize; 9],
+    mean: [f64; 3],
+    /// Inverse of the regularised colour covariance
+    inverse: [[f64; 3]; 3],
+}
+
+/// Pull a soft alpha matte from an image and a trimap. Trimap values up to the GrabCut
+/// `SURE_BACKGROUND` are background, from `SURE_FOREGROUND` foreground, and between unknown, so
+/// a trimap of black, grey and white works. Alpha in the unknown band follows the colours of
+/// the image, so keep the band narrow, just covering the soft edge.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to pull the matte from
+/// * `trimap`: Trimap plane from 0 to 1, the size of the input
+/// * `iterations`: Most rounds of conjugate gradients, e.g. 500, more for wider unknown bands
+///
+/// returns: Plane, an alpha matte from 0 for background to 1 for foreground
+pub fn closed_form_matte(input: &ImageBuffer, trimap: &Plane, iterations: u32) -> Plane {
+    let (width, height) = (input.width() as usize, input.height() as usize);
+    let colours: Vec<[f64; 3]> = input.pixels().map(|p| [p[0] as f64 / 255., p[1] as f64 / 255., p[2] as f64 / 255.]).collect();
+    let known: Vec<Option<f64>> = trimap
+        .data
+        .iter()
+        .map(|&v| if v <= SURE_BACKGROUND { Some(0.) } else if v >= SURE_FOREGROUND { Some(1.) } else { None })
+        .collect();
+
+    let windows = unknown_windows(&colours, &known, width, height);
+
+    let apply = |x: &[f64], out: &mut [f64]| {
+        for (o, (&xi, k)) in out.iter_mut().zip(x.iter().zip(&known)) {
+            *o = if k.is_some() { CONSTRAINT_WEIGHT * xi } else { 0. };
+        }
+        for window in &windows {
+            let offsets = window.pixels.map(|i| [colours[i][0] - window.mean[0], colours[i][1] - window.mean[1], colours[i][2] - window.mean[2]]);
+            let sum: f64 = window.pixels.iter().map(|&i| x[i]).sum();
+            let weighted: [f64; 3] = std::array::from_fn(|c| window.pixels.iter().zip(&offsets).map(|(&i, a)| a[c] * x[i]).sum());
+            let v: [f64; 3] = std::array::from_fn(|r| (0..3).map(|c| window.inverse[r][c] * weighted[c]).sum());
+            for (&i, a) in window.pixels.iter().zip(&offsets) {
+                out[i] += x[i] - (sum + a[0] * v[0] + a[1] * v[1] + a[2] * v[2]) / 9.;
+            }
+        }
+    };
+
+    // Solve (L + weight D) alpha = weight D trimap, starting the unknown pixels at a half
+    let b: Vec<f64> = known.iter().map(|k| CONSTRAINT_WEIGHT * k.unwrap_or(0.)).collect();
+    let mut alpha: Vec<f64> = known.iter().map(|k| k.unwrap_or(0.5)).collect();
+    let mut product = vec![0.; alpha.len()];
+    apply(&alpha, &mut product);
+    let mut residual: Vec<f64> = b.iter().zip(&product).map(|(b, p)| b - p).collect();
+    let mut direction = residual.clone();
+    let mut residual_norm: f64 = residual.iter().map(|r| r * r).sum();
+    let target = TOLERANCE * b.iter().map(|b| b * b).sum::<f64>().max(1.);
+
+    for _ in 0..iterations {
+        if residual_norm <= target {
+            break;
+        }
+        apply(&direction, &mut product);
+        let step = residual_norm / direction.iter().zip(&product).map(|(d, p)| d * p).sum::<f64>();
+        for ((a, r), (d, p)) in alpha.iter_mut().zip(residual.iter_mut()).zip(direction.iter().zip(&product)) {
+            *a += step * d;
+            *r -= step * p;
+        }
+        let next_norm: f64 = residual.iter().map(|r| r * r).sum();
+        let beta = next_norm / residual_norm;
+        for (d, r) in direction.iter_mut().zip(&residual) {
+            *d = r + beta * *d;
+        }
+        residual_norm = next_norm;
+    }
+
+    Plane { width: input.width(), height: input.height(), data: alpha.iter().map(|&a| a.clamp(0., 1.) as f32).collect() }
+}
+
+/// Make a trimap from a hard matte, e.g. from GrabCut, with an unknown band either side of the
+/// edge for `closed_form_matte` to soften
+///
+/// # Arguments
+///
+/// * `matte`: Matte from 0 to 1, foreground from 0.5
+/// * `band`: Width in pixels of the unknown band on each side of the edge
+///
+/// returns: Plane, 0 for background, 0.5 for unknown and 1 for foreground
+pub fn trimap_from_matte(matte: &Plane, band: u32) -> Plane {
+    let hard = matte.map(|v| if v >= 0.5 { 1. } else { 0. });
+    hard.box_blur(band).zip(&hard, |blurred, value| if blurred > 0.001 && blurred < 0.999 { 0.5 } else { value })
+}
+
+/// The 3x3 windows holding an unknown pixel, as windows of only known pixels add nothing once
+/// those are held fixed
+fn unknown_windows(colours: &[[f64; 3]], known: &[Option<f64>], width: usize, height: usize) -> Vec<Window> {
+    let mut windows = Vec::new();
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let pixels: [usize; 9] = std::array::from_fn(|k| (y + k / 3 - 1) * width + x + k % 3 - 1);
+            if pixels.iter().all(|&i| known[i].is_some()) {
+                continue;
+            }
+
+            let mean: [f64; 3] = std::array::from_fn(|c| pixels.iter().map(|&i| colours[i][c]).sum::<f64>() / 9.);
+            let mut covariance: [[f64; 3]; 3] =
+                std::array::from_fn(|r| std::array::from_fn(|c| pixels.iter().map(|&i| (colours[i][r] - mean[r]) * (colours[i][c] - mean[c])).sum::<f64>() / 9.));
+            // Scaled so the smallest eigenvalue is at least 1, as flat windows have tiny
+            // determinants that `invert3` would take as singular
+            let scale = 9. / REGULARISATION;
+            for (r, row) in covariance.iter_mut().enumerate() {
+                row[r] += REGULARISATION / 9.;
+            }
+            let scaled = covariance.map(|row| row.map(|v| v * scale));
+
+            if let Some(inverse) = invert3(&scaled) {
+                windows.push(Window { pixels, mean, inverse: inverse.map(|row| row.map(|v| v * scale)) });
+            }
+        }
+    }
+    windows
+}
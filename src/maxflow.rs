@@ -0,0 +1,291 @@
+//! Minimum cuts of graphs by maximum flow, for labelling pixels one of two ways where each
+//! pixel has a cost for either label and neighbours a cost for differing. Uses the algorithm
+//! of Boykov and Kolmogorov (2004), which grows search trees from the source and the sink
+//! and reuses them between paths rather than searching again from scratch, much faster than
+//! the general algorithms on the short paths of image grids.
+
+use std::collections::VecDeque;
+
+/// Residual capacity below which an arc counts as saturated
+const EPSILON: f64 = 1e-9;
+
+/// Parent of a node in neither tree
+const NONE: usize = usize::MAX;
+
+/// Parent of a node joined straight to its tree's terminal
+const TERMINAL: usize = usize::MAX - 1;
+
+/// Parent of a node cut off from its tree, waiting for a new parent
+const ORPHAN: usize = usize::MAX - 2;
+
+/// A graph of pixel nodes joined to each other and to a source and a sink, the two labels
+pub(crate) struct Graph {
+    /// Arcs in pairs, each followed by its reverse, so the reverse of arc `a` is `a ^ 1`
+    head: Vec<usize>,
+    capacity: Vec<f64>,
+    next: Vec<usize>,
+
+    first: Vec<usize>,
+    /// Residual capacity from the source when positive, or to the sink when negative
+    terminal: Vec<f64>,
+    /// Arc from each node to its parent in its tree, or one of `NONE`, `TERMINAL`, `ORPHAN`
+    parent: Vec<usize>,
+    in_sink_tree: Vec<bool>,
+    /// When `distance` was last known right, in augmentations, to keep the trees shallow
+    timestamp: Vec<u64>,
+    /// Arcs from each node to its tree's terminal
+    distance: Vec<u64>,
+}
+
+impl Graph {
+    pub(crate) fn new(nodes: usize) -> Graph {
+        Graph {
+            head: Vec::new(),
+            capacity: Vec::new(),
+            next: Vec::new(),
+            first: vec![NONE; nodes],
+            terminal: vec![0.; nodes],
+            parent: vec![NONE; nodes],
+            in_sink_tree: vec![false; nodes],
+            timestamp: vec![0; nodes],
+            distance: vec![0; nodes],
+        }
+    }
+
+    fn add_arc(&mut self, from: usize, to: usize, capacity: f64) {
+        self.head.push(to);
+        self.capacity.push(capacity);
+        self.next.push(self.first[from]);
+        self.first[from] = self.head.len() - 1;
+    }
+
+    /// Join two nodes, with the cost of cutting between them either way
+    pub(crate) fn add_edge(&mut self, a: usize, b: usize, capacity: f64, reverse_capacity: f64) {
+        self.add_arc(a, b, capacity);
+        self.add_arc(b, a, reverse_capacity);
+    }
+
+    /// Join a node to the source and the sink, with the cost of labelling it sink and source
+    /// respectively. Flow the two share goes straight through, so only the difference is kept.
+    pub(crate) fn add_terminals(&mut self, node: usize, source_capacity: f64, sink_capacity: f64) {
+        self.terminal[node] += source_capacity - sink_capacity;
+    }
+
+    /// Push as much flow as possible from the source to the sink, leaving the minimum cut.
+    /// Flow straight from the source to the sink through one node isn't counted.
+    pub(crate) fn max_flow(&mut self) -> f64 {
+        let mut active = VecDeque::new();
+        let mut queued = vec![false; self.first.len()];
+        let mut orphans = Vec::new();
+        let mut time = 0;
+        let mut flow = 0.;
+
+        for (node, is_queued) in queued.iter_mut().enumerate() {
+            if self.terminal[node].abs() > EPSILON {
+                self.parent[node] = TERMINAL;
+                self.in_sink_tree[node] = self.terminal[node] < 0.;
+                self.distance[node] = 1;
+                *is_queued = true;
+                active.push_back(node);
+            }
+        }
+
+        while let Some(node) = active.pop_front() {
+            queued[node] = false;
+            if self.parent[node] == NONE {
+                continue;
+            }
+
+            let Some(middle) = self.grow(node, &mut active, &mut queued) else {
+                continue;
+            };
+
+            time += 1;
+            flow += self.augment(middle, &mut orphans);
+            self.adopt(&mut orphans, time, &mut active, &mut queued);
+
+            // The node may reach the other tree by another arc, so search from it again
+            if self.parent[node] != NONE && !queued[node] {
+                queued[node] = true;
+                active.push_front(node);
+            }
+        }
+
+        flow
+    }
+
+    /// Whether a node ends on the source side of the minimum cut, after `max_flow`
+    pub(crate) fn is_source_side(&self, node: usize) -> bool {
+        self.parent[node] != NONE && !self.in_sink_tree[node]
+    }
+
+    /// Residual capacity of an arc in the direction flow moves through a node's tree, away
+    /// from the source or towards the sink
+    fn tree_capacity(&self, arc: usize, in_sink_tree: bool) -> f64 {
+        if in_sink_tree {
+            self.capacity[arc ^ 1]
+        } else {
+            self.capacity[arc]
+        }
+    }
+
+    /// Grow a node's tree over its free neighbours, until it meets the other tree
+    ///
+    /// returns: Option<usize>, the arc joining the trees from the source side to the sink side
+    fn grow(&mut self, node: usize, active: &mut VecDeque<usize>, queued: &mut [bool]) -> Option<usize> {
+        let in_sink_tree = self.in_sink_tree[node];
+        let mut arc = self.first[node];
+
+        while arc != NONE {
+            let neighbour = self.head[arc];
+            if self.tree_capacity(arc, in_sink_tree) > EPSILON {
+                if self.parent[neighbour] == NONE {
+                    self.in_sink_tree[neighbour] = in_sink_tree;
+                    self.parent[neighbour] = arc ^ 1;
+                    self.timestamp[neighbour] = self.timestamp[node];
+                    self.distance[neighbour] = self.distance[node] + 1;
+                    if !queued[neighbour] {
+                        queued[neighbour] = true;
+                        active.push_back(neighbour);
+                    }
+                } else if self.in_sink_tree[neighbour] != in_sink_tree {
+                    return Some(if in_sink_tree { arc ^ 1 } else { arc });
+                } else if self.timestamp[neighbour] <= self.timestamp[node] && self.distance[neighbour] > self.distance[node] {
+                    // A shorter way to the terminal, keeping the tree shallow
+                    self.parent[neighbour] = arc ^ 1;
+                    self.timestamp[neighbour] = self.timestamp[node];
+                    self.distance[neighbour] = self.distance[node] + 1;
+                }
+            }
+            arc = self.next[arc];
+        }
+
+        None
+    }
+
+    /// Push flow along the path through the arc joining the trees, orphaning nodes whose arc to
+    /// their parent it saturates
+    ///
+    /// returns: f64, the flow pushed
+    fn augment(&mut self, middle: usize, orphans: &mut Vec<usize>) -> f64 {
+        let mut pushed = self.capacity[middle];
+        for (start, in_sink_tree) in [(self.head[middle ^ 1], false), (self.head[middle], true)] {
+            let mut node = start;
+            loop {
+                let arc = self.parent[node];
+                if arc == TERMINAL {
+                    pushed = pushed.min(self.terminal[node].abs());
+                    break;
+                }
+                pushed = pushed.min(self.tree_capacity(arc ^ 1, in_sink_tree));
+                node = self.head[arc];
+            }
+        }
+
+        self.capacity[middle] -= pushed;
+        self.capacity[middle ^ 1] += pushed;
+        for (start, in_sink_tree) in [(self.head[middle ^ 1], false), (self.head[middle], true)] {
+            let sign = if in_sink_tree { -1. } else { 1. };
+            let mut node = start;
+            loop {
+                let arc = self.parent[node];
+                if arc == TERMINAL {
+                    self.terminal[node] -= sign * pushed;
+                    if sign * self.terminal[node] <= EPSILON {
+                        self.parent[node] = ORPHAN;
+                        orphans.push(node);
+                    }
+                    break;
+                }
+
+                // Flow runs down the source tree, from parent to child, and up the sink tree
+                let forward = if in_sink_tree { arc } else { arc ^ 1 };
+                self.capacity[forward] -= pushed;
+                self.capacity[forward ^ 1] += pushed;
+                if self.capacity[forward] <= EPSILON {
+                    self.parent[node] = ORPHAN;
+                    orphans.push(node);
+                }
+                node = self.head[arc];
+            }
+        }
+
+        pushed
+    }
+
+    /// Find each orphan a new parent in its tree that still leads to the terminal, or free it,
+    /// orphaning its children in turn
+    fn adopt(&mut self, orphans: &mut Vec<usize>, time: u64, active: &mut VecDeque<usize>, queued: &mut [bool]) {
+        while let Some(node) = orphans.pop() {
+            let in_sink_tree = self.in_sink_tree[node];
+            let (mut best, mut best_distance) = (NONE, u64::MAX);
+
+            let mut arc = self.first[node];
+            while arc != NONE {
+                let neighbour = self.head[arc];
+                if self.tree_capacity(arc ^ 1, in_sink_tree) > EPSILON && self.parent[neighbour] != NONE && self.in_sink_tree[neighbour] == in_sink_tree {
+                    if let Some(distance) = self.origin_distance(neighbour, time) {
+                        if distance < best_distance {
+                            (best, best_distance) = (arc, distance);
+                        }
+                        // Mark the way up as known good for the rest of this adoption
+                        let (mut step, mut d) = (neighbour, distance);
+                        while self.timestamp[step] != time {
+                            self.timestamp[step] = time;
+                            self.distance[step] = d;
+                            d -= 1;
+                            step = self.head[self.parent[step]];
+                        }
+                    }
+                }
+                arc = self.next[arc];
+            }
+
+            if best != NONE {
+                self.parent[node] = best;
+                self.timestamp[node] = time;
+                self.distance[node] = best_distance + 1;
+                continue;
+            }
+
+            // No way back to the terminal, so the node leaves its tree
+            let mut arc = self.first[node];
+            while arc != NONE {
+                let neighbour = self.head[arc];
+                let parent = self.parent[neighbour];
+                if parent != NONE && self.in_sink_tree[neighbour] == in_sink_tree {
+                    if self.tree_capacity(arc ^ 1, in_sink_tree) > EPSILON && !queued[neighbour] {
+                        queued[neighbour] = true;
+                        active.push_back(neighbour);
+                    }
+                    if parent != TERMINAL && parent != ORPHAN && self.head[parent] == node {
+                        self.parent[neighbour] = ORPHAN;
+                        orphans.push(neighbour);
+                    }
+                }
+                arc = self.next[arc];
+            }
+            self.parent[node] = NONE;
+        }
+    }
+
+    /// Arcs from a node up to its tree's terminal, or None if the way up passes an orphan
+    fn origin_distance(&mut self, node: usize, time: u64) -> Option<u64> {
+        let (mut step, mut distance) = (node, 0);
+        loop {
+            if self.timestamp[step] == time {
+                return Some(distance + self.distance[step]);
+            }
+            distance += 1;
+            match self.parent[step] {
+                TERMINAL => {
+                    self.timestamp[step] = time;
+                    self.distance[step] = 1;
+                    return Some(distance);
+                }
+                ORPHAN => return None,
+                arc => step = self.head[arc],
+            }
+        }
+    }
+}
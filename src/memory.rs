@@ -0,0 +1,117 @@
+//! Memory budgets, so large jobs can run on constrained machines. A budget limits how many
+//! images a batch decodes and processes at once, and when even one image won't fit it's run
+//! in tiles small enough that the filters' working copies of each tile do.
+
+use log::warn;
+
+/// Working copies of an image a pipeline holds at its peak besides the input, the output among
+/// them. This is the most `benches/memory.rs` measures, for adaptive sharpening; point
+/// operations need one and the other filters two to seven.
+pub const WORKING_COPIES: u64 = 10;
+
+/// Tiles aren't made smaller than this, as below it the halos cost more than the tiles
+pub const MIN_TILE_SIZE: u32 = 64;
+
+/// Bytes of memory a job may use
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryBudget {
+    bytes: u64,
+}
+
+impl MemoryBudget {
+    pub fn new(bytes: u64) -> MemoryBudget {
+        MemoryBudget { bytes }
+    }
+
+    /// Parse a size such as `512M`, `2G` or `1.5g`, a number with an optional suffix K, M or G
+    /// (powers of 1024, with an optional trailing B), or a plain number of bytes
+    pub fn parse(text: &str) -> Option<MemoryBudget> {
+        let text = text.trim().to_ascii_uppercase();
+        let text = text.strip_suffix('B').unwrap_or(&text);
+        let (number, scale) = match text.chars().last()? {
+            'K' => (&text[..text.len() - 1], 1u64 << 10),
+            'M' => (&text[..text.len() - 1], 1 << 20),
+            'G' => (&text[..text.len() - 1], 1 << 30),
+            _ => (text, 1),
+        };
+        let number: f64 = number.trim().parse().ok()?;
+        if !number.is_finite() || number <= 0. {
+            return None;
+        }
+        Some(MemoryBudget::new((number * scale as f64) as u64))
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// An equal share of the budget for each of `parts` jobs run at once
+    pub fn share(&self, parts: usize) -> MemoryBudget {
+        MemoryBudget::new(self.bytes / parts.max(1) as u64)
+    }
+
+    /// How many images of the given size can be processed at once, each whole, at least 1 even
+    /// when one doesn't fit (it's then tiled) and at most `limit`
+    ///
+    /// # Arguments
+    ///
+    /// * `width`: Width of the largest image
+    /// * `height`: Height of the largest image
+    /// * `limit`: Most images to run at once, e.g. the number of cores
+    ///
+    /// returns: usize
+    pub fn concurrent_images(&self, width: u32, height: u32, limit: usize) -> usize {
+        let per_image = whole_image_bytes(width, height).max(1);
+        ((self.bytes / per_image) as usize).clamp(1, limit.max(1))
+    }
+
+    /// The tile size to run an image in to stay within the budget, or None if it fits whole.
+    /// The input and output are kept whole while the tiles pass through, so the tiles get
+    /// what's left, down to `MIN_TILE_SIZE` however small the budget, with a warning when that
+    /// goes over it.
+    ///
+    /// # Arguments
+    ///
+    /// * `width`: Width of the image
+    /// * `height`: Height of the image
+    /// * `halo`: Pixels each tile reads beyond its edges, as given by `Pipeline::halo`
+    ///
+    /// returns: Option<u32>
+    pub fn tile_size(&self, width: u32, height: u32, halo: u32) -> Option<u32> {
+        if whole_image_bytes(width, height) <= self.bytes {
+            return None;
+        }
+
+        let remaining = self.bytes.saturating_sub(2 * image_bytes(width, height));
+        let padded = ((remaining / (WORKING_COPIES * 4)) as f64).sqrt() as u32;
+        let tile_size = padded.saturating_sub(2 * halo);
+        if tile_size < MIN_TILE_SIZE {
+            warn!(
+                "A {}x{} image can't be run within {} bytes, using {} pixel tiles which need about {}",
+                width,
+                height,
+                self.bytes,
+                MIN_TILE_SIZE,
+                tile_bytes(width, height, MIN_TILE_SIZE + 2 * halo)
+            );
+            return Some(MIN_TILE_SIZE);
+        }
+        Some(tile_size)
+    }
+}
+
+/// Bytes of an rgba image
+pub fn image_bytes(width: u32, height: u32) -> u64 {
+    width as u64 * height as u64 * 4
+}
+
+/// Bytes needed to run a pipeline over an image in tiles of a padded size: the input and output
+/// whole and the working copies of one tile
+fn tile_bytes(width: u32, height: u32, padded: u32) -> u64 {
+    2 * image_bytes(width, height) + WORKING_COPIES * image_bytes(padded, padded)
+}
+
+/// Bytes needed to run a pipeline over a whole image: the input and its working copies
+pub fn whole_image_bytes(width: u32, height: u32) -> u64 {
+    image_bytes(width, height) * (WORKING_COPIES + 1)
+}
@@ -0,0 +1,271 @@
+//! Reading exif data, the tiff structure of tagged entries cameras embed in their images
+
+use crate::error::{Error, Result};
+
+pub const MAKE: u16 = 0x010f;
+pub const MODEL: u16 = 0x0110;
+pub const ORIENTATION: u16 = 0x0112;
+pub const SOFTWARE: u16 = 0x0131;
+pub const DATE_TIME: u16 = 0x0132;
+pub const EXIF_IFD: u16 = 0x8769;
+pub const EXPOSURE_TIME: u16 = 0x829a;
+pub const F_NUMBER: u16 = 0x829d;
+pub const ISO_SPEED: u16 = 0x8827;
+pub const DATE_TIME_ORIGINAL: u16 = 0x9003;
+pub const FOCAL_LENGTH: u16 = 0x920a;
+
+/// Entries beyond this in one directory are taken as corrupt data
+const MAX_ENTRIES: usize = 1000;
+
+/// The value of an entry, by its tiff type
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExifValue {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    SignedRational(Vec<(i32, i32)>),
+    /// Undefined and other types, as their raw bytes
+    Undefined(Vec<u8>),
+}
+
+impl ExifValue {
+    /// The first value as an integer, for the byte, short and long types
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            ExifValue::Byte(values) => values.first().map(|&v| v as u32),
+            ExifValue::Short(values) => values.first().map(|&v| v as u32),
+            ExifValue::Long(values) => values.first().copied(),
+            _ => None,
+        }
+    }
+
+    /// The first value as a number, including rationals
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            ExifValue::Rational(values) => values.first().filter(|(_, d)| *d != 0).map(|&(n, d)| n as f64 / d as f64),
+            ExifValue::SignedRational(values) => values.first().filter(|(_, d)| *d != 0).map(|&(n, d)| n as f64 / d as f64),
+            _ => self.as_u32().map(|v| v as f64),
+        }
+    }
+
+    /// Every value as an integer, for the byte, short and long types
+    pub fn to_u32s(&self) -> Vec<u32> {
+        match self {
+            ExifValue::Byte(values) => values.iter().map(|&v| v as u32).collect(),
+            ExifValue::Short(values) => values.iter().map(|&v| v as u32).collect(),
+            ExifValue::Long(values) => values.clone(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Every value as a number, including rationals
+    pub fn to_f64s(&self) -> Vec<f64> {
+        match self {
+            ExifValue::Rational(values) => values.iter().map(|&(n, d)| n as f64 / d.max(1) as f64).collect(),
+            ExifValue::SignedRational(values) => values.iter().map(|&(n, d)| n as f64 / if d == 0 { 1. } else { d as f64 }).collect(),
+            _ => self.to_u32s().into_iter().map(|v| v as f64).collect(),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            ExifValue::Ascii(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExifEntry {
+    pub tag: u16,
+    pub value: ExifValue,
+}
+
+/// How the stored pixels are turned to display the image upright
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Orientation {
+    Normal,
+    FlipHorizontal,
+    Rotate180,
+    FlipVertical,
+    /// Mirrored across the top left to bottom right diagonal
+    Transpose,
+    /// Rotated 90 degrees clockwise
+    Rotate90,
+    /// Mirrored across the top right to bottom left diagonal
+    Transverse,
+    Rotate270,
+}
+
+impl Orientation {
+    /// The orientation for an exif orientation value, unknown values as normal
+    pub fn from_exif(value: u32) -> Orientation {
+        match value {
+            2 => Orientation::FlipHorizontal,
+            3 => Orientation::Rotate180,
+            4 => Orientation::FlipVertical,
+            5 => Orientation::Transpose,
+            6 => Orientation::Rotate90,
+            7 => Orientation::Transverse,
+            8 => Orientation::Rotate270,
+            _ => Orientation::Normal,
+        }
+    }
+}
+
+/// Parsed exif entries from the main image directory and the exif sub directory
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Exif {
+    entries: Vec<ExifEntry>,
+}
+
+impl Exif {
+    /// Parse raw exif data, starting with its `II` or `MM` byte order mark
+    pub fn parse(data: &[u8]) -> Result<Exif> {
+        let reader = Reader::new(data)?;
+        let mut entries = Vec::new();
+
+        let ifd0 = reader.u32(4)? as usize;
+        reader.read_directory(ifd0, &mut entries)?;
+
+        let sub_ifd = entries.iter().find(|entry| entry.tag == EXIF_IFD).and_then(|entry| entry.value.as_u32());
+        if let Some(offset) = sub_ifd {
+            reader.read_directory(offset as usize, &mut entries)?;
+        }
+
+        Ok(Exif { entries })
+    }
+
+    pub fn entries(&self) -> &[ExifEntry] {
+        &self.entries
+    }
+
+    pub fn get(&self, tag: u16) -> Option<&ExifValue> {
+        self.entries.iter().find(|entry| entry.tag == tag).map(|entry| &entry.value)
+    }
+
+    pub fn orientation(&self) -> Orientation {
+        self.get(ORIENTATION).and_then(|value| value.as_u32()).map(Orientation::from_exif).unwrap_or(Orientation::Normal)
+    }
+}
+
+/// Mark raw exif data as upright, once its pixels have been turned to match, so viewers don't
+/// turn them again. Data without an orientation entry is left as it is.
+pub(crate) fn reset_orientation(data: &mut [u8]) {
+    let Ok(reader) = Reader::new(data) else { return };
+    let Some(offset) = reader.find_entry(ORIENTATION) else { return };
+
+    let upright = match reader.little_endian {
+        true => 1u16.to_le_bytes(),
+        false => 1u16.to_be_bytes(),
+    };
+    // The entry's value may run past the end of truncated data
+    if let Some(value) = data.get_mut(offset + 8..offset + 10) {
+        value.copy_from_slice(&upright);
+    }
+}
+
+/// The byte order and the first directory's offset of a tiff file, for reading tiff based
+/// formats other than exif
+#[cfg(feature = "raw")]
+pub(crate) fn tiff_header(data: &[u8]) -> Result<(bool, usize)> {
+    let reader = Reader::new(data)?;
+    Ok((reader.little_endian, reader.u32(4)? as usize))
+}
+
+/// The entries of the tiff directory at an offset, and the offset of the directory after it
+/// (zero for the last)
+#[cfg(feature = "raw")]
+pub(crate) fn tiff_directory(data: &[u8], offset: usize) -> Result<(Vec<ExifEntry>, usize)> {
+    let reader = Reader::new(data)?;
+    let mut entries = Vec::new();
+    reader.read_directory(offset, &mut entries)?;
+
+    let next = reader.u32(offset + 2 + reader.u16(offset)? as usize * 12)? as usize;
+    Ok((entries, next))
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    little_endian: bool,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Result<Reader<'a>> {
+        let little_endian = match data.get(0..2) {
+            Some(b"II") => true,
+            Some(b"MM") => false,
+            _ => return Err(Error::Codec("exif data has no byte order mark".to_string())),
+        };
+
+        Ok(Reader { data, little_endian })
+    }
+
+    fn bytes(&self, offset: usize, len: usize) -> Result<&'a [u8]> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or_else(|| Error::Codec("exif entry runs past the end of the data".to_string()))
+    }
+
+    fn u16(&self, offset: usize) -> Result<u16> {
+        let bytes = self.bytes(offset, 2)?.try_into().unwrap();
+        Ok(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32(&self, offset: usize) -> Result<u32> {
+        let bytes = self.bytes(offset, 4)?.try_into().unwrap();
+        Ok(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    /// Offset of the 12 byte entry for a tag in the main directory
+    fn find_entry(&self, tag: u16) -> Option<usize> {
+        let ifd = self.u32(4).ok()? as usize;
+        let count = self.u16(ifd).ok()? as usize;
+
+        (0..count.min(MAX_ENTRIES)).map(|i| ifd + 2 + i * 12).find(|&entry| self.u16(entry).ok() == Some(tag) && self.u16(entry + 2).ok() == Some(3))
+    }
+
+    fn read_directory(&self, offset: usize, entries: &mut Vec<ExifEntry>) -> Result<()> {
+        let count = self.u16(offset)? as usize;
+        if count > MAX_ENTRIES {
+            return Err(Error::Codec(format!("exif directory has {} entries", count)));
+        }
+
+        for i in 0..count {
+            let entry = offset + 2 + i * 12;
+            let tag = self.u16(entry)?;
+            let kind = self.u16(entry + 2)?;
+            let count = self.u32(entry + 4)? as usize;
+
+            let size = match kind {
+                1 | 2 | 6 | 7 => 1,
+                3 | 8 => 2,
+                4 | 9 | 11 => 4,
+                5 | 10 | 12 => 8,
+                _ => continue,
+            };
+            let len = size * count;
+            // Values of up to 4 bytes are stored in the entry itself, longer ones at an offset
+            let start = if len <= 4 { entry + 8 } else { self.u32(entry + 8)? as usize };
+            let bytes = self.bytes(start, len)?;
+
+            let value = match kind {
+                1 => ExifValue::Byte(bytes.to_vec()),
+                2 => ExifValue::Ascii(String::from_utf8_lossy(bytes).trim_end_matches('\0').to_string()),
+                3 => ExifValue::Short((0..count).map(|j| self.u16(start + j * 2)).collect::<Result<_>>()?),
+                4 => ExifValue::Long((0..count).map(|j| self.u32(start + j * 4)).collect::<Result<_>>()?),
+                5 => ExifValue::Rational((0..count).map(|j| Ok((self.u32(start + j * 8)?, self.u32(start + j * 8 + 4)?))).collect::<Result<_>>()?),
+                10 => ExifValue::SignedRational(
+                    (0..count).map(|j| Ok((self.u32(start + j * 8)? as i32, self.u32(start + j * 8 + 4)? as i32))).collect::<Result<_>>()?,
+                ),
+                _ => ExifValue::Undefined(bytes.to_vec()),
+            };
+            entries.push(ExifEntry { tag, value });
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,277 @@
+//! Reading icc colour profiles, and converting images from rgb matrix profiles such as
+//! Display P3 and Adobe RGB to sRGB
+
+use crate::error::{Error, Result};
+use crate::ImageBuffer;
+
+/// Xyz (D50, as profiles are) to linear sRGB, Bradford adapted to sRGB's D65 white
+const XYZ_D50_TO_SRGB: [[f32; 3]; 3] = [
+    [3.133856, -1.6168667, -0.4906146],
+    [-0.9787684, 1.9161415, 0.033454],
+    [0.0719453, -0.2289914, 1.4052427],
+];
+
+/// The sRGB primaries as they appear in D50 adapted profiles, for recognising sRGB profiles
+const SRGB_COLORANTS: [[f32; 3]; 3] = [[0.4361, 0.2225, 0.0139], [0.3851, 0.7169, 0.0971], [0.1431, 0.0606, 0.7141]];
+
+/// Steps in the table used to encode linear light as sRGB
+const ENCODE_STEPS: usize = 4096;
+
+/// Tone response of one channel, from encoded value to linear light
+#[derive(Clone, Debug, PartialEq)]
+pub enum Curve {
+    Identity,
+    Gamma(f32),
+    /// Evenly spaced samples over 0 to 1
+    Table(Vec<f32>),
+    /// An icc parametric curve, its function type and parameters `g, a, b, c, d, e, f`
+    Parametric(u16, [f32; 7]),
+}
+
+impl Curve {
+    pub fn eval(&self, x: f32) -> f32 {
+        match self {
+            Curve::Identity => x,
+            Curve::Gamma(gamma) => x.powf(*gamma),
+            Curve::Table(table) => {
+                let position = x.clamp(0., 1.) * (table.len() - 1) as f32;
+                let i = (position as usize).min(table.len() - 2);
+                let t = position - i as f32;
+                table[i] * (1. - t) + table[i + 1] * t
+            }
+            Curve::Parametric(kind, [g, a, b, c, d, e, f]) => match kind {
+                0 => x.powf(*g),
+                1 if x >= -b / a => (a * x + b).powf(*g),
+                1 => 0.,
+                2 if x >= -b / a => (a * x + b).powf(*g) + c,
+                2 => *c,
+                3 if x >= *d => (a * x + b).powf(*g),
+                3 => c * x,
+                _ if x >= *d => (a * x + b).powf(*g) + e,
+                _ => c * x + f,
+            },
+        }
+    }
+}
+
+/// The parts of an icc profile needed to describe and convert its colours
+#[derive(Clone, Debug, PartialEq)]
+pub struct IccProfile {
+    /// Data colour space signature, e.g. `RGB ` or `GRAY`
+    pub colour_space: [u8; 4],
+    pub description: Option<String>,
+    /// Red, green and blue primaries in D50 xyz, for matrix profiles
+    pub colorants: Option<[[f32; 3]; 3]>,
+    /// Red, green and blue tone curves, for matrix profiles
+    pub curves: Option<[Curve; 3]>,
+}
+
+impl IccProfile {
+    pub fn parse(data: &[u8]) -> Result<IccProfile> {
+        if data.len() < 132 || &data[36..40] != b"acsp" {
+            return Err(Error::Codec("not an icc profile".to_string()));
+        }
+
+        let tag_count = be_u32(data, 128)? as usize;
+        let mut tags = Vec::with_capacity(tag_count.min(100));
+        for i in 0..tag_count.min(1000) {
+            let entry = 132 + i * 12;
+            let signature = data.get(entry..entry + 4).ok_or_else(truncated)?;
+            let offset = be_u32(data, entry + 4)? as usize;
+            let size = be_u32(data, entry + 8)? as usize;
+            let tag = offset.checked_add(size).and_then(|end| data.get(offset..end)).ok_or_else(truncated)?;
+            tags.push((signature, tag));
+        }
+        let tag = |signature: &[u8]| tags.iter().find(|(s, _)| *s == signature).map(|(_, tag)| *tag);
+
+        let colorants = match (tag(b"rXYZ"), tag(b"gXYZ"), tag(b"bXYZ")) {
+            (Some(r), Some(g), Some(b)) => Some([parse_xyz(r)?, parse_xyz(g)?, parse_xyz(b)?]),
+            _ => None,
+        };
+        let curves = match (tag(b"rTRC"), tag(b"gTRC"), tag(b"bTRC")) {
+            (Some(r), Some(g), Some(b)) => Some([parse_curve(r)?, parse_curve(g)?, parse_curve(b)?]),
+            _ => None,
+        };
+
+        Ok(IccProfile {
+            colour_space: data[16..20].try_into().unwrap(),
+            description: tag(b"desc").and_then(parse_text),
+            colorants,
+            curves,
+        })
+    }
+
+    /// Whether the profile has sRGB primaries and tone curves, up to rounding, so its pixels
+    /// need no conversion
+    pub fn is_srgb(&self) -> bool {
+        let (Some(colorants), Some(curves)) = (&self.colorants, &self.curves) else { return false };
+
+        let primaries_match = colorants.iter().flatten().zip(SRGB_COLORANTS.iter().flatten()).all(|(a, b)| (a - b).abs() < 0.002);
+        let curves_match = curves.iter().all(|curve| (0..=10).all(|i| {
+            let x = i as f32 / 10.;
+            (curve.eval(x) - srgb_to_linear(x)).abs() < 0.01
+        }));
+
+        primaries_match && curves_match
+    }
+
+    /// Whether `convert_to_srgb` can handle the profile, an rgb profile with primaries and tone
+    /// curves rather than lookup tables
+    pub fn is_convertible(&self) -> bool {
+        &self.colour_space == b"RGB " && self.colorants.is_some() && self.curves.is_some()
+    }
+
+    /// Convert an image's colours from this profile to sRGB in place, leaving alpha as it is
+    ///
+    /// returns: bool, false if the profile can't be converted from and the image was left unchanged
+    pub fn convert_to_srgb(&self, image: &mut ImageBuffer) -> bool {
+        match self.srgb_transform() {
+            Some(transform) => {
+                transform.apply(image);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The conversion from this profile to sRGB, for converting many images or strips of one
+    pub fn srgb_transform(&self) -> Option<SrgbTransform> {
+        let (Some(colorants), Some(curves)) = (&self.colorants, &self.curves) else { return None };
+        if !self.is_convertible() {
+            return None;
+        }
+
+        // Linear light of each 8 bit value for each channel, as the tone curves are per channel
+        let decode = [0, 1, 2].map(|c| std::array::from_fn(|v| curves[c].eval(v as f32 / 255.)));
+        let encode = (0..=ENCODE_STEPS)
+            .map(|i| (linear_to_srgb(i as f32 / ENCODE_STEPS as f32) * 255.).round() as u8)
+            .collect();
+
+        // Profile rgb to xyz has the colorants as its columns, then on to linear sRGB
+        let mut matrix = [[0f32; 3]; 3];
+        for (row, matrix_row) in matrix.iter_mut().enumerate() {
+            for (column, value) in matrix_row.iter_mut().enumerate() {
+                *value = (0..3).map(|k| XYZ_D50_TO_SRGB[row][k] * colorants[column][k]).sum();
+            }
+        }
+
+        Some(SrgbTransform { decode, matrix, encode })
+    }
+}
+
+/// A prepared conversion from a matrix profile to sRGB
+pub struct SrgbTransform {
+    decode: [[f32; 256]; 3],
+    matrix: [[f32; 3]; 3],
+    encode: Vec<u8>,
+}
+
+impl SrgbTransform {
+    pub fn apply(&self, image: &mut ImageBuffer) {
+        self.apply_rgba(image);
+    }
+
+    /// Convert rgba pixel data in place, leaving alpha as it is
+    pub fn apply_rgba(&self, data: &mut [u8]) {
+        for pixel in data.chunks_exact_mut(4) {
+            let linear = [0, 1, 2].map(|c| self.decode[c][pixel[c] as usize]);
+            for (channel, row) in self.matrix.iter().enumerate() {
+                let value = row[0] * linear[0] + row[1] * linear[1] + row[2] * linear[2];
+                pixel[channel] = self.encode[(value.clamp(0., 1.) * ENCODE_STEPS as f32).round() as usize];
+            }
+        }
+    }
+}
+
+fn srgb_to_linear(x: f32) -> f32 {
+    if x <= 0.04045 {
+        x / 12.92
+    } else {
+        ((x + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(x: f32) -> f32 {
+    if x <= 0.0031308 {
+        x * 12.92
+    } else {
+        1.055 * x.powf(1. / 2.4) - 0.055
+    }
+}
+
+fn truncated() -> Error {
+    Error::Codec("icc profile is truncated".to_string())
+}
+
+fn be_u16(data: &[u8], offset: usize) -> Result<u16> {
+    Ok(u16::from_be_bytes(data.get(offset..offset + 2).ok_or_else(truncated)?.try_into().unwrap()))
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Result<u32> {
+    Ok(u32::from_be_bytes(data.get(offset..offset + 4).ok_or_else(truncated)?.try_into().unwrap()))
+}
+
+fn s15_fixed16(data: &[u8], offset: usize) -> Result<f32> {
+    Ok(be_u32(data, offset)? as i32 as f32 / 65536.)
+}
+
+fn parse_xyz(tag: &[u8]) -> Result<[f32; 3]> {
+    if !tag.starts_with(b"XYZ ") {
+        return Err(Error::Codec("icc colorant isn't an xyz value".to_string()));
+    }
+
+    Ok([s15_fixed16(tag, 8)?, s15_fixed16(tag, 12)?, s15_fixed16(tag, 16)?])
+}
+
+fn parse_curve(tag: &[u8]) -> Result<Curve> {
+    match tag.get(0..4) {
+        Some(b"curv") => {
+            let count = be_u32(tag, 8)? as usize;
+            match count {
+                0 => Ok(Curve::Identity),
+                1 => Ok(Curve::Gamma(be_u16(tag, 12)? as f32 / 256.)),
+                _ => {
+                    let table = (0..count).map(|i| Ok(be_u16(tag, 12 + i * 2)? as f32 / 65535.)).collect::<Result<_>>()?;
+                    Ok(Curve::Table(table))
+                }
+            }
+        }
+        Some(b"para") => {
+            let kind = be_u16(tag, 8)?;
+            let count = match kind {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => return Err(Error::Codec(format!("unknown icc parametric curve type {}", kind))),
+            };
+
+            let mut parameters = [1., 1., 0., 0., 0., 0., 0.];
+            for (i, parameter) in parameters.iter_mut().enumerate().take(count) {
+                *parameter = s15_fixed16(tag, 12 + i * 4)?;
+            }
+            Ok(Curve::Parametric(kind, parameters))
+        }
+        _ => Err(Error::Codec("unknown icc tone curve type".to_string())),
+    }
+}
+
+/// Text from a version 2 `desc` or version 4 `mluc` tag, the first language of the latter
+fn parse_text(tag: &[u8]) -> Option<String> {
+    match tag.get(0..4)? {
+        b"desc" => {
+            let length = be_u32(tag, 8).ok()? as usize;
+            let text = tag.get(12..length.checked_add(12)?)?;
+            Some(String::from_utf8_lossy(text).trim_end_matches('\0').to_string())
+        }
+        b"mluc" => {
+            let length = be_u32(tag, 20).ok()? as usize;
+            let offset = be_u32(tag, 24).ok()? as usize;
+            let units: Vec<u16> = tag.get(offset..offset.checked_add(length)?)?.chunks_exact(2).map(|unit| u16::from_be_bytes([unit[0], unit[1]])).collect();
+            Some(String::from_utf16_lossy(&units).trim_end_matches('\0').to_string())
+        }
+        b"text" => Some(String::from_utf8_lossy(tag.get(8..)?).trim_end_matches('\0').to_string()),
+        _ => None,
+    }
+}
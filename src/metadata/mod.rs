@@ -0,0 +1,239 @@
+//! Metadata carried alongside the pixels of an image file, so it can be kept when writing a
+//! processed copy
+
+use std::borrow::Cow;
+use std::fs;
+use std::path::Path;
+
+use png::text_metadata::{ITXtChunk, TEXtChunk};
+
+use crate::error::Result;
+
+pub mod exif;
+pub mod icc;
+
+use exif::{Exif, Orientation};
+use icc::IccProfile;
+
+const JPEG_EXIF_HEADER: &[u8] = b"Exif\0\0";
+const JPEG_ICC_HEADER: &[u8] = b"ICC_PROFILE\0";
+/// Largest payload of a jpeg segment, whose length field counts itself
+const JPEG_SEGMENT_MAX: usize = 65535 - 2;
+
+/// The metadata of a png or jpeg file. Other formats are read as having none.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Metadata {
+    /// Raw exif data, a tiff structure starting with its byte order mark
+    pub exif: Option<Vec<u8>>,
+    /// Embedded icc colour profile
+    pub icc_profile: Option<Vec<u8>>,
+    /// Keyword and text pairs, from png text chunks or jpeg comments (keyword `Comment`)
+    pub text: Vec<(String, String)>,
+}
+
+impl Metadata {
+    /// Read the metadata of an image file
+    pub fn read(path: impl AsRef<Path>) -> Result<Metadata> {
+        Ok(Metadata::from_bytes(&fs::read(path)?))
+    }
+
+    /// Read the metadata of an encoded image, recognising the format from its content
+    pub fn from_bytes(data: &[u8]) -> Metadata {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            read_png(data).unwrap_or_default()
+        } else if data.starts_with(&[0xff, 0xd8]) {
+            read_jpeg(data)
+        } else {
+            Metadata::default()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exif.is_none() && self.icc_profile.is_none() && self.text.is_empty()
+    }
+
+    /// The exif entries, none if there's no exif data or it can't be parsed
+    pub fn parse_exif(&self) -> Option<Exif> {
+        self.exif.as_deref().and_then(|exif| Exif::parse(exif).ok())
+    }
+
+    /// The colour profile, none if there's no profile or it can't be parsed
+    pub fn parse_icc_profile(&self) -> Option<IccProfile> {
+        self.icc_profile.as_deref().and_then(|profile| IccProfile::parse(profile).ok())
+    }
+
+    /// How the pixels should be turned to display upright, from the exif orientation
+    pub fn orientation(&self) -> Orientation {
+        self.parse_exif().map(|exif| exif.orientation()).unwrap_or(Orientation::Normal)
+    }
+
+    /// Record that the pixels have been turned upright, see `io::apply_orientation`
+    pub fn mark_upright(&mut self) {
+        if let Some(exif) = &mut self.exif {
+            exif::reset_orientation(exif);
+        }
+    }
+
+    /// Set the metadata on the header of a png about to be written
+    pub(crate) fn apply_to_png(&self, info: &mut png::Info<'_>) {
+        info.exif_metadata = self.exif.clone().map(Cow::Owned);
+        info.icc_profile = self.icc_profile.clone().map(Cow::Owned);
+
+        for (keyword, text) in &self.text {
+            if text.is_ascii() {
+                info.uncompressed_latin1_text.push(TEXtChunk::new(keyword.clone(), text.clone()));
+            } else {
+                info.utf8_text.push(ITXtChunk::new(keyword.clone(), text.clone()));
+            }
+        }
+    }
+
+    /// Insert the metadata into an encoded jpeg as segments following its header
+    pub(crate) fn insert_into_jpeg(&self, jpeg: &[u8]) -> Vec<u8> {
+        let mut segments = Vec::new();
+
+        if let Some(exif) = self.exif.as_ref().filter(|exif| exif.len() + JPEG_EXIF_HEADER.len() <= JPEG_SEGMENT_MAX) {
+            push_jpeg_segment(&mut segments, 0xe1, &[JPEG_EXIF_HEADER, exif]);
+        }
+
+        if let Some(profile) = &self.icc_profile {
+            let chunks: Vec<&[u8]> = profile.chunks(JPEG_SEGMENT_MAX - JPEG_ICC_HEADER.len() - 2).collect();
+            for (i, chunk) in chunks.iter().enumerate() {
+                push_jpeg_segment(&mut segments, 0xe2, &[JPEG_ICC_HEADER, &[i as u8 + 1, chunks.len() as u8], chunk]);
+            }
+        }
+
+        for (_, text) in &self.text {
+            let text = &text.as_bytes()[..text.len().min(JPEG_SEGMENT_MAX)];
+            push_jpeg_segment(&mut segments, 0xfe, &[text]);
+        }
+
+        // After the start of image marker and the encoder's JFIF segment, if it wrote one
+        let mut offset = 2;
+        if jpeg.len() > 6 && jpeg[2..4] == [0xff, 0xe0] {
+            offset += 2 + u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize;
+        }
+
+        let mut output = Vec::with_capacity(jpeg.len() + segments.len());
+        output.extend_from_slice(&jpeg[..offset]);
+        output.extend_from_slice(&segments);
+        output.extend_from_slice(&jpeg[offset..]);
+
+        output
+    }
+}
+
+fn read_png(data: &[u8]) -> Result<Metadata> {
+    let reader = png::Decoder::new(data).read_info()?;
+    let info = reader.info();
+
+    let mut text = Vec::new();
+    for chunk in &info.uncompressed_latin1_text {
+        text.push((chunk.keyword.clone(), chunk.text.clone()));
+    }
+    for chunk in &info.compressed_latin1_text {
+        text.push((chunk.keyword.clone(), chunk.get_text()?));
+    }
+    for chunk in &info.utf8_text {
+        text.push((chunk.keyword.clone(), chunk.get_text()?));
+    }
+
+    // The decoder doesn't read eXIf chunks, only the encoder writes them
+    let exif = png_chunks(data).into_iter().find(|(name, _)| name == b"eXIf").map(|(_, chunk)| chunk.to_vec());
+
+    Ok(Metadata {
+        exif,
+        icc_profile: info.icc_profile.as_ref().map(|profile| profile.to_vec()),
+        text,
+    })
+}
+
+/// Walk the segments before the compressed data, collecting exif, icc profile chunks (which may
+/// be split over several segments) and comments
+fn read_jpeg(data: &[u8]) -> Metadata {
+    let mut metadata = Metadata::default();
+    let mut icc_chunks: Vec<(u8, &[u8])> = Vec::new();
+
+    for (marker, payload) in jpeg_segments(data) {
+        match marker {
+            0xe1 if payload.starts_with(JPEG_EXIF_HEADER) && metadata.exif.is_none() => {
+                metadata.exif = Some(payload[JPEG_EXIF_HEADER.len()..].to_vec());
+            }
+            0xe2 if payload.starts_with(JPEG_ICC_HEADER) && payload.len() >= JPEG_ICC_HEADER.len() + 2 => {
+                let sequence = payload[JPEG_ICC_HEADER.len()];
+                icc_chunks.push((sequence, &payload[JPEG_ICC_HEADER.len() + 2..]));
+            }
+            0xfe => metadata.text.push(("Comment".to_string(), String::from_utf8_lossy(payload).into_owned())),
+            _ => {}
+        }
+    }
+
+    if !icc_chunks.is_empty() {
+        icc_chunks.sort_by_key(|(sequence, _)| *sequence);
+        metadata.icc_profile = Some(icc_chunks.iter().flat_map(|(_, chunk)| chunk.iter().copied()).collect());
+    }
+
+    metadata
+}
+
+/// The name and data of each png chunk up to the image data
+fn png_chunks(data: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut chunks = Vec::new();
+    let mut i = 8;
+
+    while i + 8 <= data.len() {
+        let length = u32::from_be_bytes(data[i..i + 4].try_into().unwrap()) as usize;
+        let name: [u8; 4] = data[i + 4..i + 8].try_into().unwrap();
+        if &name == b"IDAT" || length > data.len() - i - 8 {
+            break;
+        }
+        chunks.push((name, &data[i + 8..i + 8 + length]));
+        // Length, name and crc around the data
+        i += 12 + length;
+    }
+
+    chunks
+}
+
+/// The marker and payload of each jpeg segment up to the start of scan
+fn jpeg_segments(data: &[u8]) -> Vec<(u8, &[u8])> {
+    let mut segments = Vec::new();
+    let mut i = 2;
+
+    while i + 4 <= data.len() && data[i] == 0xff {
+        let marker = data[i + 1];
+        match marker {
+            // Fill bytes before a marker
+            0xff => {
+                i += 1;
+                continue;
+            }
+            // Markers without a payload
+            0x01 | 0xd0..=0xd7 => {
+                i += 2;
+                continue;
+            }
+            0xda | 0xd9 => break,
+            _ => {}
+        }
+
+        let length = u16::from_be_bytes([data[i + 2], data[i + 3]]) as usize;
+        if length < 2 || i + 2 + length > data.len() {
+            break;
+        }
+        segments.push((marker, &data[i + 4..i + 2 + length]));
+        i += 2 + length;
+    }
+
+    segments
+}
+
+fn push_jpeg_segment(output: &mut Vec<u8>, marker: u8, parts: &[&[u8]]) {
+    let length: usize = parts.iter().map(|part| part.len()).sum::<usize>() + 2;
+
+    output.extend_from_slice(&[0xff, marker]);
+    output.extend_from_slice(&(length as u16).to_be_bytes());
+    for part in parts {
+        output.extend_from_slice(part);
+    }
+}
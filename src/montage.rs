@@ -0,0 +1,217 @@
+//! Contact sheets: images tiled into a grid of equal cells, each scaled to fit its cell and
+//! optionally captioned underneath, e.g. to compare the outputs of different filters side by side.
+//! Before and after composites of a pair of images, for reviewing a single filter's result.
+
+use image::imageops::{overlay, resize, FilterType};
+
+use crate::text::{draw_text, text_size};
+use crate::ImageBuffer;
+
+/// Cell width from which captions are drawn at twice the size
+const LARGE_CAPTION_WIDTH: u32 = 240;
+
+/// Width in pixels of the line between before and after
+const DIVIDER_WIDTH: u32 = 2;
+
+/// How `compare_visual` puts the before and after images together
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Comparison {
+    /// Before on the left and after on the right, each whole
+    SideBySide,
+    /// One image, before left of a vertical line at this fraction of the width and after right
+    Split(f32),
+    /// Squares of this many pixels alternating between before and after
+    Checkerboard(u32),
+}
+
+impl Comparison {
+    /// The comparison of a name, with a split down the middle and 32 pixel squares
+    pub fn from_name(name: &str) -> Option<Comparison> {
+        match name {
+            "side-by-side" => Some(Comparison::SideBySide),
+            "split" => Some(Comparison::Split(0.5)),
+            "checkerboard" => Some(Comparison::Checkerboard(32)),
+            _ => None,
+        }
+    }
+}
+
+/// How `Montage::compose` lays out the grid
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Montage {
+    columns: Option<u32>,
+    cell_size: Option<(u32, u32)>,
+    padding: u32,
+    background: image::Rgba<u8>,
+    captions: bool,
+}
+
+impl Default for Montage {
+    fn default() -> Montage {
+        Montage { columns: None, cell_size: None, padding: 8, background: image::Rgba([255, 255, 255, 255]), captions: true }
+    }
+}
+
+impl Montage {
+    pub fn new() -> Montage {
+        Montage::default()
+    }
+
+    /// Cells across each row, by default as near square a grid as holds the images
+    pub fn with_columns(mut self, columns: u32) -> Montage {
+        self.columns = Some(columns.max(1));
+        self
+    }
+
+    /// Size of each cell, not counting its caption, by default the largest image's
+    pub fn with_cell_size(mut self, width: u32, height: u32) -> Montage {
+        self.cell_size = Some((width.max(1), height.max(1)));
+        self
+    }
+
+    /// Pixels around the grid and between its cells
+    pub fn with_padding(mut self, padding: u32) -> Montage {
+        self.padding = padding;
+        self
+    }
+
+    /// Colour behind and between the cells, showing through transparent images
+    pub fn with_background(mut self, background: image::Rgba<u8>) -> Montage {
+        self.background = background;
+        self
+    }
+
+    /// Whether to write each image's caption below its cell
+    pub fn with_captions(mut self, captions: bool) -> Montage {
+        self.captions = captions;
+        self
+    }
+
+    /// Tile images into a grid in reading order, each scaled to fit its cell keeping its aspect
+    /// ratio and centred in it. Captions too wide for the cell are cut short with `...`.
+    ///
+    /// # Arguments
+    ///
+    /// * `images`: Images to tile
+    /// * `captions`: Caption of each image, e.g. its file name, missing ones left blank
+    ///
+    /// returns: ImageBuffer
+    pub fn compose(&self, images: &[ImageBuffer], captions: &[&str]) -> ImageBuffer {
+        let count = images.len().max(1) as u32;
+        let columns = self.columns.unwrap_or_else(|| (count as f32).sqrt().ceil() as u32).min(count);
+        let rows = count.div_ceil(columns);
+        let (cell_width, cell_height) = self.cell_size.unwrap_or_else(|| {
+            let width = images.iter().map(|image| image.width()).max().unwrap_or(1).max(1);
+            let height = images.iter().map(|image| image.height()).max().unwrap_or(1).max(1);
+            (width, height)
+        });
+
+        let caption_scale = if cell_width >= LARGE_CAPTION_WIDTH { 2 } else { 1 };
+        let caption_height = if self.captions { text_size("", caption_scale).1 + 2 * caption_scale } else { 0 };
+        let (step_x, step_y) = (cell_width + self.padding, cell_height + caption_height + self.padding);
+
+        let mut output = ImageBuffer::from_pixel(columns * step_x + self.padding, rows * step_y + self.padding, self.background);
+        let text_colour = caption_colour(self.background);
+
+        for (i, image) in images.iter().enumerate() {
+            let (left, top) = (self.padding + (i as u32 % columns) * step_x, self.padding + (i as u32 / columns) * step_y);
+
+            if image.width() > 0 && image.height() > 0 {
+                let scale = (cell_width as f32 / image.width() as f32).min(cell_height as f32 / image.height() as f32);
+                let (width, height) = (((image.width() as f32 * scale).round() as u32).clamp(1, cell_width), ((image.height() as f32 * scale).round() as u32).clamp(1, cell_height));
+                let fitted = if (width, height) == image.dimensions() { image.clone() } else { resize(image, width, height, FilterType::Lanczos3) };
+                overlay(&mut output, &fitted, (left + (cell_width - width) / 2) as i64, (top + (cell_height - height) / 2) as i64);
+            }
+
+            if let Some(caption) = captions.get(i).filter(|_| self.captions) {
+                let caption = fit_caption(caption, cell_width, caption_scale);
+                let width = text_size(&caption, caption_scale).0;
+                draw_text(&mut output, (left + cell_width.saturating_sub(width) / 2) as i64, (top + cell_height + caption_scale) as i64, &caption, text_colour, caption_scale);
+            }
+        }
+
+        output
+    }
+}
+
+/// Black text on light backgrounds and white on dark
+fn caption_colour(background: image::Rgba<u8>) -> image::Rgba<u8> {
+    let luma = 0.299 * background[0] as f32 + 0.587 * background[1] as f32 + 0.114 * background[2] as f32;
+    if luma > 127. {
+        image::Rgba([0, 0, 0, 255])
+    } else {
+        image::Rgba([255, 255, 255, 255])
+    }
+}
+
+/// The caption, cut short with `...` if it's wider than the cell
+fn fit_caption(caption: &str, width: u32, scale: u32) -> String {
+    if text_size(caption, scale).0 <= width {
+        return caption.to_string();
+    }
+
+    let mut characters: Vec<char> = caption.chars().collect();
+    while !characters.is_empty() && text_size(&format!("{}...", characters.iter().collect::<String>()), scale).0 > width {
+        characters.pop();
+    }
+    format!("{}...", characters.iter().collect::<String>())
+}
+
+/// Put a before and after image together to review a change at a glance. Side by side and
+/// split composites are labelled `before` and `after` in their top corners.
+///
+/// # Arguments
+///
+/// * `before`: Image before the change
+/// * `after`: Image after the change, resized to the before image if they differ in size
+/// * `comparison`: How to put them together
+///
+/// returns: ImageBuffer
+pub fn compare_visual(before: &ImageBuffer, after: &ImageBuffer, comparison: Comparison) -> ImageBuffer {
+    let (width, height) = before.dimensions();
+    let after = if after.dimensions() == (width, height) { after.clone() } else { resize(after, width, height, FilterType::Triangle) };
+    let divider = image::Rgba([255, 255, 255, 255]);
+
+    match comparison {
+        Comparison::SideBySide => {
+            let mut output = ImageBuffer::from_pixel(2 * width + DIVIDER_WIDTH, height, divider);
+            overlay(&mut output, before, 0, 0);
+            overlay(&mut output, &after, (width + DIVIDER_WIDTH) as i64, 0);
+            label(&mut output, 0, "before");
+            label(&mut output, 2 * width + DIVIDER_WIDTH, "after");
+            output
+        }
+        Comparison::Split(position) => {
+            let split = (position.clamp(0., 1.) * width as f32).round() as u32;
+            let mut output = ImageBuffer::from_fn(width, height, |x, y| if x < split { *before.get_pixel(x, y) } else { *after.get_pixel(x, y) });
+            for y in 0..height {
+                for x in split.saturating_sub(DIVIDER_WIDTH / 2)..(split + DIVIDER_WIDTH / 2).min(width) {
+                    output.put_pixel(x, y, divider);
+                }
+            }
+            label(&mut output, 0, "before");
+            label(&mut output, width, "after");
+            output
+        }
+        Comparison::Checkerboard(size) => {
+            let size = size.max(1);
+            ImageBuffer::from_fn(width, height, |x, y| if (x / size + y / size).is_multiple_of(2) { *before.get_pixel(x, y) } else { *after.get_pixel(x, y) })
+        }
+    }
+}
+
+/// Write a label in white on a black box in the top corner of an image, the left corner if
+/// `right` is 0 and otherwise the right corner ending there
+fn label(image: &mut ImageBuffer, right: u32, text: &str) {
+    let scale = if image.height() >= 2 * LARGE_CAPTION_WIDTH { 2 } else { 1 };
+    let (text_width, text_height) = text_size(text, scale);
+    let (box_width, box_height) = (text_width + 4 * scale, text_height + 4 * scale);
+    let left = if right == 0 { 0 } else { right.saturating_sub(box_width) };
+
+    for y in 0..box_height.min(image.height()) {
+        for x in left..(left + box_width).min(image.width()) {
+            image.put_pixel(x, y, image::Rgba([0, 0, 0, 255]));
+        }
+    }
+    draw_text(image, (left + 2 * scale) as i64, (2 * scale) as i64, text, image::Rgba([255, 255, 255, 255]), scale);
+}
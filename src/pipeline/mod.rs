@@ -0,0 +1,309 @@
+//! Pipelines: an ordered list of operations, either chained together in code with the builder
+//! methods or read from a json, toml or yaml file so that multi-step processing is reproducible.
+
+mod operation;
+mod plan;
+
+pub use operation::Operation;
+pub use plan::{Plan, PointLut, Stage};
+
+use std::fs;
+use std::path::Path;
+
+use ndarray::Array2;
+
+use crate::error::{Error, Result};
+use crate::grading::SplitToning;
+use crate::greyscale::Greyscale;
+use crate::memory::MemoryBudget;
+use crate::progress::Progress;
+use crate::timing::timed;
+use crate::tiling::{process_file_streaming, process_tiled, process_tiled_with_progress};
+use crate::value::{json, toml, yaml, Value};
+use crate::ImageBuffer;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipelineFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl PipelineFormat {
+    /// Pick the format from a file extension
+    pub fn from_path(path: &Path) -> Result<PipelineFormat> {
+        let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("").to_lowercase();
+
+        match extension.as_str() {
+            "json" => Ok(PipelineFormat::Json),
+            "toml" => Ok(PipelineFormat::Toml),
+            "yaml" | "yml" => Ok(PipelineFormat::Yaml),
+            _ => Err(Error::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pipeline {
+    operations: Vec<Operation>,
+}
+
+impl Pipeline {
+    /// Start an empty pipeline, operations are then chained on with the builder methods:
+    ///
+    /// ```no_run
+    /// use rust_image_processing::{load_image, Pipeline};
+    ///
+    /// let input = load_image("./images/houseTest.jpg".to_string());
+    /// let output = Pipeline::new().contrast(4.).median_filter(1).edge_detect().run(&input);
+    /// ```
+    pub fn new() -> Pipeline {
+        Pipeline::default()
+    }
+
+    pub fn from_operations(operations: Vec<Operation>) -> Pipeline {
+        Pipeline { operations }
+    }
+
+    /// Read a pipeline file, with the format picked from the file extension
+    ///
+    /// # Arguments
+    ///
+    /// * `path`: Path to a .json, .toml, .yaml or .yml file
+    ///
+    /// returns: Result<Pipeline>
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Pipeline> {
+        let path = path.as_ref();
+        let format = PipelineFormat::from_path(path)?;
+        let source = fs::read_to_string(path)?;
+
+        Pipeline::parse(&source, format)
+    }
+
+    /// Parse a pipeline description. The document is either a list of steps, or a table with
+    /// the list of steps under `steps`, e.g. in toml:
+    ///
+    /// ```toml
+    /// [[steps]]
+    /// op = "contrast"
+    /// value = 4.0
+    ///
+    /// [[steps]]
+    /// op = "median_filter"
+    /// window = 2
+    /// ```
+    ///
+    /// # Arguments
+    ///
+    /// * `source`: Text of the pipeline description
+    /// * `format`: Format the text is written in
+    ///
+    /// returns: Result<Pipeline>
+    pub fn parse(source: &str, format: PipelineFormat) -> Result<Pipeline> {
+        let document = match format {
+            PipelineFormat::Json => json::parse(source)?,
+            PipelineFormat::Toml => toml::parse(source)?,
+            PipelineFormat::Yaml => yaml::parse(source)?,
+        };
+
+        Pipeline::from_value(&document)
+    }
+
+    pub fn from_value(document: &Value) -> Result<Pipeline> {
+        let steps = match document {
+            Value::Array(steps) => steps.as_slice(),
+            Value::Table(_) => match document.get("steps") {
+                Some(Value::Array(steps)) => steps.as_slice(),
+                Some(_) => return Err(Error::InvalidParameter { operation: "pipeline".to_string(), parameter: "steps".to_string() }),
+                None => &[],
+            },
+            Value::Null => &[],
+            _ => return Err(Error::InvalidParameter { operation: "pipeline".to_string(), parameter: "steps".to_string() }),
+        };
+
+        let operations = steps.iter().map(Operation::from_value).collect::<Result<Vec<_>>>()?;
+
+        Ok(Pipeline { operations })
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.operations
+    }
+
+    /// Append an operation to the end of the pipeline
+    pub fn then(mut self, operation: Operation) -> Pipeline {
+        self.operations.push(operation);
+        self
+    }
+
+    pub fn adjust_brightness(self, value: i32) -> Pipeline {
+        self.then(Operation::AdjustBrightness(value))
+    }
+
+    pub fn adjust_contrast(self, value: f32) -> Pipeline {
+        self.then(Operation::AdjustContrast(value))
+    }
+
+    pub fn adjust_gamma(self, gamma: f32) -> Pipeline {
+        self.then(Operation::AdjustGamma(gamma))
+    }
+
+    pub fn apply_lut(self, table: [u8; 256]) -> Pipeline {
+        self.then(Operation::ApplyLut(Box::new(table)))
+    }
+
+    pub fn contrast(self, value: f32) -> Pipeline {
+        self.then(Operation::Contrast(value))
+    }
+
+    pub fn sharpen(self, value: f32) -> Pipeline {
+        self.then(Operation::Sharpen(value))
+    }
+
+    pub fn adaptive_sharpen(self, amount: f32, radius: f32, masking: f32) -> Pipeline {
+        self.then(Operation::AdaptiveSharpen { amount, radius, masking })
+    }
+
+    pub fn bilinear_filter(self) -> Pipeline {
+        self.then(Operation::BilinearFilter)
+    }
+
+    pub fn gaussian_blur(self) -> Pipeline {
+        self.then(Operation::GaussianBlur)
+    }
+
+    pub fn median_filter(self, window: u32) -> Pipeline {
+        self.then(Operation::MedianFilter(window))
+    }
+
+    pub fn edge_detect(self) -> Pipeline {
+        self.then(Operation::EdgeDetect)
+    }
+
+    pub fn x_grad(self) -> Pipeline {
+        self.then(Operation::XGrad)
+    }
+
+    pub fn y_grad(self) -> Pipeline {
+        self.then(Operation::YGrad)
+    }
+
+    pub fn apply_matrix(self, matrix: Array2<f32>) -> Pipeline {
+        self.then(Operation::ApplyMatrix(matrix))
+    }
+
+    pub fn greyscale(self, conversion: Greyscale) -> Pipeline {
+        self.then(Operation::Greyscale(conversion))
+    }
+
+    pub fn channel_mix(self, matrix: [[f32; 3]; 3]) -> Pipeline {
+        self.then(Operation::ChannelMix(matrix))
+    }
+
+    pub fn split_tone(self, toning: SplitToning) -> Pipeline {
+        self.then(Operation::SplitTone(toning))
+    }
+
+    /// Work out the passes needed to run the pipeline, adjacent point operations (brightness,
+    /// contrast, gamma and lookup tables) are fused into a single pass over the image
+    pub fn plan(&self) -> Plan<'_> {
+        Plan::new(&self.operations)
+    }
+
+    /// Run each operation in order, feeding the output of one into the next
+    ///
+    /// # Arguments
+    ///
+    /// * `input`: ImageBuffer to process
+    ///
+    /// returns: ImageBuffer
+    pub fn run(&self, input: &ImageBuffer) -> ImageBuffer {
+        self.plan().run(input)
+    }
+
+    /// Run the pipeline over raw 8 bit rgba pixel data, such as a browser canvas's `ImageData`,
+    /// without touching the filesystem
+    ///
+    /// # Arguments
+    ///
+    /// * `data`: Pixels row by row, 4 bytes per pixel
+    /// * `width`: Image width in pixels
+    /// * `height`: Image height in pixels
+    ///
+    /// returns: Result<Vec<u8>>, the processed pixels in the same layout
+    pub fn run_rgba(&self, data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+        let expected = width as usize * height as usize * 4;
+        if data.len() != expected {
+            return Err(Error::BufferSize { expected, actual: data.len() });
+        }
+
+        let input: ImageBuffer = image::ImageBuffer::from_raw(width, height, data.to_vec()).unwrap();
+
+        Ok(self.run(&input).into_raw())
+    }
+
+    /// As `run`, reporting progress and stopping early with `Error::Cancelled` if cancelled
+    pub fn run_with_progress(&self, input: &ImageBuffer, progress: &Progress) -> Result<ImageBuffer> {
+        self.plan().run_with_progress(input, progress)
+    }
+
+    /// The halo needed to run the whole pipeline on tiles, the sum of each operation's halo
+    pub fn halo(&self) -> u32 {
+        self.operations.iter().map(|operation| operation.halo()).sum()
+    }
+
+    /// Run the pipeline over the image in tiles, giving the same result as `run` while only
+    /// working on one tile (plus its halo) at a time, or on the whole image for a tile size of 0
+    pub fn run_tiled(&self, input: &ImageBuffer, tile_size: u32) -> ImageBuffer {
+        process_tiled(input, tile_size, self.halo(), |tile| self.run(tile))
+    }
+
+    /// As `run_tiled`, reporting progress after each tile and stopping early if cancelled
+    pub fn run_tiled_with_progress(&self, input: &ImageBuffer, tile_size: u32, progress: &Progress) -> Result<ImageBuffer> {
+        process_tiled_with_progress(input, tile_size, self.halo(), progress, |tile| self.run(tile))
+    }
+
+    /// Run the pipeline within a memory budget, whole if the image and its working copies fit
+    /// and otherwise in tiles sized to fit (see `MemoryBudget::tile_size`)
+    pub fn run_with_budget(&self, input: &ImageBuffer, budget: MemoryBudget, progress: &Progress) -> Result<ImageBuffer> {
+        match budget.tile_size(input.width(), input.height(), self.halo()) {
+            Some(tile_size) => self.run_tiled_with_progress(input, tile_size, progress),
+            None => self.run_with_progress(input, progress),
+        }
+    }
+
+    /// Run the pipeline from one image file to another, streaming the image through in strips
+    /// of rows when the formats allow (see `tiling::process_file_streaming`)
+    pub fn run_file_streaming(&self, input_path: &Path, output_path: &Path, strip_height: u32, progress: &Progress) -> Result<()> {
+        process_file_streaming(input_path, output_path, strip_height, self.halo(), progress, |strip| self.run(strip))
+    }
+
+    /// Run the pipeline, calling `inspect` with each operation and the image it produced. Every
+    /// operation gets its own pass here so that each intermediate image exists
+    ///
+    /// # Arguments
+    ///
+    /// * `input`: ImageBuffer to process
+    /// * `inspect`: Called after every operation with the operation and its output
+    ///
+    /// returns: ImageBuffer
+    pub fn run_with(&self, input: &ImageBuffer, mut inspect: impl FnMut(&Operation, &ImageBuffer)) -> ImageBuffer {
+        let mut output = input.clone();
+
+        for operation in &self.operations {
+            output = timed(operation.name(), || operation.apply(&output));
+            inspect(operation, &output);
+        }
+
+        output
+    }
+
+    /// Run the pipeline keeping the output of every operation, the last entry being the final
+    /// result (or no entries for an empty pipeline)
+    pub fn run_with_intermediates(&self, input: &ImageBuffer) -> Vec<ImageBuffer> {
+        let mut intermediates = Vec::with_capacity(self.operations.len());
+        self.run_with(input, |_, output| intermediates.push(output.clone()));
+
+        intermediates
+    }
+}
@@ -0,0 +1,307 @@
+use image::imageops::contrast;
+use ndarray::Array2;
+
+use crate::arithmetic::{adjust_brightness, adjust_brightness_into, adjust_contrast, adjust_contrast_into, adjust_gamma, adjust_gamma_into, apply_lut, apply_lut_into};
+use crate::error::{Error, Result};
+use crate::filters::{adaptive_sharpen, apply_matrix, apply_matrix_into, apply_matrix_with_progress, bilinear_filter, edge_detect, gaussian_blur, median_filter, median_filter_into, median_filter_with_progress, sharpen, x_grad, y_grad};
+use crate::grading::{SplitToning, ToneRange};
+use crate::greyscale::{channel_mix, greyscale, Greyscale};
+use crate::progress::Progress;
+use crate::value::Value;
+use crate::ImageBuffer;
+
+/// A single step of a pipeline, each variant maps onto one of the crate's image functions
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation {
+    AdjustBrightness(i32),
+    AdjustContrast(f32),
+    AdjustGamma(f32),
+    ApplyLut(Box<[u8; 256]>),
+    Contrast(f32),
+    Sharpen(f32),
+    AdaptiveSharpen { amount: f32, radius: f32, masking: f32 },
+    BilinearFilter,
+    GaussianBlur,
+    MedianFilter(u32),
+    EdgeDetect,
+    XGrad,
+    YGrad,
+    ApplyMatrix(Array2<f32>),
+    Greyscale(Greyscale),
+    ChannelMix([[f32; 3]; 3]),
+    SplitTone(SplitToning),
+}
+
+impl Operation {
+    /// The name used for this operation in pipeline files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Operation::AdjustBrightness(_) => "adjust_brightness",
+            Operation::AdjustContrast(_) => "adjust_contrast",
+            Operation::AdjustGamma(_) => "adjust_gamma",
+            Operation::ApplyLut(_) => "apply_lut",
+            Operation::Contrast(_) => "contrast",
+            Operation::Sharpen(_) => "sharpen",
+            Operation::AdaptiveSharpen { .. } => "adaptive_sharpen",
+            Operation::BilinearFilter => "bilinear_filter",
+            Operation::GaussianBlur => "gaussian_blur",
+            Operation::MedianFilter(_) => "median_filter",
+            Operation::EdgeDetect => "edge_detect",
+            Operation::XGrad => "x_grad",
+            Operation::YGrad => "y_grad",
+            Operation::ApplyMatrix(_) => "apply_matrix",
+            Operation::Greyscale(_) => "greyscale",
+            Operation::ChannelMix(_) => "channel_mix",
+            Operation::SplitTone(_) => "split_tone",
+        }
+    }
+
+    /// How far in pixels the operation reads from the pixel it writes, which tiled processing
+    /// needs as the halo around each tile
+    pub fn halo(&self) -> u32 {
+        match self {
+            Operation::Sharpen(_) | Operation::BilinearFilter => 2,
+            // The detail's blur, the blur of its energy, then the gradient and neighbour range
+            Operation::AdaptiveSharpen { radius, .. } => (3. * radius.max(0.1)).ceil() as u32 + (3. * radius.max(1.)).ceil() as u32 + 2,
+            Operation::GaussianBlur => 4,
+            Operation::MedianFilter(window) => *window,
+            Operation::EdgeDetect => 3,
+            Operation::XGrad | Operation::YGrad => 1,
+            Operation::ApplyMatrix(matrix) => (matrix.shape()[0].max(matrix.shape()[1]) as u32).saturating_sub(1),
+            _ => 0,
+        }
+    }
+
+    pub fn apply(&self, input: &ImageBuffer) -> ImageBuffer {
+        match self {
+            Operation::AdjustBrightness(value) => adjust_brightness(input, *value),
+            Operation::AdjustContrast(value) => adjust_contrast(input, *value),
+            Operation::AdjustGamma(gamma) => adjust_gamma(input, *gamma),
+            Operation::ApplyLut(table) => apply_lut(input, table),
+            Operation::Contrast(value) => contrast(input, *value),
+            Operation::Sharpen(value) => sharpen(input, *value),
+            Operation::AdaptiveSharpen { amount, radius, masking } => adaptive_sharpen(input, *amount, *radius, *masking),
+            Operation::BilinearFilter => bilinear_filter(input),
+            Operation::GaussianBlur => gaussian_blur(input),
+            Operation::MedianFilter(window) => median_filter(input, *window),
+            Operation::EdgeDetect => edge_detect(input),
+            Operation::XGrad => x_grad(input),
+            Operation::YGrad => y_grad(input),
+            Operation::ApplyMatrix(matrix) => apply_matrix(input, matrix.clone()),
+            Operation::Greyscale(conversion) => greyscale(input, *conversion),
+            Operation::ChannelMix(matrix) => channel_mix(input, *matrix),
+            Operation::SplitTone(toning) => toning.apply(input),
+        }
+    }
+
+    /// Apply the operation writing into an existing buffer the same size as `input`, the
+    /// composite operations (sharpening, edge detection, ...) still allocate their own working
+    /// images
+    pub fn apply_into(&self, input: &ImageBuffer, output: &mut ImageBuffer) {
+        match self {
+            Operation::AdjustBrightness(value) => adjust_brightness_into(input, *value, output),
+            Operation::AdjustContrast(value) => adjust_contrast_into(input, *value, output),
+            Operation::AdjustGamma(gamma) => adjust_gamma_into(input, *gamma, output),
+            Operation::ApplyLut(table) => apply_lut_into(input, table, output),
+            Operation::MedianFilter(window) => median_filter_into(input, *window, output),
+            Operation::ApplyMatrix(matrix) => apply_matrix_into(input, matrix, output),
+            _ => *output = self.apply(input),
+        }
+    }
+
+    /// As `apply_into`, reporting progress and stopping early if cancelled. Convolutions and
+    /// median filters report as they go, other operations only once they are done.
+    pub fn apply_with_progress(&self, input: &ImageBuffer, output: &mut ImageBuffer, progress: &Progress) -> Result<()> {
+        progress.check()?;
+
+        match self {
+            Operation::MedianFilter(window) => median_filter_with_progress(input, *window, output, progress)?,
+            Operation::ApplyMatrix(matrix) => apply_matrix_with_progress(input, matrix, output, progress)?,
+            _ => {
+                self.apply_into(input, output);
+                progress.report(1.);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build an operation from a pipeline file step, a table holding the operation name under
+    /// `op` alongside its parameters, e.g. `{ op = "median_filter", window = 2 }`
+    ///
+    /// # Arguments
+    ///
+    /// * `step`: Table describing the step
+    ///
+    /// returns: Result<Operation>
+    pub fn from_value(step: &Value) -> Result<Operation> {
+        let name = match step.get("op") {
+            Some(Value::String(name)) => name.as_str(),
+            Some(_) => return Err(invalid("step", "op")),
+            None => return Err(missing("step", "op")),
+        };
+
+        let operation = match name {
+            "adjust_brightness" => Operation::AdjustBrightness(integer(step, name, "value")?),
+            "adjust_contrast" => Operation::AdjustContrast(number(step, name, "value")? as f32),
+            "adjust_gamma" => Operation::AdjustGamma(number(step, name, "gamma")? as f32),
+            "apply_lut" => Operation::ApplyLut(lut(step, name, "table")?),
+            "contrast" => Operation::Contrast(number(step, name, "value")? as f32),
+            "sharpen" => Operation::Sharpen(number(step, name, "value")? as f32),
+            "adaptive_sharpen" => Operation::AdaptiveSharpen {
+                amount: number(step, name, "amount")? as f32,
+                radius: optional_number(step, name, "radius")?.unwrap_or(1.) as f32,
+                masking: optional_number(step, name, "masking")?.unwrap_or(0.) as f32,
+            },
+            "bilinear_filter" => Operation::BilinearFilter,
+            "gaussian_blur" => Operation::GaussianBlur,
+            "median_filter" => Operation::MedianFilter(u32::try_from(integer(step, name, "window")?).map_err(|_| invalid(name, "window"))?),
+            "edge_detect" => Operation::EdgeDetect,
+            "x_grad" => Operation::XGrad,
+            "y_grad" => Operation::YGrad,
+            "apply_matrix" => Operation::ApplyMatrix(matrix(step, name, "matrix")?),
+            "greyscale" => Operation::Greyscale(greyscale_conversion(step, name)?),
+            "channel_mix" => Operation::ChannelMix(mix_matrix(step, name, "matrix")?),
+            "split_tone" => Operation::SplitTone(split_toning(step, name)?),
+            _ => return Err(Error::UnknownOperation(name.to_string())),
+        };
+
+        Ok(operation)
+    }
+}
+
+fn missing(operation: &str, parameter: &str) -> Error {
+    Error::MissingParameter { operation: operation.to_string(), parameter: parameter.to_string() }
+}
+
+fn invalid(operation: &str, parameter: &str) -> Error {
+    Error::InvalidParameter { operation: operation.to_string(), parameter: parameter.to_string() }
+}
+
+fn number(step: &Value, operation: &str, parameter: &str) -> Result<f64> {
+    match step.get(parameter) {
+        Some(value) => value.as_f64().ok_or_else(|| invalid(operation, parameter)),
+        None => Err(missing(operation, parameter)),
+    }
+}
+
+fn optional_number(step: &Value, operation: &str, parameter: &str) -> Result<Option<f64>> {
+    match step.get(parameter) {
+        Some(value) => value.as_f64().map(Some).ok_or_else(|| invalid(operation, parameter)),
+        None => Ok(None),
+    }
+}
+
+fn integer(step: &Value, operation: &str, parameter: &str) -> Result<i32> {
+    let value = number(step, operation, parameter)?;
+
+    if value.fract() != 0. || value < i32::MIN as f64 || value > i32::MAX as f64 {
+        return Err(invalid(operation, parameter));
+    }
+
+    Ok(value as i32)
+}
+
+/// Read a lookup table written as an array of 256 values in [0, 255]
+fn lut(step: &Value, operation: &str, parameter: &str) -> Result<Box<[u8; 256]>> {
+    let values = match step.get(parameter) {
+        Some(value) => value.as_array().ok_or_else(|| invalid(operation, parameter))?,
+        None => return Err(missing(operation, parameter)),
+    };
+
+    if values.len() != 256 {
+        return Err(invalid(operation, parameter));
+    }
+
+    let mut table = Box::new([0; 256]);
+    for (entry, value) in table.iter_mut().zip(values) {
+        match value.as_f64() {
+            Some(value) if (0. ..=255.).contains(&value) && value.fract() == 0. => *entry = value as u8,
+            _ => return Err(invalid(operation, parameter)),
+        }
+    }
+
+    Ok(table)
+}
+
+/// Read a matrix written as an array of equal length rows
+fn matrix(step: &Value, operation: &str, parameter: &str) -> Result<Array2<f32>> {
+    let rows = match step.get(parameter) {
+        Some(value) => value.as_array().ok_or_else(|| invalid(operation, parameter))?,
+        None => return Err(missing(operation, parameter)),
+    };
+
+    let width = rows.first().and_then(|row| row.as_array()).map(|row| row.len()).unwrap_or(0);
+    if width == 0 {
+        return Err(invalid(operation, parameter));
+    }
+
+    let mut values = Vec::with_capacity(rows.len() * width);
+    for row in rows {
+        let row = row.as_array().ok_or_else(|| invalid(operation, parameter))?;
+        if row.len() != width {
+            return Err(invalid(operation, parameter));
+        }
+
+        for value in row {
+            values.push(value.as_f64().ok_or_else(|| invalid(operation, parameter))? as f32);
+        }
+    }
+
+    Array2::from_shape_vec((rows.len(), width), values).map_err(|_| invalid(operation, parameter))
+}
+
+/// Read a greyscale conversion, named under `method` (rec709 by default) or as red, green and
+/// blue `weights`
+fn greyscale_conversion(step: &Value, operation: &str) -> Result<Greyscale> {
+    match (step.get("method"), step.get("weights")) {
+        (Some(method), None) => method.as_str().and_then(Greyscale::from_name).ok_or_else(|| invalid(operation, "method")),
+        (None, Some(_)) => Ok(Greyscale::Mix(mix_matrix_row(step, operation, "weights")?)),
+        (None, None) => Ok(Greyscale::default()),
+        (Some(_), Some(_)) => Err(invalid(operation, "weights")),
+    }
+}
+
+/// Read three numbers, red, green and blue weights
+fn mix_matrix_row(step: &Value, operation: &str, parameter: &str) -> Result<[f32; 3]> {
+    let values = match step.get(parameter) {
+        Some(value) => value.as_array().ok_or_else(|| invalid(operation, parameter))?,
+        None => return Err(missing(operation, parameter)),
+    };
+
+    match values {
+        [r, g, b] => {
+            let weight = |value: &Value| value.as_f64().map(|v| v as f32).ok_or_else(|| invalid(operation, parameter));
+            Ok([weight(r)?, weight(g)?, weight(b)?])
+        }
+        _ => Err(invalid(operation, parameter)),
+    }
+}
+
+/// Read a 3 by 3 channel mixing matrix, a row of weights for each output channel
+fn mix_matrix(step: &Value, operation: &str, parameter: &str) -> Result<[[f32; 3]; 3]> {
+    let matrix = matrix(step, operation, parameter)?;
+    if matrix.shape() != [3, 3] {
+        return Err(invalid(operation, parameter));
+    }
+
+    Ok(std::array::from_fn(|row| std::array::from_fn(|column| matrix[[row, column]])))
+}
+
+/// Read split toning from `shadow_`, `midtone_` and `highlight_` prefixed `hue`, `strength`,
+/// `lift` and `gain`, and `balance`, all optional
+fn split_toning(step: &Value, operation: &str) -> Result<SplitToning> {
+    let range = |prefix: &str| -> Result<ToneRange> {
+        let parameter = |name: &str| optional_number(step, operation, &format!("{}_{}", prefix, name));
+        Ok(ToneRange::new()
+            .with_tint(parameter("hue")?.unwrap_or(0.) as f32, parameter("strength")?.unwrap_or(0.) as f32)
+            .with_lift(parameter("lift")?.unwrap_or(0.) as f32)
+            .with_gain(parameter("gain")?.unwrap_or(1.) as f32))
+    };
+
+    Ok(SplitToning::new()
+        .with_shadows(range("shadow")?)
+        .with_midtones(range("midtone")?)
+        .with_highlights(range("highlight")?)
+        .with_balance(optional_number(step, operation, "balance")?.unwrap_or(0.) as f32))
+}
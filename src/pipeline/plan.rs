@@ -0,0 +1,156 @@
+use crate::arithmetic::gamma_lut;
+use crate::error::Result;
+use crate::pixel::{safe_add, safe_mult};
+use crate::progress::Progress;
+use crate::timing::timed;
+use crate::ImageBuffer;
+
+use super::Operation;
+
+/// Point operations work on each channel value on its own, so any run of them collapses into a
+/// lookup table for the colour channels and one for alpha, applied in a single pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PointLut {
+    colour: [u8; 256],
+    alpha: [u8; 256],
+}
+
+impl PointLut {
+    fn identity() -> PointLut {
+        let mut table = [0; 256];
+        for (i, value) in table.iter_mut().enumerate() {
+            *value = i as u8;
+        }
+
+        PointLut { colour: table, alpha: table }
+    }
+
+    /// The lookup tables for an operation, or None if it is not a point operation
+    pub fn for_operation(operation: &Operation) -> Option<PointLut> {
+        let identity = PointLut::identity();
+
+        let lut = match operation {
+            Operation::AdjustBrightness(value) => PointLut { colour: identity.colour.map(|v| safe_add(v, *value)), ..identity },
+            Operation::AdjustContrast(value) => PointLut { colour: identity.colour.map(|v| safe_mult(v, *value)), ..identity },
+            Operation::AdjustGamma(gamma) => PointLut { colour: gamma_lut(*gamma), ..identity },
+            Operation::ApplyLut(table) => PointLut { colour: **table, ..identity },
+            Operation::Contrast(value) => {
+                // Same maths as image::imageops::contrast, which includes the alpha channel
+                let percent = ((100. + value) / 100.).powi(2);
+                let table = identity.colour.map(|v| ((((v as f32) / 255. - 0.5) * percent + 0.5) * 255.).clamp(0., 255.) as u8);
+                PointLut { colour: table, alpha: table }
+            }
+            _ => return None,
+        };
+
+        Some(lut)
+    }
+
+    /// The lookup tables for applying `self` followed by `next`
+    pub fn then(&self, next: &PointLut) -> PointLut {
+        PointLut {
+            colour: self.colour.map(|v| next.colour[v as usize]),
+            alpha: self.alpha.map(|v| next.alpha[v as usize]),
+        }
+    }
+
+    pub fn apply(&self, input: &ImageBuffer) -> ImageBuffer {
+        let mut output = input.clone();
+        self.apply_in_place(&mut output);
+
+        output
+    }
+
+    pub fn apply_in_place(&self, input: &mut ImageBuffer) {
+        for pixel in input.pixels_mut() {
+            *pixel = image::Rgba([
+                self.colour[pixel[0] as usize],
+                self.colour[pixel[1] as usize],
+                self.colour[pixel[2] as usize],
+                self.alpha[pixel[3] as usize],
+            ]);
+        }
+    }
+}
+
+/// One pass over the image when running a plan
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stage<'a> {
+    /// A run of adjacent point operations fused into one lookup
+    Fused { operations: &'a [Operation], lut: Box<PointLut> },
+    Single(&'a Operation),
+}
+
+/// The passes a pipeline runs as, built from the pipeline's operations when it is run rather
+/// than as each builder method is called
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan<'a> {
+    stages: Vec<Stage<'a>>,
+}
+
+impl<'a> Plan<'a> {
+    pub fn new(operations: &'a [Operation]) -> Plan<'a> {
+        let mut stages = Vec::new();
+        let mut start = 0;
+
+        while start < operations.len() {
+            let mut lut = match PointLut::for_operation(&operations[start]) {
+                Some(lut) => lut,
+                None => {
+                    stages.push(Stage::Single(&operations[start]));
+                    start += 1;
+                    continue;
+                }
+            };
+
+            let mut end = start + 1;
+            while let Some(next) = operations.get(end).and_then(PointLut::for_operation) {
+                lut = lut.then(&next);
+                end += 1;
+            }
+
+            stages.push(Stage::Fused { operations: &operations[start..end], lut: Box::new(lut) });
+            start = end;
+        }
+
+        Plan { stages }
+    }
+
+    pub fn stages(&self) -> &[Stage<'a>] {
+        &self.stages
+    }
+
+    /// Run each stage, point stages work in place and the rest alternate between two buffers,
+    /// so at most two images are allocated however long the pipeline is
+    pub fn run(&self, input: &ImageBuffer) -> ImageBuffer {
+        self.run_with_progress(input, &Progress::none()).unwrap()
+    }
+
+    /// As `run`, with each stage reporting as an equal share of the overall progress
+    pub fn run_with_progress(&self, input: &ImageBuffer, progress: &Progress) -> Result<ImageBuffer> {
+        let mut current = input.clone();
+        let mut scratch: Option<ImageBuffer> = None;
+        let count = self.stages.len() as f32;
+
+        for (i, stage) in self.stages.iter().enumerate() {
+            let stage_progress = progress.part(i as f32 / count, (i + 1) as f32 / count);
+            stage_progress.check()?;
+
+            match stage {
+                Stage::Fused { operations, lut } => {
+                    let names: Vec<&str> = operations.iter().map(|operation| operation.name()).collect();
+                    timed(&format!("fused {}", names.join(" + ")), || lut.apply_in_place(&mut current));
+                    stage_progress.report(1.);
+                }
+                Stage::Single(operation) => {
+                    let (width, height) = current.dimensions();
+                    let output = scratch.get_or_insert_with(|| image::ImageBuffer::new(width, height));
+                    timed(operation.name(), || operation.apply_with_progress(&current, output, &stage_progress))?;
+                    std::mem::swap(&mut current, output);
+                }
+            }
+        }
+
+        Ok(current)
+    }
+}
@@ -0,0 +1,91 @@
+pub fn pixel_sub(pixel_1: image::Rgba<u8>, pixel_2: image::Rgba<u8>) -> image::Rgba<u8> {
+    image::Rgba([
+        safe_add(pixel_1[0], -(pixel_2[0] as i32)),
+        safe_add(pixel_1[1], -(pixel_2[1] as i32)),
+        safe_add(pixel_1[2], -(pixel_2[2] as i32)),
+        pixel_1[3]
+    ])
+}
+
+pub fn pixel_add(pixel_1: image::Rgba<u8>, pixel_2: image::Rgba<u8>) -> image::Rgba<u8> {
+    image::Rgba([
+        safe_add(pixel_1[0], pixel_2[0] as i32),
+        safe_add(pixel_1[1], pixel_2[1] as i32),
+        safe_add(pixel_1[2], pixel_2[2] as i32),
+        pixel_1[3]
+    ])
+}
+
+/// Shift a pixels r,g,b values by a constant value (positive or negative)
+///
+/// # Arguments
+///
+/// * `pixel`: Single  pixel of an image
+/// * `value`: Positive or negative value to shift the pixel by
+///
+/// returns: rgba pixel
+pub fn pixel_shift(pixel: image::Rgba<u8>, value: i32) -> image::Rgba<u8> {
+    image::Rgba([safe_add(pixel[0], value), safe_add(pixel[1], value), safe_add(pixel[2], value), pixel[3]])
+}
+
+/// Scales (multiplies) a pixels r,g,b values by a constant value
+///
+/// # Arguments
+///
+/// * `pixel`: Single  pixel of an image
+/// * `value`: Scale factor of the pixel
+///
+/// returns: rgba pixel
+pub fn pixel_scale(pixel: image::Rgba<u8>, value: f32) -> image::Rgba<u8> {
+    image::Rgba([safe_mult(pixel[0], value), safe_mult(pixel[1], value), safe_mult(pixel[2], value), pixel[3]])
+}
+
+/// Given a u8 and an integer, perform addition in the i32 space but then clamp back to a u8
+///
+/// # Arguments
+///
+/// * `a`: u8 value
+/// * `b`: i32 value
+///
+/// returns: u8
+pub fn safe_add(a: u8, b: i32) -> u8 {
+    let c = (a as i32) + b;
+    let scaled = c.clamp(0, 255);
+
+    scaled as u8
+}
+
+/// Given a u8 and a float, multiply as floats, round to i32 then clamb pack to to a u8
+///
+/// # Arguments
+///
+/// * `a`: u8 value
+/// * `b`: f32 value
+///
+/// returns: u8
+pub fn safe_mult(a: u8, b: f32) -> u8 {
+    let c = ((a as f32) * b) as i32;
+    let scaled = c.clamp(0, 255);
+
+    scaled as u8
+}
+
+/// Parse a colour written in hex as `#rrggbb`, or `#rrggbbaa` with an alpha, the `#` optional
+///
+/// # Arguments
+///
+/// * `colour`: Colour in hex
+///
+/// returns: Option<rgba pixel>, None if it isn't 6 or 8 hex digits
+pub fn parse_colour(colour: &str) -> Option<image::Rgba<u8>> {
+    let hex = colour.strip_prefix('#').unwrap_or(colour);
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut channels = [255; 4];
+    for (channel, i) in channels.iter_mut().zip((0..hex.len()).step_by(2)) {
+        *channel = u8::from_str_radix(&hex[i..i + 2], 16).ok()?;
+    }
+    Some(image::Rgba(channels))
+}
@@ -0,0 +1,159 @@
+//! Single channel floating point images, for the analysis that works on intensity rather than
+//! colour (features, calibration, depth, saliency) and needs values outside 0 to 255
+
+use crate::ImageBuffer;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Plane {
+    pub width: u32,
+    pub height: u32,
+    /// Values row by row
+    pub data: Vec<f32>,
+}
+
+impl Plane {
+    pub fn new(width: u32, height: u32) -> Plane {
+        Plane { width, height, data: vec![0.; width as usize * height as usize] }
+    }
+
+    /// The luma of an image, `0.299 r + 0.587 g + 0.114 b` from 0 to 255
+    pub fn from_luma(input: &ImageBuffer) -> Plane {
+        let data = input.pixels().map(|p| 0.299 * p[0] as f32 + 0.587 * p[1] as f32 + 0.114 * p[2] as f32).collect();
+        Plane { width: input.width(), height: input.height(), data }
+    }
+
+    /// One channel of an image from 0 to 255
+    pub fn from_channel(input: &ImageBuffer, channel: usize) -> Plane {
+        let data = input.pixels().map(|p| p[channel] as f32).collect();
+        Plane { width: input.width(), height: input.height(), data }
+    }
+
+    /// A grey opaque image of the values, clamped to 0 to 255
+    pub fn to_image(&self) -> ImageBuffer {
+        ImageBuffer::from_fn(self.width, self.height, |x, y| {
+            let value = self.get(x, y).round().clamp(0., 255.) as u8;
+            image::Rgba([value, value, value, 255])
+        })
+    }
+
+    /// A grey image of the values stretched so the lowest is black and the highest white
+    pub fn to_normalised_image(&self) -> ImageBuffer {
+        let (min, max) = self.data.iter().fold((f32::MAX, f32::MIN), |(min, max), &v| (min.min(v), max.max(v)));
+        let scale = if max > min { 255. / (max - min) } else { 0. };
+        Plane { data: self.data.iter().map(|v| (v - min) * scale).collect(), ..*self }.to_image()
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> f32 {
+        self.data[y as usize * self.width as usize + x as usize]
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, value: f32) {
+        self.data[y as usize * self.width as usize + x as usize] = value;
+    }
+
+    /// The value at a position that may be outside the plane, taking the nearest edge value
+    pub fn get_clamped(&self, x: i64, y: i64) -> f32 {
+        let x = x.clamp(0, self.width as i64 - 1) as u32;
+        let y = y.clamp(0, self.height as i64 - 1) as u32;
+        self.get(x, y)
+    }
+
+    /// The value interpolated between the four nearest samples, clamped to the edges
+    pub fn sample(&self, x: f32, y: f32) -> f32 {
+        let x = x.clamp(0., (self.width - 1) as f32);
+        let y = y.clamp(0., (self.height - 1) as f32);
+        let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+        let (x1, y1) = ((x0 + 1).min(self.width - 1), (y0 + 1).min(self.height - 1));
+        let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+        let top = self.get(x0, y0) * (1. - tx) + self.get(x1, y0) * tx;
+        let bottom = self.get(x0, y1) * (1. - tx) + self.get(x1, y1) * tx;
+        top * (1. - ty) + bottom * ty
+    }
+
+    pub fn map<F: Fn(f32) -> f32>(&self, f: F) -> Plane {
+        Plane { data: self.data.iter().map(|&v| f(v)).collect(), ..*self }
+    }
+
+    /// Combine two planes of the same size value by value
+    pub fn zip<F: Fn(f32, f32) -> f32>(&self, other: &Plane, f: F) -> Plane {
+        assert_eq!((self.width, self.height), (other.width, other.height), "planes must be the same size");
+        Plane { data: self.data.iter().zip(&other.data).map(|(&a, &b)| f(a, b)).collect(), ..*self }
+    }
+
+    /// Horizontal and vertical central differences, one sided on the border
+    pub fn gradients(&self) -> (Plane, Plane) {
+        let mut dx = Plane::new(self.width, self.height);
+        let mut dy = Plane::new(self.width, self.height);
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let (x, y) = (x as i64, y as i64);
+                dx.set(x as u32, y as u32, (self.get_clamped(x + 1, y) - self.get_clamped(x - 1, y)) / 2.);
+                dy.set(x as u32, y as u32, (self.get_clamped(x, y + 1) - self.get_clamped(x, y - 1)) / 2.);
+            }
+        }
+
+        (dx, dy)
+    }
+
+    /// Gaussian blur as two passes of a sampled kernel reaching three standard deviations,
+    /// clamped to the edges
+    pub fn gaussian_blur(&self, sigma: f32) -> Plane {
+        if sigma <= 0. {
+            return self.clone();
+        }
+
+        let radius = (sigma * 3.).ceil() as i64;
+        let kernel: Vec<f32> = (-radius..=radius).map(|i| (-(i * i) as f32 / (2. * sigma * sigma)).exp()).collect();
+        let total: f32 = kernel.iter().sum();
+        let kernel: Vec<f32> = kernel.iter().map(|k| k / total).collect();
+
+        let mut horizontal = Plane::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let value = kernel.iter().enumerate().map(|(i, k)| k * self.get_clamped(x as i64 + i as i64 - radius, y as i64)).sum();
+                horizontal.set(x, y, value);
+            }
+        }
+
+        let mut output = Plane::new(self.width, self.height);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let value = kernel.iter().enumerate().map(|(i, k)| k * horizontal.get_clamped(x as i64, y as i64 + i as i64 - radius)).sum();
+                output.set(x, y, value);
+            }
+        }
+
+        output
+    }
+
+    /// Mean over the square window reaching `radius` pixels each way, over the part of the
+    /// window inside the plane near the edges
+    pub fn box_blur(&self, radius: u32) -> Plane {
+        let (width, height) = (self.width as usize, self.height as usize);
+        // Summed area table with a zero row and column in front
+        let mut sums = vec![0f64; (width + 1) * (height + 1)];
+        for y in 0..height {
+            let mut row = 0f64;
+            for x in 0..width {
+                row += self.data[y * width + x] as f64;
+                sums[(y + 1) * (width + 1) + x + 1] = sums[y * (width + 1) + x + 1] + row;
+            }
+        }
+
+        let radius = radius as usize;
+        let mut output = Plane::new(self.width, self.height);
+        for y in 0..height {
+            let (top, bottom) = (y.saturating_sub(radius), (y + radius + 1).min(height));
+            for x in 0..width {
+                let (left, right) = (x.saturating_sub(radius), (x + radius + 1).min(width));
+                let at = |x: usize, y: usize| sums[y * (width + 1) + x];
+                let total = at(right, bottom) - at(left, bottom) - at(right, top) + at(left, top);
+                output.data[y * width + x] = (total / ((right - left) * (bottom - top)) as f64) as f32;
+            }
+        }
+
+        output
+    }
+}
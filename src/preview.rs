@@ -0,0 +1,193 @@
+use std::fmt::Write;
+
+use image::imageops::{resize, FilterType};
+use image::Rgba;
+use rust_image_processing::ImageBuffer;
+
+/// Terminal pixel widths assumed for the graphics protocols, which draw in pixels rather than
+/// character cells
+const CELL_WIDTH: u32 = 8;
+
+/// How an image is drawn in the terminal
+#[derive(Clone, Copy)]
+pub enum Protocol {
+    /// Truecolor `▀` characters, two pixels per cell, which work in most terminals
+    Blocks,
+    /// The kitty graphics protocol, also supported by WezTerm and Konsole
+    Kitty,
+    /// Sixel graphics, supported by xterm (with `-ti vt340`), mlterm, foot and others
+    Sixel,
+}
+
+impl Protocol {
+    pub fn from_name(name: &str) -> Option<Protocol> {
+        match name {
+            "blocks" => Some(Protocol::Blocks),
+            "kitty" => Some(Protocol::Kitty),
+            "sixel" => Some(Protocol::Sixel),
+            _ => None,
+        }
+    }
+}
+
+/// Render an image as escape codes for printing to the terminal, scaled down to fit `columns`
+/// character cells across while keeping its aspect ratio
+///
+/// # Arguments
+///
+/// * `image`: Image to draw, transparent areas are drawn over black
+/// * `columns`: Width to draw at in character cells
+/// * `protocol`: How to draw it
+///
+/// returns: String
+pub fn render(image: &ImageBuffer, columns: u32, protocol: Protocol) -> String {
+    match protocol {
+        Protocol::Blocks => render_blocks(&fit(image, columns.max(1))),
+        Protocol::Kitty => render_kitty(&fit(image, columns.max(1) * CELL_WIDTH), columns.max(1)),
+        Protocol::Sixel => render_sixel(&fit(image, columns.max(1) * CELL_WIDTH)),
+    }
+}
+
+/// Scale down to at most `width` pixels across, square pixels assumed
+fn fit(image: &ImageBuffer, width: u32) -> ImageBuffer {
+    if image.width() <= width {
+        return image.clone();
+    }
+
+    let height = ((image.height() as u64 * width as u64) / image.width() as u64).max(1) as u32;
+    resize(image, width, height, FilterType::Triangle)
+}
+
+fn over_black(pixel: &Rgba<u8>) -> [u8; 3] {
+    let alpha = pixel[3] as u32;
+    [0, 1, 2].map(|c| (pixel[c] as u32 * alpha / 255) as u8)
+}
+
+fn render_blocks(image: &ImageBuffer) -> String {
+    let mut output = String::new();
+
+    for y in (0..image.height()).step_by(2) {
+        for x in 0..image.width() {
+            let [tr, tg, tb] = over_black(image.get_pixel(x, y));
+            write!(output, "\x1b[38;2;{};{};{}m", tr, tg, tb).unwrap();
+
+            if y + 1 < image.height() {
+                let [br, bg, bb] = over_black(image.get_pixel(x, y + 1));
+                write!(output, "\x1b[48;2;{};{};{}m", br, bg, bb).unwrap();
+            } else {
+                output.push_str("\x1b[49m");
+            }
+            output.push('▀');
+        }
+        output.push_str("\x1b[0m\n");
+    }
+
+    output
+}
+
+fn render_kitty(image: &ImageBuffer, columns: u32) -> String {
+    const CHUNK: usize = 4096;
+
+    let encoded = base64(image.as_raw());
+    let chunks: Vec<&[u8]> = encoded.as_bytes().chunks(CHUNK).collect();
+    let mut output = String::new();
+
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = (i + 1 < chunks.len()) as u8;
+        if i == 0 {
+            write!(output, "\x1b_Ga=T,f=32,s={},v={},c={},m={};", image.width(), image.height(), columns, more).unwrap();
+        } else {
+            write!(output, "\x1b_Gm={};", more).unwrap();
+        }
+        output.push_str(std::str::from_utf8(chunk).unwrap());
+        output.push_str("\x1b\\");
+    }
+    output.push('\n');
+
+    output
+}
+
+/// Sixel output quantised to a 6x6x6 colour cube, each band of six rows drawn one colour at a
+/// time
+fn render_sixel(image: &ImageBuffer) -> String {
+    let level = |value: u8| (value as u32 * 5 + 127) / 255;
+    let indices: Vec<usize> = image
+        .pixels()
+        .map(|pixel| {
+            let [r, g, b] = over_black(pixel);
+            (level(r) * 36 + level(g) * 6 + level(b)) as usize
+        })
+        .collect();
+
+    let mut output = String::from("\x1bPq");
+    write!(output, "\"1;1;{};{}", image.width(), image.height()).unwrap();
+    for index in 0..216 {
+        let percent = |component: usize| component * 100 / 5;
+        write!(output, "#{};2;{};{};{}", index, percent(index / 36), percent(index / 6 % 6), percent(index % 6)).unwrap();
+    }
+
+    let width = image.width() as usize;
+    let height = image.height() as usize;
+    for band in (0..height).step_by(6) {
+        let rows = (height - band).min(6);
+        let mut used = [false; 216];
+        for row in 0..rows {
+            for &index in &indices[(band + row) * width..(band + row + 1) * width] {
+                used[index] = true;
+            }
+        }
+
+        for (colour, _) in used.iter().enumerate().filter(|(_, used)| **used) {
+            write!(output, "#{}", colour).unwrap();
+            let sixels = (0..width).map(|x| {
+                let bits = (0..rows).filter(|row| indices[(band + row) * width + x] == colour).fold(0, |bits, row| bits | 1 << row);
+                (63 + bits) as u8 as char
+            });
+            push_run_length(&mut output, sixels);
+            output.push('$');
+        }
+        output.push('-');
+    }
+    output.push_str("\x1b\\\n");
+
+    output
+}
+
+fn push_run_length(output: &mut String, sixels: impl Iterator<Item = char>) {
+    let flush = |output: &mut String, sixel: char, count: usize| match count {
+        0 => {}
+        1..=3 => (0..count).for_each(|_| output.push(sixel)),
+        _ => write!(output, "!{}{}", count, sixel).unwrap(),
+    };
+
+    let mut run = ('?', 0);
+    for sixel in sixels {
+        if sixel == run.0 {
+            run.1 += 1;
+        } else {
+            flush(output, run.0, run.1);
+            run = (sixel, 1);
+        }
+    }
+    flush(output, run.0, run.1);
+}
+
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let group = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
@@ -0,0 +1,107 @@
+//! Progress reporting and cancellation for long running operations, so that a GUI or server
+//! can show how far along work is and abort it part way through.
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crate::error::{Error, Result};
+
+/// Shared flag to ask running work to stop, clones all refer to the same flag
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> CancellationToken {
+        CancellationToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Passed to the `_with_progress` variants of operations. The callback is given the fraction
+/// of work complete in [0, 1], and once the token is cancelled the operation returns
+/// `Error::Cancelled` at its next check.
+#[derive(Clone)]
+pub struct Progress {
+    callback: Option<Arc<dyn Fn(f32) + Send + Sync>>,
+    cancellation: Option<CancellationToken>,
+    start: f32,
+    end: f32,
+}
+
+impl Progress {
+    /// Progress that reports nowhere and can't be cancelled
+    pub fn none() -> Progress {
+        Progress { callback: None, cancellation: None, start: 0., end: 1. }
+    }
+
+    pub fn with_callback(mut self, callback: impl Fn(f32) + Send + Sync + 'static) -> Progress {
+        self.callback = Some(Arc::new(callback));
+        self
+    }
+
+    pub fn with_cancellation(mut self, cancellation: CancellationToken) -> Progress {
+        self.cancellation = Some(cancellation);
+        self
+    }
+
+    /// Report the fraction of work complete
+    pub fn report(&self, fraction: f32) {
+        if let Some(callback) = &self.callback {
+            callback(self.start + (self.end - self.start) * fraction.clamp(0., 1.));
+        }
+    }
+
+    /// Returns `Error::Cancelled` if cancellation has been requested
+    pub fn check(&self) -> Result<()> {
+        match &self.cancellation {
+            Some(cancellation) if cancellation.is_cancelled() => Err(Error::Cancelled),
+            _ => Ok(()),
+        }
+    }
+
+    /// Progress for one part of a larger job, covering the fraction `start` to `end` of this
+    /// progress, so nested work reports on the overall scale
+    pub fn part(&self, start: f32, end: f32) -> Progress {
+        let span = self.end - self.start;
+
+        Progress {
+            callback: self.callback.clone(),
+            cancellation: self.cancellation.clone(),
+            start: self.start + span * start,
+            end: self.start + span * end,
+        }
+    }
+
+    /// The same cancellation with nowhere to report, for work run alongside other work whose
+    /// reports would interleave
+    pub(crate) fn cancellation_only(&self) -> Progress {
+        Progress { callback: None, cancellation: self.cancellation.clone(), start: self.start, end: self.end }
+    }
+}
+
+impl Default for Progress {
+    fn default() -> Progress {
+        Progress::none()
+    }
+}
+
+impl fmt::Debug for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Progress")
+            .field("callback", &self.callback.is_some())
+            .field("cancellation", &self.cancellation)
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .finish()
+    }
+}
@@ -0,0 +1,133 @@
+//! Image pyramids, a plane at a series of halving sizes. A gaussian pyramid holds ever blurrier
+//! copies, and a laplacian pyramid the detail each level adds to the next smaller, which
+//! collapses back to the plane exactly. Blending laplacian pyramids level by level with
+//! smoothly varying weights joins images without visible seams, as each band of detail is
+//! blended over a width that suits it.
+
+use crate::plane::Plane;
+
+/// Smallest side a pyramid level is shrunk to
+const MIN_LEVEL_SIZE: u32 = 8;
+
+/// Levels a pyramid of a plane can have before its smallest level would be below 8 pixels on a
+/// side, at least 1
+pub fn max_levels(width: u32, height: u32) -> usize {
+    let mut levels = 1;
+    let mut side = width.min(height);
+    while side / 2 >= MIN_LEVEL_SIZE {
+        side = side.div_ceil(2);
+        levels += 1;
+    }
+    levels
+}
+
+/// Blur and halve a plane, rounding odd sizes up
+pub fn downsample(plane: &Plane) -> Plane {
+    let blurred = plane.gaussian_blur(1.);
+    let (width, height) = (plane.width.div_ceil(2).max(1), plane.height.div_ceil(2).max(1));
+
+    let mut output = Plane::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            output.set(x, y, blurred.get((2 * x).min(plane.width - 1), (2 * y).min(plane.height - 1)));
+        }
+    }
+    output
+}
+
+/// Interpolate a plane made by `downsample` back up to the size it was made from
+pub fn upsample(plane: &Plane, width: u32, height: u32) -> Plane {
+    let mut output = Plane::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            output.set(x, y, plane.sample(x as f32 / 2., y as f32 / 2.));
+        }
+    }
+    output
+}
+
+/// The plane and successively downsampled copies of it
+///
+/// # Arguments
+///
+/// * `plane`: Plane at full size, the first level
+/// * `levels`: Number of levels, limited by `max_levels`
+///
+/// returns: Vec<Plane>, largest first
+pub fn gaussian_pyramid(plane: &Plane, levels: usize) -> Vec<Plane> {
+    let levels = levels.clamp(1, max_levels(plane.width, plane.height));
+    let mut pyramid = vec![plane.clone()];
+    while pyramid.len() < levels {
+        let next = downsample(&pyramid[pyramid.len() - 1]);
+        pyramid.push(next);
+    }
+    pyramid
+}
+
+/// The detail each level of the gaussian pyramid adds to the next, with the smallest gaussian
+/// level last, so `collapse` rebuilds the plane exactly
+///
+/// # Arguments
+///
+/// * `plane`: Plane to decompose
+/// * `levels`: Number of levels, limited by `max_levels`
+///
+/// returns: Vec<Plane>, largest first
+pub fn laplacian_pyramid(plane: &Plane, levels: usize) -> Vec<Plane> {
+    let gaussian = gaussian_pyramid(plane, levels);
+    let mut pyramid: Vec<Plane> = gaussian
+        .windows(2)
+        .map(|pair| {
+            let expanded = upsample(&pair[1], pair[0].width, pair[0].height);
+            pair[0].zip(&expanded, |value, coarse| value - coarse)
+        })
+        .collect();
+    pyramid.push(gaussian[gaussian.len() - 1].clone());
+    pyramid
+}
+
+/// Rebuild a plane from its laplacian pyramid
+pub fn collapse(pyramid: &[Plane]) -> Plane {
+    let Some(mut plane) = pyramid.last().cloned() else {
+        return Plane::new(0, 0);
+    };
+    for level in pyramid.iter().rev().skip(1) {
+        plane = upsample(&plane, level.width, level.height).zip(level, |coarse, detail| coarse + detail);
+    }
+    plane
+}
+
+/// Blend planes of the same size by per pixel weights, level by level of their laplacian
+/// pyramids with the weights' gaussian pyramids, so the joins between them don't show. The
+/// weights at each pixel are normalised to sum to 1, and pixels no plane has weight at are 0.
+///
+/// # Arguments
+///
+/// * `planes`: Planes to blend, all the same size
+/// * `weights`: Weight of each plane at each pixel, the size of the planes
+/// * `levels`: Number of pyramid levels, more blending over wider regions
+///
+/// returns: Plane
+pub fn blend(planes: &[Plane], weights: &[Plane], levels: usize) -> Plane {
+    if planes.is_empty() || weights.is_empty() {
+        return Plane::new(0, 0);
+    }
+    let weight_pyramids: Vec<Vec<Plane>> = weights.iter().map(|weight| gaussian_pyramid(weight, levels)).collect();
+    let mut blended: Vec<Plane> = weight_pyramids[0].iter().map(|level| Plane::new(level.width, level.height)).collect();
+    let mut totals = blended.clone();
+
+    for (plane, weight_pyramid) in planes.iter().zip(&weight_pyramids) {
+        for (((level, weight), out), total) in laplacian_pyramid(plane, levels).iter().zip(weight_pyramid).zip(blended.iter_mut()).zip(totals.iter_mut()) {
+            for i in 0..level.data.len() {
+                out.data[i] += weight.data[i] * level.data[i];
+                total.data[i] += weight.data[i];
+            }
+        }
+    }
+
+    for (out, total) in blended.iter_mut().zip(&totals) {
+        *out = out.zip(total, |value, total| if total > 1e-6 { value / total } else { 0. });
+    }
+
+    collapse(&blended)
+}
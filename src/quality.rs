@@ -0,0 +1,121 @@
+//! No reference quality measures, judging a photo on its own without an original to compare
+//! with, for culling blurred and badly exposed shots
+
+use image::imageops::{resize, FilterType};
+
+use crate::plane::Plane;
+use crate::value::Value;
+use crate::ImageBuffer;
+
+/// Images are shrunk to at most this on their longest side before measuring sharpness, so
+/// scores of photos of different resolutions can be compared
+const SHARPNESS_SIZE: u32 = 1024;
+
+/// Sharpness at which `quality` counts a photo as half sharp, around where blur starts to show
+const SHARPNESS_MIDPOINT: f64 = 100.;
+
+/// Luma at or below which a pixel is crushed to black
+const SHADOW_CLIP: f32 = 2.;
+
+/// Luma at or above which a pixel is blown out to white
+const HIGHLIGHT_CLIP: f32 = 253.;
+
+/// Luma of 18% grey in sRGB, the brightness a well exposed scene averages to
+const MID_GREY: f64 = 118.;
+
+/// How the brightness of a photo is spread, from its luma histogram
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Exposure {
+    /// Mean luma from 0 to 255
+    pub mean: f64,
+    /// Fraction of pixels crushed to black
+    pub shadows_clipped: f64,
+    /// Fraction of pixels blown out to white
+    pub highlights_clipped: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quality {
+    /// Variance of the laplacian, see `sharpness`
+    pub sharpness: f64,
+    pub exposure: Exposure,
+    /// Overall score from 0 to 1, higher is better
+    pub score: f64,
+}
+
+impl Quality {
+    pub fn to_value(&self) -> Value {
+        Value::Table(vec![
+            ("score".to_string(), Value::Number(self.score)),
+            ("sharpness".to_string(), Value::Number(self.sharpness)),
+            ("mean_luma".to_string(), Value::Number(self.exposure.mean)),
+            ("shadows_clipped".to_string(), Value::Number(self.exposure.shadows_clipped)),
+            ("highlights_clipped".to_string(), Value::Number(self.exposure.highlights_clipped)),
+        ])
+    }
+}
+
+/// Sharpness as the variance of the laplacian of the luma. Focused edges give strong second
+/// derivatives both ways and blur flattens them, so blurred photos score low, typically under
+/// 100. Scores depend on content too: a sharp photo of a blank wall scores low, so compare
+/// photos of similar scenes.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to measure, shrunk to 1024 pixels on its longest side if larger
+///
+/// returns: f64
+pub fn sharpness(input: &ImageBuffer) -> f64 {
+    let scale = SHARPNESS_SIZE as f32 / input.width().max(input.height()) as f32;
+    let luma = if scale < 1. {
+        let (width, height) = (((input.width() as f32 * scale).round() as u32).max(1), ((input.height() as f32 * scale).round() as u32).max(1));
+        Plane::from_luma(&resize(input, width, height, FilterType::Triangle))
+    } else {
+        Plane::from_luma(input)
+    };
+
+    let mut laplacian = Vec::with_capacity(luma.data.len());
+    for y in 0..luma.height as i64 {
+        for x in 0..luma.width as i64 {
+            let at = |dx: i64, dy: i64| luma.get_clamped(x + dx, y + dy) as f64;
+            laplacian.push(at(-1, 0) + at(1, 0) + at(0, -1) + at(0, 1) - 4. * at(0, 0));
+        }
+    }
+
+    let count = laplacian.len().max(1) as f64;
+    let mean = laplacian.iter().sum::<f64>() / count;
+    laplacian.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / count
+}
+
+/// Measure how a photo is exposed, its mean brightness and how much is clipped to black or white
+pub fn exposure(input: &ImageBuffer) -> Exposure {
+    let luma = Plane::from_luma(input);
+    let count = luma.data.len().max(1) as f64;
+
+    Exposure {
+        mean: luma.data.iter().map(|&v| v as f64).sum::<f64>() / count,
+        shadows_clipped: luma.data.iter().filter(|&&v| v <= SHADOW_CLIP).count() as f64 / count,
+        highlights_clipped: luma.data.iter().filter(|&&v| v >= HIGHLIGHT_CLIP).count() as f64 / count,
+    }
+}
+
+/// Score a photo from 0 to 1, the product of a sharpness and an exposure score. Sharpness
+/// scores `s / (s + 100)`, half at the usual blur threshold. Exposure loses five times the
+/// fraction of clipped pixels, and more the further the mean luma is from mid grey.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to score
+///
+/// returns: Quality, the score and the measures it comes from
+pub fn quality(input: &ImageBuffer) -> Quality {
+    let sharpness = sharpness(input);
+    let exposure = exposure(input);
+
+    let sharpness_score = sharpness / (sharpness + SHARPNESS_MIDPOINT);
+    let clipping = 1. - 5. * (exposure.shadows_clipped + exposure.highlights_clipped);
+    let brightness = 1. - ((exposure.mean - MID_GREY) / MID_GREY).powi(2);
+    let exposure_score = clipping.clamp(0., 1.) * brightness.clamp(0., 1.);
+
+    Quality { sharpness, exposure, score: sharpness_score * exposure_score }
+}
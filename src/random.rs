@@ -0,0 +1,51 @@
+//! Random number generators for the stochastic algorithms, such as sampling and clustering.
+//! Each takes an optional seed: the same seed always gives the same result, and without one the
+//! generator is seeded afresh each call, unless a fixed seed has been set for the whole crate
+//! with `set_fixed_seed`, e.g. to make a batch run or a test reproducible without passing seeds
+//! through every call.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+
+/// Seed used in place of a fresh one when none is given, if set
+static FIXED_SEED: Mutex<Option<u64>> = Mutex::new(None);
+
+/// Seed every generator made without a seed from now on with this one, or go back to fresh
+/// seeds with None
+///
+/// # Arguments
+///
+/// * `seed`: Seed for all unseeded generators, or None
+///
+/// returns: ()
+pub fn set_fixed_seed(seed: Option<u64>) {
+    *FIXED_SEED.lock().unwrap_or_else(|err| err.into_inner()) = seed;
+}
+
+/// The seed set by `set_fixed_seed`, if any
+pub fn fixed_seed() -> Option<u64> {
+    *FIXED_SEED.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// A generator for a stochastic algorithm
+///
+/// # Arguments
+///
+/// * `seed`: Seed for the generator, or None for the crate's fixed seed or else a fresh one
+///
+/// returns: StdRng
+pub fn rng(seed: Option<u64>) -> StdRng {
+    StdRng::seed_from_u64(seed.or_else(fixed_seed).unwrap_or_else(fresh_seed))
+}
+
+/// A seed from the standard library's randomly keyed hashing, as getrandom doesn't build for
+/// the browser. There the keys are constant, so unseeded generators repeat between runs.
+fn fresh_seed() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(0);
+    hasher.finish()
+}
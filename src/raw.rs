@@ -0,0 +1,376 @@
+//! Developing camera raw sensor data, a bayer mosaic with one colour sample per pixel, into an
+//! rgb image the pipeline can start from
+//!
+//! Uncompressed dng files are read directly, and undeveloped mosaics saved as 8 or 16 bit
+//! greyscale images (pgm, png or tiff) can be loaded given their pattern. Proprietary camera
+//! formats need converting to dng first. Camera colour matrices aren't applied, so colours are
+//! the white balanced camera rgb.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::metadata::exif::{tiff_directory, tiff_header, ExifEntry};
+use crate::ImageBuffer;
+
+const RED: usize = 0;
+const GREEN: usize = 1;
+const BLUE: usize = 2;
+
+const SUB_IFDS: u16 = 330;
+const IMAGE_WIDTH: u16 = 256;
+const IMAGE_LENGTH: u16 = 257;
+const BITS_PER_SAMPLE: u16 = 258;
+const COMPRESSION: u16 = 259;
+const PHOTOMETRIC_INTERPRETATION: u16 = 262;
+const STRIP_OFFSETS: u16 = 273;
+const STRIP_BYTE_COUNTS: u16 = 279;
+const CFA_PATTERN: u16 = 33422;
+const BLACK_LEVEL: u16 = 50714;
+const WHITE_LEVEL: u16 = 50717;
+const AS_SHOT_NEUTRAL: u16 = 50728;
+const PHOTOMETRIC_CFA: u32 = 32803;
+
+/// Colours of the top left 2x2 block of a bayer mosaic, row by row
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BayerPattern {
+    Rggb,
+    Bggr,
+    Grbg,
+    Gbrg,
+}
+
+impl BayerPattern {
+    pub fn from_name(name: &str) -> Option<BayerPattern> {
+        match name.to_ascii_lowercase().as_str() {
+            "rggb" => Some(BayerPattern::Rggb),
+            "bggr" => Some(BayerPattern::Bggr),
+            "grbg" => Some(BayerPattern::Grbg),
+            "gbrg" => Some(BayerPattern::Gbrg),
+            _ => None,
+        }
+    }
+
+    /// The pattern of a dng `CFAPattern`, colour indices of the top left 2x2 block
+    fn from_cfa(cfa: &[u32]) -> Option<BayerPattern> {
+        match cfa {
+            [0, 1, 1, 2] => Some(BayerPattern::Rggb),
+            [2, 1, 1, 0] => Some(BayerPattern::Bggr),
+            [1, 0, 2, 1] => Some(BayerPattern::Grbg),
+            [1, 2, 0, 1] => Some(BayerPattern::Gbrg),
+            _ => None,
+        }
+    }
+
+    /// The colour sampled at a pixel
+    pub fn colour_at(&self, x: usize, y: usize) -> usize {
+        let block = match self {
+            BayerPattern::Rggb => [RED, GREEN, GREEN, BLUE],
+            BayerPattern::Bggr => [BLUE, GREEN, GREEN, RED],
+            BayerPattern::Grbg => [GREEN, RED, BLUE, GREEN],
+            BayerPattern::Gbrg => [GREEN, BLUE, RED, GREEN],
+        };
+        block[(y % 2) * 2 + x % 2]
+    }
+}
+
+/// How the two missing colours at each pixel are interpolated
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Demosaic {
+    /// Average of the nearest samples of each colour
+    Bilinear,
+    /// Malvar, He and Cutler's gradient corrected interpolation, sharper with fewer colour
+    /// fringes at edges
+    Malvar,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WhiteBalance {
+    /// The camera's as shot white balance where the file records one, otherwise grey world
+    AsShot,
+    /// Scale each colour so the averages of all three match
+    GreyWorld,
+    /// Red, green and blue multipliers
+    Custom([f32; 3]),
+}
+
+/// Settings for developing raw data, by default Malvar demosaicing, as shot white balance and
+/// a 2.2 gamma curve
+#[derive(Clone, Debug)]
+pub struct DevelopOptions {
+    demosaic: Demosaic,
+    white_balance: WhiteBalance,
+    gamma: f32,
+}
+
+impl Default for DevelopOptions {
+    fn default() -> DevelopOptions {
+        DevelopOptions { demosaic: Demosaic::Malvar, white_balance: WhiteBalance::AsShot, gamma: 2.2 }
+    }
+}
+
+impl DevelopOptions {
+    pub fn new() -> DevelopOptions {
+        DevelopOptions::default()
+    }
+
+    pub fn with_demosaic(mut self, demosaic: Demosaic) -> DevelopOptions {
+        self.demosaic = demosaic;
+        self
+    }
+
+    pub fn with_white_balance(mut self, white_balance: WhiteBalance) -> DevelopOptions {
+        self.white_balance = white_balance;
+        self
+    }
+
+    /// Gamma of the output curve, sensor data being linear; 1 leaves it linear
+    pub fn with_gamma(mut self, gamma: f32) -> DevelopOptions {
+        self.gamma = gamma;
+        self
+    }
+}
+
+/// Undeveloped sensor data
+#[derive(Clone, Debug)]
+pub struct RawImage {
+    pub width: usize,
+    pub height: usize,
+    /// One sample per pixel, row by row
+    pub data: Vec<u16>,
+    pub pattern: BayerPattern,
+    /// Sample value of no light
+    pub black_level: f32,
+    /// Sample value at which the sensor saturates
+    pub white_level: f32,
+    /// Red, green and blue multipliers recorded by the camera
+    pub as_shot_white_balance: Option<[f32; 3]>,
+}
+
+impl RawImage {
+    /// Read a raw file, a dng or otherwise a greyscale mosaic taken to be rggb
+    pub fn open(path: impl AsRef<Path>) -> Result<RawImage> {
+        let path = path.as_ref();
+
+        match is_raw_path(path) {
+            true => RawImage::from_dng(&fs::read(path)?),
+            false => RawImage::open_mosaic(path, BayerPattern::Rggb),
+        }
+    }
+
+    /// Read a mosaic saved as an 8 or 16 bit greyscale image, using its full range
+    pub fn open_mosaic(path: impl AsRef<Path>, pattern: BayerPattern) -> Result<RawImage> {
+        let image = image::open(path)?;
+        let eight_bit = image.color().bytes_per_pixel() == image.color().channel_count();
+        let mosaic = image.into_luma16();
+        let (width, height) = (mosaic.width() as usize, mosaic.height() as usize);
+
+        let (data, white_level) = match eight_bit {
+            // Widening to 16 bits scales 8 bit values by 257
+            true => (mosaic.into_raw().into_iter().map(|v| v / 257).collect(), 255.),
+            false => (mosaic.into_raw(), 65535.),
+        };
+
+        Ok(RawImage { width, height, data, pattern, black_level: 0., white_level, as_shot_white_balance: None })
+    }
+
+    /// Read the raw data of an uncompressed dng, with 8 or 16 bit samples stored in strips
+    pub fn from_dng(data: &[u8]) -> Result<RawImage> {
+        let (little_endian, first) = tiff_header(data)?;
+        let (ifd0, _) = tiff_directory(data, first)?;
+
+        let mut directories = vec![ifd0.clone()];
+        if let Some(sub_ifds) = find(&ifd0, SUB_IFDS) {
+            for offset in sub_ifds.to_u32s() {
+                directories.push(tiff_directory(data, offset as usize)?.0);
+            }
+        }
+
+        let number = |entries: &[ExifEntry], tag| find(entries, tag).and_then(|value| value.as_u32());
+        let raw = directories
+            .iter()
+            .find(|entries| number(entries, PHOTOMETRIC_INTERPRETATION) == Some(PHOTOMETRIC_CFA))
+            .ok_or_else(|| Error::UnsupportedFormat("dng without bayer raw data".to_string()))?;
+
+        let width = number(raw, IMAGE_WIDTH).ok_or_else(|| Error::Codec("dng raw data has no width".to_string()))? as usize;
+        let height = number(raw, IMAGE_LENGTH).ok_or_else(|| Error::Codec("dng raw data has no height".to_string()))? as usize;
+        let bits = number(raw, BITS_PER_SAMPLE).unwrap_or(16);
+        if number(raw, COMPRESSION).unwrap_or(1) != 1 {
+            return Err(Error::UnsupportedFormat("compressed dng raw data".to_string()));
+        }
+        if bits != 8 && bits != 16 {
+            return Err(Error::UnsupportedFormat(format!("{} bit dng raw data", bits)));
+        }
+
+        let cfa = find(raw, CFA_PATTERN).map(|value| value.to_u32s()).unwrap_or_else(|| vec![0, 1, 1, 2]);
+        let pattern = BayerPattern::from_cfa(&cfa).ok_or_else(|| Error::UnsupportedFormat("non bayer colour filter".to_string()))?;
+
+        let offsets = find(raw, STRIP_OFFSETS).map(|value| value.to_u32s()).unwrap_or_default();
+        let counts = find(raw, STRIP_BYTE_COUNTS).map(|value| value.to_u32s()).unwrap_or_default();
+        let mut bytes = Vec::with_capacity(width * height * bits as usize / 8);
+        for (&offset, &count) in offsets.iter().zip(&counts) {
+            let strip = data.get(offset as usize..offset as usize + count as usize).ok_or_else(|| Error::Codec("dng strip runs past the end of the file".to_string()))?;
+            bytes.extend_from_slice(strip);
+        }
+
+        let samples: Vec<u16> = match bits {
+            8 => bytes.iter().map(|&v| v as u16).collect(),
+            _ if little_endian => bytes.chunks_exact(2).map(|b| u16::from_le_bytes([b[0], b[1]])).collect(),
+            _ => bytes.chunks_exact(2).map(|b| u16::from_be_bytes([b[0], b[1]])).collect(),
+        };
+        if samples.len() < width * height {
+            return Err(Error::BufferSize { expected: width * height * bits as usize / 8, actual: bytes.len() });
+        }
+
+        let black_level = find(raw, BLACK_LEVEL).map(|value| value.to_f64s()).filter(|levels| !levels.is_empty());
+        let black_level = black_level.map(|levels| levels.iter().sum::<f64>() / levels.len() as f64).unwrap_or(0.) as f32;
+        let white_level = number(raw, WHITE_LEVEL).map(|level| level as f32).unwrap_or(((1u32 << bits) - 1) as f32);
+
+        // The neutral is the camera's reading of white, so the multipliers are its inverse
+        let neutral = find(&ifd0, AS_SHOT_NEUTRAL).map(|value| value.to_f64s()).filter(|neutral| neutral.len() == 3 && neutral.iter().all(|&v| v > 0.));
+        let as_shot_white_balance = neutral.map(|n| [(n[1] / n[0]) as f32, 1., (n[1] / n[2]) as f32]);
+
+        Ok(RawImage { width, height, data: samples[..width * height].to_vec(), pattern, black_level, white_level, as_shot_white_balance })
+    }
+
+    /// Develop the sensor data into an rgb image
+    ///
+    /// # Arguments
+    ///
+    /// * `options`: Demosaicing, white balance and gamma settings
+    ///
+    /// returns: ImageBuffer
+    pub fn develop(&self, options: &DevelopOptions) -> ImageBuffer {
+        let range = (self.white_level - self.black_level).max(1.);
+        let mut mosaic: Vec<f32> = self.data.iter().map(|&v| ((v as f32 - self.black_level) / range).clamp(0., 1.)).collect();
+
+        let multipliers = match options.white_balance {
+            WhiteBalance::Custom(multipliers) => multipliers,
+            WhiteBalance::AsShot if self.as_shot_white_balance.is_some() => self.as_shot_white_balance.unwrap(),
+            WhiteBalance::AsShot | WhiteBalance::GreyWorld => self.grey_world(&mosaic),
+        };
+        for (i, value) in mosaic.iter_mut().enumerate() {
+            *value *= multipliers[self.pattern.colour_at(i % self.width, i / self.width)];
+        }
+
+        let rgb = match options.demosaic {
+            Demosaic::Bilinear => self.demosaic_bilinear(&mosaic),
+            Demosaic::Malvar => self.demosaic_malvar(&mosaic),
+        };
+
+        let inverse_gamma = 1. / options.gamma;
+        let mut output: ImageBuffer = image::ImageBuffer::new(self.width as u32, self.height as u32);
+        for (i, pixel) in output.pixels_mut().enumerate() {
+            for c in 0..3 {
+                pixel[c] = (rgb[i][c].clamp(0., 1.).powf(inverse_gamma) * 255.).round() as u8;
+            }
+            pixel[3] = 255;
+        }
+
+        output
+    }
+
+    fn grey_world(&self, mosaic: &[f32]) -> [f32; 3] {
+        let mut sums = [0f64; 3];
+        let mut counts = [0usize; 3];
+        for (i, &value) in mosaic.iter().enumerate() {
+            let colour = self.pattern.colour_at(i % self.width, i / self.width);
+            sums[colour] += value as f64;
+            counts[colour] += 1;
+        }
+
+        let means = [0, 1, 2].map(|c| (sums[c] / counts[c].max(1) as f64).max(1e-6));
+        [0, 1, 2].map(|c| (means[GREEN] / means[c]) as f32)
+    }
+
+    /// A sample with coordinates past the edges reflected back in, which keeps the bayer
+    /// colour of the position
+    fn sample(&self, mosaic: &[f32], x: isize, y: isize) -> f32 {
+        let reflect = |v: isize, size: usize| {
+            let size = size as isize;
+            let v = if v < 0 { -v } else { v };
+            (if v >= size { 2 * size - 2 - v } else { v }).clamp(0, size - 1) as usize
+        };
+        mosaic[reflect(y, self.height) * self.width + reflect(x, self.width)]
+    }
+
+    fn demosaic_bilinear(&self, mosaic: &[f32]) -> Vec<[f32; 3]> {
+        let mut rgb = vec![[0f32; 3]; self.width * self.height];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let mut sums = [0f32; 3];
+                let mut counts = [0u32; 3];
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        let colour = self.pattern.colour_at((x as isize + dx + 2) as usize, (y as isize + dy + 2) as usize);
+                        sums[colour] += self.sample(mosaic, x as isize + dx, y as isize + dy);
+                        counts[colour] += 1;
+                    }
+                }
+
+                let own = self.pattern.colour_at(x, y);
+                for c in 0..3 {
+                    rgb[y * self.width + x][c] = match c == own {
+                        true => mosaic[y * self.width + x],
+                        false => sums[c] / counts[c].max(1) as f32,
+                    };
+                }
+            }
+        }
+
+        rgb
+    }
+
+    fn demosaic_malvar(&self, mosaic: &[f32]) -> Vec<[f32; 3]> {
+        // Kernels from the paper as (dx, dy, weight) over 8, with the vertical forms transposed
+        const GREEN_AT_RED_OR_BLUE: &[(isize, isize, f32)] = &[(0, -2, -1.), (0, -1, 2.), (-2, 0, -1.), (-1, 0, 2.), (0, 0, 4.), (1, 0, 2.), (2, 0, -1.), (0, 1, 2.), (0, 2, -1.)];
+        const HORIZONTAL_AT_GREEN: &[(isize, isize, f32)] =
+            &[(0, -2, 0.5), (-1, -1, -1.), (1, -1, -1.), (-2, 0, -1.), (-1, 0, 4.), (0, 0, 5.), (1, 0, 4.), (2, 0, -1.), (-1, 1, -1.), (1, 1, -1.), (0, 2, 0.5)];
+        const VERTICAL_AT_GREEN: &[(isize, isize, f32)] =
+            &[(0, -2, -1.), (-1, -1, -1.), (0, -1, 4.), (1, -1, -1.), (-2, 0, 0.5), (0, 0, 5.), (2, 0, 0.5), (-1, 1, -1.), (0, 1, 4.), (1, 1, -1.), (0, 2, -1.)];
+        const OPPOSITE: &[(isize, isize, f32)] =
+            &[(0, -2, -1.5), (-1, -1, 2.), (1, -1, 2.), (-2, 0, -1.5), (0, 0, 6.), (2, 0, -1.5), (-1, 1, 2.), (1, 1, 2.), (0, 2, -1.5)];
+
+        let mut rgb = vec![[0f32; 3]; self.width * self.height];
+
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let apply = |kernel: &[(isize, isize, f32)]| {
+                    kernel.iter().map(|&(dx, dy, weight)| weight * self.sample(mosaic, x as isize + dx, y as isize + dy)).sum::<f32>() / 8.
+                };
+
+                let own = self.pattern.colour_at(x, y);
+                let value = mosaic[y * self.width + x];
+                let pixel = &mut rgb[y * self.width + x];
+                pixel[own] = value;
+
+                if own == GREEN {
+                    // The colour beside it in the row takes the horizontal kernel
+                    let beside = self.pattern.colour_at(x + 1, y);
+                    pixel[beside] = apply(HORIZONTAL_AT_GREEN);
+                    pixel[RED + BLUE - beside] = apply(VERTICAL_AT_GREEN);
+                } else {
+                    pixel[GREEN] = apply(GREEN_AT_RED_OR_BLUE);
+                    pixel[RED + BLUE - own] = apply(OPPOSITE);
+                }
+            }
+        }
+
+        rgb
+    }
+}
+
+fn find(entries: &[ExifEntry], tag: u16) -> Option<&crate::metadata::exif::ExifValue> {
+    entries.iter().find(|entry| entry.tag == tag).map(|entry| &entry.value)
+}
+
+/// Whether a path has the extension of a raw file this module reads
+pub fn is_raw_path(path: &Path) -> bool {
+    path.extension().and_then(|extension| extension.to_str()).map(|extension| extension.eq_ignore_ascii_case("dng")).unwrap_or(false)
+}
+
+/// Open a raw file and develop it with the default settings, see `RawImage::open`
+pub fn open_raw(path: impl AsRef<Path>) -> Result<ImageBuffer> {
+    Ok(RawImage::open(path)?.develop(&DevelopOptions::default()))
+}
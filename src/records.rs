@@ -0,0 +1,106 @@
+//! Saving and loading the results of the detectors, such as corners, detections, code outlines
+//! and shape descriptors, so a pipeline can keep them between steps or hand them to other tools.
+//! Records go through a `Value`, written as json or in the compact binary encoding of
+//! `value::binary`.
+
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::value::{binary, json, Value};
+
+/// A result that converts to and from a `Value`
+pub trait Record: Sized {
+    /// What the record is, for errors about malformed ones
+    const NAME: &'static str;
+
+    fn to_value(&self) -> Value;
+
+    fn from_value(value: &Value) -> Result<Self>;
+}
+
+/// Write records to a file, as a json array if the path ends in `.json` and in the binary
+/// encoding otherwise
+///
+/// # Arguments
+///
+/// * `records`: Records to write
+/// * `path`: File to write, e.g. `corners.json` or `corners.bin`
+///
+/// returns: Result<()>
+pub fn save_records<T: Record>(records: &[T], path: impl AsRef<Path>) -> Result<()> {
+    let path = path.as_ref();
+    let value = Value::Array(records.iter().map(Record::to_value).collect());
+    let is_json = path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("json"));
+
+    if is_json {
+        Ok(fs::write(path, json::to_string(&value))?)
+    } else {
+        Ok(fs::write(path, binary::to_bytes(&value))?)
+    }
+}
+
+/// Read records written by `save_records`, telling the binary encoding from json by its header
+///
+/// # Arguments
+///
+/// * `path`: File to read
+///
+/// returns: Result<Vec<T>>
+pub fn load_records<T: Record>(path: impl AsRef<Path>) -> Result<Vec<T>> {
+    let bytes = fs::read(path)?;
+    let value = if bytes.starts_with(binary::MAGIC) {
+        binary::from_bytes(&bytes)?
+    } else {
+        json::parse(std::str::from_utf8(&bytes).map_err(|_| Error::Codec("invalid utf-8 in json".to_string()))?)?
+    };
+
+    match value.as_array() {
+        Some(values) => values.iter().map(T::from_value).collect(),
+        None => Err(invalid(T::NAME, "records")),
+    }
+}
+
+/// Outlines of four corners, such as those of `barcode::find_qr_codes` and `document::find_page`
+impl Record for [(f64, f64); 4] {
+    const NAME: &'static str = "quadrilateral";
+
+    fn to_value(&self) -> Value {
+        points_to_value(self)
+    }
+
+    fn from_value(value: &Value) -> Result<Self> {
+        let points = points_from_value(value, Self::NAME, "corners")?;
+        points.try_into().map_err(|_| invalid(Self::NAME, "corners"))
+    }
+}
+
+pub(crate) fn field<'a>(value: &'a Value, record: &str, key: &str) -> Result<&'a Value> {
+    value.get(key).ok_or_else(|| Error::MissingParameter { operation: record.to_string(), parameter: key.to_string() })
+}
+
+pub(crate) fn number(value: &Value, record: &str, key: &str) -> Result<f64> {
+    field(value, record, key)?.as_f64().ok_or_else(|| invalid(record, key))
+}
+
+pub(crate) fn points_to_value<T: Copy + Into<f64>>(points: &[(T, T)]) -> Value {
+    Value::Array(points.iter().map(|&(x, y)| Value::Array(vec![Value::Number(x.into()), Value::Number(y.into())])).collect())
+}
+
+/// Read an `[x, y]` pair, `key` naming it for errors
+pub(crate) fn point_from_value(value: &Value, record: &str, key: &str) -> Result<(f64, f64)> {
+    match value.as_array() {
+        Some([x, y]) => Ok((x.as_f64().ok_or_else(|| invalid(record, key))?, y.as_f64().ok_or_else(|| invalid(record, key))?)),
+        _ => Err(invalid(record, key)),
+    }
+}
+
+/// Read an array of `[x, y]` pairs, `key` naming it for errors
+pub(crate) fn points_from_value(value: &Value, record: &str, key: &str) -> Result<Vec<(f64, f64)>> {
+    let points = value.as_array().ok_or_else(|| invalid(record, key))?;
+    points.iter().map(|point| point_from_value(point, record, key)).collect()
+}
+
+pub(crate) fn invalid(record: &str, key: &str) -> Error {
+    Error::InvalidParameter { operation: record.to_string(), parameter: key.to_string() }
+}
@@ -0,0 +1,72 @@
+//! Axis aligned rectangles of pixels, for crops and regions of an image
+
+use crate::error::Result;
+use crate::records::{invalid, Record};
+use crate::value::Value;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Rect {
+    pub fn new(x: u32, y: u32, width: u32, height: u32) -> Rect {
+        Rect { x, y, width, height }
+    }
+
+    /// One past the last column
+    pub fn right(&self) -> u32 {
+        self.x + self.width
+    }
+
+    /// One past the last row
+    pub fn bottom(&self) -> u32 {
+        self.y + self.height
+    }
+
+    pub fn area(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+
+    pub fn contains(&self, x: u32, y: u32) -> bool {
+        x >= self.x && x < self.right() && y >= self.y && y < self.bottom()
+    }
+
+    /// The overlap of two rectangles, None if they don't overlap
+    pub fn intersect(&self, other: &Rect) -> Option<Rect> {
+        let (x, y) = (self.x.max(other.x), self.y.max(other.y));
+        let (right, bottom) = (self.right().min(other.right()), self.bottom().min(other.bottom()));
+        (right > x && bottom > y).then(|| Rect::new(x, y, right - x, bottom - y))
+    }
+
+    /// Intersection over union, the area two rectangles share over the area they cover
+    /// together, from 0 for apart to 1 for the same rectangle
+    pub fn iou(&self, other: &Rect) -> f32 {
+        let shared = self.intersect(other).map_or(0, |overlap| overlap.area());
+        let union = self.area() + other.area() - shared;
+        if union == 0 {
+            0.
+        } else {
+            (shared as f64 / union as f64) as f32
+        }
+    }
+}
+
+/// Written as `[x, y, width, height]`
+impl Record for Rect {
+    const NAME: &'static str = "rect";
+
+    fn to_value(&self) -> Value {
+        Value::Array([self.x, self.y, self.width, self.height].iter().map(|&v| Value::Number(v as f64)).collect())
+    }
+
+    fn from_value(value: &Value) -> Result<Rect> {
+        match value.as_array().map(|values| values.iter().map(|v| v.as_f64()).collect::<Option<Vec<f64>>>()) {
+            Some(Some(values)) if values.len() == 4 && values.iter().all(|&v| v >= 0.) => Ok(Rect::new(values[0] as u32, values[1] as u32, values[2] as u32, values[3] as u32)),
+            _ => Err(invalid(Self::NAME, "rect")),
+        }
+    }
+}
@@ -0,0 +1,166 @@
+//! Hiding parts of an image, such as faces and number plates, by pixelating, blurring or filling
+//! them. Only pixels inside a region are used to redact it, so nothing of the surroundings is
+//! smeared in and nothing of the region leaks out.
+
+use image::imageops::{crop_imm, replace};
+
+use crate::plane::Plane;
+use crate::rect::Rect;
+use crate::ImageBuffer;
+
+/// Passes of the box blur, three being close to a gaussian
+const BLUR_PASSES: u32 = 3;
+
+/// How a region is hidden
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Redaction {
+    /// Replace blocks of this many pixels square with their average colour
+    Pixelate(u32),
+    /// Blur by three passes of a box blur of this radius
+    Blur(u32),
+    /// Paint over with a solid colour, the only choice that can't be partly undone
+    Fill(image::Rgba<u8>),
+}
+
+impl Redaction {
+    /// Parse `pixelate`, `blur` or `fill` with the size to use for pixelating or blurring,
+    /// filling in black
+    pub fn from_name(name: &str, size: u32) -> Option<Redaction> {
+        match name {
+            "pixelate" => Some(Redaction::Pixelate(size)),
+            "blur" => Some(Redaction::Blur(size)),
+            "fill" => Some(Redaction::Fill(image::Rgba([0, 0, 0, 255]))),
+            _ => None,
+        }
+    }
+
+    fn apply(&self, image: &mut ImageBuffer, region: Rect) {
+        match *self {
+            Redaction::Pixelate(block_size) => pixelate_region(image, region, block_size),
+            Redaction::Blur(radius) => blur_region(image, region, radius),
+            Redaction::Fill(colour) => {
+                for y in region.y..region.bottom() {
+                    for x in region.x..region.right() {
+                        image.put_pixel(x, y, colour);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Pixelate a whole image, replacing each block with its average colour, the blocks starting
+/// from the top left corner
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to pixelate
+/// * `block_size`: Width and height of the blocks in pixels, those on the right and bottom
+///   edges being smaller where the size doesn't divide the image
+///
+/// returns: ImageBuffer
+pub fn pixelate(input: &ImageBuffer, block_size: u32) -> ImageBuffer {
+    let mut output = input.clone();
+    pixelate_region(&mut output, Rect::new(0, 0, input.width(), input.height()), block_size);
+    output
+}
+
+/// Redact rectangles of an image. Rectangles are clipped to the image, and may overlap.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to redact
+/// * `regions`: Rectangles to hide
+/// * `redaction`: How to hide them
+///
+/// returns: ImageBuffer
+pub fn redact(input: &ImageBuffer, regions: &[Rect], redaction: Redaction) -> ImageBuffer {
+    let bounds = Rect::new(0, 0, input.width(), input.height());
+    let mut output = input.clone();
+    for region in regions.iter().filter_map(|region| region.intersect(&bounds)) {
+        redaction.apply(&mut output, region);
+    }
+    output
+}
+
+/// Redact the parts of an image a mask covers, blending by the mask so soft edged masks give
+/// soft edged redactions
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to redact
+/// * `mask`: How much to redact each pixel, from 0 to 1, the size of the input
+/// * `redaction`: How to hide the masked parts
+///
+/// returns: ImageBuffer
+pub fn redact_mask(input: &ImageBuffer, mask: &Plane, redaction: Redaction) -> ImageBuffer {
+    assert_eq!((mask.width, mask.height), input.dimensions(), "mask must be the size of the image");
+
+    let Some(region) = mask_bounds(mask) else {
+        return input.clone();
+    };
+    let mut redacted = input.clone();
+    redaction.apply(&mut redacted, region);
+
+    ImageBuffer::from_fn(input.width(), input.height(), |x, y| {
+        let amount = mask.get(x, y).clamp(0., 1.);
+        let (original, hidden) = (input.get_pixel(x, y), redacted.get_pixel(x, y));
+        image::Rgba(std::array::from_fn(|c| (original[c] as f32 * (1. - amount) + hidden[c] as f32 * amount).round() as u8))
+    })
+}
+
+/// The smallest rectangle holding every pixel the mask covers at all
+fn mask_bounds(mask: &Plane) -> Option<Rect> {
+    let (mut left, mut top, mut right, mut bottom) = (u32::MAX, u32::MAX, 0, 0);
+    for y in 0..mask.height {
+        for x in 0..mask.width {
+            if mask.get(x, y) > 0. {
+                (left, top) = (left.min(x), top.min(y));
+                (right, bottom) = (right.max(x + 1), bottom.max(y + 1));
+            }
+        }
+    }
+    (right > left).then(|| Rect::new(left, top, right - left, bottom - top))
+}
+
+/// Replace blocks of a region with their average, the blocks starting from its top left corner
+fn pixelate_region(image: &mut ImageBuffer, region: Rect, block_size: u32) {
+    let block_size = block_size.max(1);
+
+    for block_y in (region.y..region.bottom()).step_by(block_size as usize) {
+        for block_x in (region.x..region.right()).step_by(block_size as usize) {
+            let (right, bottom) = ((block_x + block_size).min(region.right()), (block_y + block_size).min(region.bottom()));
+
+            let mut total = [0u64; 4];
+            for y in block_y..bottom {
+                for x in block_x..right {
+                    for (sum, &value) in total.iter_mut().zip(image.get_pixel(x, y).0.iter()) {
+                        *sum += value as u64;
+                    }
+                }
+            }
+
+            let count = ((right - block_x) * (bottom - block_y)) as u64;
+            let average = image::Rgba(total.map(|sum| ((sum + count / 2) / count) as u8));
+            for y in block_y..bottom {
+                for x in block_x..right {
+                    image.put_pixel(x, y, average);
+                }
+            }
+        }
+    }
+}
+
+/// Blur a region using only the pixels inside it
+fn blur_region(image: &mut ImageBuffer, region: Rect, radius: u32) {
+    let cropped = crop_imm(image, region.x, region.y, region.width, region.height).to_image();
+
+    let channels: Vec<Plane> = (0..4)
+        .map(|c| (0..BLUR_PASSES).fold(Plane::from_channel(&cropped, c), |plane, _| plane.box_blur(radius)))
+        .collect();
+    let blurred = ImageBuffer::from_fn(region.width, region.height, |x, y| {
+        image::Rgba(std::array::from_fn(|c| channels[c].get(x, y).round().clamp(0., 255.) as u8))
+    });
+
+    replace(image, &blurred, region.x as i64, region.y as i64);
+}
@@ -0,0 +1,125 @@
+//! Saliency maps, how strongly each part of an image draws the eye, for cropping to the subject
+//! and attention based analysis.
+//!
+//! Saliency is coarse by nature, so maps are computed on a small copy of the image and scaled
+//! back up, keeping them fast on large photos.
+
+use image::imageops::{resize, FilterType};
+
+use crate::fft::{fft2, Complex};
+use crate::plane::Plane;
+use crate::ImageBuffer;
+
+/// Longest side of the copy edge saliency is computed on
+const EDGE_WORKING_SIZE: u32 = 256;
+
+/// Side of the square copy the spectral residual is computed on, a power of two for the fft
+const SPECTRAL_WORKING_SIZE: u32 = 64;
+
+/// How saliency is measured
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Saliency {
+    /// Density of edges, see `edge_saliency`
+    Edges,
+    /// What stands out from the rest of the image, see `spectral_residual_saliency`
+    SpectralResidual,
+}
+
+impl Saliency {
+    pub fn from_name(name: &str) -> Option<Saliency> {
+        match name {
+            "edges" => Some(Saliency::Edges),
+            "spectral" => Some(Saliency::SpectralResidual),
+            _ => None,
+        }
+    }
+
+    /// The saliency map of an image by this measure, the size of the image from 0 to 1
+    pub fn map(&self, input: &ImageBuffer) -> Plane {
+        match self {
+            Saliency::Edges => edge_saliency(input),
+            Saliency::SpectralResidual => spectral_residual_saliency(input),
+        }
+    }
+}
+
+/// Saliency as the density of edges: the gradient magnitude of the luma averaged over a
+/// neighbourhood, so busy detailed areas score high and flat sky or walls low
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to analyse
+///
+/// returns: Plane, the size of the input, from 0 to 1 for the most salient pixel
+pub fn edge_saliency(input: &ImageBuffer) -> Plane {
+    let luma = working_luma(input, EDGE_WORKING_SIZE);
+    let (dx, dy) = luma.gradients();
+    let magnitude = dx.zip(&dy, |x, y| (x * x + y * y).sqrt());
+    let density = magnitude.gaussian_blur(luma.width.max(luma.height) as f32 / 50.);
+
+    normalise(&upsample(&density, input.width(), input.height()))
+}
+
+/// Saliency by the spectral residual (Hou and Zhang, 2007). The log amplitude spectra of natural
+/// images are close to a smooth curve, so what's left after subtracting a smoothed copy is what
+/// makes this image unusual; transforming that residual back with the original phase highlights
+/// where it comes from. Unlike edge density, repetitive texture like foliage or brickwork scores
+/// low and a lone object against it high.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to analyse
+///
+/// returns: Plane, the size of the input, from 0 to 1 for the most salient pixel
+pub fn spectral_residual_saliency(input: &ImageBuffer) -> Plane {
+    let size = SPECTRAL_WORKING_SIZE;
+    let luma = Plane::from_luma(&resize(input, size, size, FilterType::Triangle));
+
+    // The transform treats the image as tiling the plane, and the jumps where opposite edges
+    // meet would show up as salient, so fade the edges out around the mean first
+    let mean = luma.data.iter().map(|&v| v as f64).sum::<f64>() / luma.data.len() as f64;
+    let window = |i: u32| 0.5 - 0.5 * (2. * std::f64::consts::PI * (i as f64 + 0.5) / size as f64).cos();
+    let mut spectrum: Vec<Complex> = luma.data.iter().enumerate()
+        .map(|(i, &v)| Complex::new((v as f64 - mean) * window(i as u32 % size) * window(i as u32 / size), 0.))
+        .collect();
+    fft2(&mut spectrum, size as usize, size as usize, false);
+
+    let log_amplitude = Plane { data: spectrum.iter().map(|c| (c.norm() + 1e-9).ln() as f32).collect(), ..luma };
+    let smoothed = log_amplitude.box_blur(1);
+    for (i, value) in spectrum.iter_mut().enumerate() {
+        let residual = (log_amplitude.data[i] - smoothed.data[i]) as f64;
+        *value = Complex::from_angle(value.arg()).scale(residual.exp());
+    }
+    fft2(&mut spectrum, size as usize, size as usize, true);
+
+    let energy = Plane { data: spectrum.iter().map(|c| (c.norm() * c.norm()) as f32).collect(), ..luma };
+    let smoothed = energy.gaussian_blur(size as f32 / 24.);
+
+    normalise(&upsample(&smoothed, input.width(), input.height()))
+}
+
+/// The luma of a copy of the image shrunk to at most `size` on its longest side
+fn working_luma(input: &ImageBuffer, size: u32) -> Plane {
+    let scale = (size as f32 / input.width().max(input.height()) as f32).min(1.);
+    let width = ((input.width() as f32 * scale).round() as u32).max(1);
+    let height = ((input.height() as f32 * scale).round() as u32).max(1);
+    Plane::from_luma(&resize(input, width, height, FilterType::Triangle))
+}
+
+/// Stretch a plane of a working copy back to the image size, bilinearly
+fn upsample(plane: &Plane, width: u32, height: u32) -> Plane {
+    let (sx, sy) = (plane.width as f32 / width as f32, plane.height as f32 / height as f32);
+    let mut output = Plane::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            output.set(x, y, plane.sample((x as f32 + 0.5) * sx - 0.5, (y as f32 + 0.5) * sy - 0.5));
+        }
+    }
+    output
+}
+
+/// Scale a plane of non negative values so the largest is 1
+fn normalise(plane: &Plane) -> Plane {
+    let max = plane.data.iter().copied().fold(0., f32::max);
+    if max > 0. { plane.map(|v| v / max) } else { plane.clone() }
+}
@@ -0,0 +1,280 @@
+//! Splitting an image into regions of similar colour
+
+use std::collections::HashMap;
+
+use crate::plane::Plane;
+use crate::ImageBuffer;
+
+/// Mean shift stops moving a point once a step is shorter than this
+const CONVERGENCE: f32 = 0.1;
+
+/// Most mean shift steps taken for a point
+const MAX_ITERATIONS: u32 = 10;
+
+/// A region label for every pixel
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Segmentation {
+    pub width: u32,
+    pub height: u32,
+    /// Labels row by row, from 0 to `count - 1`
+    pub labels: Vec<u32>,
+    pub count: u32,
+}
+
+impl Segmentation {
+    pub fn get(&self, x: u32, y: u32) -> u32 {
+        self.labels[y as usize * self.width as usize + x as usize]
+    }
+
+    /// Number of pixels in each region
+    pub fn sizes(&self) -> Vec<u64> {
+        let mut sizes = vec![0; self.count as usize];
+        for &label in &self.labels {
+            sizes[label as usize] += 1;
+        }
+        sizes
+    }
+
+    /// Paint each region with its mean colour in an image, to view the segmentation
+    pub fn mean_colours(&self, input: &ImageBuffer) -> ImageBuffer {
+        let mut totals = vec![[0u64; 4]; self.count as usize];
+        for (pixel, &label) in input.pixels().zip(&self.labels) {
+            for (total, &value) in totals[label as usize].iter_mut().zip(pixel.0.iter()) {
+                *total += value as u64;
+            }
+        }
+
+        let sizes = self.sizes();
+        let means: Vec<image::Rgba<u8>> = totals.iter().zip(&sizes).map(|(total, &size)| image::Rgba(total.map(|t| (t / size.max(1)) as u8))).collect();
+        ImageBuffer::from_fn(self.width, self.height, |x, y| means[self.get(x, y) as usize])
+    }
+}
+
+/// Mean shift filtering (Comaniciu and Meer, 2002): each pixel is moved, in position and
+/// colour together, to the mean of the pixels within both bandwidths, over and over until it
+/// settles on a mode of the joint distribution, and takes the colour of that mode. Areas
+/// flatten to their dominant colour while edges stronger than the range bandwidth are kept.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to filter
+/// * `spatial_bandwidth`: How far in pixels each step looks, e.g. 8
+/// * `range_bandwidth`: How different in r,g,b distance colours can be and still be averaged,
+///   e.g. 16
+///
+/// returns: ImageBuffer
+pub fn mean_shift_filter(input: &ImageBuffer, spatial_bandwidth: u32, range_bandwidth: f32) -> ImageBuffer {
+    let modes = mean_shift_modes(input, spatial_bandwidth, range_bandwidth);
+    ImageBuffer::from_fn(input.width(), input.height(), |x, y| {
+        let mode = modes[y as usize * input.width() as usize + x as usize];
+        image::Rgba([mode[0].round() as u8, mode[1].round() as u8, mode[2].round() as u8, input.get_pixel(x, y)[3]])
+    })
+}
+
+/// Segment an image by mean shift: pixels are filtered as in `mean_shift_filter`, neighbours
+/// that settled on modes closer than half the range bandwidth are joined into regions, and
+/// regions smaller than `min_region` are merged into the neighbour nearest in colour.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to segment
+/// * `spatial_bandwidth`: How far in pixels each mean shift step looks, e.g. 8
+/// * `range_bandwidth`: How different in r,g,b distance colours can be and still be averaged,
+///   e.g. 16
+/// * `min_region`: Fewest pixels a region may have, e.g. 50
+///
+/// returns: Segmentation
+pub fn mean_shift_segmentation(input: &ImageBuffer, spatial_bandwidth: u32, range_bandwidth: f32, min_region: u64) -> Segmentation {
+    let (width, height) = (input.width() as usize, input.height() as usize);
+    let modes = mean_shift_modes(input, spatial_bandwidth, range_bandwidth);
+
+    let mut regions = DisjointSet::new(width * height);
+    let join_distance = (range_bandwidth / 2.).powi(2);
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            if x + 1 < width && colour_distance(&modes[i], &modes[i + 1]) < join_distance {
+                regions.union(i, i + 1);
+            }
+            if y + 1 < height && colour_distance(&modes[i], &modes[i + width]) < join_distance {
+                regions.union(i, i + width);
+            }
+        }
+    }
+
+    merge_small_regions(&mut regions, &modes, width, height, min_region);
+
+    // Number the regions from zero in scan order
+    let mut numbering = HashMap::new();
+    let labels = (0..width * height)
+        .map(|i| {
+            let next = numbering.len() as u32;
+            *numbering.entry(regions.find(i)).or_insert(next)
+        })
+        .collect();
+
+    Segmentation { width: input.width(), height: input.height(), labels, count: numbering.len() as u32 }
+}
+
+/// Label the separate parts of a mask, pixels from 0.5 touching along an edge or at a corner
+/// being parts of the same region
+///
+/// # Arguments
+///
+/// * `mask`: Mask plane, set from 0.5
+///
+/// returns: Segmentation, with label 0 for the unset pixels and the parts from 1
+pub fn connected_components(mask: &Plane) -> Segmentation {
+    let (width, height) = (mask.width as i64, mask.height as i64);
+    let mut labels = vec![0; mask.data.len()];
+    let mut count = 1;
+    let mut stack = Vec::new();
+
+    for start in 0..labels.len() {
+        if mask.data[start] < 0.5 || labels[start] != 0 {
+            continue;
+        }
+
+        labels[start] = count;
+        stack.push(start);
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i as i64 % width, i as i64 / width);
+            for (dx, dy) in [(-1, -1), (0, -1), (1, -1), (-1, 0), (1, 0), (-1, 1), (0, 1), (1, 1)] {
+                let (nx, ny) = (x + dx, y + dy);
+                if nx < 0 || ny < 0 || nx >= width || ny >= height {
+                    continue;
+                }
+                let j = (ny * width + nx) as usize;
+                if mask.data[j] >= 0.5 && labels[j] == 0 {
+                    labels[j] = count;
+                    stack.push(j);
+                }
+            }
+        }
+        count += 1;
+    }
+
+    Segmentation { width: mask.width, height: mask.height, labels, count }
+}
+
+/// The r,g,b mode each pixel settles on
+fn mean_shift_modes(input: &ImageBuffer, spatial_bandwidth: u32, range_bandwidth: f32) -> Vec<[f32; 3]> {
+    let (width, height) = input.dimensions();
+    let colour = |x: i64, y: i64| {
+        let pixel = input.get_pixel(x as u32, y as u32);
+        [pixel[0] as f32, pixel[1] as f32, pixel[2] as f32]
+    };
+    let radius = spatial_bandwidth as i64;
+    let range = range_bandwidth * range_bandwidth;
+
+    let mut modes = Vec::with_capacity((width * height) as usize);
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            let (mut px, mut py, mut pc) = (x as f32, y as f32, colour(x, y));
+
+            for _ in 0..MAX_ITERATIONS {
+                let (cx, cy) = (px.round() as i64, py.round() as i64);
+                let (mut sx, mut sy, mut sc, mut n) = (0f32, 0f32, [0f32; 3], 0f32);
+
+                for ny in (cy - radius).max(0)..=(cy + radius).min(height as i64 - 1) {
+                    for nx in (cx - radius).max(0)..=(cx + radius).min(width as i64 - 1) {
+                        let c = colour(nx, ny);
+                        if colour_distance(&c, &pc) < range {
+                            (sx, sy, n) = (sx + nx as f32, sy + ny as f32, n + 1.);
+                            sc = [sc[0] + c[0], sc[1] + c[1], sc[2] + c[2]];
+                        }
+                    }
+                }
+                if n == 0. {
+                    break;
+                }
+
+                let (nx, ny, nc) = (sx / n, sy / n, sc.map(|v| v / n));
+                let shift = (nx - px).powi(2) + (ny - py).powi(2) + colour_distance(&nc, &pc);
+                (px, py, pc) = (nx, ny, nc);
+                if shift < CONVERGENCE * CONVERGENCE {
+                    break;
+                }
+            }
+
+            modes.push(pc);
+        }
+    }
+
+    modes
+}
+
+/// Merge each region under the minimum size into its neighbour with the nearest mean colour,
+/// until none are left or the image is one region
+fn merge_small_regions(regions: &mut DisjointSet, modes: &[[f32; 3]], width: usize, height: usize, min_region: u64) {
+    loop {
+        let mut sizes: HashMap<usize, (u64, [f32; 3])> = HashMap::new();
+        for (i, mode) in modes.iter().enumerate() {
+            let entry = sizes.entry(regions.find(i)).or_insert((0, [0.; 3]));
+            entry.0 += 1;
+            entry.1 = [entry.1[0] + mode[0], entry.1[1] + mode[1], entry.1[2] + mode[2]];
+        }
+        let mean = |root: usize| {
+            let (size, total) = sizes[&root];
+            total.map(|t| t / size as f32)
+        };
+
+        // The nearest coloured neighbour of each small region
+        let mut nearest: HashMap<usize, (usize, f32)> = HashMap::new();
+        for y in 0..height {
+            for x in 0..width {
+                let i = y * width + x;
+                let neighbours = [(x + 1 < width).then_some(i + 1), (y + 1 < height).then_some(i + width)];
+                for j in neighbours.into_iter().flatten() {
+                    let (a, b) = (regions.find(i), regions.find(j));
+                    if a == b {
+                        continue;
+                    }
+                    let distance = colour_distance(&mean(a), &mean(b));
+                    for (small, other) in [(a, b), (b, a)] {
+                        if sizes[&small].0 < min_region && nearest.get(&small).is_none_or(|&(_, d)| distance < d) {
+                            nearest.insert(small, (other, distance));
+                        }
+                    }
+                }
+            }
+        }
+
+        if nearest.is_empty() {
+            return;
+        }
+        for (small, (other, _)) in nearest {
+            regions.union(small, other);
+        }
+    }
+}
+
+fn colour_distance(a: &[f32; 3], b: &[f32; 3]) -> f32 {
+    (a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)
+}
+
+/// Union find over pixel indices, with path halving
+struct DisjointSet {
+    parents: Vec<usize>,
+}
+
+impl DisjointSet {
+    fn new(size: usize) -> DisjointSet {
+        DisjointSet { parents: (0..size).collect() }
+    }
+
+    fn find(&mut self, mut i: usize) -> usize {
+        while self.parents[i] != i {
+            self.parents[i] = self.parents[self.parents[i]];
+            i = self.parents[i];
+        }
+        i
+    }
+
+    fn union(&mut self, a: usize, b: usize) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a != b {
+            self.parents[a.max(b)] = a.min(b);
+        }
+    }
+}
@@ -0,0 +1,317 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use rust_image_processing::io::{from_bytes, to_bytes, OutputFormat};
+use rust_image_processing::pipeline::PipelineFormat;
+use rust_image_processing::{Operation, Pipeline};
+
+/// Cap on the request line and headers together, on top of the body limit
+const MAX_HEAD_BYTES: usize = 64 * 1024;
+
+/// Cap on the json pipeline, well short of the headers' limit
+const MAX_PIPELINE_BYTES: usize = 8 * 1024;
+
+/// Caps on the work one request can ask for, so it can't tie up a connection for minutes
+const MAX_STEPS: usize = 32;
+const MAX_MEDIAN_WINDOW: u32 = 8;
+const MAX_SHARPEN_RADIUS: f32 = 16.;
+const MAX_MATRIX_SIZE: usize = 15;
+const MAX_PIXELS: u64 = 50_000_000;
+
+/// Limits on what the server will take on at once
+pub struct ServerConfig {
+    pub max_connections: usize,
+    pub max_body_bytes: usize,
+    pub timeout: Duration,
+}
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(key, _)| key.eq_ignore_ascii_case(name)).map(|(_, value)| value.as_str())
+    }
+
+    fn query(&self, name: &str) -> Option<&str> {
+        self.query.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+}
+
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn text(status: u16, message: impl Into<String>) -> Response {
+        let mut body = message.into().into_bytes();
+        body.push(b'\n');
+        Response { status, content_type: "text/plain; charset=utf-8", body }
+    }
+}
+
+/// Counts a connection against the limit for as long as it's alive
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Serve `POST /process` until the process is stopped, each connection on its own thread. The
+/// body is an encoded image and the response the processed png, with the json pipeline given in
+/// the `X-Pipeline` header or the `pipeline` query parameter. `GET /health` answers `ok`.
+///
+/// # Arguments
+///
+/// * `address`: Address to listen on, e.g. `127.0.0.1:8080`
+/// * `config`: Connection, size and time limits
+///
+/// returns: std::io::Result<()>, only returning if the address can't be bound
+pub fn serve(address: &str, config: ServerConfig) -> std::io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    info!("Listening on http://{}", listener.local_addr()?);
+
+    let config = Arc::new(config);
+    let connections = Arc::new(AtomicUsize::new(0));
+
+    for stream in listener.incoming() {
+        let mut stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                warn!("Failed to accept connection: {}", err);
+                continue;
+            }
+        };
+
+        if connections.fetch_add(1, Ordering::SeqCst) >= config.max_connections {
+            connections.fetch_sub(1, Ordering::SeqCst);
+            write_response(&mut stream, Response::text(503, "too many concurrent requests")).ok();
+            continue;
+        }
+
+        let slot = ConnectionSlot(connections.clone());
+        let config = config.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            handle_connection(stream, &config);
+        });
+    }
+
+    Ok(())
+}
+
+fn handle_connection(mut stream: TcpStream, config: &ServerConfig) {
+    stream.set_read_timeout(Some(config.timeout)).ok();
+    stream.set_write_timeout(Some(config.timeout)).ok();
+
+    let response = match read_request(&mut stream, config.max_body_bytes) {
+        Ok(request) => {
+            // A bug in an operation shouldn't take the connection's thread down without a reply
+            let response = catch_unwind(AssertUnwindSafe(|| route(&request))).unwrap_or_else(|_| Response::text(500, "internal error"));
+            debug!("{} {} -> {}", request.method, request.path, response.status);
+            response
+        }
+        Err(response) => response,
+    };
+
+    if let Err(err) = write_response(&mut stream, response) {
+        debug!("Failed to write response: {}", err);
+    }
+}
+
+fn route(request: &Request) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/health") => Response::text(200, "ok"),
+        ("POST", "/process") => process(request),
+        (_, "/health") | (_, "/process") => Response::text(405, "method not allowed"),
+        _ => Response::text(404, "not found"),
+    }
+}
+
+fn process(request: &Request) -> Response {
+    let source = match request.header("x-pipeline").or_else(|| request.query("pipeline")) {
+        Some(source) => source,
+        None => return Response::text(400, "missing pipeline, give it in the X-Pipeline header or ?pipeline="),
+    };
+    if source.len() > MAX_PIPELINE_BYTES {
+        return Response::text(413, format!("pipeline is longer than the {} byte limit", MAX_PIPELINE_BYTES));
+    }
+    let pipeline = match Pipeline::parse(source, PipelineFormat::Json) {
+        Ok(pipeline) => pipeline,
+        Err(err) => return Response::text(400, format!("invalid pipeline: {}", err)),
+    };
+    if let Err(message) = check_limits(&pipeline) {
+        return Response::text(400, format!("pipeline is too expensive: {}", message));
+    }
+
+    // Check the size from the header before decoding, as a small file can decode to a huge image
+    let dimensions = image::io::Reader::new(std::io::Cursor::new(&request.body)).with_guessed_format().ok().and_then(|reader| reader.into_dimensions().ok());
+    if let Some((width, height)) = dimensions {
+        if width as u64 * height as u64 > MAX_PIXELS {
+            return Response::text(413, format!("image is larger than the {} pixel limit", MAX_PIXELS));
+        }
+    }
+
+    let input = match from_bytes(&request.body) {
+        Ok(input) => input,
+        Err(err) => return Response::text(400, format!("invalid image: {}", err)),
+    };
+
+    let output = pipeline.run(&input);
+
+    match to_bytes(&output, OutputFormat::Png) {
+        Ok(body) => Response { status: 200, content_type: "image/png", body },
+        Err(err) => Response::text(500, format!("failed to encode image: {}", err)),
+    }
+}
+
+/// Check a pipeline against the caps on steps and on each operation's window or radius
+fn check_limits(pipeline: &Pipeline) -> Result<(), String> {
+    if pipeline.operations().len() > MAX_STEPS {
+        return Err(format!("more than {} steps", MAX_STEPS));
+    }
+
+    for operation in pipeline.operations() {
+        match operation {
+            Operation::MedianFilter(window) if *window > MAX_MEDIAN_WINDOW => return Err(format!("median_filter window over {}", MAX_MEDIAN_WINDOW)),
+            Operation::AdaptiveSharpen { radius, .. } if *radius > MAX_SHARPEN_RADIUS => {
+                return Err(format!("adaptive_sharpen radius over {}", MAX_SHARPEN_RADIUS));
+            }
+            Operation::ApplyMatrix(matrix) if matrix.shape().iter().any(|&size| size > MAX_MATRIX_SIZE) => {
+                return Err(format!("apply_matrix larger than {0}x{0}", MAX_MATRIX_SIZE));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+fn read_request(stream: &mut TcpStream, max_body_bytes: usize) -> Result<Request, Response> {
+    let mut reader = BufReader::new(stream.take((MAX_HEAD_BYTES + max_body_bytes) as u64));
+    let bad_request = |message: &str| Response::text(400, message);
+
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|_| bad_request("failed to read request"))?;
+    let mut parts = line.split_whitespace();
+    let (method, target) = match (parts.next(), parts.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => return Err(bad_request("malformed request line")),
+    };
+
+    let mut headers = Vec::new();
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(|_| bad_request("failed to read headers"))?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if headers.len() >= 100 {
+            return Err(Response::text(431, "too many headers"));
+        }
+
+        match header.split_once(':') {
+            Some((name, value)) => headers.push((name.trim().to_string(), value.trim().to_string())),
+            None => return Err(bad_request("malformed header")),
+        }
+    }
+
+    let (path, query) = match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), parse_query(query)),
+        None => (target, Vec::new()),
+    };
+    let mut request = Request { method, path, query, headers, body: Vec::new() };
+
+    let length = match request.header("content-length") {
+        Some(length) => length.parse::<usize>().map_err(|_| bad_request("invalid content-length"))?,
+        None if request.method == "POST" => return Err(Response::text(411, "content-length required")),
+        None => 0,
+    };
+    if length > max_body_bytes {
+        return Err(Response::text(413, format!("body is larger than the {} byte limit", max_body_bytes)));
+    }
+
+    request.body = vec![0; length];
+    reader.read_exact(&mut request.body).map_err(|_| bad_request("body shorter than content-length"))?;
+
+    Ok(request)
+}
+
+fn write_response(stream: &mut TcpStream, response: Response) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        reason(response.status),
+        response.content_type,
+        response.body.len()
+    )?;
+    stream.write_all(&response.body)?;
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once('=') {
+            Some((key, value)) => (percent_decode(key), percent_decode(value)),
+            None => (percent_decode(pair), String::new()),
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' if i + 2 < bytes.len() => {
+                let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).ok();
+                match hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        decoded.push(byte);
+                        i += 2;
+                    }
+                    None => decoded.push(b'%'),
+                }
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).into_owned()
+}
@@ -0,0 +1,306 @@
+//! Describing the shapes of regions in a mask, e.g. the parts from
+//! `segmentation::connected_components`, for telling shapes apart by simple rules
+
+use crate::error::Result;
+use crate::plane::Plane;
+use crate::records::{field, invalid, number, point_from_value, points_from_value, points_to_value, Record};
+use crate::rect::Rect;
+use crate::segmentation::Segmentation;
+use crate::value::Value;
+
+/// Steps to the eight neighbours of a pixel, clockwise with y down starting from the west
+const NEIGHBOURS: [(i64, i64); 8] = [(-1, 0), (-1, -1), (0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1)];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShapeDescriptors {
+    pub label: u32,
+    /// Pixels in the region
+    pub area: f64,
+    /// Length of the outer contour, a step to a side neighbour counting 1 and a corner √2
+    pub perimeter: f64,
+    pub centroid: (f64, f64),
+    pub bounding_box: Rect,
+    /// The outer boundary pixels in order, clockwise with y down from the top left pixel
+    pub contour: Vec<(u32, u32)>,
+    /// Convex hull around the pixels' outer corners, clockwise with y down
+    pub convex_hull: Vec<(f64, f64)>,
+    pub convex_area: f64,
+    /// Area over convex area, 1 for convex shapes and lower for shapes with dents or holes
+    pub solidity: f64,
+    /// `4π area / perimeter²`, near 1 for a disc and lower for longer or rougher shapes
+    pub circularity: f64,
+    /// Eccentricity of the ellipse with the same second moments, 0 for a disc, approaching 1
+    /// for a line
+    pub eccentricity: f64,
+    /// Angle of the major axis in radians, clockwise from the x axis with y down
+    pub orientation: f64,
+    /// The seven Hu moments, unchanged by moving, scaling and rotating the shape, with the
+    /// seventh changing sign for a mirror image
+    pub hu_moments: [f64; 7],
+}
+
+impl Record for ShapeDescriptors {
+    const NAME: &'static str = "shape";
+
+    fn to_value(&self) -> Value {
+        let number = |v: f64| Value::Number(v);
+        Value::Table(vec![
+            ("label".to_string(), number(self.label as f64)),
+            ("area".to_string(), number(self.area)),
+            ("perimeter".to_string(), number(self.perimeter)),
+            ("centroid".to_string(), Value::Array(vec![number(self.centroid.0), number(self.centroid.1)])),
+            ("bounding_box".to_string(), self.bounding_box.to_value()),
+            ("contour".to_string(), points_to_value(&self.contour)),
+            ("convex_hull".to_string(), points_to_value(&self.convex_hull)),
+            ("convex_area".to_string(), number(self.convex_area)),
+            ("solidity".to_string(), number(self.solidity)),
+            ("circularity".to_string(), number(self.circularity)),
+            ("eccentricity".to_string(), number(self.eccentricity)),
+            ("orientation".to_string(), number(self.orientation)),
+            ("hu_moments".to_string(), Value::Array(self.hu_moments.iter().map(|&v| number(v)).collect())),
+        ])
+    }
+
+    fn from_value(value: &Value) -> Result<ShapeDescriptors> {
+        let number = |key: &str| number(value, Self::NAME, key);
+        let points = |key: &str| points_from_value(field(value, Self::NAME, key)?, Self::NAME, key);
+        let hu_moments: Vec<f64> = field(value, Self::NAME, "hu_moments")?.as_array().into_iter().flatten().filter_map(Value::as_f64).collect();
+
+        Ok(ShapeDescriptors {
+            label: number("label")? as u32,
+            area: number("area")?,
+            perimeter: number("perimeter")?,
+            centroid: point_from_value(field(value, Self::NAME, "centroid")?, Self::NAME, "centroid")?,
+            bounding_box: Rect::from_value(field(value, Self::NAME, "bounding_box")?)?,
+            contour: points("contour")?.into_iter().map(|(x, y)| (x as u32, y as u32)).collect(),
+            convex_hull: points("convex_hull")?,
+            convex_area: number("convex_area")?,
+            solidity: number("solidity")?,
+            circularity: number("circularity")?,
+            eccentricity: number("eccentricity")?,
+            orientation: number("orientation")?,
+            hu_moments: hu_moments.try_into().map_err(|_| invalid(Self::NAME, "hu_moments"))?,
+        })
+    }
+}
+
+/// Describe the shape of every region of a segmentation but label 0, taken as the background
+/// as in `connected_components`
+///
+/// # Arguments
+///
+/// * `segmentation`: Labelled regions
+///
+/// returns: Vec<ShapeDescriptors>, in label order
+pub fn describe_regions(segmentation: &Segmentation) -> Vec<ShapeDescriptors> {
+    let mut pixels = vec![Vec::new(); segmentation.count as usize];
+    for y in 0..segmentation.height {
+        for x in 0..segmentation.width {
+            pixels[segmentation.get(x, y) as usize].push((x, y));
+        }
+    }
+
+    pixels.iter().enumerate().skip(1).filter(|(_, p)| !p.is_empty()).map(|(label, p)| describe(segmentation, label as u32, p)).collect()
+}
+
+/// Thin a mask to lines one pixel wide along the middle of its shapes, by Zhang and Suen's
+/// method (1984), peeling off boundary pixels that don't break a shape apart or shorten a line
+/// until none are left
+///
+/// # Arguments
+///
+/// * `mask`: Mask plane, set from 0.5
+///
+/// returns: Plane, 1 on the skeleton and 0 elsewhere
+pub fn thin(mask: &Plane) -> Plane {
+    let (width, height) = (mask.width as i64, mask.height as i64);
+    let mut set: Vec<bool> = mask.data.iter().map(|&v| v >= 0.5).collect();
+    let at = |set: &[bool], x: i64, y: i64| x >= 0 && y >= 0 && x < width && y < height && set[(y * width + x) as usize];
+
+    loop {
+        let mut changed = false;
+        for pass in 0..2 {
+            let mut remove = Vec::new();
+            for y in 0..height {
+                for x in 0..width {
+                    if !at(&set, x, y) {
+                        continue;
+                    }
+                    // Neighbours clockwise from north, p2 to p9 in the paper
+                    let p = [(0, -1), (1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1)].map(|(dx, dy)| at(&set, x + dx, y + dy));
+                    let neighbours = p.iter().filter(|&&v| v).count();
+                    let transitions = (0..8).filter(|&i| !p[i] && p[(i + 1) % 8]).count();
+                    let (north, east, south, west) = (p[0], p[2], p[4], p[6]);
+                    // The first pass peels the south east side and north west corner, the second the
+                    // opposite, so shapes thin evenly towards the middle
+                    let sides = if pass == 0 { !(east && south && (north || west)) } else { !(north && west && (east || south)) };
+                    if (2..=6).contains(&neighbours) && transitions == 1 && sides {
+                        remove.push((y * width + x) as usize);
+                    }
+                }
+            }
+            changed |= !remove.is_empty();
+            for i in remove {
+                set[i] = false;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    Plane { width: mask.width, height: mask.height, data: set.iter().map(|&v| if v { 1. } else { 0. }).collect() }
+}
+
+/// Convex hull by the monotone chain, in order around the hull
+pub fn convex_hull(mut points: Vec<(f64, f64)>) -> Vec<(f64, f64)> {
+    points.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.total_cmp(&b.1)));
+    points.dedup();
+    if points.len() < 3 {
+        return points;
+    }
+
+    let cross = |o: (f64, f64), a: (f64, f64), b: (f64, f64)| (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0);
+    let chain = |points: &mut dyn Iterator<Item = &(f64, f64)>| {
+        let mut chain: Vec<(f64, f64)> = Vec::new();
+        for &point in points {
+            while chain.len() >= 2 && cross(chain[chain.len() - 2], chain[chain.len() - 1], point) <= 0. {
+                chain.pop();
+            }
+            chain.push(point);
+        }
+        // The last point starts the other chain
+        chain.pop();
+        chain
+    };
+
+    let mut hull = chain(&mut points.iter());
+    hull.extend(chain(&mut points.iter().rev()));
+    hull
+}
+
+/// Area of a simple polygon by the shoelace formula
+pub fn polygon_area(points: &[(f64, f64)]) -> f64 {
+    let twice: f64 = (0..points.len()).map(|i| {
+        let (a, b) = (points[i], points[(i + 1) % points.len()]);
+        a.0 * b.1 - b.0 * a.1
+    }).sum();
+    twice.abs() / 2.
+}
+
+fn describe(segmentation: &Segmentation, label: u32, pixels: &[(u32, u32)]) -> ShapeDescriptors {
+    let area = pixels.len() as f64;
+    let (cx, cy) = (pixels.iter().map(|p| p.0 as f64).sum::<f64>() / area, pixels.iter().map(|p| p.1 as f64).sum::<f64>() / area);
+
+    // Central moments up to the third order
+    let mut mu = [[0f64; 4]; 4];
+    for &(x, y) in pixels {
+        let (dx, dy) = (x as f64 - cx, y as f64 - cy);
+        for (p, row) in mu.iter_mut().enumerate() {
+            for (q, value) in row.iter_mut().enumerate().take(4 - p) {
+                *value += dx.powi(p as i32) * dy.powi(q as i32);
+            }
+        }
+    }
+
+    let (min_x, max_x) = (pixels.iter().map(|p| p.0).min().unwrap_or(0), pixels.iter().map(|p| p.0).max().unwrap_or(0));
+    let (min_y, max_y) = (pixels.iter().map(|p| p.1).min().unwrap_or(0), pixels.iter().map(|p| p.1).max().unwrap_or(0));
+
+    let contour = trace_contour(segmentation, label, pixels[0]);
+    let perimeter: f64 = if contour.len() < 2 {
+        0.
+    } else {
+        (0..contour.len())
+            .map(|i| {
+                let (a, b) = (contour[i], contour[(i + 1) % contour.len()]);
+                if a.0 != b.0 && a.1 != b.1 {
+                    std::f64::consts::SQRT_2
+                } else {
+                    1.
+                }
+            })
+            .sum()
+    };
+
+    let corners = contour.iter().flat_map(|&(x, y)| [(-0.5, -0.5), (0.5, -0.5), (0.5, 0.5), (-0.5, 0.5)].map(|(dx, dy)| (x as f64 + dx, y as f64 + dy))).collect();
+    let convex_hull = convex_hull(corners);
+    let convex_area = polygon_area(&convex_hull);
+
+    // The covariance's eigenvalues are the squared semi axes of the equivalent ellipse, over four
+    let (xx, xy, yy) = (mu[2][0] / area, mu[1][1] / area, mu[0][2] / area);
+    let spread = ((xx - yy).powi(2) + 4. * xy * xy).sqrt();
+    let (major, minor) = ((xx + yy + spread) / 2., (xx + yy - spread) / 2.);
+
+    ShapeDescriptors {
+        label,
+        area,
+        perimeter,
+        centroid: (cx, cy),
+        bounding_box: Rect::new(min_x, min_y, max_x - min_x + 1, max_y - min_y + 1),
+        contour,
+        convex_area,
+        solidity: if convex_area > 0. { (area / convex_area).min(1.) } else { 1. },
+        convex_hull,
+        circularity: if perimeter > 0. { (4. * std::f64::consts::PI * area / (perimeter * perimeter)).min(1.) } else { 1. },
+        eccentricity: if major > 0. { (1. - minor / major).max(0.).sqrt() } else { 0. },
+        orientation: 0.5 * (2. * xy).atan2(xx - yy),
+        hu_moments: hu_moments(&mu, area),
+    }
+}
+
+/// Hu's seven moment invariants from the central moments, normalised for scale
+fn hu_moments(mu: &[[f64; 4]; 4], area: f64) -> [f64; 7] {
+    let eta = |p: usize, q: usize| mu[p][q] / area.powf(1. + (p + q) as f64 / 2.);
+    let (n20, n02, n11) = (eta(2, 0), eta(0, 2), eta(1, 1));
+    let (n30, n03, n21, n12) = (eta(3, 0), eta(0, 3), eta(2, 1), eta(1, 2));
+    let (a, b) = (n30 + n12, n21 + n03);
+
+    [
+        n20 + n02,
+        (n20 - n02).powi(2) + 4. * n11 * n11,
+        (n30 - 3. * n12).powi(2) + (3. * n21 - n03).powi(2),
+        a * a + b * b,
+        (n30 - 3. * n12) * a * (a * a - 3. * b * b) + (3. * n21 - n03) * b * (3. * a * a - b * b),
+        (n20 - n02) * (a * a - b * b) + 4. * n11 * a * b,
+        (3. * n21 - n03) * a * (a * a - 3. * b * b) - (n30 - 3. * n12) * b * (3. * a * a - b * b),
+    ]
+}
+
+/// Follow the outer boundary of a region by Moore neighbour tracing, from its first pixel in
+/// scan order, which has no region pixel to its west or above
+fn trace_contour(segmentation: &Segmentation, label: u32, start: (u32, u32)) -> Vec<(u32, u32)> {
+    let inside = |x: i64, y: i64| x >= 0 && y >= 0 && x < segmentation.width as i64 && y < segmentation.height as i64 && segmentation.get(x as u32, y as u32) == label;
+    let step = |(x, y): (i64, i64), direction: usize| (x + NEIGHBOURS[direction].0, y + NEIGHBOURS[direction].1);
+
+    let start = (start.0 as i64, start.1 as i64);
+    let mut contour = vec![start];
+    let (mut current, mut backtrack) = (start, 0);
+    let mut second = None;
+
+    // At most every pixel's side is walked past, so bound the walk by that
+    for _ in 0..8 * (segmentation.width as usize * segmentation.height as usize + 1) {
+        let Some(turn) = (1..=8).find(|&k| {
+            let (x, y) = step(current, (backtrack + k) % 8);
+            inside(x, y)
+        }) else {
+            // A lone pixel
+            break;
+        };
+
+        let direction = (backtrack + turn) % 8;
+        let next = step(current, direction);
+        // Look on next from the last outside neighbour checked, which is beside it
+        let outside = step(current, (direction + 7) % 8);
+        backtrack = NEIGHBOURS.iter().position(|&d| (next.0 + d.0, next.1 + d.1) == outside).unwrap_or(0);
+
+        if current == start && second == Some(next) {
+            contour.pop();
+            break;
+        }
+        second.get_or_insert(next);
+        contour.push(next);
+        current = next;
+    }
+
+    contour.into_iter().map(|(x, y)| (x as u32, y as u32)).collect()
+}
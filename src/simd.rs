@@ -0,0 +1,196 @@
+//! SSE2 versions of the hot per pixel loops, enabled with the `simd` feature on x86_64 (where
+//! SSE2 is always available). Images are worked on 16 bytes, so 4 rgba pixels, at a time with
+//! any remaining pixels done by the scalar pixel functions. Results match the scalar code
+//! exactly, alpha is carried through from the first image as in `pixel_add` and friends.
+
+use std::arch::x86_64::*;
+
+use ndarray::Array2;
+
+use crate::pixel::{pixel_add, pixel_scale, pixel_shift, pixel_sub};
+use crate::ImageBuffer;
+
+/// Run a 16 byte operation over `len` bytes, where `out` may be the same buffer as `a`
+///
+/// # Safety
+///
+/// `a`, `b` and `out` must each be valid for `len` bytes, with `len` a multiple of 4
+unsafe fn zip_pixels(
+    a: *const u8,
+    b: *const u8,
+    out: *mut u8,
+    len: usize,
+    simd: impl Fn(__m128i, __m128i) -> __m128i,
+    scalar: impl Fn(image::Rgba<u8>, image::Rgba<u8>) -> image::Rgba<u8>,
+) {
+    let alpha = _mm_set1_epi32(0xff00_0000_u32 as i32);
+    let chunks = len / 16;
+
+    for chunk in 0..chunks {
+        let offset = chunk * 16;
+        let a_chunk = _mm_loadu_si128(a.add(offset) as *const __m128i);
+        let b_chunk = _mm_loadu_si128(b.add(offset) as *const __m128i);
+
+        let result = simd(a_chunk, b_chunk);
+        let result = _mm_or_si128(_mm_andnot_si128(alpha, result), _mm_and_si128(alpha, a_chunk));
+
+        _mm_storeu_si128(out.add(offset) as *mut __m128i, result);
+    }
+
+    for offset in (chunks * 16..len).step_by(4) {
+        let a_pixel = image::Rgba([*a.add(offset), *a.add(offset + 1), *a.add(offset + 2), *a.add(offset + 3)]);
+        let b_pixel = image::Rgba([*b.add(offset), *b.add(offset + 1), *b.add(offset + 2), *b.add(offset + 3)]);
+
+        let result = scalar(a_pixel, b_pixel);
+        for channel in 0..4 {
+            *out.add(offset + channel) = result[channel];
+        }
+    }
+}
+
+fn check_lengths(a: &[u8], b: &[u8]) {
+    assert_eq!(a.len(), b.len(), "images must be the same size");
+}
+
+/// Saturating per channel addition, `pixel_add` on every pixel
+pub fn add(a: &ImageBuffer, b: &ImageBuffer, out: &mut ImageBuffer) {
+    check_lengths(a, b);
+    check_lengths(a, out);
+    unsafe { zip_pixels(a.as_ptr(), b.as_ptr(), out.as_mut_ptr(), a.len(), |a, b| _mm_adds_epu8(a, b), pixel_add) }
+}
+
+pub fn add_in_place(a: &mut ImageBuffer, b: &ImageBuffer) {
+    check_lengths(a, b);
+    let a = a.as_mut_ptr();
+    unsafe { zip_pixels(a, b.as_ptr(), a, b.len(), |a, b| _mm_adds_epu8(a, b), pixel_add) }
+}
+
+/// Saturating per channel subtraction, `pixel_sub` on every pixel
+pub fn sub(a: &ImageBuffer, b: &ImageBuffer, out: &mut ImageBuffer) {
+    check_lengths(a, b);
+    check_lengths(a, out);
+    unsafe { zip_pixels(a.as_ptr(), b.as_ptr(), out.as_mut_ptr(), a.len(), |a, b| _mm_subs_epu8(a, b), pixel_sub) }
+}
+
+pub fn sub_in_place(a: &mut ImageBuffer, b: &ImageBuffer) {
+    check_lengths(a, b);
+    let a = a.as_mut_ptr();
+    unsafe { zip_pixels(a, b.as_ptr(), a, b.len(), |a, b| _mm_subs_epu8(a, b), pixel_sub) }
+}
+
+/// Shift the r,g,b values by a constant, `pixel_shift` on every pixel
+pub fn shift(a: &ImageBuffer, value: i32, out: &mut ImageBuffer) {
+    check_lengths(a, out);
+    let a_ptr = a.as_ptr();
+    unsafe { shift_raw(a_ptr, out.as_mut_ptr(), a.len(), value) }
+}
+
+pub fn shift_in_place(a: &mut ImageBuffer, value: i32) {
+    let len = a.len();
+    let a = a.as_mut_ptr();
+    unsafe { shift_raw(a, a, len, value) }
+}
+
+unsafe fn shift_raw(a: *const u8, out: *mut u8, len: usize, value: i32) {
+    let amount = _mm_set1_epi8(value.unsigned_abs().min(255) as u8 as i8);
+
+    if value >= 0 {
+        zip_pixels(a, a, out, len, |a, _| _mm_adds_epu8(a, amount), |a, _| pixel_shift(a, value));
+    } else {
+        zip_pixels(a, a, out, len, |a, _| _mm_subs_epu8(a, amount), |a, _| pixel_shift(a, value));
+    }
+}
+
+/// Scale the r,g,b values by a constant, `pixel_scale` on every pixel
+pub fn scale(a: &ImageBuffer, value: f32, out: &mut ImageBuffer) {
+    check_lengths(a, out);
+    let a_ptr = a.as_ptr();
+    unsafe { zip_pixels(a_ptr, a_ptr, out.as_mut_ptr(), a.len(), |a, _| scale_chunk(a, value), |a, _| pixel_scale(a, value)) }
+}
+
+pub fn scale_in_place(a: &mut ImageBuffer, value: f32) {
+    let len = a.len();
+    let a = a.as_mut_ptr();
+    unsafe { zip_pixels(a, a, a, len, |a, _| scale_chunk(a, value), |a, _| pixel_scale(a, value)) }
+}
+
+/// Linear blend of two images, `pixel_add` of the two `pixel_scale`d pixels
+pub fn blend(a: &ImageBuffer, b: &ImageBuffer, value: f32, out: &mut ImageBuffer) {
+    check_lengths(a, b);
+    check_lengths(a, out);
+    unsafe { zip_pixels(a.as_ptr(), b.as_ptr(), out.as_mut_ptr(), a.len(), |a, b| blend_chunk(a, b, value), |a, b| blend_pixel(a, b, value)) }
+}
+
+pub fn blend_in_place(a: &mut ImageBuffer, b: &ImageBuffer, value: f32) {
+    check_lengths(a, b);
+    let a = a.as_mut_ptr();
+    unsafe { zip_pixels(a, b.as_ptr(), a, b.len(), |a, b| blend_chunk(a, b, value), |a, b| blend_pixel(a, b, value)) }
+}
+
+fn blend_chunk(a: __m128i, b: __m128i, value: f32) -> __m128i {
+    unsafe { _mm_adds_epu8(scale_chunk(a, 1. - value), scale_chunk(b, value)) }
+}
+
+fn blend_pixel(a: image::Rgba<u8>, b: image::Rgba<u8>, value: f32) -> image::Rgba<u8> {
+    pixel_add(pixel_scale(a, 1. - value), pixel_scale(b, value))
+}
+
+/// Multiply 16 u8 values by a float, truncating and clamping to [0, 255] as `safe_mult` does.
+/// Clamping in float first keeps the truncation the same as `as i32` even for huge products.
+fn scale_chunk(chunk: __m128i, value: f32) -> __m128i {
+    unsafe {
+        let zero = _mm_setzero_si128();
+        let value = _mm_set1_ps(value);
+        let max = _mm_set1_ps(255.);
+
+        let scale_quarter = |quarter: __m128i| {
+            let product = _mm_mul_ps(_mm_cvtepi32_ps(quarter), value);
+            _mm_cvttps_epi32(_mm_min_ps(_mm_max_ps(product, _mm_setzero_ps()), max))
+        };
+
+        let low = _mm_unpacklo_epi8(chunk, zero);
+        let high = _mm_unpackhi_epi8(chunk, zero);
+
+        let low = _mm_packs_epi32(scale_quarter(_mm_unpacklo_epi16(low, zero)), scale_quarter(_mm_unpackhi_epi16(low, zero)));
+        let high = _mm_packs_epi32(scale_quarter(_mm_unpacklo_epi16(high, zero)), scale_quarter(_mm_unpackhi_epi16(high, zero)));
+
+        _mm_packus_epi16(low, high)
+    }
+}
+
+/// The convolution sum for one output pixel of `apply_matrix`, with the r,g,b channels
+/// multiplied in one go. Each product is truncated to an integer before summing as in the
+/// scalar code, for kernel weights of any sensible magnitude the sums are identical.
+pub(crate) fn convolve_pixel(input: &ImageBuffer, matrix: &Array2<f32>, x: u32, y: u32) -> [i32; 3] {
+    let (input_x, input_y) = input.dimensions();
+    let (matrix_x, matrix_y) = (matrix.shape()[0], matrix.shape()[1]);
+    let raw = input.as_raw();
+
+    unsafe {
+        let zero = _mm_setzero_si128();
+        let limit = _mm_set1_ps(16_777_216.);
+        let mut total = _mm_setzero_si128();
+
+        for i in 0..matrix_x {
+            let x_curr = ((x as i32) + (i as i32)).clamp(0, input_x as i32 - 1) as usize;
+
+            for j in 0..matrix_y {
+                let y_curr = ((y as i32) + (j as i32)).clamp(0, input_y as i32 - 1) as usize;
+                let offset = (y_curr * input_x as usize + x_curr) * 4;
+
+                let pixel = i32::from_le_bytes([raw[offset], raw[offset + 1], raw[offset + 2], raw[offset + 3]]);
+                let pixel = _mm_unpacklo_epi16(_mm_unpacklo_epi8(_mm_cvtsi32_si128(pixel), zero), zero);
+
+                let product = _mm_mul_ps(_mm_cvtepi32_ps(pixel), _mm_set1_ps(matrix[[i, j]]));
+                let product = _mm_min_ps(_mm_max_ps(product, _mm_sub_ps(_mm_setzero_ps(), limit)), limit);
+
+                total = _mm_add_epi32(total, _mm_cvttps_epi32(product));
+            }
+        }
+
+        let mut sums = [0_i32; 4];
+        _mm_storeu_si128(sums.as_mut_ptr() as *mut __m128i, total);
+
+        [sums[0], sums[1], sums[2]]
+    }
+}
@@ -0,0 +1,234 @@
+//! Reducing noise by stacking several frames of the same scene, as in astrophotography and low
+//! light bursts: the frames are aligned to the first and each pixel merged across them, so the
+//! noise, different in every frame, averages away while the scene, the same in each, stays.
+//! Frames focused at different depths are stacked too, keeping each part from the frame where
+//! it's sharpest.
+
+use log::debug;
+
+use crate::alignment::Alignment;
+use crate::error::{Error, Result};
+use crate::plane::Plane;
+use crate::pyramid::{blend, max_levels};
+use crate::ImageBuffer;
+
+/// Rounds of clipping for `MergeMethod::SigmaClipped`
+const CLIP_ROUNDS: usize = 3;
+
+/// Standard deviation in pixels of the window focus stacking sums sharpness over
+const SHARPNESS_SIGMA: f32 = 2.;
+
+/// Most pyramid levels focus stacking blends over
+const FOCUS_LEVELS: usize = 6;
+
+/// How each pixel is merged across the frames
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum MergeMethod {
+    /// The mean, reducing noise the most but keeping anything in only one frame as a ghost
+    Mean,
+    /// The median, dropping satellites, passers by and hot pixels but noisier than the mean
+    Median,
+    /// The mean of the values within a number of standard deviations of the mean, repeated a
+    /// few times, nearly as smooth as the mean while dropping outliers as the median does
+    #[default]
+    SigmaClipped,
+}
+
+impl MergeMethod {
+    pub fn from_name(name: &str) -> Option<MergeMethod> {
+        match name {
+            "mean" => Some(MergeMethod::Mean),
+            "median" => Some(MergeMethod::Median),
+            "sigma-clip" => Some(MergeMethod::SigmaClipped),
+            _ => None,
+        }
+    }
+}
+
+/// How `stack` aligns and merges frames
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StackOptions {
+    alignment: Alignment,
+    method: MergeMethod,
+    clip_sigma: f32,
+    seed: Option<u64>,
+}
+
+impl Default for StackOptions {
+    fn default() -> StackOptions {
+        StackOptions { alignment: Alignment::Homography, method: MergeMethod::SigmaClipped, clip_sigma: 2.5, seed: None }
+    }
+}
+
+impl StackOptions {
+    pub fn new() -> StackOptions {
+        StackOptions::default()
+    }
+
+    pub fn with_alignment(mut self, alignment: Alignment) -> StackOptions {
+        self.alignment = alignment;
+        self
+    }
+
+    pub fn with_merge_method(mut self, method: MergeMethod) -> StackOptions {
+        self.method = method;
+        self
+    }
+
+    /// Standard deviations from the mean past which `MergeMethod::SigmaClipped` drops a value
+    pub fn with_clip_sigma(mut self, sigma: f32) -> StackOptions {
+        self.clip_sigma = sigma.max(0.1);
+        self
+    }
+
+    /// Seed for the alignment's random sampling, see `random::rng`
+    pub fn with_seed(mut self, seed: Option<u64>) -> StackOptions {
+        self.seed = seed;
+        self
+    }
+}
+
+/// Stack frames of the same scene into one with less noise, aligned to the first frame. Where
+/// alignment leaves a pixel uncovered by the other frames, it's merged from the ones that cover.
+///
+/// # Arguments
+///
+/// * `frames`: Frames of the scene, all the same size
+/// * `options`: How to align and merge them
+///
+/// returns: Result<ImageBuffer>, an error if there are no frames or they differ in size
+pub fn stack(frames: &[ImageBuffer], options: &StackOptions) -> Result<ImageBuffer> {
+    let Some(reference) = frames.first() else {
+        return Err(Error::InvalidParameter { operation: "stack".to_string(), parameter: "frames".to_string() });
+    };
+    if let Some(frame) = frames.iter().find(|frame| frame.dimensions() != reference.dimensions()) {
+        return Err(Error::DimensionMismatch { expected: reference.dimensions(), actual: frame.dimensions() });
+    }
+
+    let mut aligned = vec![reference.clone()];
+    for (i, frame) in frames.iter().enumerate().skip(1) {
+        debug!("Aligning frame {} of {}", i + 1, frames.len());
+        aligned.push(options.alignment.align(reference, frame, options.seed));
+    }
+
+    Ok(merge(&aligned, options))
+}
+
+/// Focus stack frames taken at different focus distances into one sharp throughout, e.g.
+/// macro shots with too little depth of field: the frames are aligned to the first, each pixel's
+/// sharpness measured by the energy of the laplacian around it, and the sharpest frame at each
+/// pixel blended in by laplacian pyramids, so the joins between frames don't show
+///
+/// # Arguments
+///
+/// * `frames`: Frames of the scene in any order of focus, all the same size
+/// * `alignment`: How to align them, the homography following the slight change of scale
+///   between focus distances
+/// * `seed`: Seed for the alignment's random sampling, see `random::rng`
+///
+/// returns: Result<ImageBuffer>, an error if there are no frames or they differ in size
+pub fn focus_stack(frames: &[ImageBuffer], alignment: Alignment, seed: Option<u64>) -> Result<ImageBuffer> {
+    let Some(reference) = frames.first() else {
+        return Err(Error::InvalidParameter { operation: "focus_stack".to_string(), parameter: "frames".to_string() });
+    };
+    if let Some(frame) = frames.iter().find(|frame| frame.dimensions() != reference.dimensions()) {
+        return Err(Error::DimensionMismatch { expected: reference.dimensions(), actual: frame.dimensions() });
+    }
+
+    let mut aligned = vec![reference.clone()];
+    for (i, frame) in frames.iter().enumerate().skip(1) {
+        debug!("Aligning frame {} of {}", i + 1, frames.len());
+        aligned.push(alignment.align(reference, frame, seed));
+    }
+
+    // Each pixel goes to the frame sharpest there, left out where alignment uncovered it
+    let sharpness: Vec<Plane> = aligned.iter().map(laplacian_energy).collect();
+    let (width, height) = reference.dimensions();
+    let mut weights = vec![Plane::new(width, height); aligned.len()];
+    for i in 0..(width * height) as usize {
+        let covering = (0..aligned.len()).filter(|&k| aligned[k].as_raw()[i * 4 + 3] > 0);
+        if let Some(best) = covering.max_by(|&a, &b| sharpness[a].data[i].total_cmp(&sharpness[b].data[i])) {
+            weights[best].data[i] = 1.;
+        }
+    }
+
+    let levels = max_levels(width, height).min(FOCUS_LEVELS);
+    let mut output = reference.clone();
+    for channel in 0..3 {
+        let planes: Vec<Plane> = aligned.iter().map(|frame| Plane::from_channel(frame, channel)).collect();
+        let merged = blend(&planes, &weights, levels);
+        for (pixel, &value) in output.pixels_mut().zip(&merged.data) {
+            pixel[channel] = value.round().clamp(0., 255.) as u8;
+        }
+    }
+
+    Ok(output)
+}
+
+/// Focus measure: the squared laplacian of the luma summed over a gaussian window
+fn laplacian_energy(frame: &ImageBuffer) -> Plane {
+    let luma = Plane::from_luma(frame);
+    let mut energy = Plane::new(luma.width, luma.height);
+    for y in 0..luma.height as i64 {
+        for x in 0..luma.width as i64 {
+            let laplacian = luma.get_clamped(x - 1, y) + luma.get_clamped(x + 1, y) + luma.get_clamped(x, y - 1) + luma.get_clamped(x, y + 1) - 4. * luma.get_clamped(x, y);
+            energy.set(x as u32, y as u32, laplacian * laplacian);
+        }
+    }
+    energy.gaussian_blur(SHARPNESS_SIGMA)
+}
+
+/// Merge aligned frames pixel by pixel, leaving out transparent pixels
+fn merge(frames: &[ImageBuffer], options: &StackOptions) -> ImageBuffer {
+    let (width, height) = frames[0].dimensions();
+    let mut output = ImageBuffer::new(width, height);
+    let mut values: Vec<f32> = Vec::with_capacity(frames.len());
+
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let covering: Vec<&image::Rgba<u8>> = frames.iter().map(|frame| frame.get_pixel(x, y)).filter(|p| p[3] > 0).collect();
+        for channel in 0..4 {
+            values.clear();
+            values.extend(covering.iter().map(|p| p[channel] as f32));
+            pixel[channel] = match options.method {
+                MergeMethod::Mean => mean(&values),
+                MergeMethod::Median => median(&mut values),
+                MergeMethod::SigmaClipped => sigma_clipped_mean(&mut values, options.clip_sigma),
+            }
+            .round()
+            .clamp(0., 255.) as u8;
+        }
+    }
+
+    output
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len().max(1) as f32
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    if values.is_empty() {
+        return 0.;
+    }
+    values.sort_by(f32::total_cmp);
+    let middle = values.len() / 2;
+    if values.len().is_multiple_of(2) {
+        (values[middle - 1] + values[middle]) / 2.
+    } else {
+        values[middle]
+    }
+}
+
+fn sigma_clipped_mean(values: &mut Vec<f32>, sigma: f32) -> f32 {
+    for _ in 0..CLIP_ROUNDS {
+        let centre = mean(values);
+        let deviation = (values.iter().map(|v| (v - centre) * (v - centre)).sum::<f32>() / values.len().max(1) as f32).sqrt();
+        let kept: Vec<f32> = values.iter().copied().filter(|v| (v - centre).abs() <= sigma * deviation).collect();
+        if kept.len() == values.len() || kept.is_empty() {
+            break;
+        }
+        *values = kept;
+    }
+
+    mean(values)
+}
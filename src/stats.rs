@@ -0,0 +1,100 @@
+//! Summary statistics of an image's channels
+
+use crate::value::Value;
+use crate::ImageBuffer;
+
+/// Names of the channels in the order `ImageStats::channels` holds them
+pub const CHANNEL_NAMES: [&str; 4] = ["red", "green", "blue", "alpha"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct ChannelStats {
+    pub mean: f64,
+    pub std_dev: f64,
+    pub min: u8,
+    pub max: u8,
+    pub median: u8,
+    /// Shannon entropy of the histogram in bits, from 0 for a single value to 8 when all 256
+    /// values are equally common
+    pub entropy: f64,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageStats {
+    pub width: u32,
+    pub height: u32,
+    /// Statistics of red, green, blue and alpha
+    pub channels: [ChannelStats; 4],
+}
+
+impl ImageStats {
+    pub fn to_value(&self) -> Value {
+        let channels = CHANNEL_NAMES
+            .iter()
+            .zip(&self.channels)
+            .map(|(name, channel)| {
+                let table = Value::Table(vec![
+                    ("mean".to_string(), Value::Number(channel.mean)),
+                    ("std_dev".to_string(), Value::Number(channel.std_dev)),
+                    ("min".to_string(), Value::Number(channel.min as f64)),
+                    ("max".to_string(), Value::Number(channel.max as f64)),
+                    ("median".to_string(), Value::Number(channel.median as f64)),
+                    ("entropy".to_string(), Value::Number(channel.entropy)),
+                ]);
+                (name.to_string(), table)
+            })
+            .collect();
+
+        Value::Table(vec![
+            ("width".to_string(), Value::Number(self.width as f64)),
+            ("height".to_string(), Value::Number(self.height as f64)),
+            ("channels".to_string(), Value::Table(channels)),
+        ])
+    }
+}
+
+/// Per channel mean, standard deviation, minimum, maximum, median and entropy, all from the
+/// channels' histograms
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to measure
+///
+/// returns: ImageStats, all zeros for an empty image
+pub fn stats(input: &ImageBuffer) -> ImageStats {
+    let mut histograms = [[0u64; 256]; 4];
+    for pixel in input.pixels() {
+        for (histogram, &value) in histograms.iter_mut().zip(pixel.0.iter()) {
+            histogram[value as usize] += 1;
+        }
+    }
+
+    ImageStats { width: input.width(), height: input.height(), channels: histograms.map(|histogram| channel_stats(&histogram)) }
+}
+
+fn channel_stats(histogram: &[u64; 256]) -> ChannelStats {
+    let count: u64 = histogram.iter().sum();
+    if count == 0 {
+        return ChannelStats::default();
+    }
+
+    let occupied = || histogram.iter().enumerate().filter(|(_, &n)| n > 0);
+    let mean = occupied().map(|(v, &n)| v as f64 * n as f64).sum::<f64>() / count as f64;
+    let variance = occupied().map(|(v, &n)| (v as f64 - mean).powi(2) * n as f64).sum::<f64>() / count as f64;
+    let entropy = -occupied().map(|(_, &n)| n as f64 / count as f64).map(|p| p * p.log2()).sum::<f64>();
+
+    // The lower median, the first value reaching half the pixels
+    let mut seen = 0;
+    let median = histogram.iter().position(|&n| {
+        seen += n;
+        seen * 2 >= count
+    });
+
+    ChannelStats {
+        mean,
+        std_dev: variance.sqrt(),
+        min: occupied().next().map(|(v, _)| v as u8).unwrap_or(0),
+        max: occupied().next_back().map(|(v, _)| v as u8).unwrap_or(0),
+        median: median.unwrap_or(0) as u8,
+        entropy: entropy.max(0.),
+    }
+}
@@ -0,0 +1,267 @@
+//! Depth from a stereo pair by block matching. For each pixel of the left image the matching
+//! pixel of the right is searched for along the same row, comparing the blocks around them, and
+//! the horizontal shift between them (the disparity) is inversely proportional to depth.
+//!
+//! The pair must be rectified, with corresponding points on the same row, as from a calibrated
+//! stereo rig.
+
+use crate::plane::Plane;
+use crate::ImageBuffer;
+
+/// Disparity of pixels with no reliable match: occluded in the other view, textureless, or
+/// failing the left-right consistency check
+pub const INVALID_DISPARITY: f32 = -1.;
+
+/// Radius of the census transform window
+const CENSUS_RADIUS: i64 = 2;
+
+/// How blocks are compared
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchCost {
+    /// Sum of absolute differences of the colours, fast but thrown by exposure differences
+    /// between the cameras
+    Sad,
+    /// Hamming distance between census transforms, which record whether each neighbour is
+    /// darker than the centre and so ignore brightness and contrast differences
+    Census,
+}
+
+impl MatchCost {
+    pub fn from_name(name: &str) -> Option<MatchCost> {
+        match name {
+            "sad" => Some(MatchCost::Sad),
+            "census" => Some(MatchCost::Census),
+            _ => None,
+        }
+    }
+}
+
+/// Block matching settings, by default searching 64 pixels with 7x7 census blocks
+#[derive(Clone, Debug)]
+pub struct StereoOptions {
+    max_disparity: u32,
+    block_radius: u32,
+    cost: MatchCost,
+    max_difference: f32,
+}
+
+impl Default for StereoOptions {
+    fn default() -> StereoOptions {
+        StereoOptions { max_disparity: 64, block_radius: 3, cost: MatchCost::Census, max_difference: 1. }
+    }
+}
+
+impl StereoOptions {
+    pub fn new() -> StereoOptions {
+        StereoOptions::default()
+    }
+
+    /// Largest disparity searched, which bounds how near objects can be
+    pub fn with_max_disparity(mut self, max_disparity: u32) -> StereoOptions {
+        self.max_disparity = max_disparity;
+        self
+    }
+
+    /// How far the compared blocks reach each way from their centre pixel. Larger blocks are
+    /// less noisy but blur depth edges.
+    pub fn with_block_radius(mut self, block_radius: u32) -> StereoOptions {
+        self.block_radius = block_radius;
+        self
+    }
+
+    pub fn with_cost(mut self, cost: MatchCost) -> StereoOptions {
+        self.cost = cost;
+        self
+    }
+
+    /// Largest difference between the left and right disparities of a match for it to be kept
+    pub fn with_max_difference(mut self, max_difference: f32) -> StereoOptions {
+        self.max_difference = max_difference;
+        self
+    }
+}
+
+/// Compute the disparity of every pixel of the left image, to sub pixel accuracy. Pixels are
+/// matched from left to right and right to left, and kept only where both agree.
+///
+/// # Arguments
+///
+/// * `left`: ImageBuffer from the left camera
+/// * `right`: ImageBuffer from the right camera, the same size
+/// * `options`: Search range, block size and cost
+///
+/// returns: Plane, disparities in pixels, `INVALID_DISPARITY` where there's no reliable match
+pub fn disparity_map(left: &ImageBuffer, right: &ImageBuffer, options: &StereoOptions) -> Plane {
+    assert_eq!(left.dimensions(), right.dimensions(), "stereo images must be the same size");
+    let (width, height) = (left.width() as usize, left.height() as usize);
+
+    let pixel_cost = pixel_costs(left, right, options.cost);
+
+    // Best disparity and cost for each pixel of each view, with the left view's costs either
+    // side of its best for the sub pixel fit
+    let mut left_best = vec![(0u32, f32::MAX, f32::MAX, f32::MAX); width * height];
+    let mut right_best = vec![(0u32, f32::MAX); width * height];
+    let mut previous = Plane::new(left.width(), left.height());
+
+    for disparity in 0..=options.max_disparity.min(left.width().saturating_sub(1)) {
+        let d = disparity as usize;
+        let raw = pixel_cost(disparity);
+        let aggregated = raw.box_blur(options.block_radius);
+
+        for y in 0..height {
+            for x in d..width {
+                let i = y * width + x;
+                let cost = aggregated.data[i];
+
+                let best = &mut left_best[i];
+                if disparity > 0 && best.0 == disparity - 1 {
+                    best.3 = cost;
+                }
+                if cost < best.1 {
+                    let before = if disparity > 0 { previous.data[i] } else { f32::MAX };
+                    *best = (disparity, cost, before, f32::MAX);
+                }
+
+                let best = &mut right_best[i - d];
+                if cost < best.1 {
+                    *best = (disparity, cost);
+                }
+            }
+        }
+
+        previous = aggregated;
+    }
+
+    let mut disparities = Plane::new(left.width(), left.height());
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let (disparity, cost, before, after) = left_best[i];
+            // Pixels are only matched with right pixels at or left of them, so this stays in the row
+            let (matched, _) = right_best[i - disparity as usize];
+
+            disparities.data[i] = if cost == f32::MAX || (matched as f32 - disparity as f32).abs() > options.max_difference {
+                INVALID_DISPARITY
+            } else {
+                disparity as f32 + sub_pixel_offset(before, cost, after)
+            };
+        }
+    }
+
+    disparities
+}
+
+/// The per pixel cost of matching each left pixel with the right pixel `disparity` to its left,
+/// for a given disparity. Pixels without a partner are compared with the right image's edge.
+fn pixel_costs<'a>(left: &'a ImageBuffer, right: &'a ImageBuffer, cost: MatchCost) -> Box<dyn Fn(u32) -> Plane + 'a> {
+    let (width, height) = left.dimensions();
+
+    match cost {
+        MatchCost::Sad => Box::new(move |disparity| {
+            let mut plane = Plane::new(width, height);
+            for (x, y, pixel) in left.enumerate_pixels() {
+                let other = right.get_pixel(x.saturating_sub(disparity), y);
+                let difference: i32 = (0..3).map(|c| (pixel[c] as i32 - other[c] as i32).abs()).sum();
+                plane.set(x, y, difference as f32 / 3.);
+            }
+            plane
+        }),
+        MatchCost::Census => {
+            let (left, right) = (census(&Plane::from_luma(left)), census(&Plane::from_luma(right)));
+            Box::new(move |disparity| {
+                let mut plane = Plane::new(width, height);
+                for y in 0..height {
+                    for x in 0..width {
+                        let i = (y * width) as usize;
+                        let distance = (left[i + x as usize] ^ right[i + x.saturating_sub(disparity) as usize]).count_ones();
+                        plane.set(x, y, distance as f32);
+                    }
+                }
+                plane
+            })
+        }
+    }
+}
+
+/// Census transform, a bit for each neighbour in the window set where it's darker than the centre
+fn census(luma: &Plane) -> Vec<u32> {
+    let mut transform = Vec::with_capacity(luma.data.len());
+
+    for y in 0..luma.height as i64 {
+        for x in 0..luma.width as i64 {
+            let centre = luma.get_clamped(x, y);
+            let mut bits = 0u32;
+            for dy in -CENSUS_RADIUS..=CENSUS_RADIUS {
+                for dx in -CENSUS_RADIUS..=CENSUS_RADIUS {
+                    if dx != 0 || dy != 0 {
+                        bits = bits << 1 | (luma.get_clamped(x + dx, y + dy) < centre) as u32;
+                    }
+                }
+            }
+            transform.push(bits);
+        }
+    }
+
+    transform
+}
+
+/// Offset of the minimum of a parabola through the costs at the best disparity and either side
+fn sub_pixel_offset(before: f32, best: f32, after: f32) -> f32 {
+    if before == f32::MAX || after == f32::MAX {
+        return 0.;
+    }
+
+    let curvature = before - 2. * best + after;
+    if curvature <= 0. {
+        return 0.;
+    }
+    ((before - after) / (2. * curvature)).clamp(-0.5, 0.5)
+}
+
+/// Convert disparities to depths, `focal_length * baseline / disparity`
+///
+/// # Arguments
+///
+/// * `disparities`: Disparities from `disparity_map`
+/// * `focal_length`: Focal length of the rectified cameras in pixels
+/// * `baseline`: Distance between the cameras, in the unit depths are wanted in
+///
+/// returns: Plane, depths, zero where the disparity is invalid or zero
+pub fn depth_from_disparity(disparities: &Plane, focal_length: f32, baseline: f32) -> Plane {
+    disparities.map(|disparity| if disparity > 0. { focal_length * baseline / disparity } else { 0. })
+}
+
+/// Colour disparities for viewing with the turbo colour map, near (high disparity) in red
+/// through to far in blue, with invalid pixels black
+///
+/// # Arguments
+///
+/// * `disparities`: Disparities from `disparity_map`
+/// * `max_disparity`: Disparity shown at the red end of the map
+///
+/// returns: ImageBuffer
+pub fn colorize_disparity(disparities: &Plane, max_disparity: f32) -> ImageBuffer {
+    ImageBuffer::from_fn(disparities.width, disparities.height, |x, y| {
+        let disparity = disparities.get(x, y);
+        if disparity < 0. {
+            return image::Rgba([0, 0, 0, 255]);
+        }
+
+        let [r, g, b] = turbo((disparity / max_disparity.max(f32::EPSILON)).clamp(0., 1.));
+        image::Rgba([r, g, b, 255])
+    })
+}
+
+/// Polynomial approximation of the turbo colour map
+fn turbo(t: f32) -> [u8; 3] {
+    const RED: [f64; 6] = [0.13572138, 4.6153926, -42.66032258, 132.13108234, -152.94239396, 59.28637943];
+    const GREEN: [f64; 6] = [0.09140261, 2.19418839, 4.84296658, -14.18503333, 4.27729857, 2.82956604];
+    const BLUE: [f64; 6] = [0.1066733, 12.64194608, -60.58204836, 110.36276771, -89.90310912, 27.34824973];
+
+    let t = t as f64;
+    let channel = |coefficients: [f64; 6]| {
+        let value = coefficients.iter().rev().fold(0., |value, c| value * t + c);
+        (value.clamp(0., 1.) * 255.).round() as u8
+    };
+    [channel(RED), channel(GREEN), channel(BLUE)]
+}
@@ -0,0 +1,147 @@
+//! Artistic filters, turning photos into stylised images
+
+use crate::filters::{bilateral_filter, edge_detect};
+use crate::plane::Plane;
+use crate::ImageBuffer;
+
+/// Spatial and colour standard deviations of each smoothing pass of `cartoonify`
+const CARTOON_SIGMA_SPACE: f32 = 3.;
+const CARTOON_SIGMA_COLOUR: f32 = 20.;
+
+/// Make a photo look like a cartoon by:
+///     1) Smoothing away texture while keeping edges, by repeated bilateral filtering
+///     2) Quantizing the colours into flat bands
+///     3) Drawing the edges found by `edge_detect` over the top in black, thickened a pixel
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to cartoonify
+/// * `smoothing`: Number of bilateral passes, more gives flatter areas, e.g. 3
+/// * `levels`: Levels each of r, g, b is quantized to, fewer gives bolder bands, e.g. 6
+/// * `edge_threshold`: Edge strength from 0 to 255 above which a line is drawn, lower draws
+///   more lines, e.g. 80
+///
+/// returns: ImageBuffer
+pub fn cartoonify(input: &ImageBuffer, smoothing: u32, levels: u32, edge_threshold: u8) -> ImageBuffer {
+    let smoothed = (0..smoothing).fold(input.clone(), |image, _| bilateral_filter(&image, CARTOON_SIGMA_SPACE, CARTOON_SIGMA_COLOUR));
+
+    // Edges come from the smoothed image so fine texture doesn't draw lines
+    let edges = edge_detect(&smoothed);
+    let is_edge = |x: u32, y: u32| {
+        let pixel = edges.get_pixel(x, y);
+        pixel[0].max(pixel[1]).max(pixel[2]) > edge_threshold
+    };
+
+    let (width, height) = input.dimensions();
+    let step = 255. / levels.max(2).saturating_sub(1) as f32;
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let on_line = (y.saturating_sub(1)..(y + 2).min(height)).any(|ny| (x.saturating_sub(1)..(x + 2).min(width)).any(|nx| is_edge(nx, ny)));
+        let pixel = smoothed.get_pixel(x, y);
+        if on_line {
+            return image::Rgba([0, 0, 0, pixel[3]]);
+        }
+
+        let quantize = |value: u8| ((value as f32 / step).round() * step).round() as u8;
+        image::Rgba([quantize(pixel[0]), quantize(pixel[1]), quantize(pixel[2]), pixel[3]])
+    })
+}
+
+/// Emboss an image into a grey relief, as if its brightness were height lit from one side.
+/// Flat areas are mid grey, with slopes facing the light brighter and those facing away darker.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to emboss
+/// * `angle`: Direction the light comes from in degrees, anticlockwise from the right, 135
+///   lighting from the top left
+/// * `depth`: How strongly slopes are shaded, 1 for a sharp brightness step of 255 facing
+///   the light to reach white
+///
+/// returns: ImageBuffer
+pub fn emboss(input: &ImageBuffer, angle: f32, depth: f32) -> ImageBuffer {
+    let shading = shading(input, angle, depth);
+    ImageBuffer::from_fn(input.width(), input.height(), |x, y| {
+        let value = (128. + shading.get(x, y)).round().clamp(0., 255.) as u8;
+        image::Rgba([value, value, value, input.get_pixel(x, y)[3]])
+    })
+}
+
+/// As `emboss`, with the light from the opposite side, so edges look cut into the surface
+/// rather than raised from it
+pub fn engrave(input: &ImageBuffer, angle: f32, depth: f32) -> ImageBuffer {
+    emboss(input, angle + 180., depth)
+}
+
+/// Shade an image's own colours with the relief of `emboss`, keeping the colour of flat areas
+pub fn relief(input: &ImageBuffer, angle: f32, depth: f32) -> ImageBuffer {
+    let shading = shading(input, angle, depth);
+    ImageBuffer::from_fn(input.width(), input.height(), |x, y| {
+        let pixel = input.get_pixel(x, y);
+        let shade = |value: u8| (value as f32 + shading.get(x, y)).round().clamp(0., 255.) as u8;
+        image::Rgba([shade(pixel[0]), shade(pixel[1]), shade(pixel[2]), pixel[3]])
+    })
+}
+
+/// The brightness change of lighting the luma as a height map, the sobel gradient towards the
+/// light scaled by `depth`. It's centred on zero, the grey bias being left to the caller so
+/// nothing is clamped before it's added.
+fn shading(input: &ImageBuffer, angle: f32, depth: f32) -> Plane {
+    let luma = Plane::from_luma(input);
+    // Rows run down the image, so up is negative y
+    let (light_x, light_y) = (angle.to_radians().cos(), -angle.to_radians().sin());
+
+    let mut shading = Plane::new(luma.width, luma.height);
+    for y in 0..luma.height as i64 {
+        for x in 0..luma.width as i64 {
+            let at = |dx: i64, dy: i64| luma.get_clamped(x + dx, y + dy);
+            // Scaled so a sharp step gives its full height, rather than the per pixel slope
+            let dx = (at(1, -1) + 2. * at(1, 0) + at(1, 1) - at(-1, -1) - 2. * at(-1, 0) - at(-1, 1)) / 4.;
+            let dy = (at(-1, 1) + 2. * at(0, 1) + at(1, 1) - at(-1, -1) - 2. * at(0, -1) - at(1, -1)) / 4.;
+            shading.set(x as u32, y as u32, depth * (dx * light_x + dy * light_y));
+        }
+    }
+
+    shading
+}
+
+/// Fake a tilt-shift lens, the miniature look of a shallow depth of field across a scene: a
+/// horizontal band stays sharp and the blur grows smoothly above and below it. Each row blends
+/// the two nearest of a stack of blurs, doubling in strength, so the blur varies continuously.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to blur
+/// * `centre`: Height of the middle of the sharp band, as a fraction of the image height
+/// * `band`: Height of the sharp band, as a fraction of the image height
+/// * `falloff`: Distance from the band to full blur, as a fraction of the image height
+/// * `max_blur`: Standard deviation of the strongest blur in pixels
+///
+/// returns: ImageBuffer
+pub fn tilt_shift(input: &ImageBuffer, centre: f32, band: f32, falloff: f32, max_blur: f32) -> ImageBuffer {
+    let (width, height) = input.dimensions();
+
+    // Blur strengths from none up to the maximum, each double the one before
+    let mut sigmas = vec![0.];
+    let mut sigma = max_blur;
+    while sigma > 0.5 {
+        sigmas.insert(1, sigma);
+        sigma /= 2.;
+    }
+    let levels: Vec<Vec<Plane>> = sigmas.iter().map(|&sigma| (0..4).map(|c| Plane::from_channel(input, c).gaussian_blur(sigma)).collect()).collect();
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let distance = ((y as f32 + 0.5) / height as f32 - centre).abs() - band / 2.;
+        let t = (distance / falloff.max(f32::EPSILON)).clamp(0., 1.);
+        let sigma = max_blur * t * t * (3. - 2. * t);
+
+        // The pair of levels either side of the wanted blur and how far between them it is
+        let upper = sigmas.iter().position(|&s| s >= sigma).unwrap_or(sigmas.len() - 1);
+        let lower = upper.saturating_sub(1);
+        let mix = if upper == lower { 0. } else { (sigma - sigmas[lower]) / (sigmas[upper] - sigmas[lower]) };
+
+        image::Rgba(std::array::from_fn(|c| {
+            let value = levels[lower][c].get(x, y) * (1. - mix) + levels[upper][c].get(x, y) * mix;
+            value.round().clamp(0., 255.) as u8
+        }))
+    })
+}
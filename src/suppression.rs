@@ -0,0 +1,123 @@
+//! Non-maximum suppression, thinning detections down to the best of each cluster: peaks of a
+//! response map, such as corner strengths or template match scores, and overlapping boxes,
+//! such as the windows a sliding detector fires on around each object
+
+use crate::error::Result;
+use crate::plane::Plane;
+use crate::records::{field, number, Record};
+use crate::rect::Rect;
+use crate::value::Value;
+
+/// A local maximum of a response map
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Peak {
+    pub x: u32,
+    pub y: u32,
+    pub value: f32,
+}
+
+/// A box found by a detector, and how sure the detector is of it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Detection {
+    pub rect: Rect,
+    /// Higher is surer
+    pub score: f32,
+}
+
+impl Record for Peak {
+    const NAME: &'static str = "peak";
+
+    fn to_value(&self) -> Value {
+        Value::Table(vec![
+            ("x".to_string(), Value::Number(self.x as f64)),
+            ("y".to_string(), Value::Number(self.y as f64)),
+            ("value".to_string(), Value::Number(self.value as f64)),
+        ])
+    }
+
+    fn from_value(value: &Value) -> Result<Peak> {
+        Ok(Peak {
+            x: number(value, Self::NAME, "x")? as u32,
+            y: number(value, Self::NAME, "y")? as u32,
+            value: number(value, Self::NAME, "value")? as f32,
+        })
+    }
+}
+
+impl Record for Detection {
+    const NAME: &'static str = "detection";
+
+    fn to_value(&self) -> Value {
+        Value::Table(vec![("rect".to_string(), self.rect.to_value()), ("score".to_string(), Value::Number(self.score as f64))])
+    }
+
+    fn from_value(value: &Value) -> Result<Detection> {
+        Ok(Detection { rect: Rect::from_value(field(value, Self::NAME, "rect")?)?, score: number(value, Self::NAME, "score")? as f32 })
+    }
+}
+
+/// Positions where a plane is above `threshold` times its maximum and the greatest value within
+/// `radius`, strongest first
+///
+/// # Arguments
+///
+/// * `plane`: Response map, higher for stronger responses
+/// * `threshold`: Fraction of the strongest response a peak must reach, e.g. 0.01
+/// * `radius`: Distance in pixels a peak must be the strongest within, at least 1
+///
+/// returns: Vec<Peak>
+pub fn local_maxima(plane: &Plane, threshold: f32, radius: u32) -> Vec<Peak> {
+    let max = plane.data.iter().copied().fold(0., f32::max);
+    let floor = (max * threshold).max(f32::MIN_POSITIVE);
+    let radius = radius.max(1) as i64;
+
+    let mut maxima = Vec::new();
+    for y in 0..plane.height {
+        for x in 0..plane.width {
+            let value = plane.get(x, y);
+            if value < floor {
+                continue;
+            }
+
+            // Ties go to the first in scan order, so plateaus give one maximum
+            let is_maximum = (-radius..=radius).all(|dy| {
+                (-radius..=radius).all(|dx| {
+                    let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+                    if (dx == 0 && dy == 0) || nx < 0 || ny < 0 || nx >= plane.width as i64 || ny >= plane.height as i64 {
+                        return true;
+                    }
+                    let other = plane.get(nx as u32, ny as u32);
+                    other < value || (other == value && (dy, dx) > (0, 0))
+                })
+            });
+            if is_maximum {
+                maxima.push(Peak { x, y, value });
+            }
+        }
+    }
+
+    maxima.sort_by(|a, b| b.value.total_cmp(&a.value));
+    maxima
+}
+
+/// Keep the best of each group of overlapping detections, going from the highest score down and
+/// dropping any that overlap one already kept by more than `max_overlap`
+///
+/// # Arguments
+///
+/// * `detections`: Detections in any order
+/// * `max_overlap`: Greatest intersection over union allowed between kept detections, e.g. 0.3
+///
+/// returns: Vec<Detection>, highest score first
+pub fn suppress_overlaps(detections: &[Detection], max_overlap: f32) -> Vec<Detection> {
+    let mut sorted = detections.to_vec();
+    sorted.sort_by(|a, b| b.score.total_cmp(&a.score));
+
+    let mut kept: Vec<Detection> = Vec::new();
+    for detection in sorted {
+        if kept.iter().all(|other| detection.rect.iou(&other.rect) <= max_overlap) {
+            kept.push(detection);
+        }
+    }
+    kept
+}
@@ -0,0 +1,238 @@
+//! Golden image regression testing: run an operation over a deterministic input and compare
+//! the result with a stored golden image, allowing small differences per pixel (rounding
+//! changes, a different float order) and a few differing pixels. A failed check writes the
+//! actual result and an image of the differences beside the golden, to see what changed.
+//!
+//! Goldens are png files named after the check in a directory of the caller's choosing. One
+//! that's missing is written from the first result, and all of them are rewritten from the
+//! results when `UPDATE_GOLDEN` is set in the environment (or with `Golden::with_update`),
+//! e.g. `UPDATE_GOLDEN=1 cargo test` after an intended change.
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use log::warn;
+
+use crate::error::{Error, Result};
+use crate::generate::perlin_noise;
+use crate::ImageBuffer;
+
+/// Environment variable which, when set, rewrites goldens from results
+pub const UPDATE_VARIABLE: &str = "UPDATE_GOLDEN";
+
+/// An input for golden checks, the same for a seed on every machine: a gradient in red, cloudy
+/// noise in green, and squares with hard edges in blue, so smoothing, sharpening and colour
+/// operations all have something to change
+///
+/// # Arguments
+///
+/// * `width`: Width of the image
+/// * `height`: Height of the image
+/// * `seed`: Seed of the noise
+///
+/// returns: ImageBuffer
+pub fn test_image(width: u32, height: u32, seed: u64) -> ImageBuffer {
+    let noise = perlin_noise(width, height, 32., 4, seed);
+    let square = (width.min(height) / 8).max(1);
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let red = (x * 255 / width.saturating_sub(1).max(1)) as u8;
+        let blue = if (x / square + y / square).is_multiple_of(2) { 220 } else { 35 };
+        image::Rgba([red, noise.get_pixel(x, y)[0], blue, 255])
+    })
+}
+
+/// How far a result may differ from its golden and still pass
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Tolerance {
+    channel: u8,
+    differing_pixels: usize,
+}
+
+impl Tolerance {
+    /// No differences at all
+    pub fn exact() -> Tolerance {
+        Tolerance::default()
+    }
+
+    /// Largest difference in any channel, alpha included, for a pixel to count as the same
+    pub fn with_channel(mut self, channel: u8) -> Tolerance {
+        self.channel = channel;
+        self
+    }
+
+    /// Number of pixels allowed to differ by more than the channel tolerance
+    pub fn with_differing_pixels(mut self, differing_pixels: usize) -> Tolerance {
+        self.differing_pixels = differing_pixels;
+        self
+    }
+}
+
+/// How a result differs from its golden
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Difference {
+    /// Pixels differing by more than the channel tolerance
+    pub differing_pixels: usize,
+    /// Largest difference of any channel of any pixel
+    pub max_difference: u8,
+    /// Whether the differences are within the tolerance
+    pub passed: bool,
+}
+
+/// Compare an image with its golden
+///
+/// # Arguments
+///
+/// * `actual`: Result to check
+/// * `expected`: Golden image, the same size
+/// * `tolerance`: Differences allowed
+///
+/// returns: Result<Difference>, or `Error::DimensionMismatch` if the sizes differ
+pub fn compare(actual: &ImageBuffer, expected: &ImageBuffer, tolerance: Tolerance) -> Result<Difference> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(Error::DimensionMismatch { expected: expected.dimensions(), actual: actual.dimensions() });
+    }
+
+    let mut differing_pixels = 0;
+    let mut max_difference = 0;
+    for (a, e) in actual.pixels().zip(expected.pixels()) {
+        let difference = channel_difference(*a, *e);
+        differing_pixels += (difference > tolerance.channel) as usize;
+        max_difference = max_difference.max(difference);
+    }
+
+    Ok(Difference { differing_pixels, max_difference, passed: differing_pixels <= tolerance.differing_pixels })
+}
+
+/// An image of where a result differs from its golden: pixels beyond the channel tolerance in
+/// red, brighter the larger the difference, over a dimmed grey copy of the golden
+///
+/// # Arguments
+///
+/// * `actual`: Result to check
+/// * `expected`: Golden image, the same size
+/// * `tolerance`: Differences allowed
+///
+/// returns: Result<ImageBuffer>, or `Error::DimensionMismatch` if the sizes differ
+pub fn diff_image(actual: &ImageBuffer, expected: &ImageBuffer, tolerance: Tolerance) -> Result<ImageBuffer> {
+    if actual.dimensions() != expected.dimensions() {
+        return Err(Error::DimensionMismatch { expected: expected.dimensions(), actual: actual.dimensions() });
+    }
+
+    Ok(ImageBuffer::from_fn(expected.width(), expected.height(), |x, y| {
+        let (a, e) = (*actual.get_pixel(x, y), *expected.get_pixel(x, y));
+        let difference = channel_difference(a, e);
+        if difference > tolerance.channel {
+            image::Rgba([(128 + difference as u32 / 2) as u8, 0, 0, 255])
+        } else {
+            let grey = ((e[0] as u32 * 299 + e[1] as u32 * 587 + e[2] as u32 * 114) / 3000) as u8;
+            image::Rgba([grey, grey, grey, 255])
+        }
+    }))
+}
+
+/// A directory of golden images to check results against
+#[derive(Clone, Debug)]
+pub struct Golden {
+    dir: PathBuf,
+    tolerance: Tolerance,
+    update: bool,
+}
+
+impl Golden {
+    /// Goldens in a directory, e.g. `tests/golden`, checked exactly unless given a tolerance,
+    /// and updated if `UPDATE_GOLDEN` is set
+    pub fn new(dir: impl AsRef<Path>) -> Golden {
+        Golden { dir: dir.as_ref().to_path_buf(), tolerance: Tolerance::exact(), update: env::var_os(UPDATE_VARIABLE).is_some() }
+    }
+
+    pub fn with_tolerance(mut self, tolerance: Tolerance) -> Golden {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Rewrite goldens from results instead of checking them
+    pub fn with_update(mut self, update: bool) -> Golden {
+        self.update = update;
+        self
+    }
+
+    /// Path of a check's golden image
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.png", name))
+    }
+
+    /// Check a result against its golden, writing the golden if missing or updating. On a
+    /// failure `<name>.actual.png` and, if the sizes match, `<name>.diff.png` are written
+    /// beside it, and any from an earlier failure are removed on a pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `name`: Name of the check, naming its golden image
+    /// * `actual`: Result to check
+    ///
+    /// returns: Result<Difference>, with `Error::Golden` if the result differs by more than
+    /// the tolerance, or `Error::DimensionMismatch` if it's a different size
+    pub fn check(&self, name: &str, actual: &ImageBuffer) -> Result<Difference> {
+        let path = self.path(name);
+        let actual_path = self.dir.join(format!("{}.actual.png", name));
+        let diff_path = self.dir.join(format!("{}.diff.png", name));
+
+        let remove_stale = || -> Result<()> {
+            for stale in [&actual_path, &diff_path] {
+                if stale.exists() {
+                    std::fs::remove_file(stale)?;
+                }
+            }
+            Ok(())
+        };
+
+        if self.update || !path.exists() {
+            if !self.update {
+                warn!("Writing missing golden image {}", path.display());
+            }
+            std::fs::create_dir_all(&self.dir)?;
+            actual.save(&path)?;
+            remove_stale()?;
+            return Ok(Difference { differing_pixels: 0, max_difference: 0, passed: true });
+        }
+
+        let expected = image::open(&path)?.to_rgba8();
+        let difference = match compare(actual, &expected, self.tolerance) {
+            Ok(difference) => difference,
+            Err(err) => {
+                actual.save(&actual_path)?;
+                return Err(err);
+            }
+        };
+
+        if difference.passed {
+            remove_stale()?;
+            return Ok(difference);
+        }
+
+        actual.save(&actual_path)?;
+        diff_image(actual, &expected, self.tolerance)?.save(&diff_path)?;
+        Err(Error::Golden(format!(
+            "'{}' has {} pixels differing by more than {} (up to {}) where {} are allowed, see {}",
+            name,
+            difference.differing_pixels,
+            self.tolerance.channel,
+            difference.max_difference,
+            self.tolerance.differing_pixels,
+            diff_path.display()
+        )))
+    }
+
+    /// As `check`, panicking on a failure, for use in tests
+    pub fn assert(&self, name: &str, actual: &ImageBuffer) {
+        if let Err(err) = self.check(name, actual) {
+            panic!("{}", err);
+        }
+    }
+}
+
+/// Largest difference of any channel between two pixels
+fn channel_difference(a: image::Rgba<u8>, b: image::Rgba<u8>) -> u8 {
+    (0..4).map(|c| a[c].abs_diff(b[c])).max().unwrap_or(0)
+}
@@ -0,0 +1,103 @@
+//! Drawing short labels into images with a built in 5 by 7 pixel bitmap font, enough for captions
+//! and annotations without loading a font file. It covers printable ascii; anything else draws
+//! as `?`.
+
+use crate::ImageBuffer;
+
+/// Width of a glyph in font pixels
+pub const GLYPH_WIDTH: u32 = 5;
+
+/// Height of a glyph in font pixels
+pub const GLYPH_HEIGHT: u32 = 7;
+
+/// Font pixels from the start of one glyph to the next, leaving a column between them
+const ADVANCE: u32 = GLYPH_WIDTH + 1;
+
+/// Glyphs for ' ' to '~' by column from the left, the lowest bit of each the top row
+const GLYPHS: [[u8; 5]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00], [0x00, 0x00, 0x5f, 0x00, 0x00], [0x00, 0x07, 0x00, 0x07, 0x00], [0x14, 0x7f, 0x14, 0x7f, 0x14],
+    [0x24, 0x2a, 0x7f, 0x2a, 0x12], [0x23, 0x13, 0x08, 0x64, 0x62], [0x36, 0x49, 0x55, 0x22, 0x50], [0x00, 0x05, 0x03, 0x00, 0x00],
+    [0x00, 0x1c, 0x22, 0x41, 0x00], [0x00, 0x41, 0x22, 0x1c, 0x00], [0x08, 0x2a, 0x1c, 0x2a, 0x08], [0x08, 0x08, 0x3e, 0x08, 0x08],
+    [0x00, 0x50, 0x30, 0x00, 0x00], [0x08, 0x08, 0x08, 0x08, 0x08], [0x00, 0x60, 0x60, 0x00, 0x00], [0x20, 0x10, 0x08, 0x04, 0x02],
+    [0x3e, 0x51, 0x49, 0x45, 0x3e], [0x00, 0x42, 0x7f, 0x40, 0x00], [0x42, 0x61, 0x51, 0x49, 0x46], [0x21, 0x41, 0x45, 0x4b, 0x31],
+    [0x18, 0x14, 0x12, 0x7f, 0x10], [0x27, 0x45, 0x45, 0x45, 0x39], [0x3c, 0x4a, 0x49, 0x49, 0x30], [0x01, 0x71, 0x09, 0x05, 0x03],
+    [0x36, 0x49, 0x49, 0x49, 0x36], [0x06, 0x49, 0x49, 0x29, 0x1e], [0x00, 0x36, 0x36, 0x00, 0x00], [0x00, 0x56, 0x36, 0x00, 0x00],
+    [0x08, 0x14, 0x22, 0x41, 0x00], [0x14, 0x14, 0x14, 0x14, 0x14], [0x00, 0x41, 0x22, 0x14, 0x08], [0x02, 0x01, 0x51, 0x09, 0x06],
+    [0x32, 0x49, 0x79, 0x41, 0x3e], [0x7e, 0x11, 0x11, 0x11, 0x7e], [0x7f, 0x49, 0x49, 0x49, 0x36], [0x3e, 0x41, 0x41, 0x41, 0x22],
+    [0x7f, 0x41, 0x41, 0x22, 0x1c], [0x7f, 0x49, 0x49, 0x49, 0x41], [0x7f, 0x09, 0x09, 0x09, 0x01], [0x3e, 0x41, 0x49, 0x49, 0x7a],
+    [0x7f, 0x08, 0x08, 0x08, 0x7f], [0x00, 0x41, 0x7f, 0x41, 0x00], [0x20, 0x40, 0x41, 0x3f, 0x01], [0x7f, 0x08, 0x14, 0x22, 0x41],
+    [0x7f, 0x40, 0x40, 0x40, 0x40], [0x7f, 0x02, 0x0c, 0x02, 0x7f], [0x7f, 0x04, 0x08, 0x10, 0x7f], [0x3e, 0x41, 0x41, 0x41, 0x3e],
+    [0x7f, 0x09, 0x09, 0x09, 0x06], [0x3e, 0x41, 0x51, 0x21, 0x5e], [0x7f, 0x09, 0x19, 0x29, 0x46], [0x46, 0x49, 0x49, 0x49, 0x31],
+    [0x01, 0x01, 0x7f, 0x01, 0x01], [0x3f, 0x40, 0x40, 0x40, 0x3f], [0x1f, 0x20, 0x40, 0x20, 0x1f], [0x3f, 0x40, 0x38, 0x40, 0x3f],
+    [0x63, 0x14, 0x08, 0x14, 0x63], [0x07, 0x08, 0x70, 0x08, 0x07], [0x61, 0x51, 0x49, 0x45, 0x43], [0x00, 0x7f, 0x41, 0x41, 0x00],
+    [0x02, 0x04, 0x08, 0x10, 0x20], [0x00, 0x41, 0x41, 0x7f, 0x00], [0x04, 0x02, 0x01, 0x02, 0x04], [0x40, 0x40, 0x40, 0x40, 0x40],
+    [0x00, 0x01, 0x02, 0x04, 0x00], [0x20, 0x54, 0x54, 0x54, 0x78], [0x7f, 0x48, 0x44, 0x44, 0x38], [0x38, 0x44, 0x44, 0x44, 0x20],
+    [0x38, 0x44, 0x44, 0x48, 0x7f], [0x38, 0x54, 0x54, 0x54, 0x18], [0x08, 0x7e, 0x09, 0x01, 0x02], [0x0c, 0x52, 0x52, 0x52, 0x3e],
+    [0x7f, 0x08, 0x04, 0x04, 0x78], [0x00, 0x44, 0x7d, 0x40, 0x00], [0x20, 0x40, 0x44, 0x3d, 0x00], [0x7f, 0x10, 0x28, 0x44, 0x00],
+    [0x00, 0x41, 0x7f, 0x40, 0x00], [0x7c, 0x04, 0x18, 0x04, 0x78], [0x7c, 0x08, 0x04, 0x04, 0x78], [0x38, 0x44, 0x44, 0x44, 0x38],
+    [0x7c, 0x14, 0x14, 0x14, 0x08], [0x08, 0x14, 0x14, 0x18, 0x7c], [0x7c, 0x08, 0x04, 0x04, 0x08], [0x48, 0x54, 0x54, 0x54, 0x20],
+    [0x04, 0x3f, 0x44, 0x40, 0x20], [0x3c, 0x40, 0x40, 0x20, 0x7c], [0x1c, 0x20, 0x40, 0x20, 0x1c], [0x3c, 0x40, 0x30, 0x40, 0x3c],
+    [0x44, 0x28, 0x10, 0x28, 0x44], [0x0c, 0x50, 0x50, 0x50, 0x3c], [0x44, 0x64, 0x54, 0x4c, 0x44], [0x00, 0x08, 0x36, 0x41, 0x00],
+    [0x00, 0x00, 0x7f, 0x00, 0x00], [0x00, 0x41, 0x36, 0x08, 0x00], [0x08, 0x04, 0x08, 0x10, 0x08],
+];
+
+/// Size in pixels of a line of text drawn at a scale
+///
+/// # Arguments
+///
+/// * `text`: Text on one line
+/// * `scale`: Image pixels to a side of each font pixel
+///
+/// returns: (u32, u32), the width and height
+pub fn text_size(text: &str, scale: u32) -> (u32, u32) {
+    let characters = text.chars().count().min(u32::MAX as usize) as u32;
+    (characters.saturating_mul(ADVANCE).saturating_sub(1).saturating_mul(scale), GLYPH_HEIGHT.saturating_mul(scale))
+}
+
+/// Draw a line of text with its top left corner at a position, clipped to the image
+///
+/// # Arguments
+///
+/// * `image`: ImageBuffer to draw on
+/// * `x`: Left of the text, which may be outside the image
+/// * `y`: Top of the text, which may be outside the image
+/// * `text`: Text on one line
+/// * `colour`: Colour of the text, opaque as drawn
+/// * `scale`: Image pixels to a side of each font pixel, at least 1
+///
+/// returns: ()
+pub fn draw_text(image: &mut ImageBuffer, x: i64, y: i64, text: &str, colour: image::Rgba<u8>, scale: u32) {
+    let scale = scale.max(1) as i64;
+    for (i, character) in text.chars().enumerate() {
+        let glyph = glyph(character);
+        let left = x.saturating_add((i as i64).saturating_mul(ADVANCE as i64 * scale));
+        if left >= image.width() as i64 {
+            break;
+        }
+        for (column, bits) in glyph.iter().enumerate() {
+            for row in 0..GLYPH_HEIGHT as i64 {
+                if bits >> row & 1 == 0 {
+                    continue;
+                }
+                fill(image, left.saturating_add(column as i64 * scale), y.saturating_add(row * scale), scale, colour);
+            }
+        }
+    }
+}
+
+fn glyph(character: char) -> &'static [u8; 5] {
+    match character {
+        ' '..='~' => &GLYPHS[character as usize - ' ' as usize],
+        _ => &GLYPHS['?' as usize - ' ' as usize],
+    }
+}
+
+/// Fill a square of pixels, clipped to the image
+fn fill(image: &mut ImageBuffer, x: i64, y: i64, size: i64, colour: image::Rgba<u8>) {
+    let (width, height) = (image.width() as i64, image.height() as i64);
+    for py in y.max(0)..y.saturating_add(size).min(height) {
+        for px in x.max(0)..x.saturating_add(size).min(width) {
+            image.put_pixel(px as u32, py as u32, colour);
+        }
+    }
+}
@@ -0,0 +1,77 @@
+//! Thumbnails cropped to the most salient part of the image rather than its centre, so a subject
+//! off to one side isn't cut out when changing the aspect ratio
+
+use image::imageops::{crop_imm, resize, FilterType};
+
+use crate::plane::Plane;
+use crate::rect::Rect;
+use crate::saliency::Saliency;
+use crate::ImageBuffer;
+
+/// Make a thumbnail of exactly the given size, cropping to its aspect ratio around the most
+/// salient region then downscaling with a Lanczos filter
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to make a thumbnail of
+/// * `width`: Width of the thumbnail in pixels
+/// * `height`: Height of the thumbnail in pixels
+/// * `saliency`: How to find the region to keep
+///
+/// returns: ImageBuffer, transparent if the input is empty
+pub fn thumbnail(input: &ImageBuffer, width: u32, height: u32, saliency: Saliency) -> ImageBuffer {
+    if input.width() == 0 || input.height() == 0 || width == 0 || height == 0 {
+        return ImageBuffer::new(width, height);
+    }
+
+    let crop = salient_crop(&saliency.map(input), width as f32 / height as f32);
+    let cropped = crop_imm(input, crop.x, crop.y, crop.width, crop.height).to_image();
+    resize(&cropped, width, height, FilterType::Lanczos3)
+}
+
+/// The largest crop of an aspect ratio holding the most total saliency. Only one axis is free,
+/// as the crop spans the whole of the other, and ties go to the crop nearest the centre so
+/// featureless images are centre cropped.
+///
+/// # Arguments
+///
+/// * `saliency`: Saliency map the size of the image, e.g. from `Saliency::map`
+/// * `aspect`: Width over height of the crop
+///
+/// returns: Rect, empty for an empty map
+pub fn salient_crop(saliency: &Plane, aspect: f32) -> Rect {
+    let (width, height) = (saliency.width, saliency.height);
+    if width == 0 || height == 0 {
+        return Rect::new(0, 0, width, height);
+    }
+
+    if width as f32 / height as f32 > aspect {
+        let crop_width = ((height as f32 * aspect).round() as u32).clamp(1, width);
+        let columns: Vec<f32> = (0..width).map(|x| (0..height).map(|y| saliency.get(x, y)).sum()).collect();
+        Rect::new(best_window(&columns, crop_width), 0, crop_width, height)
+    } else {
+        let crop_height = ((width as f32 / aspect).round() as u32).clamp(1, height);
+        let rows: Vec<f32> = (0..height).map(|y| (0..width).map(|x| saliency.get(x, y)).sum()).collect();
+        Rect::new(0, best_window(&rows, crop_height), width, crop_height)
+    }
+}
+
+/// Start of the window of `length` along a profile with the greatest sum, nearest the centre
+fn best_window(profile: &[f32], length: u32) -> u32 {
+    let length = length as usize;
+    let prefix: Vec<f64> = std::iter::once(0.).chain(profile.iter().scan(0., |sum, &v| {
+        *sum += v as f64;
+        Some(*sum)
+    })).collect();
+
+    let sums: Vec<f64> = (0..=profile.len() - length).map(|start| prefix[start + length] - prefix[start]).collect();
+    let best = sums.iter().copied().fold(0., f64::max);
+    // Sums within rounding of the best count as equal
+    let tolerance = 1e-6 * prefix[profile.len()].max(1.);
+
+    let centre = (profile.len() - length) as f64 / 2.;
+    (0..sums.len())
+        .filter(|&start| sums[start] >= best - tolerance)
+        .min_by(|&a, &b| (a as f64 - centre).abs().total_cmp(&(b as f64 - centre).abs()))
+        .unwrap_or(0) as u32
+}
@@ -0,0 +1,198 @@
+//! Processing of large images a piece at a time. Windowed filters read pixels around the one
+//! they write, so each tile is cut out with a halo of extra pixels on every side, processed, and
+//! only the centre without the halo is written back. Near the image border the halo is clipped,
+//! where the filter's own edge clamping then gives the same result as on the whole image.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+use image::imageops::crop_imm;
+use image::GenericImage;
+
+use crate::error::{Error, Result};
+use crate::io::open_image;
+use crate::metadata::icc::IccProfile;
+use crate::progress::Progress;
+use crate::ImageBuffer;
+
+/// Apply an operation to an image tile by tile
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to process
+/// * `tile_size`: Width and height of each tile, not including the halo, 0 to process the
+///   image as a single tile
+/// * `halo`: How far (in pixels) the operation reads from the pixel it writes
+/// * `operation`: Operation to apply, which must return an image the same size as its input
+///
+/// returns: ImageBuffer
+pub fn process_tiled<F>(input: &ImageBuffer, tile_size: u32, halo: u32, operation: F) -> ImageBuffer
+where
+    F: Fn(&ImageBuffer) -> ImageBuffer,
+{
+    if tile_size == 0 {
+        return operation(input);
+    }
+
+    process_tiled_with_progress(input, tile_size, halo, &Progress::none(), operation).unwrap()
+}
+
+/// As `process_tiled`, reporting progress after each tile and stopping early if cancelled
+///
+/// returns: Result<ImageBuffer>, `Error::InvalidParameter` for a tile size of 0
+pub fn process_tiled_with_progress<F>(input: &ImageBuffer, tile_size: u32, halo: u32, progress: &Progress, operation: F) -> Result<ImageBuffer>
+where
+    F: Fn(&ImageBuffer) -> ImageBuffer,
+{
+    if tile_size == 0 {
+        return Err(Error::InvalidParameter { operation: "tiling".to_string(), parameter: "tile_size".to_string() });
+    }
+
+    let (input_x, input_y) = input.dimensions();
+    let mut output: ImageBuffer = image::ImageBuffer::new(input_x, input_y);
+
+    let tile_count = input_x.div_ceil(tile_size) * input_y.div_ceil(tile_size);
+    let mut tiles_done = 0;
+
+    for tile_y in (0..input_y).step_by(tile_size as usize) {
+        for tile_x in (0..input_x).step_by(tile_size as usize) {
+            progress.check()?;
+
+            let tile_width = tile_size.min(input_x - tile_x);
+            let tile_height = tile_size.min(input_y - tile_y);
+
+            let region_x = tile_x.saturating_sub(halo);
+            let region_y = tile_y.saturating_sub(halo);
+            let region_width = (tile_x + tile_width + halo).min(input_x) - region_x;
+            let region_height = (tile_y + tile_height + halo).min(input_y) - region_y;
+
+            let region = crop_imm(input, region_x, region_y, region_width, region_height).to_image();
+            let processed = operation(&region);
+            assert_eq!(processed.dimensions(), region.dimensions(), "tiled operations must not change the image size");
+
+            let centre = crop_imm(&processed, tile_x - region_x, tile_y - region_y, tile_width, tile_height);
+            output.copy_from(&*centre, tile_x, tile_y).unwrap();
+
+            tiles_done += 1;
+            progress.report(tiles_done as f32 / tile_count as f32);
+        }
+    }
+
+    Ok(output)
+}
+
+/// Apply an operation to an image file strip by strip. When both files are non-interlaced pngs
+/// only the rows of the current strip and its halo are held in memory, as rows are decoded and
+/// encoded as they are needed. Any other format is loaded whole and processed in tiles.
+///
+/// # Arguments
+///
+/// * `input_path`: Image to read
+/// * `output_path`: Image to write, as an 8 bit rgba png when streaming
+/// * `strip_height`: Number of output rows produced per strip
+/// * `halo`: How far (in pixels) the operation reads from the pixel it writes
+/// * `progress`: Reported after each strip, and checked for cancellation before each strip
+/// * `operation`: Operation to apply, which must return an image the same size as its input
+///
+/// returns: Result<()>, `Error::InvalidParameter` for a strip height of 0
+pub fn process_file_streaming<F>(input_path: &Path, output_path: &Path, strip_height: u32, halo: u32, progress: &Progress, operation: F) -> Result<()>
+where
+    F: Fn(&ImageBuffer) -> ImageBuffer,
+{
+    if strip_height == 0 {
+        return Err(Error::InvalidParameter { operation: "tiling".to_string(), parameter: "strip_height".to_string() });
+    }
+
+    if !(is_png(input_path) && is_png(output_path)) {
+        let input = open_image(input_path)?;
+        let output = process_tiled_with_progress(&input, strip_height, halo, progress, operation)?;
+        return Ok(output.save(output_path)?);
+    }
+
+    let mut decoder = png::Decoder::new(BufReader::new(File::open(input_path)?));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info()?;
+
+    if reader.info().interlaced {
+        // Interlaced rows arrive in several passes, so strips can't be formed as they're read
+        drop(reader);
+        let input = open_image(input_path)?;
+        let output = process_tiled_with_progress(&input, strip_height, halo, progress, operation)?;
+        return Ok(output.save(output_path)?);
+    }
+
+    let (width, height) = (reader.info().width, reader.info().height);
+    let (color_type, _) = reader.output_color_type();
+    // Rows are converted to sRGB as they arrive, as `open_image` converts whole images
+    let profile = reader.info().icc_profile.as_deref().and_then(|profile| IccProfile::parse(profile).ok());
+    let transform = profile.filter(|profile| !profile.is_srgb()).and_then(|profile| profile.srgb_transform());
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(output_path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    let mut stream = writer.stream_writer()?;
+
+    // Rows currently held, starting from input row `window_start`
+    let mut window: VecDeque<Vec<u8>> = VecDeque::new();
+    let mut window_start = 0;
+
+    for strip_y in (0..height).step_by(strip_height as usize) {
+        progress.check()?;
+
+        let strip_end = (strip_y + strip_height).min(height);
+        let needed_start = strip_y.saturating_sub(halo);
+        let needed_end = (strip_end + halo).min(height);
+
+        while window_start < needed_start {
+            window.pop_front();
+            window_start += 1;
+        }
+
+        while window_start + (window.len() as u32) < needed_end {
+            let mut row = match reader.next_row()? {
+                Some(row) => to_rgba_row(row.data(), color_type),
+                None => return Err(Error::Codec("png ended before its last row".to_string())),
+            };
+            if let Some(transform) = &transform {
+                transform.apply_rgba(&mut row);
+            }
+            window.push_back(row);
+        }
+
+        let region_bytes: Vec<u8> = window.iter().flatten().copied().collect();
+        let region: ImageBuffer = image::ImageBuffer::from_raw(width, window.len() as u32, region_bytes).unwrap();
+
+        let processed = operation(&region);
+        assert_eq!(processed.dimensions(), region.dimensions(), "tiled operations must not change the image size");
+
+        let row_bytes = width as usize * 4;
+        let first = (strip_y - window_start) as usize * row_bytes;
+        let last = (strip_end - window_start) as usize * row_bytes;
+        stream.write_all(&processed.as_raw()[first..last])?;
+
+        progress.report(strip_end as f32 / height as f32);
+    }
+
+    stream.finish()?;
+
+    Ok(())
+}
+
+fn is_png(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).map(|e| e.eq_ignore_ascii_case("png")).unwrap_or(false)
+}
+
+/// Widen a decoded 8 bit row of any colour type to rgba
+fn to_rgba_row(data: &[u8], color_type: png::ColorType) -> Vec<u8> {
+    match color_type {
+        png::ColorType::Rgba => data.to_vec(),
+        png::ColorType::Rgb => data.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => data.chunks_exact(2).flat_map(|p| [p[0], p[0], p[0], p[1]]).collect(),
+        png::ColorType::Grayscale => data.iter().flat_map(|&v| [v, v, v, 255]).collect(),
+        // Palettes are expanded to rgb or rgba by the normalize transformation
+        png::ColorType::Indexed => unreachable!("indexed pngs are expanded when decoding"),
+    }
+}
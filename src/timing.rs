@@ -0,0 +1,12 @@
+use std::time::Instant;
+
+use log::debug;
+
+/// Run `f`, logging how long it took at debug level under `name`
+pub(crate) fn timed<T>(name: &str, f: impl FnOnce() -> T) -> T {
+    let start = Instant::now();
+    let result = f();
+    debug!("{} took {:.2?}", name, start.elapsed());
+
+    result
+}
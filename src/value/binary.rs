@@ -0,0 +1,153 @@
+//! A compact binary encoding of `Value`, for results too large to be quick as text, such as
+//! thousands of keypoints. Each value is a tag byte followed by its contents, with numbers as
+//! little endian f64 and strings, arrays and tables prefixed by a little endian u32 length.
+
+use super::{Value, MAX_DEPTH};
+use crate::error::{Error, Result};
+
+/// Start of every encoded document, with the version of the encoding
+pub const MAGIC: &[u8; 5] = b"RIPV\x01";
+
+const NULL: u8 = 0;
+const FALSE: u8 = 1;
+const TRUE: u8 = 2;
+const NUMBER: u8 = 3;
+const STRING: u8 = 4;
+const ARRAY: u8 = 5;
+const TABLE: u8 = 6;
+
+/// Encode a value, header first
+pub fn to_bytes(value: &Value) -> Vec<u8> {
+    let mut output = MAGIC.to_vec();
+    write_value(value, &mut output);
+
+    output
+}
+
+/// Decode a document written by `to_bytes`
+///
+/// # Arguments
+///
+/// * `bytes`: Encoded document, header first
+///
+/// returns: Result<Value>
+pub fn from_bytes(bytes: &[u8]) -> Result<Value> {
+    let Some(body) = bytes.strip_prefix(MAGIC.as_slice()) else {
+        return Err(Error::Codec("not a binary value document".to_string()));
+    };
+
+    let mut reader = Reader { bytes: body, pos: 0, depth: 0 };
+    let value = reader.value()?;
+    if reader.pos != body.len() {
+        return Err(Error::Codec("unexpected trailing bytes".to_string()));
+    }
+
+    Ok(value)
+}
+
+fn write_value(value: &Value, output: &mut Vec<u8>) {
+    match value {
+        Value::Null => output.push(NULL),
+        Value::Bool(false) => output.push(FALSE),
+        Value::Bool(true) => output.push(TRUE),
+        Value::Number(number) => {
+            output.push(NUMBER);
+            output.extend_from_slice(&number.to_le_bytes());
+        }
+        Value::String(string) => {
+            output.push(STRING);
+            write_string(string, output);
+        }
+        Value::Array(values) => {
+            output.push(ARRAY);
+            output.extend_from_slice(&(values.len() as u32).to_le_bytes());
+            for value in values {
+                write_value(value, output);
+            }
+        }
+        Value::Table(entries) => {
+            output.push(TABLE);
+            output.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+            for (key, value) in entries {
+                write_string(key, output);
+                write_value(value, output);
+            }
+        }
+    }
+}
+
+fn write_string(string: &str, output: &mut Vec<u8>) {
+    output.extend_from_slice(&(string.len() as u32).to_le_bytes());
+    output.extend_from_slice(string.as_bytes());
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    /// Arrays and tables open around the current value
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(count).filter(|&end| end <= self.bytes.len()).ok_or_else(|| Error::Codec("unexpected end of document".to_string()))?;
+        let taken = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(taken)
+    }
+
+    fn length(&mut self) -> Result<usize> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let length = self.length()?;
+        String::from_utf8(self.take(length)?.to_vec()).map_err(|_| Error::Codec("invalid utf-8 in string".to_string()))
+    }
+
+    /// Read an array or table nested one level deeper, failing past `MAX_DEPTH` levels
+    fn nested(&mut self, read: impl FnOnce(&mut Reader<'a>) -> Result<Value>) -> Result<Value> {
+        if self.depth >= MAX_DEPTH {
+            return Err(Error::Codec(format!("nested deeper than {} levels", MAX_DEPTH)));
+        }
+
+        self.depth += 1;
+        let result = read(self);
+        self.depth -= 1;
+
+        result
+    }
+
+    fn value(&mut self) -> Result<Value> {
+        match self.take(1)?[0] {
+            NULL => Ok(Value::Null),
+            FALSE => Ok(Value::Bool(false)),
+            TRUE => Ok(Value::Bool(true)),
+            NUMBER => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(self.take(8)?);
+                Ok(Value::Number(f64::from_le_bytes(bytes)))
+            }
+            STRING => Ok(Value::String(self.string()?)),
+            ARRAY => self.nested(|reader| {
+                let length = reader.length()?;
+                // Each value takes at least a byte, so a corrupt length can't reserve much
+                let mut values = Vec::with_capacity(length.min(reader.bytes.len() - reader.pos));
+                for _ in 0..length {
+                    values.push(reader.value()?);
+                }
+                Ok(Value::Array(values))
+            }),
+            TABLE => self.nested(|reader| {
+                let length = reader.length()?;
+                let mut entries = Vec::with_capacity(length.min(reader.bytes.len() - reader.pos));
+                for _ in 0..length {
+                    entries.push((reader.string()?, reader.value()?));
+                }
+                Ok(Value::Table(entries))
+            }),
+            tag => Err(Error::Codec(format!("unknown value tag {}", tag))),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use super::{Cursor, Value};
+use crate::error::Result;
+
+/// Parse a json document
+///
+/// # Arguments
+///
+/// * `source`: json text
+///
+/// returns: Result<Value>
+pub fn parse(source: &str) -> Result<Value> {
+    let mut cursor = Cursor::new(source);
+
+    cursor.skip_whitespace(true);
+    let value = parse_value(&mut cursor)?;
+    cursor.skip_whitespace(true);
+
+    if !cursor.is_done() {
+        return Err(cursor.error("unexpected trailing characters"));
+    }
+
+    Ok(value)
+}
+
+/// Write a value out as compact json
+pub fn to_string(value: &Value) -> String {
+    let mut output = String::new();
+    write_value(value, &mut output);
+
+    output
+}
+
+fn parse_value(cursor: &mut Cursor) -> Result<Value> {
+    match cursor.peek() {
+        Some('{') => cursor.nested(parse_object),
+        Some('[') => cursor.nested(parse_array),
+        Some('"') => Ok(Value::String(cursor.parse_quoted_string()?)),
+        Some(c) if c == '-' || c.is_ascii_digit() => cursor.parse_number(),
+        _ => {
+            if cursor.eat_word("true") {
+                Ok(Value::Bool(true))
+            } else if cursor.eat_word("false") {
+                Ok(Value::Bool(false))
+            } else if cursor.eat_word("null") {
+                Ok(Value::Null)
+            } else {
+                Err(cursor.error("expected a value"))
+            }
+        }
+    }
+}
+
+fn parse_object(cursor: &mut Cursor) -> Result<Value> {
+    cursor.expect('{')?;
+    let mut entries = Vec::new();
+
+    cursor.skip_whitespace(true);
+    if cursor.eat('}') {
+        return Ok(Value::Table(entries));
+    }
+
+    loop {
+        cursor.skip_whitespace(true);
+        let key = cursor.parse_quoted_string()?;
+        cursor.skip_whitespace(true);
+        cursor.expect(':')?;
+        cursor.skip_whitespace(true);
+        let value = parse_value(cursor)?;
+        entries.push((key, value));
+
+        cursor.skip_whitespace(true);
+        if cursor.eat('}') {
+            return Ok(Value::Table(entries));
+        }
+        cursor.expect(',')?;
+    }
+}
+
+fn parse_array(cursor: &mut Cursor) -> Result<Value> {
+    cursor.expect('[')?;
+    let mut values = Vec::new();
+
+    cursor.skip_whitespace(true);
+    if cursor.eat(']') {
+        return Ok(Value::Array(values));
+    }
+
+    loop {
+        cursor.skip_whitespace(true);
+        values.push(parse_value(cursor)?);
+
+        cursor.skip_whitespace(true);
+        if cursor.eat(']') {
+            return Ok(Value::Array(values));
+        }
+        cursor.expect(',')?;
+    }
+}
+
+fn write_value(value: &Value, output: &mut String) {
+    match value {
+        Value::Null => output.push_str("null"),
+        Value::Bool(boolean) => output.push_str(if *boolean { "true" } else { "false" }),
+        Value::Number(number) => {
+            if number.is_finite() {
+                output.push_str(&number.to_string());
+            } else {
+                output.push_str("null");
+            }
+        }
+        Value::String(string) => write_string(string, output),
+        Value::Array(values) => {
+            output.push('[');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                write_value(value, output);
+            }
+            output.push(']');
+        }
+        Value::Table(entries) => {
+            output.push('{');
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    output.push(',');
+                }
+                write_string(key, output);
+                output.push(':');
+                write_value(value, output);
+            }
+            output.push('}');
+        }
+    }
+}
+
+fn write_string(string: &str, output: &mut String) {
+    output.push('"');
+    for c in string.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+}
@@ -0,0 +1,237 @@
+//! A small document model shared by the pipeline file formats. Each format module parses its own
+//! syntax into a `Value` tree, so the code consuming the document never cares which format it
+//! came from.
+
+pub mod binary;
+pub mod json;
+pub mod toml;
+pub mod yaml;
+
+use crate::error::{parse_error, Error, Result};
+
+/// Deepest nesting of arrays and tables the parsers accept, so a document of thousands of open
+/// brackets is a parse error rather than a stack overflow
+pub const MAX_DEPTH: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Value>),
+    Table(Vec<(String, Value)>),
+}
+
+impl Value {
+    /// Look up a key of a table, returns None for missing keys or if this value is not a table
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Table(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Number(number) => Some(*number),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(boolean) => Some(*boolean),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Value]> {
+        match self {
+            Value::Array(values) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+/// Character cursor used by the json and toml parsers, keeps track of the current line so parse
+/// errors can point at the offending input
+pub(crate) struct Cursor {
+    chars: Vec<char>,
+    pos: usize,
+    line: usize,
+    depth: usize,
+}
+
+impl Cursor {
+    pub(crate) fn new(source: &str) -> Cursor {
+        Cursor { chars: source.chars().collect(), pos: 0, line: 1, depth: 0 }
+    }
+
+    pub(crate) fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    pub(crate) fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += 1;
+        if c == '\n' {
+            self.line += 1;
+        }
+
+        Some(c)
+    }
+
+    pub(crate) fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    pub(crate) fn expect(&mut self, c: char) -> Result<()> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{}'", c)))
+        }
+    }
+
+    pub(crate) fn eat_word(&mut self, word: &str) -> bool {
+        let matches = word.chars().enumerate().all(|(i, c)| self.chars.get(self.pos + i) == Some(&c));
+        if matches {
+            for _ in word.chars() {
+                self.bump();
+            }
+        }
+
+        matches
+    }
+
+    pub(crate) fn is_done(&self) -> bool {
+        self.pos >= self.chars.len()
+    }
+
+    pub(crate) fn error(&self, message: impl Into<String>) -> Error {
+        parse_error(self.line, message)
+    }
+
+    /// Parse an array or table nested one level deeper, failing past `MAX_DEPTH` levels
+    pub(crate) fn nested<T>(&mut self, parse: impl FnOnce(&mut Cursor) -> Result<T>) -> Result<T> {
+        if self.depth >= MAX_DEPTH {
+            return Err(self.error(format!("nested deeper than {} levels", MAX_DEPTH)));
+        }
+
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+
+        result
+    }
+
+    /// Skip spaces and tabs, and newlines as well if `newlines` is set
+    pub(crate) fn skip_whitespace(&mut self, newlines: bool) {
+        while let Some(c) = self.peek() {
+            if c == ' ' || c == '\t' || c == '\r' || (newlines && c == '\n') {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    pub(crate) fn skip_comment(&mut self) {
+        if self.peek() == Some('#') {
+            while let Some(c) = self.peek() {
+                if c == '\n' {
+                    break;
+                }
+                self.bump();
+            }
+        }
+    }
+
+    /// Parse a number, allowing the `_` digit separators toml permits
+    pub(crate) fn parse_number(&mut self) -> Result<Value> {
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E' | '_') {
+                if c != '_' {
+                    text.push(c);
+                }
+                self.bump();
+            } else {
+                break;
+            }
+        }
+
+        text.parse::<f64>()
+            .map(Value::Number)
+            .map_err(|_| self.error(format!("invalid number '{}'", text)))
+    }
+
+    /// Parse a double quoted string with json/toml style escapes, the cursor must be on the quote
+    pub(crate) fn parse_quoted_string(&mut self) -> Result<String> {
+        self.expect('"')?;
+        let mut string = String::new();
+
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(string),
+                Some('\\') => {
+                    let escaped = match self.bump() {
+                        Some('n') => '\n',
+                        Some('t') => '\t',
+                        Some('r') => '\r',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('u') => self.parse_unicode_escape()?,
+                        _ => return Err(self.error("invalid escape sequence")),
+                    };
+                    string.push(escaped);
+                }
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    /// Parse a single quoted string, where no escapes are processed
+    pub(crate) fn parse_literal_string(&mut self) -> Result<String> {
+        self.expect('\'')?;
+        let mut string = String::new();
+
+        loop {
+            match self.bump() {
+                None | Some('\n') => return Err(self.error("unterminated string")),
+                Some('\'') => return Ok(string),
+                Some(c) => string.push(c),
+            }
+        }
+    }
+
+    fn parse_unicode_escape(&mut self) -> Result<char> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.bump().and_then(|c| c.to_digit(16));
+            match digit {
+                Some(digit) => code = code * 16 + digit,
+                None => return Err(self.error("invalid unicode escape")),
+            }
+        }
+
+        char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+}
@@ -0,0 +1,222 @@
+//! Parser for the subset of toml used by pipeline files: `[table]` and `[[array.of.tables]]`
+//! headers, `key = value` pairs, comments, and inline strings, numbers, booleans, arrays and
+//! tables. Dates and multi-line strings are not supported.
+
+use super::{Cursor, Value};
+use crate::error::Result;
+
+/// Parse a toml document into a table
+///
+/// # Arguments
+///
+/// * `source`: toml text
+///
+/// returns: Result<Value>
+pub fn parse(source: &str) -> Result<Value> {
+    let mut cursor = Cursor::new(source);
+    let mut root = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+
+    loop {
+        skip_whitespace_and_comments(&mut cursor);
+
+        if cursor.is_done() {
+            break;
+        }
+
+        if cursor.eat('[') {
+            let array = cursor.eat('[');
+            let path = parse_key_path(&mut cursor)?;
+            cursor.expect(']')?;
+            if array {
+                cursor.expect(']')?;
+                push_table(&mut root, &path, &cursor)?;
+            } else {
+                table_at(&mut root, &path, &cursor)?;
+            }
+            current = path;
+        } else {
+            let key = parse_key(&mut cursor)?;
+            cursor.skip_whitespace(false);
+            cursor.expect('=')?;
+            cursor.skip_whitespace(false);
+            let value = parse_value(&mut cursor)?;
+
+            let table = table_at(&mut root, &current, &cursor)?;
+            if table.iter().any(|(k, _)| *k == key) {
+                return Err(cursor.error(format!("duplicate key '{}'", key)));
+            }
+            table.push((key, value));
+        }
+
+        end_of_line(&mut cursor)?;
+    }
+
+    Ok(Value::Table(root))
+}
+
+fn end_of_line(cursor: &mut Cursor) -> Result<()> {
+    cursor.skip_whitespace(false);
+    cursor.skip_comment();
+
+    if cursor.is_done() || cursor.eat('\n') {
+        Ok(())
+    } else {
+        Err(cursor.error("expected a new line"))
+    }
+}
+
+fn parse_key(cursor: &mut Cursor) -> Result<String> {
+    match cursor.peek() {
+        Some('"') => cursor.parse_quoted_string(),
+        Some('\'') => cursor.parse_literal_string(),
+        _ => {
+            let mut key = String::new();
+            while let Some(c) = cursor.peek() {
+                if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+                    key.push(c);
+                    cursor.bump();
+                } else {
+                    break;
+                }
+            }
+
+            if key.is_empty() {
+                Err(cursor.error("expected a key"))
+            } else {
+                Ok(key)
+            }
+        }
+    }
+}
+
+fn parse_key_path(cursor: &mut Cursor) -> Result<Vec<String>> {
+    let mut path = Vec::new();
+
+    loop {
+        cursor.skip_whitespace(false);
+        path.push(parse_key(cursor)?);
+        cursor.skip_whitespace(false);
+
+        if !cursor.eat('.') {
+            return Ok(path);
+        }
+    }
+}
+
+fn parse_value(cursor: &mut Cursor) -> Result<Value> {
+    match cursor.peek() {
+        Some('"') => Ok(Value::String(cursor.parse_quoted_string()?)),
+        Some('\'') => Ok(Value::String(cursor.parse_literal_string()?)),
+        Some('[') => cursor.nested(parse_array),
+        Some('{') => cursor.nested(parse_inline_table),
+        Some(c) if c == '-' || c == '+' || c.is_ascii_digit() => cursor.parse_number(),
+        _ => {
+            if cursor.eat_word("true") {
+                Ok(Value::Bool(true))
+            } else if cursor.eat_word("false") {
+                Ok(Value::Bool(false))
+            } else {
+                Err(cursor.error("expected a value"))
+            }
+        }
+    }
+}
+
+/// Arrays may span several lines and contain comments and a trailing comma
+fn parse_array(cursor: &mut Cursor) -> Result<Value> {
+    cursor.expect('[')?;
+    let mut values = Vec::new();
+
+    loop {
+        skip_whitespace_and_comments(cursor);
+        if cursor.eat(']') {
+            return Ok(Value::Array(values));
+        }
+
+        values.push(parse_value(cursor)?);
+
+        skip_whitespace_and_comments(cursor);
+        if cursor.eat(']') {
+            return Ok(Value::Array(values));
+        }
+        cursor.expect(',')?;
+    }
+}
+
+fn skip_whitespace_and_comments(cursor: &mut Cursor) {
+    loop {
+        cursor.skip_whitespace(true);
+        if cursor.peek() == Some('#') {
+            cursor.skip_comment();
+        } else {
+            break;
+        }
+    }
+}
+
+fn parse_inline_table(cursor: &mut Cursor) -> Result<Value> {
+    cursor.expect('{')?;
+    let mut entries = Vec::new();
+
+    cursor.skip_whitespace(false);
+    if cursor.eat('}') {
+        return Ok(Value::Table(entries));
+    }
+
+    loop {
+        cursor.skip_whitespace(false);
+        let key = parse_key(cursor)?;
+        cursor.skip_whitespace(false);
+        cursor.expect('=')?;
+        cursor.skip_whitespace(false);
+        entries.push((key, parse_value(cursor)?));
+
+        cursor.skip_whitespace(false);
+        if cursor.eat('}') {
+            return Ok(Value::Table(entries));
+        }
+        cursor.expect(',')?;
+    }
+}
+
+/// Walk (creating as needed) to the table at `path`, where an array of tables along the way
+/// resolves to its most recently added table
+fn table_at<'a>(root: &'a mut Vec<(String, Value)>, path: &[String], cursor: &Cursor) -> Result<&'a mut Vec<(String, Value)>> {
+    let mut table = root;
+
+    for key in path {
+        if !table.iter().any(|(k, _)| k == key) {
+            table.push((key.clone(), Value::Table(Vec::new())));
+        }
+
+        let value = table.iter_mut().find(|(k, _)| k == key).map(|(_, v)| v).unwrap();
+        let value = match value {
+            Value::Array(values) => match values.last_mut() {
+                Some(value) => value,
+                None => return Err(cursor.error(format!("key '{}' is an empty array", key))),
+            },
+            value => value,
+        };
+
+        table = match value {
+            Value::Table(entries) => entries,
+            _ => return Err(cursor.error(format!("key '{}' is not a table", key))),
+        };
+    }
+
+    Ok(table)
+}
+
+fn push_table(root: &mut Vec<(String, Value)>, path: &[String], cursor: &Cursor) -> Result<()> {
+    let (key, parent) = path.split_last().unwrap();
+    let parent = table_at(root, parent, cursor)?;
+
+    match parent.iter_mut().find(|(k, _)| k == key) {
+        Some((_, Value::Array(values))) => values.push(Value::Table(Vec::new())),
+        Some(_) => return Err(cursor.error(format!("key '{}' is not an array of tables", key))),
+        None => parent.push((key.clone(), Value::Array(vec![Value::Table(Vec::new())]))),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,241 @@
+//! Parser for the block style subset of yaml used by pipeline files: nested mappings and `- `
+//! sequences by indentation, comments, quoted and plain scalars, and `[a, b]` flow sequences.
+//! Anchors, tags, multi-document streams and block scalars are not supported.
+
+use super::{Cursor, Value, MAX_DEPTH};
+use crate::error::{parse_error, Result};
+
+struct Line {
+    number: usize,
+    indent: usize,
+    content: String,
+}
+
+/// Parse a yaml document
+///
+/// # Arguments
+///
+/// * `source`: yaml text
+///
+/// returns: Result<Value>
+pub fn parse(source: &str) -> Result<Value> {
+    let mut lines = Vec::new();
+
+    for (i, raw) in source.lines().enumerate() {
+        let content = strip_comment(raw);
+        let trimmed = content.trim_start();
+        if trimmed.is_empty() || trimmed == "---" {
+            continue;
+        }
+
+        if content.starts_with('\t') {
+            return Err(parse_error(i + 1, "tabs are not allowed for indentation"));
+        }
+
+        lines.push(Line {
+            number: i + 1,
+            indent: content.len() - trimmed.len(),
+            content: trimmed.trim_end().to_string(),
+        });
+    }
+
+    if lines.is_empty() {
+        return Ok(Value::Null);
+    }
+
+    let mut pos = 0;
+    let indent = lines[0].indent;
+    let value = parse_block(&mut lines, &mut pos, indent, 0)?;
+
+    if pos < lines.len() {
+        return Err(parse_error(lines[pos].number, "unexpected indentation"));
+    }
+
+    Ok(value)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+
+    for (i, c) in line.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '#' && (previous == ' ' || previous == '\t') => return &line[..i],
+            None => {}
+        }
+        previous = c;
+    }
+
+    line
+}
+
+/// Parse the block at `indent`, nested `depth` blocks deep, failing past `MAX_DEPTH`
+fn parse_block(lines: &mut [Line], pos: &mut usize, indent: usize, depth: usize) -> Result<Value> {
+    if depth >= MAX_DEPTH {
+        return Err(parse_error(lines[*pos].number, format!("nested deeper than {} levels", MAX_DEPTH)));
+    }
+
+    if is_sequence_item(&lines[*pos].content) {
+        parse_sequence(lines, pos, indent, depth)
+    } else {
+        parse_mapping(lines, pos, indent, depth)
+    }
+}
+
+fn is_sequence_item(content: &str) -> bool {
+    content == "-" || content.starts_with("- ")
+}
+
+fn parse_sequence(lines: &mut [Line], pos: &mut usize, indent: usize, depth: usize) -> Result<Value> {
+    let mut values = Vec::new();
+
+    while *pos < lines.len() && lines[*pos].indent == indent && is_sequence_item(&lines[*pos].content) {
+        let rest = lines[*pos].content[1..].trim_start().to_string();
+
+        if rest.is_empty() {
+            *pos += 1;
+            values.push(parse_nested(lines, pos, indent, depth)?);
+        } else if split_key(&rest).is_some() {
+            // `- key: value` starts a mapping whose keys line up with the first key
+            let line = &mut lines[*pos];
+            line.indent = indent + line.content.len() - rest.len();
+            line.content = rest;
+            let item_indent = line.indent;
+            values.push(parse_block(lines, pos, item_indent, depth + 1)?);
+        } else {
+            values.push(parse_scalar(&rest, lines[*pos].number)?);
+            *pos += 1;
+        }
+    }
+
+    Ok(Value::Array(values))
+}
+
+fn parse_mapping(lines: &mut [Line], pos: &mut usize, indent: usize, depth: usize) -> Result<Value> {
+    let mut entries: Vec<(String, Value)> = Vec::new();
+
+    while *pos < lines.len() && lines[*pos].indent == indent && !is_sequence_item(&lines[*pos].content) {
+        let number = lines[*pos].number;
+        let (key, rest) = match split_key(&lines[*pos].content) {
+            Some((key, rest)) => (key, rest),
+            None => return Err(parse_error(number, "expected 'key: value'")),
+        };
+
+        if entries.iter().any(|(k, _)| *k == key) {
+            return Err(parse_error(number, format!("duplicate key '{}'", key)));
+        }
+
+        *pos += 1;
+        let value = if rest.is_empty() {
+            let sequence_follows = *pos < lines.len() && lines[*pos].indent == indent && is_sequence_item(&lines[*pos].content);
+            if sequence_follows {
+                parse_block(lines, pos, indent, depth + 1)?
+            } else {
+                parse_nested(lines, pos, indent, depth)?
+            }
+        } else {
+            parse_scalar(&rest, number)?
+        };
+
+        entries.push((key, value));
+    }
+
+    Ok(Value::Table(entries))
+}
+
+/// Parse the block indented deeper than `indent`, or null if there is none
+fn parse_nested(lines: &mut [Line], pos: &mut usize, indent: usize, depth: usize) -> Result<Value> {
+    if *pos < lines.len() && lines[*pos].indent > indent {
+        let nested_indent = lines[*pos].indent;
+        parse_block(lines, pos, nested_indent, depth + 1)
+    } else {
+        Ok(Value::Null)
+    }
+}
+
+/// Split `key: value` into its key and (possibly empty) value
+fn split_key(content: &str) -> Option<(String, String)> {
+    let (key, rest) = if content.starts_with('"') || content.starts_with('\'') {
+        let quote = content.chars().next().unwrap();
+        let end = content[1..].find(quote)? + 1;
+        (content[1..end].to_string(), &content[end + 1..])
+    } else {
+        let end = content.find(':')?;
+        (content[..end].trim_end().to_string(), &content[end..])
+    };
+
+    let rest = rest.strip_prefix(':')?;
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+
+    Some((key, rest.trim().to_string()))
+}
+
+fn parse_scalar(text: &str, number: usize) -> Result<Value> {
+    let mut cursor = Cursor::new(text);
+    let value = parse_flow_value(&mut cursor, false).map_err(|_| parse_error(number, format!("invalid value '{}'", text)))?;
+
+    cursor.skip_whitespace(false);
+    if !cursor.is_done() {
+        return Err(parse_error(number, format!("invalid value '{}'", text)));
+    }
+
+    Ok(value)
+}
+
+fn parse_flow_value(cursor: &mut Cursor, in_sequence: bool) -> Result<Value> {
+    cursor.skip_whitespace(false);
+
+    match cursor.peek() {
+        Some('"') => Ok(Value::String(cursor.parse_quoted_string()?)),
+        Some('\'') => Ok(Value::String(cursor.parse_literal_string()?)),
+        Some('[') => cursor.nested(parse_flow_sequence),
+        _ => {
+            let mut text = String::new();
+            while let Some(c) = cursor.peek() {
+                if in_sequence && (c == ',' || c == ']') {
+                    break;
+                }
+                text.push(c);
+                cursor.bump();
+            }
+
+            Ok(plain_scalar(text.trim()))
+        }
+    }
+}
+
+fn parse_flow_sequence(cursor: &mut Cursor) -> Result<Value> {
+    cursor.expect('[')?;
+    let mut values = Vec::new();
+
+    cursor.skip_whitespace(false);
+    if cursor.eat(']') {
+        return Ok(Value::Array(values));
+    }
+
+    loop {
+        values.push(parse_flow_value(cursor, true)?);
+        cursor.skip_whitespace(false);
+        if cursor.eat(']') {
+            return Ok(Value::Array(values));
+        }
+        cursor.expect(',')?;
+    }
+}
+
+fn plain_scalar(text: &str) -> Value {
+    match text {
+        "" | "~" | "null" => Value::Null,
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => match text.parse::<f64>() {
+            Ok(number) => Value::Number(number),
+            Err(_) => Value::String(text.to_string()),
+        },
+    }
+}
@@ -0,0 +1,75 @@
+//! Radial brightness falloff: correcting the darkening lenses give towards the corners, and
+//! adding it as an effect.
+//!
+//! Falloff is modelled as a gain of `1 - strength * r^falloff`, where `r` is the distance from
+//! the centre as a fraction of the distance to the farthest corner.
+
+use crate::ImageBuffer;
+
+/// Gain is kept above this when correcting, so a strength of 1 doesn't divide by zero
+const MIN_GAIN: f32 = 0.05;
+
+/// Brighten the edges of an image to undo lens vignetting, about the image centre
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to correct
+/// * `strength`: How much darker the farthest corner is than the centre, from 0 to 1
+/// * `falloff`: How quickly the darkening grows with distance, 2 for the usual lens falloff
+///
+/// returns: ImageBuffer
+pub fn devignette(input: &ImageBuffer, strength: f32, falloff: f32) -> ImageBuffer {
+    devignette_at(input, strength, falloff, image_centre(input))
+}
+
+/// As `devignette`, about an optical centre in pixels
+pub fn devignette_at(input: &ImageBuffer, strength: f32, falloff: f32, centre: (f32, f32)) -> ImageBuffer {
+    scale_radially(input, centre, |r| 1. / (1. - strength * r.powf(falloff)).max(MIN_GAIN))
+}
+
+/// Darken the edges of an image as a creative effect, the inverse of `devignette`
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to darken
+/// * `strength`: How much to darken the farthest corner, from 0 to 1
+/// * `falloff`: How quickly the darkening grows with distance, higher keeps more of the
+///   middle untouched
+///
+/// returns: ImageBuffer
+pub fn vignette(input: &ImageBuffer, strength: f32, falloff: f32) -> ImageBuffer {
+    vignette_at(input, strength, falloff, image_centre(input))
+}
+
+/// As `vignette`, about a centre in pixels, e.g. to keep a subject off the middle lit
+pub fn vignette_at(input: &ImageBuffer, strength: f32, falloff: f32, centre: (f32, f32)) -> ImageBuffer {
+    scale_radially(input, centre, |r| (1. - strength * r.powf(falloff)).max(0.))
+}
+
+fn image_centre(input: &ImageBuffer) -> (f32, f32) {
+    ((input.width() as f32 - 1.) / 2., (input.height() as f32 - 1.) / 2.)
+}
+
+/// Scale the r,g,b values of each pixel by a gain for its normalised distance from the centre,
+/// leaving alpha untouched
+fn scale_radially<F>(input: &ImageBuffer, (cx, cy): (f32, f32), gain: F) -> ImageBuffer
+where
+    F: Fn(f32) -> f32,
+{
+    let (width, height) = input.dimensions();
+    // The farthest corner from the centre is at r = 1
+    let corner_x = cx.max(width as f32 - 1. - cx);
+    let corner_y = cy.max(height as f32 - 1. - cy);
+    let radius = corner_x.hypot(corner_y).max(1.);
+
+    let mut output = input.clone();
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let r = (x as f32 - cx).hypot(y as f32 - cy) / radius;
+        let gain = gain(r);
+        for channel in 0..3 {
+            pixel[channel] = (pixel[channel] as f32 * gain).round().clamp(0., 255.) as u8;
+        }
+    }
+
+    output
+}
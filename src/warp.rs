@@ -0,0 +1,656 @@
+//! Geometric transforms, which build each output pixel by resampling the input at a position
+//! mapped from the output pixel's own, and the lens distortion and chromatic aberration
+//! corrections built on them
+
+use crate::error::{Error, Result};
+use crate::linalg::{invert3, multiply3, null_vector, transform3};
+use crate::plane::Plane;
+use crate::ImageBuffer;
+
+/// Iterations used to invert the distortion model, which has no closed form inverse
+const UNDISTORT_ITERATIONS: usize = 20;
+
+/// Resample an image through a mapping from output to input positions, with pixel centres at
+/// whole coordinates. Output pixels mapped outside the input are left transparent.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to resample
+/// * `width`, `height`: Size of the output
+/// * `map`: Input position to sample for each output position
+///
+/// returns: ImageBuffer
+pub fn warp<F>(input: &ImageBuffer, width: u32, height: u32, map: F) -> ImageBuffer
+where
+    F: Fn(f32, f32) -> (f32, f32),
+{
+    let mut output = ImageBuffer::new(width, height);
+
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        let (source_x, source_y) = map(x as f32, y as f32);
+        if let Some(sample) = sample_bilinear(input, source_x, source_y) {
+            *pixel = image::Rgba(sample);
+        }
+    }
+
+    output
+}
+
+/// How to sample an image between pixel centres
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Interpolation {
+    /// The nearest pixel, blocky but never mixing colours
+    Nearest,
+    /// The four pixels around, weighted by distance
+    #[default]
+    Bilinear,
+    /// The sixteen pixels around, by a cubic convolution, sharper than bilinear but can ring
+    /// at hard edges
+    Bicubic,
+}
+
+impl Interpolation {
+    /// The input sampled at a position, or None past the edges
+    pub fn sample(&self, input: &ImageBuffer, x: f32, y: f32) -> Option<[u8; 4]> {
+        match self {
+            Interpolation::Nearest => sample_nearest(input, x, y),
+            Interpolation::Bilinear => sample_bilinear(input, x, y),
+            Interpolation::Bicubic => sample_bicubic(input, x, y),
+        }
+    }
+}
+
+/// Resample an image at the positions given per output pixel by a pair of maps, e.g. from an
+/// optical flow field or a precomputed lens correction. Output pixels mapped outside the input
+/// are left transparent.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to resample
+/// * `map_x`: Input column to sample for each output pixel, its size the output's
+/// * `map_y`: Input row to sample for each output pixel, the size of `map_x`
+///
+/// returns: Result<ImageBuffer>, an error if the maps differ in size
+pub fn remap(input: &ImageBuffer, map_x: &Plane, map_y: &Plane) -> Result<ImageBuffer> {
+    remap_with(input, map_x, map_y, Interpolation::Bilinear)
+}
+
+/// As `remap`, sampling by a chosen interpolation
+pub fn remap_with(input: &ImageBuffer, map_x: &Plane, map_y: &Plane, interpolation: Interpolation) -> Result<ImageBuffer> {
+    if (map_x.width, map_x.height) != (map_y.width, map_y.height) {
+        return Err(Error::DimensionMismatch { expected: (map_x.width, map_x.height), actual: (map_y.width, map_y.height) });
+    }
+
+    Ok(resample(input, map_x, map_y, interpolation))
+}
+
+/// `remap_with` for maps known to be the same size
+fn resample(input: &ImageBuffer, map_x: &Plane, map_y: &Plane, interpolation: Interpolation) -> ImageBuffer {
+    let mut output = ImageBuffer::new(map_x.width, map_x.height);
+    for (pixel, (&x, &y)) in output.pixels_mut().zip(map_x.data.iter().zip(&map_y.data)) {
+        if let Some(sample) = interpolation.sample(input, x, y) {
+            *pixel = image::Rgba(sample);
+        }
+    }
+
+    output
+}
+
+/// Transform an image by a homography, e.g. to square up a photo of a flat surface taken at an
+/// angle. Output pixels mapped from outside the input are left transparent, and all of them are
+/// when the homography can't be inverted.
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to transform
+/// * `homography`: Homography from input positions to output positions
+/// * `width`, `height`: Size of the output
+///
+/// returns: ImageBuffer
+pub fn warp_perspective(input: &ImageBuffer, homography: &Homography, width: u32, height: u32) -> ImageBuffer {
+    let Some(inverse) = homography.inverse() else {
+        return ImageBuffer::new(width, height);
+    };
+
+    warp(input, width, height, |x, y| {
+        let (source_x, source_y) = inverse.apply(x as f64, y as f64);
+        (source_x as f32, source_y as f32)
+    })
+}
+
+/// Rotate an image about its centre, keeping its size, so the corners are cut off and the
+/// uncovered corners left transparent
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to rotate
+/// * `degrees`: Angle to rotate clockwise, negative for anticlockwise
+///
+/// returns: ImageBuffer
+pub fn rotate(input: &ImageBuffer, degrees: f32) -> ImageBuffer {
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let (cx, cy) = ((input.width() as f32 - 1.) / 2., (input.height() as f32 - 1.) / 2.);
+
+    // With y down, the matrix [cos -sin; sin cos] turns clockwise, so sample back the other way
+    warp(input, input.width(), input.height(), |x, y| {
+        let (dx, dy) = (x - cx, y - cy);
+        (cx + cos * dx + sin * dy, cy - sin * dx + cos * dy)
+    })
+}
+
+/// The input interpolated between the four pixels around a position, with pixel centres at
+/// whole coordinates, or None more than half a pixel past the edges
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to sample
+/// * `x`, `y`: Position to sample
+///
+/// returns: Option<[u8; 4]>, the r, g, b, a sample
+pub fn sample_bilinear(input: &ImageBuffer, x: f32, y: f32) -> Option<[u8; 4]> {
+    let (width, height) = input.dimensions();
+    if !in_bounds(input, x, y) {
+        return None;
+    }
+
+    let x = x.clamp(0., (width - 1) as f32);
+    let y = y.clamp(0., (height - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+
+    let (a, b, c, d) = (input.get_pixel(x0, y0), input.get_pixel(x1, y0), input.get_pixel(x0, y1), input.get_pixel(x1, y1));
+    Some(std::array::from_fn(|channel| {
+        let top = a[channel] as f32 * (1. - tx) + b[channel] as f32 * tx;
+        let bottom = c[channel] as f32 * (1. - tx) + d[channel] as f32 * tx;
+        (top * (1. - ty) + bottom * ty).round() as u8
+    }))
+}
+
+/// The input interpolated over the sixteen pixels around a position by Keys' cubic convolution
+/// (1981), repeating the edge pixels past the edges, or None more than half a pixel past them
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to sample
+/// * `x`, `y`: Position to sample
+///
+/// returns: Option<[u8; 4]>, the r, g, b, a sample
+pub fn sample_bicubic(input: &ImageBuffer, x: f32, y: f32) -> Option<[u8; 4]> {
+    if !in_bounds(input, x, y) {
+        return None;
+    }
+
+    let (width, height) = (input.width() as i64, input.height() as i64);
+    let (x0, y0) = (x.floor() as i64, y.floor() as i64);
+    let (wx, wy) = (cubic_weights(x - x0 as f32), cubic_weights(y - y0 as f32));
+
+    let mut sum = [0f32; 4];
+    for (j, wy) in wy.iter().enumerate() {
+        let row = (y0 + j as i64 - 1).clamp(0, height - 1) as u32;
+        for (i, wx) in wx.iter().enumerate() {
+            let pixel = input.get_pixel((x0 + i as i64 - 1).clamp(0, width - 1) as u32, row);
+            for (total, &value) in sum.iter_mut().zip(pixel.0.iter()) {
+                *total += wx * wy * value as f32;
+            }
+        }
+    }
+
+    Some(sum.map(|v| v.round().clamp(0., 255.) as u8))
+}
+
+/// The nearest pixel to a position, or None more than half a pixel past the edges
+fn sample_nearest(input: &ImageBuffer, x: f32, y: f32) -> Option<[u8; 4]> {
+    if !in_bounds(input, x, y) {
+        return None;
+    }
+    let (x, y) = (x.round().clamp(0., (input.width() - 1) as f32) as u32, y.round().clamp(0., (input.height() - 1) as f32) as u32);
+
+    Some(input.get_pixel(x, y).0)
+}
+
+fn in_bounds(input: &ImageBuffer, x: f32, y: f32) -> bool {
+    let (width, height) = input.dimensions();
+    width > 0 && height > 0 && x >= -0.5 && y >= -0.5 && x <= width as f32 - 0.5 && y <= height as f32 - 0.5
+}
+
+/// Weights of the four pixels from one before to two after a position `t` of the way between
+/// the middle two, by the cubic convolution kernel with `a = -0.5`, which reproduces quadratics
+fn cubic_weights(t: f32) -> [f32; 4] {
+    let kernel = |d: f32| {
+        let d = d.abs();
+        if d <= 1. {
+            (1.5 * d - 2.5) * d * d + 1.
+        } else if d < 2. {
+            ((-0.5 * d + 2.5) * d - 4.) * d + 2.
+        } else {
+            0.
+        }
+    };
+
+    [kernel(1. + t), kernel(t), kernel(1. - t), kernel(2. - t)]
+}
+
+/// Mapping between an image and its polar form, with the distance from a centre across and
+/// the angle, clockwise from the right, down. In the log-polar form the distance goes across on
+/// a log scale, so scaling the image about the centre shifts its log-polar form sideways just
+/// as rotating it shifts it down, which turns finding a rotation and scale into finding a shift.
+#[derive(Clone, Copy, Debug, PartialEq, Default)]
+pub struct PolarTransform {
+    centre: Option<(f32, f32)>,
+    max_radius: Option<f32>,
+    log: bool,
+}
+
+impl PolarTransform {
+    pub fn new() -> PolarTransform {
+        PolarTransform::default()
+    }
+
+    /// Centre in pixels of the cartesian image, by default its middle
+    pub fn with_centre(mut self, x: f32, y: f32) -> PolarTransform {
+        self.centre = Some((x, y));
+        self
+    }
+
+    /// Distance from the centre at the right edge of the polar form, by default the distance to
+    /// the furthest corner
+    pub fn with_max_radius(mut self, radius: f32) -> PolarTransform {
+        self.max_radius = Some(radius.max(1.));
+        self
+    }
+
+    /// Put the distance on a log scale, for the log-polar form
+    pub fn with_log_scale(mut self, log: bool) -> PolarTransform {
+        self.log = log;
+        self
+    }
+
+    /// Unroll an image into its polar form, transparent where it maps past the image
+    ///
+    /// # Arguments
+    ///
+    /// * `input`: ImageBuffer to unroll
+    /// * `width`: Columns of the polar form, covering the distances from 0 to the maximum radius
+    /// * `height`: Rows of the polar form, covering the angles from 0 to 360 degrees
+    ///
+    /// returns: ImageBuffer
+    pub fn to_polar(&self, input: &ImageBuffer, width: u32, height: u32) -> ImageBuffer {
+        let ((cx, cy), max_radius) = self.geometry(input.width(), input.height());
+        let (mut map_x, mut map_y) = (Plane::new(width, height), Plane::new(width, height));
+
+        for y in 0..height {
+            let (sin, cos) = (y as f32 / height as f32 * std::f32::consts::TAU).sin_cos();
+            for x in 0..width {
+                let radius = self.radius(x as f32 / width as f32, max_radius);
+                map_x.set(x, y, cx + radius * cos);
+                map_y.set(x, y, cy + radius * sin);
+            }
+        }
+
+        resample(input, &map_x, &map_y, Interpolation::Bilinear)
+    }
+
+    /// Roll a polar form made by `to_polar` back into a cartesian image, the inverse mapping
+    ///
+    /// # Arguments
+    ///
+    /// * `polar`: Polar form to roll up
+    /// * `width`, `height`: Size of the cartesian image, which the centre and maximum radius
+    ///   default from
+    ///
+    /// returns: ImageBuffer, transparent past the maximum radius
+    pub fn from_polar(&self, polar: &ImageBuffer, width: u32, height: u32) -> ImageBuffer {
+        let ((cx, cy), max_radius) = self.geometry(width, height);
+        let (columns, rows) = (polar.width() as f32, polar.height() as f32);
+        let (mut map_x, mut map_y) = (Plane::new(width, height), Plane::new(width, height));
+
+        for y in 0..height {
+            for x in 0..width {
+                let (dx, dy) = (x as f32 - cx, y as f32 - cy);
+                let angle = dy.atan2(dx).rem_euclid(std::f32::consts::TAU);
+                map_x.set(x, y, self.fraction(dx.hypot(dy), max_radius) * columns);
+                // The last row wraps round to the first, so keep inside it rather than past it
+                map_y.set(x, y, (angle / std::f32::consts::TAU * rows).min(rows - 0.5));
+            }
+        }
+
+        resample(polar, &map_x, &map_y, Interpolation::Bilinear)
+    }
+
+    fn geometry(&self, width: u32, height: u32) -> ((f32, f32), f32) {
+        let (cx, cy) = self.centre.unwrap_or(((width as f32 - 1.) / 2., (height as f32 - 1.) / 2.));
+        let furthest = [(0., 0.), (width as f32 - 1., 0.), (0., height as f32 - 1.), (width as f32 - 1., height as f32 - 1.)]
+            .iter()
+            .map(|&(x, y): &(f32, f32)| (x - cx).hypot(y - cy))
+            .fold(1., f32::max);
+
+        ((cx, cy), self.max_radius.unwrap_or(furthest))
+    }
+
+    /// Distance from the centre a fraction of the way across the polar form
+    fn radius(&self, fraction: f32, max_radius: f32) -> f32 {
+        if self.log {
+            (fraction * (max_radius + 1.).ln()).exp() - 1.
+        } else {
+            fraction * max_radius
+        }
+    }
+
+    /// Fraction of the way across the polar form of a distance from the centre
+    fn fraction(&self, radius: f32, max_radius: f32) -> f32 {
+        if self.log {
+            (radius + 1.).ln() / (max_radius + 1.).ln()
+        } else {
+            radius / max_radius
+        }
+    }
+}
+
+/// Brown–Conrady lens distortion: radial coefficients `k1`, `k2` and tangential (decentring)
+/// coefficients `p1`, `p2`, over coordinates normalised by the focal length about the optical
+/// centre. Without a calibrated centre and focal length the image centre and half the image
+/// diagonal are used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LensDistortion {
+    pub k1: f32,
+    pub k2: f32,
+    pub p1: f32,
+    pub p2: f32,
+    centre: Option<(f32, f32)>,
+    focal_length: Option<(f32, f32)>,
+}
+
+impl LensDistortion {
+    pub fn new(k1: f32, k2: f32, p1: f32, p2: f32) -> LensDistortion {
+        LensDistortion { k1, k2, p1, p2, centre: None, focal_length: None }
+    }
+
+    /// Optical centre in pixels
+    pub fn with_centre(mut self, x: f32, y: f32) -> LensDistortion {
+        self.centre = Some((x, y));
+        self
+    }
+
+    /// Focal length in pixels, horizontally and vertically
+    pub fn with_focal_length(mut self, x: f32, y: f32) -> LensDistortion {
+        self.focal_length = Some((x, y));
+        self
+    }
+
+    /// Where the lens images a normalised point
+    pub fn distort_point(&self, x: f32, y: f32) -> (f32, f32) {
+        let r2 = x * x + y * y;
+        let radial = 1. + self.k1 * r2 + self.k2 * r2 * r2;
+
+        (
+            x * radial + 2. * self.p1 * x * y + self.p2 * (r2 + 2. * x * x),
+            y * radial + self.p1 * (r2 + 2. * y * y) + 2. * self.p2 * x * y,
+        )
+    }
+
+    /// The normalised point the lens images at a distorted one, found by fixed point iteration
+    pub fn undistort_point(&self, x: f32, y: f32) -> (f32, f32) {
+        let (mut ux, mut uy) = (x, y);
+
+        for _ in 0..UNDISTORT_ITERATIONS {
+            let r2 = ux * ux + uy * uy;
+            let radial = 1. + self.k1 * r2 + self.k2 * r2 * r2;
+            let dx = 2. * self.p1 * ux * uy + self.p2 * (r2 + 2. * ux * ux);
+            let dy = self.p1 * (r2 + 2. * uy * uy) + 2. * self.p2 * ux * uy;
+            ux = (x - dx) / radial;
+            uy = (y - dy) / radial;
+        }
+
+        (ux, uy)
+    }
+
+    /// The optical centre and focal length in pixels for an image of the given size
+    fn normalisation(&self, width: u32, height: u32) -> ((f32, f32), (f32, f32)) {
+        let centre = self.centre.unwrap_or(((width as f32 - 1.) / 2., (height as f32 - 1.) / 2.));
+        let half_diagonal = (width as f32).hypot(height as f32) / 2.;
+
+        (centre, self.focal_length.unwrap_or((half_diagonal, half_diagonal)))
+    }
+}
+
+/// Correct lens distortion, straightening lines the lens curved
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer taken through the lens
+/// * `lens`: Distortion of the lens
+///
+/// returns: ImageBuffer, the same size as the input, transparent where no input maps
+pub fn undistort(input: &ImageBuffer, lens: &LensDistortion) -> ImageBuffer {
+    let (width, height) = input.dimensions();
+    let ((cx, cy), (fx, fy)) = lens.normalisation(width, height);
+
+    warp(input, width, height, |x, y| {
+        let (dx, dy) = lens.distort_point((x - cx) / fx, (y - cy) / fy);
+        (dx * fx + cx, dy * fy + cy)
+    })
+}
+
+/// Apply lens distortion, the inverse of `undistort`, e.g. to match rendered images to footage
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer without distortion
+/// * `lens`: Distortion to apply
+///
+/// returns: ImageBuffer, the same size as the input, transparent where no input maps
+pub fn distort(input: &ImageBuffer, lens: &LensDistortion) -> ImageBuffer {
+    let (width, height) = input.dimensions();
+    let ((cx, cy), (fx, fy)) = lens.normalisation(width, height);
+
+    warp(input, width, height, |x, y| {
+        let (ux, uy) = lens.undistort_point((x - cx) / fx, (y - cy) / fy);
+        (ux * fx + cx, uy * fy + cy)
+    })
+}
+
+/// Channel scales tried when estimating chromatic aberration, either side of 1
+const ABERRATION_SEARCH: f32 = 0.01;
+const ABERRATION_STEP: f32 = 0.0002;
+/// Strongest edges compared when estimating chromatic aberration
+const ABERRATION_SAMPLES: usize = 20000;
+
+/// Correct lateral chromatic aberration, where the lens magnifies red and blue slightly
+/// differently to green and gives colour fringes towards the edges, by rescaling the red and
+/// blue channels about the image centre to line up with green
+///
+/// # Arguments
+///
+/// * `input`: ImageBuffer to correct
+/// * `red_scale`: Magnification of the red channel relative to green, e.g. 1.002
+/// * `blue_scale`: Magnification of the blue channel relative to green
+///
+/// returns: ImageBuffer
+pub fn correct_chromatic_aberration(input: &ImageBuffer, red_scale: f32, blue_scale: f32) -> ImageBuffer {
+    correct_chromatic_aberration_at(input, red_scale, blue_scale, image_centre(input))
+}
+
+/// As `correct_chromatic_aberration`, about an optical centre in pixels
+pub fn correct_chromatic_aberration_at(input: &ImageBuffer, red_scale: f32, blue_scale: f32, (cx, cy): (f32, f32)) -> ImageBuffer {
+    let (width, height) = input.dimensions();
+    let clamp = |x: f32, y: f32| (x.clamp(0., width.saturating_sub(1) as f32), y.clamp(0., height.saturating_sub(1) as f32));
+
+    let mut output = input.clone();
+    for (x, y, pixel) in output.enumerate_pixels_mut() {
+        for (channel, scale) in [(0, red_scale), (2, blue_scale)] {
+            let (source_x, source_y) = clamp(cx + (x as f32 - cx) * scale, cy + (y as f32 - cy) * scale);
+            if let Some(sample) = sample_bilinear(input, source_x, source_y) {
+                pixel[channel] = sample[channel];
+            }
+        }
+    }
+
+    output
+}
+
+/// Correct chromatic aberration with the scales found by `estimate_chromatic_aberration`
+pub fn correct_chromatic_aberration_auto(input: &ImageBuffer) -> ImageBuffer {
+    let (red_scale, blue_scale) = estimate_chromatic_aberration(input);
+    correct_chromatic_aberration(input, red_scale, blue_scale)
+}
+
+/// Estimate the red and blue magnification relative to green about the image centre, as the
+/// scales within ±1% that best line up the channels' edges with the green channel's
+///
+/// returns: (f32, f32), the red and blue scales for `correct_chromatic_aberration`
+pub fn estimate_chromatic_aberration(input: &ImageBuffer) -> (f32, f32) {
+    estimate_chromatic_aberration_at(input, image_centre(input))
+}
+
+/// As `estimate_chromatic_aberration`, about an optical centre in pixels
+pub fn estimate_chromatic_aberration_at(input: &ImageBuffer, (cx, cy): (f32, f32)) -> (f32, f32) {
+    let (width, height) = input.dimensions();
+    if width < 3 || height < 3 {
+        return (1., 1.);
+    }
+
+    let gradients = [0, 1, 2].map(|channel| gradient_magnitude(input, channel));
+
+    // Only the strongest green edges carry alignment information
+    let mut samples: Vec<(u32, u32)> = (1..height - 1).flat_map(|y| (1..width - 1).map(move |x| (x, y))).collect();
+    let strength = |&(x, y): &(u32, u32)| gradients[1][(y * width + x) as usize];
+    samples.sort_by(|a, b| strength(b).total_cmp(&strength(a)));
+    samples.truncate(ABERRATION_SAMPLES);
+
+    let steps = (ABERRATION_SEARCH / ABERRATION_STEP).round() as i32;
+    let best_scale = |channel: usize| {
+        (-steps..=steps)
+            .map(|step| 1. + step as f32 * ABERRATION_STEP)
+            .map(|scale| {
+                let pairs = samples.iter().map(|&(x, y)| {
+                    let source = (cx + (x as f32 - cx) * scale, cy + (y as f32 - cy) * scale);
+                    (sample_plane(&gradients[channel], width, height, source), strength(&(x, y)))
+                });
+                (scale, correlation(pairs))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(scale, _)| scale)
+            .unwrap_or(1.)
+    };
+
+    (best_scale(0), best_scale(2))
+}
+
+fn image_centre(input: &ImageBuffer) -> (f32, f32) {
+    ((input.width() as f32 - 1.) / 2., (input.height() as f32 - 1.) / 2.)
+}
+
+/// Central difference gradient magnitude of one channel, zero on the border
+fn gradient_magnitude(input: &ImageBuffer, channel: usize) -> Vec<f32> {
+    let (width, height) = input.dimensions();
+    let mut magnitude = vec![0.; (width * height) as usize];
+
+    for y in 1..height.saturating_sub(1) {
+        for x in 1..width.saturating_sub(1) {
+            let value = |x: u32, y: u32| input.get_pixel(x, y)[channel] as f32;
+            let dx = value(x + 1, y) - value(x - 1, y);
+            let dy = value(x, y + 1) - value(x, y - 1);
+            magnitude[(y * width + x) as usize] = dx.hypot(dy);
+        }
+    }
+
+    magnitude
+}
+
+/// A single channel plane interpolated at a position, clamped to the edges
+fn sample_plane(plane: &[f32], width: u32, height: u32, (x, y): (f32, f32)) -> f32 {
+    let x = x.clamp(0., (width - 1) as f32);
+    let y = y.clamp(0., (height - 1) as f32);
+    let (x0, y0) = (x.floor() as u32, y.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (tx, ty) = (x - x0 as f32, y - y0 as f32);
+    let at = |x: u32, y: u32| plane[(y * width + x) as usize];
+
+    let top = at(x0, y0) * (1. - tx) + at(x1, y0) * tx;
+    let bottom = at(x0, y1) * (1. - tx) + at(x1, y1) * tx;
+    top * (1. - ty) + bottom * ty
+}
+
+/// Pearson correlation of pairs of values
+fn correlation(pairs: impl Iterator<Item = (f32, f32)> + Clone) -> f32 {
+    let count = pairs.clone().count().max(1) as f64;
+    let (mean_a, mean_b) = pairs.clone().fold((0., 0.), |(a, b), (x, y)| (a + x as f64, b + y as f64));
+    let (mean_a, mean_b) = (mean_a / count, mean_b / count);
+
+    let (mut covariance, mut variance_a, mut variance_b) = (0., 0., 0.);
+    for (a, b) in pairs {
+        let (a, b) = (a as f64 - mean_a, b as f64 - mean_b);
+        covariance += a * b;
+        variance_a += a * a;
+        variance_b += b * b;
+    }
+
+    (covariance / (variance_a * variance_b).sqrt().max(1e-12)) as f32
+}
+
+/// A projective transform of the plane, mapping `(x, y)` to `(x', y')` by `[x' y' 1] ~ H [x y 1]`,
+/// as between two photos of a flat surface
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Homography {
+    pub matrix: [[f64; 3]; 3],
+}
+
+impl Homography {
+    pub fn identity() -> Homography {
+        Homography { matrix: [[1., 0., 0.], [0., 1., 0.], [0., 0., 1.]] }
+    }
+
+    /// The least squares homography taking each `from` point to its `to` point, by the
+    /// normalised direct linear transform
+    ///
+    /// # Arguments
+    ///
+    /// * `from`: At least four points, no three on a line
+    /// * `to`: Where each point maps to
+    ///
+    /// returns: Option<Homography>, None for too few or degenerate points
+    pub fn from_points(from: &[(f64, f64)], to: &[(f64, f64)]) -> Option<Homography> {
+        if from.len() < 4 || from.len() != to.len() {
+            return None;
+        }
+
+        // Centring and scaling the points keeps the system well conditioned
+        let (normalise_from, normalise_to) = (normalisation(from), normalisation(to));
+        let mut rows = Vec::with_capacity(from.len() * 2);
+        for (&a, &b) in from.iter().zip(to) {
+            let [x, y, _] = transform3(&normalise_from, [a.0, a.1, 1.]);
+            let [u, v, _] = transform3(&normalise_to, [b.0, b.1, 1.]);
+            rows.push(vec![-x, -y, -1., 0., 0., 0., u * x, u * y, u]);
+            rows.push(vec![0., 0., 0., -x, -y, -1., v * x, v * y, v]);
+        }
+
+        let h = null_vector(&rows);
+        let normalised = [[h[0], h[1], h[2]], [h[3], h[4], h[5]], [h[6], h[7], h[8]]];
+        let matrix = multiply3(&invert3(&normalise_to)?, &multiply3(&normalised, &normalise_from));
+        if matrix[2][2].abs() < 1e-12 {
+            return None;
+        }
+
+        Some(Homography { matrix: matrix.map(|row| row.map(|v| v / matrix[2][2])) })
+    }
+
+    pub fn apply(&self, x: f64, y: f64) -> (f64, f64) {
+        let [u, v, w] = transform3(&self.matrix, [x, y, 1.]);
+        (u / w, v / w)
+    }
+
+    pub fn inverse(&self) -> Option<Homography> {
+        invert3(&self.matrix).map(|matrix| Homography { matrix })
+    }
+}
+
+/// Similarity moving points' centroid to the origin with a mean distance of √2
+fn normalisation(points: &[(f64, f64)]) -> [[f64; 3]; 3] {
+    let count = points.len() as f64;
+    let (cx, cy) = points.iter().fold((0., 0.), |(x, y), p| (x + p.0 / count, y + p.1 / count));
+    let spread = points.iter().map(|p| (p.0 - cx).hypot(p.1 - cy)).sum::<f64>() / count;
+    let scale = std::f64::consts::SQRT_2 / spread.max(1e-12);
+
+    [[scale, 0., -scale * cx], [0., scale, -scale * cy], [0., 0., 1.]]
+}
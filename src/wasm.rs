@@ -0,0 +1,87 @@
+//! Exports for running pipelines from javascript when built for `wasm32-unknown-unknown`, as
+//! plain functions over the module's memory so no binding generator is needed (wasm-bindgen
+//! can't be built offline). See `wasm/rust_image_processing.js` for the javascript side.
+//!
+//! Javascript allocates buffers in the module's memory with `rip_alloc`, copies in the canvas
+//! pixels and a json pipeline, then calls `rip_process_rgba` which overwrites the pixels with
+//! the result. A non-zero return means failure, with the message from `rip_last_error_*`.
+
+use std::cell::RefCell;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::slice;
+
+use crate::pipeline::{Pipeline, PipelineFormat};
+
+thread_local! {
+    static LAST_ERROR: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+/// Allocate `len` bytes for javascript to write into
+#[no_mangle]
+pub extern "C" fn rip_alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::<u8>::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    std::mem::forget(buffer);
+
+    ptr
+}
+
+/// Free a buffer from `rip_alloc`. The length isn't recorded, so it must be the one the buffer
+/// was allocated with
+///
+/// # Safety
+///
+/// `ptr` must come from a call to `rip_alloc` and `len` be exactly the length passed to it
+#[no_mangle]
+pub unsafe extern "C" fn rip_free(ptr: *mut u8, len: usize) {
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+/// Run a json pipeline over rgba pixels in place, returns 0 on success
+///
+/// # Safety
+///
+/// `data` must point to `width * height * 4` bytes and `pipeline` to `pipeline_len` bytes
+#[no_mangle]
+pub unsafe extern "C" fn rip_process_rgba(data: *mut u8, width: u32, height: u32, pipeline: *const u8, pipeline_len: usize) -> i32 {
+    let Some(len) = (width as usize).checked_mul(height as usize).and_then(|pixels| pixels.checked_mul(4)) else {
+        LAST_ERROR.with(|last_error| *last_error.borrow_mut() = format!("a {}x{} image is too large", width, height));
+        return 1;
+    };
+    let data = slice::from_raw_parts_mut(data, len);
+    let pipeline = slice::from_raw_parts(pipeline, pipeline_len);
+
+    // Only catches anything where panics unwind; the default wasm32 build aborts on a panic
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        std::str::from_utf8(pipeline)
+            .map_err(|err| err.to_string())
+            .and_then(|pipeline| Pipeline::parse(pipeline, PipelineFormat::Json).map_err(|err| err.to_string()))
+            .and_then(|pipeline| pipeline.run_rgba(data, width, height).map_err(|err| err.to_string()))
+    }))
+    .unwrap_or_else(|payload| {
+        let message = payload.downcast_ref::<&str>().copied().or_else(|| payload.downcast_ref::<String>().map(String::as_str));
+        Err(format!("panicked: {}", message.unwrap_or("unknown panic")))
+    });
+
+    match result {
+        Ok(output) => {
+            data.copy_from_slice(&output);
+            0
+        }
+        Err(message) => {
+            LAST_ERROR.with(|last_error| *last_error.borrow_mut() = message);
+            1
+        }
+    }
+}
+
+/// Pointer to the utf-8 message of the last failure
+#[no_mangle]
+pub extern "C" fn rip_last_error_ptr() -> *const u8 {
+    LAST_ERROR.with(|last_error| last_error.borrow().as_ptr())
+}
+
+#[no_mangle]
+pub extern "C" fn rip_last_error_len() -> usize {
+    LAST_ERROR.with(|last_error| last_error.borrow().len())
+}
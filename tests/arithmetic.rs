@@ -0,0 +1,145 @@
+use rust_image_processing::arithmetic::{
+    adjust_brightness, adjust_brightness_in_place, adjust_contrast, adjust_contrast_in_place, image_add, image_add_in_place, image_add_with, image_sub,
+    image_sub_in_place, image_sub_with, linear_blend, linear_blend_in_place, linear_blend_with, match_dimensions, SizePolicy,
+};
+use rust_image_processing::pixel::{pixel_add, pixel_scale, pixel_shift, pixel_sub};
+use rust_image_processing::testing::test_image;
+use rust_image_processing::{Error, ImageBuffer};
+
+/// Sizes with and without a remainder past the last whole 16 byte chunk
+const SIZES: [(u32, u32); 4] = [(1, 1), (3, 5), (16, 9), (37, 23)];
+
+/// A second input with partly transparent pixels, so alpha handling is checked
+fn second_image(width: u32, height: u32) -> ImageBuffer {
+    ImageBuffer::from_fn(width, height, |x, y| image::Rgba([(x * 41 + y * 7) as u8, (y * 53) as u8, 250, (x * 97 + y * 13) as u8]))
+}
+
+/// The scalar result of a two image operation, one pixel function call per pixel
+fn zip_scalar(a: &ImageBuffer, b: &ImageBuffer, f: impl Fn(image::Rgba<u8>, image::Rgba<u8>) -> image::Rgba<u8>) -> ImageBuffer {
+    ImageBuffer::from_fn(a.width(), a.height(), |x, y| f(*a.get_pixel(x, y), *b.get_pixel(x, y)))
+}
+
+fn map_scalar(a: &ImageBuffer, f: impl Fn(image::Rgba<u8>) -> image::Rgba<u8>) -> ImageBuffer {
+    ImageBuffer::from_fn(a.width(), a.height(), |x, y| f(*a.get_pixel(x, y)))
+}
+
+#[test]
+fn arithmetic_matches_pixel_functions() {
+    for (width, height) in SIZES {
+        let (a, b) = (test_image(width, height, 1), second_image(width, height));
+
+        assert_eq!(image_add(&a, &b), zip_scalar(&a, &b, pixel_add));
+        assert_eq!(image_sub(&a, &b), zip_scalar(&a, &b, pixel_sub));
+        for value in [-300, -40, 0, 17, 300] {
+            assert_eq!(adjust_brightness(&a, value), map_scalar(&a, |p| pixel_shift(p, value)), "brightness {}", value);
+        }
+        for value in [0., 0.3, 1., 1.7, 40.] {
+            assert_eq!(adjust_contrast(&a, value), map_scalar(&a, |p| pixel_scale(p, value)), "contrast {}", value);
+        }
+        for value in [0., 0.25, 0.5, 1.] {
+            let expected = zip_scalar(&a, &b, |p, q| pixel_add(pixel_scale(p, 1. - value), pixel_scale(q, value)));
+            assert_eq!(linear_blend(&a, &b, value), expected, "blend {}", value);
+        }
+    }
+}
+
+#[test]
+fn in_place_matches_new_buffer() {
+    for (width, height) in SIZES {
+        let (a, b) = (test_image(width, height, 2), second_image(width, height));
+        let in_place = |f: &dyn Fn(&mut ImageBuffer)| {
+            let mut image = a.clone();
+            f(&mut image);
+            image
+        };
+
+        assert_eq!(in_place(&|image| image_add_in_place(image, &b)), image_add(&a, &b));
+        assert_eq!(in_place(&|image| image_sub_in_place(image, &b)), image_sub(&a, &b));
+        assert_eq!(in_place(&|image| adjust_brightness_in_place(image, -25)), adjust_brightness(&a, -25));
+        assert_eq!(in_place(&|image| adjust_contrast_in_place(image, 1.4)), adjust_contrast(&a, 1.4));
+        assert_eq!(in_place(&|image| linear_blend_in_place(image, &b, 0.3)), linear_blend(&a, &b, 0.3));
+    }
+}
+
+#[cfg(all(feature = "simd", target_arch = "x86_64"))]
+#[test]
+fn simd_matches_scalar() {
+    use rust_image_processing::simd;
+
+    for (width, height) in SIZES {
+        let (a, b) = (test_image(width, height, 3), second_image(width, height));
+        let mut out = ImageBuffer::new(width, height);
+
+        simd::add(&a, &b, &mut out);
+        assert_eq!(out, zip_scalar(&a, &b, pixel_add));
+        simd::sub(&a, &b, &mut out);
+        assert_eq!(out, zip_scalar(&a, &b, pixel_sub));
+        simd::shift(&a, -60, &mut out);
+        assert_eq!(out, map_scalar(&a, |p| pixel_shift(p, -60)));
+        simd::scale(&a, 2.3, &mut out);
+        assert_eq!(out, map_scalar(&a, |p| pixel_scale(p, 2.3)));
+        simd::blend(&a, &b, 0.6, &mut out);
+        assert_eq!(out, zip_scalar(&a, &b, |p, q| pixel_add(pixel_scale(p, 1. - 0.6), pixel_scale(q, 0.6))));
+
+        let mut in_place = a.clone();
+        simd::add_in_place(&mut in_place, &b);
+        assert_eq!(in_place, zip_scalar(&a, &b, pixel_add));
+    }
+}
+
+#[test]
+fn strict_policy_rejects_different_sizes() {
+    let (a, b) = (test_image(8, 6, 1), second_image(5, 9));
+
+    assert!(matches!(image_add_with(&a, &b, SizePolicy::Strict), Err(Error::DimensionMismatch { expected: (8, 6), actual: (5, 9) })));
+    assert!(matches!(image_sub_with(&a, &b, SizePolicy::default()), Err(Error::DimensionMismatch { .. })));
+    assert!(linear_blend_with(&a, &b, 0.5, SizePolicy::Strict).is_err());
+}
+
+#[test]
+fn every_policy_borrows_images_of_the_same_size() {
+    let (a, b) = (test_image(8, 6, 1), second_image(8, 6));
+
+    for policy in [SizePolicy::Strict, SizePolicy::CropToSmallest, SizePolicy::ResizeSecond, SizePolicy::Pad] {
+        let (first, second) = match_dimensions(&a, &b, policy).unwrap();
+        assert!(matches!(first, std::borrow::Cow::Borrowed(_)) && matches!(second, std::borrow::Cow::Borrowed(_)), "{:?}", policy);
+        assert_eq!(image_add_with(&a, &b, policy).unwrap(), image_add(&a, &b));
+    }
+}
+
+#[test]
+fn crop_to_smallest_keeps_the_top_left() {
+    let (a, b) = (test_image(8, 6, 1), second_image(5, 9));
+
+    let output = image_add_with(&a, &b, SizePolicy::CropToSmallest).unwrap();
+    assert_eq!(output.dimensions(), (5, 6));
+    for (x, y, pixel) in output.enumerate_pixels() {
+        assert_eq!(*pixel, pixel_add(*a.get_pixel(x, y), *b.get_pixel(x, y)));
+    }
+}
+
+#[test]
+fn resize_second_keeps_the_first_size() {
+    let a = test_image(8, 6, 1);
+    let flat = ImageBuffer::from_pixel(3, 11, image::Rgba([10, 20, 30, 255]));
+
+    let output = image_add_with(&a, &flat, SizePolicy::ResizeSecond).unwrap();
+    assert_eq!(output.dimensions(), (8, 6));
+    assert_eq!(output, map_scalar(&a, |p| pixel_add(p, image::Rgba([10, 20, 30, 255]))));
+
+    // Either way round, the output is the size of the first image
+    assert_eq!(image_sub_with(&flat, &a, SizePolicy::ResizeSecond).unwrap().dimensions(), (3, 11));
+}
+
+#[test]
+fn pad_fills_with_transparent_black() {
+    let (a, b) = (test_image(8, 6, 1), second_image(5, 9));
+
+    let output = image_add_with(&a, &b, SizePolicy::Pad).unwrap();
+    assert_eq!(output.dimensions(), (8, 9));
+    for (x, y, pixel) in output.enumerate_pixels() {
+        let a_pixel = if x < 8 && y < 6 { *a.get_pixel(x, y) } else { image::Rgba([0, 0, 0, 0]) };
+        let b_pixel = if x < 5 && y < 9 { *b.get_pixel(x, y) } else { image::Rgba([0, 0, 0, 0]) };
+        assert_eq!(*pixel, pixel_add(a_pixel, b_pixel), "pixel {}, {}", x, y);
+    }
+}
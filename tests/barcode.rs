@@ -0,0 +1,101 @@
+use rust_image_processing::barcode::{find_barcodes, find_qr_codes, find_qr_finders};
+use rust_image_processing::ImageBuffer;
+
+const MODULE: u32 = 4;
+const BLACK: image::Rgba<u8> = image::Rgba([0, 0, 0, 255]);
+const WHITE: image::Rgba<u8> = image::Rgba([255, 255, 255, 255]);
+
+/// Whether a module of a 21 module QR code is dark: the three finder patterns, the timing
+/// patterns between them and a scatter of data
+fn dark_module(x: u32, y: u32) -> bool {
+    let finder = |fx: u32, fy: u32| {
+        let (dx, dy) = (x.abs_diff(fx + 3), y.abs_diff(fy + 3));
+        let ring = dx.max(dy);
+        (x >= fx && x < fx + 7 && y >= fy && y < fy + 7).then_some(ring != 2)
+    };
+    if let Some(dark) = finder(0, 0).or(finder(14, 0)).or(finder(0, 14)) {
+        return dark;
+    }
+    // The light separators around the finders
+    if !(8..=12).contains(&x) && y < 8 || x < 8 && y > 12 {
+        return false;
+    }
+    if x == 6 || y == 6 {
+        return (x + y).is_multiple_of(2);
+    }
+    (x * 7 + y * 13) % 5 < 2
+}
+
+/// A QR code with a four module quiet zone, its code starting at 16 pixels in
+fn qr_code() -> ImageBuffer {
+    ImageBuffer::from_fn(29 * MODULE, 29 * MODULE, |x, y| {
+        let (mx, my) = ((x / MODULE) as i64 - 4, (y / MODULE) as i64 - 4);
+        if (0..21).contains(&mx) && (0..21).contains(&my) && dark_module(mx as u32, my as u32) {
+            BLACK
+        } else {
+            WHITE
+        }
+    })
+}
+
+fn assert_close(found: &[(f64, f64); 4], expected: [(f64, f64); 4], tolerance: f64) {
+    for (a, b) in found.iter().zip(expected) {
+        assert!((a.0 - b.0).abs() <= tolerance && (a.1 - b.1).abs() <= tolerance, "found {:?}, not {:?}", found, expected);
+    }
+}
+
+#[test]
+fn finder_patterns_are_found_at_their_centres() {
+    let mut finders = find_qr_finders(&qr_code());
+    finders.sort_by(|a, b| (a.y, a.x).partial_cmp(&(b.y, b.x)).unwrap());
+
+    assert_eq!(finders.len(), 3, "{:?}", finders);
+    let centres = [(29.5, 29.5), (85.5, 29.5), (29.5, 85.5)];
+    for (finder, (x, y)) in finders.iter().zip(centres) {
+        assert!((finder.x - x).abs() < 1.5 && (finder.y - y).abs() < 1.5, "{:?}", finders);
+        assert!((finder.module_size - MODULE as f64).abs() < 0.5, "{:?}", finders);
+    }
+}
+
+#[test]
+fn qr_codes_are_outlined() {
+    let codes = find_qr_codes(&qr_code());
+    assert_eq!(codes.len(), 1);
+    assert_close(&codes[0], [(15.5, 15.5), (99.5, 15.5), (99.5, 99.5), (15.5, 99.5)], 2.5);
+
+    // Turned a quarter, the corners still run clockwise from the top left
+    let codes = find_qr_codes(&image::imageops::rotate90(&qr_code()));
+    assert_eq!(codes.len(), 1);
+    assert_close(&codes[0], [(15.5, 15.5), (99.5, 15.5), (99.5, 99.5), (15.5, 99.5)], 2.5);
+}
+
+#[test]
+fn barcodes_are_outlined() {
+    // Bars of two to six pixels from x 40, and y 30 to 90, a small part of the photo
+    let widths: Vec<u32> = (0..40).map(|i| 2 + 2 * (i * 7 % 3)).collect();
+    let mut edges = vec![40];
+    for width in &widths {
+        edges.push(edges.last().unwrap() + width);
+    }
+    let right = *edges.last().unwrap();
+    let input = ImageBuffer::from_fn(400, 240, |x, y| {
+        let bar = edges.windows(2).position(|w| x >= w[0] && x < w[1]);
+        if (30..90).contains(&y) && bar.is_some_and(|i| i.is_multiple_of(2)) {
+            BLACK
+        } else {
+            WHITE
+        }
+    });
+
+    let barcodes = find_barcodes(&input);
+    assert_eq!(barcodes.len(), 1, "{:?}", barcodes);
+    assert_close(&barcodes[0], [(40., 30.), (right as f64, 30.), (right as f64, 90.), (40., 90.)], 8.);
+}
+
+#[test]
+fn blank_images_have_no_codes() {
+    let blank = ImageBuffer::from_pixel(80, 60, WHITE);
+    assert!(find_qr_finders(&blank).is_empty());
+    assert!(find_qr_codes(&blank).is_empty());
+    assert!(find_barcodes(&blank).is_empty());
+}
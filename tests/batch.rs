@@ -0,0 +1,131 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use rust_image_processing::batch::{image_files, process_batch_with_budget, run_batch, run_batch_with_budget};
+use rust_image_processing::io::open_image;
+use rust_image_processing::memory::{image_bytes, whole_image_bytes, MIN_TILE_SIZE, WORKING_COPIES};
+use rust_image_processing::testing::test_image;
+use rust_image_processing::{save_image, CancellationToken, Error, MemoryBudget, OutputOptions, Pipeline, Progress};
+
+/// A fresh directory of test images of the given sizes, named in order
+fn inputs(name: &str, sizes: &[(u32, u32)]) -> (PathBuf, Vec<PathBuf>) {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("batch").join(name);
+    std::fs::remove_dir_all(&dir).ok();
+    std::fs::create_dir_all(dir.join("in")).unwrap();
+
+    let paths = sizes
+        .iter()
+        .enumerate()
+        .map(|(i, &(width, height))| {
+            let path = dir.join("in").join(format!("{}.png", i));
+            save_image(&test_image(width, height, i as u64), &path, &OutputOptions::new(), None).unwrap();
+            path
+        })
+        .collect();
+    (dir, paths)
+}
+
+fn pipeline() -> Pipeline {
+    Pipeline::new().gaussian_blur().adjust_brightness(20)
+}
+
+/// Check each output is the pipeline run on its input
+fn assert_outputs(inputs: &[PathBuf], outputs: &[PathBuf]) {
+    assert_eq!(outputs.len(), inputs.len());
+    for (input, output) in inputs.iter().zip(outputs) {
+        assert_eq!(output.file_stem(), input.file_stem());
+        assert_eq!(open_image(output).unwrap(), pipeline().run(&open_image(input).unwrap()), "{}", output.display());
+    }
+}
+
+#[test]
+fn budgets_parse_sizes() {
+    assert_eq!(MemoryBudget::parse("512M"), Some(MemoryBudget::new(512 << 20)));
+    assert_eq!(MemoryBudget::parse("2G"), Some(MemoryBudget::new(2 << 30)));
+    assert_eq!(MemoryBudget::parse("1.5g"), Some(MemoryBudget::new(3 << 29)));
+    assert_eq!(MemoryBudget::parse(" 64kb "), Some(MemoryBudget::new(64 << 10)));
+    assert_eq!(MemoryBudget::parse("1000"), Some(MemoryBudget::new(1000)));
+    for text in ["", "M", "-1G", "0", "lots", "infG", "NaN"] {
+        assert_eq!(MemoryBudget::parse(text), None, "{:?}", text);
+    }
+}
+
+#[test]
+fn budgets_fit_whole_images_or_tiles() {
+    let budget = MemoryBudget::new(whole_image_bytes(100, 100) * 3);
+    assert_eq!(budget.share(3).bytes(), whole_image_bytes(100, 100));
+    assert_eq!(budget.share(0), budget);
+
+    assert_eq!(budget.concurrent_images(100, 100, 8), 3);
+    assert_eq!(budget.concurrent_images(100, 100, 2), 2);
+    assert_eq!(budget.concurrent_images(10_000, 10_000, 8), 1);
+
+    assert_eq!(budget.tile_size(100, 100, 2), None);
+    assert_eq!(budget.share(3).tile_size(100, 100, 2), None);
+
+    // Tiles get what the whole input and output leave, less their halos
+    let budget = MemoryBudget::new(2 * image_bytes(1000, 1000) + WORKING_COPIES * image_bytes(300, 300));
+    assert_eq!(budget.tile_size(1000, 1000, 5), Some(290));
+    assert_eq!(MemoryBudget::new(1).tile_size(1000, 1000, 5), Some(MIN_TILE_SIZE));
+}
+
+#[test]
+fn image_files_lists_images_by_name() {
+    let (dir, paths) = inputs("listing", &[(4, 4), (5, 3), (3, 5)]);
+    std::fs::write(dir.join("in").join("notes.txt"), "not an image").unwrap();
+    std::fs::create_dir_all(dir.join("in").join("nested.png")).unwrap();
+
+    assert_eq!(image_files(&dir.join("in")).unwrap(), paths);
+    assert!(image_files(&dir.join("absent")).is_err());
+}
+
+#[test]
+fn batches_run_the_pipeline_on_each_image() {
+    let (dir, paths) = inputs("plain", &[(12, 10), (9, 14)]);
+    let reports = Arc::new(Mutex::new(Vec::new()));
+    let progress = {
+        let reports = reports.clone();
+        Progress::none().with_callback(move |fraction| reports.lock().unwrap().push(fraction))
+    };
+
+    let outputs = run_batch(&pipeline(), &paths, &dir.join("out"), &OutputOptions::new(), &progress).unwrap();
+    assert_outputs(&paths, &outputs);
+    assert_eq!(reports.lock().unwrap().last().copied(), Some(1.));
+}
+
+#[test]
+fn budgeted_batches_match_plain_ones() {
+    let (dir, paths) = inputs("budget", &[(70, 66), (12, 10), (9, 14), (20, 20)]);
+
+    for (name, budget) in [("ample", MemoryBudget::new(1 << 30)), ("tiled", MemoryBudget::new(1))] {
+        let outputs = run_batch_with_budget(&pipeline(), &paths, &dir.join(name), &OutputOptions::new(), budget, &Progress::none()).unwrap();
+        assert_outputs(&paths, &outputs);
+    }
+
+    // Only the images that don't fit are tiled
+    let tiled = Mutex::new(Vec::new());
+    let budget = MemoryBudget::new(whole_image_bytes(20, 20));
+    process_batch_with_budget(&paths, &dir.join("sizes"), &OutputOptions::new(), budget, 2, &Progress::none(), |input, tile_size, _| {
+        tiled.lock().unwrap().push((input.dimensions(), tile_size));
+        Ok(input.clone())
+    })
+    .unwrap();
+    let mut tiled = tiled.into_inner().unwrap();
+    tiled.sort();
+    assert!(tiled.iter().all(|&((width, height), tile_size)| tile_size.is_some() == (width * height > 400)), "{:?}", tiled);
+}
+
+#[test]
+fn cancelled_and_failed_batches_stop() {
+    let (dir, mut paths) = inputs("stopped", &[(6, 6), (6, 6)]);
+    let cancellation = CancellationToken::new();
+    cancellation.cancel();
+    let progress = Progress::none().with_cancellation(cancellation);
+
+    assert!(matches!(run_batch(&pipeline(), &paths, &dir.join("out"), &OutputOptions::new(), &progress), Err(Error::Cancelled)));
+    let budget = MemoryBudget::new(1 << 30);
+    assert!(matches!(run_batch_with_budget(&pipeline(), &paths, &dir.join("out"), &OutputOptions::new(), budget, &progress), Err(Error::Cancelled)));
+
+    paths.push(dir.join("in").join("absent.png"));
+    assert!(run_batch_with_budget(&pipeline(), &paths, &dir.join("out"), &OutputOptions::new(), budget, &Progress::none()).is_err());
+}
@@ -0,0 +1,149 @@
+use std::path::Path;
+
+use rust_image_processing::calibration::{calibrate, find_checkerboard, CameraCalibration};
+use rust_image_processing::warp::LensDistortion;
+use rust_image_processing::{Error, ImageBuffer};
+
+const COLUMNS: usize = 8;
+const ROWS: usize = 6;
+const SQUARE: u32 = 20;
+const MARGIN: u32 = 30;
+
+/// A camera at the size of VGA photos, with a little barrel distortion
+fn camera() -> CameraCalibration {
+    CameraCalibration { width: 640, height: 480, focal_length: (520., 510.), centre: (318., 243.), k1: -0.08, k2: 0.01, p1: 0., p2: 0., rms_error: 0. }
+}
+
+/// A board of `COLUMNS + 1` by `ROWS + 1` squares on white, its top left square dark
+fn board() -> ImageBuffer {
+    let (width, height) = (2 * MARGIN + SQUARE * (COLUMNS as u32 + 1), 2 * MARGIN + SQUARE * (ROWS as u32 + 1));
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let on_board = (MARGIN..width - MARGIN).contains(&x) && (MARGIN..height - MARGIN).contains(&y);
+        let dark = on_board && ((x - MARGIN) / SQUARE + (y - MARGIN) / SQUARE).is_multiple_of(2);
+        if dark {
+            image::Rgba([20, 20, 20, 255])
+        } else {
+            image::Rgba([235, 235, 235, 255])
+        }
+    })
+}
+
+/// Where `camera` images the board's inner corners, the board turned by `angles` about x then
+/// y and put at `distance` squares along the optical axis
+fn project(camera: &CameraCalibration, angles: (f64, f64), distance: f64) -> Vec<(f32, f32)> {
+    let (rx, ry) = (angles.0.to_radians(), angles.1.to_radians());
+    let (fx, fy) = (camera.focal_length.0 as f64, camera.focal_length.1 as f64);
+    let (cx, cy) = (camera.centre.0 as f64, camera.centre.1 as f64);
+    let (k1, k2) = (camera.k1 as f64, camera.k2 as f64);
+
+    (0..COLUMNS * ROWS)
+        .map(|k| {
+            // About the board's middle, so it stays in view
+            let (bx, by) = ((k % COLUMNS) as f64 - 3.5, (k / COLUMNS) as f64 - 2.5);
+            let (y, z) = (by * rx.cos(), by * rx.sin());
+            let (x, z) = (bx * ry.cos() + z * ry.sin(), -bx * ry.sin() + z * ry.cos());
+
+            let (x, y) = (x / (z + distance), y / (z + distance));
+            let r2 = x * x + y * y;
+            let radial = 1. + k1 * r2 + k2 * r2 * r2;
+            ((fx * x * radial + cx) as f32, (fy * y * radial + cy) as f32)
+        })
+        .collect()
+}
+
+fn views() -> Vec<Vec<(f32, f32)>> {
+    [((0., 0.), 12.), ((25., 0.), 13.), ((0., 30.), 12.), ((-20., 15.), 11.), ((15., -25.), 14.), ((-30., -10.), 12.)]
+        .into_iter()
+        .map(|(angles, distance)| project(&camera(), angles, distance))
+        .collect()
+}
+
+fn assert_close(actual: f32, expected: f32, tolerance: f32, name: &str) {
+    assert!((actual - expected).abs() <= tolerance, "{} is {}, not {}", name, actual, expected);
+}
+
+#[test]
+fn checkerboard_corners_are_found_a_row_at_a_time() {
+    let corners = find_checkerboard(&board(), COLUMNS, ROWS).unwrap();
+
+    assert_eq!(corners.len(), COLUMNS * ROWS);
+    for (k, &(x, y)) in corners.iter().enumerate() {
+        // Squares meet between pixels, half a pixel before the first of the next square
+        let expected = ((MARGIN + SQUARE * (k % COLUMNS + 1) as u32) as f32 - 0.5, (MARGIN + SQUARE * (k / COLUMNS + 1) as u32) as f32 - 0.5);
+        assert_close(x, expected.0, 0.5, &format!("corner {} x", k));
+        assert_close(y, expected.1, 0.5, &format!("corner {} y", k));
+    }
+}
+
+#[test]
+fn missing_checkerboards_are_none() {
+    assert_eq!(find_checkerboard(&ImageBuffer::from_pixel(200, 160, image::Rgba([128, 128, 128, 255])), COLUMNS, ROWS), None);
+
+    // More corners than the board has
+    assert_eq!(find_checkerboard(&board(), COLUMNS + 2, ROWS), None);
+}
+
+#[test]
+fn calibration_recovers_the_camera() {
+    let found = calibrate(&views(), COLUMNS, ROWS, 640, 480).unwrap();
+    let expected = camera();
+
+    assert_eq!((found.width, found.height), (640, 480));
+    assert_close(found.focal_length.0, expected.focal_length.0, 1., "fx");
+    assert_close(found.focal_length.1, expected.focal_length.1, 1., "fy");
+    assert_close(found.centre.0, expected.centre.0, 1., "cx");
+    assert_close(found.centre.1, expected.centre.1, 1., "cy");
+    assert_close(found.k1, expected.k1, 0.01, "k1");
+    assert_close(found.p1, 0., 1e-3, "p1");
+    assert_close(found.p2, 0., 1e-3, "p2");
+    assert!(found.rms_error < 0.01, "rms error {}", found.rms_error);
+}
+
+#[test]
+fn calibration_needs_enough_whole_views() {
+    let views = views();
+
+    assert!(matches!(calibrate(&views[..1], COLUMNS, ROWS, 640, 480), Err(Error::Calibration(_))));
+    assert!(matches!(calibrate(&views, COLUMNS + 1, ROWS, 640, 480), Err(Error::Calibration(_))));
+
+    // The same view twice says nothing about the focal length
+    let alike = vec![views[0].clone(), views[0].clone()];
+    assert!(matches!(calibrate(&alike, COLUMNS, ROWS, 640, 480), Err(Error::Calibration(_))));
+}
+
+#[test]
+fn lens_distortion_scales_with_the_image() {
+    let camera = camera();
+
+    let expected = LensDistortion::new(-0.08, 0.01, 0., 0.).with_centre(318., 243.).with_focal_length(520., 510.);
+    assert_eq!(camera.lens_distortion(640, 480), expected);
+
+    let expected = LensDistortion::new(-0.08, 0.01, 0., 0.).with_centre(158.75, 121.25).with_focal_length(260., 255.);
+    assert_eq!(camera.lens_distortion(320, 240), expected);
+}
+
+#[test]
+fn calibrations_round_trip_through_files() {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("calibration.json");
+    let calibration = CameraCalibration { rms_error: 0.25, ..camera() };
+
+    calibration.save(&path).unwrap();
+    assert_eq!(CameraCalibration::load(&path).unwrap(), calibration);
+}
+
+#[test]
+fn malformed_calibrations_are_rejected() {
+    use rust_image_processing::value::json;
+
+    let parse = |source: &str| CameraCalibration::from_value(&json::parse(source).unwrap());
+    let distortion = r#""distortion": {"k1": 0, "k2": 0, "p1": 0, "p2": 0}"#;
+
+    let whole = parse(&format!(r#"{{"width": 4, "height": 3, "focal_length": [5, 5], "centre": [2, 1], {}}}"#, distortion)).unwrap();
+    assert_eq!(whole.rms_error, 0.);
+
+    assert!(matches!(parse(&format!(r#"{{"width": 4, "height": 3, "centre": [2, 1], {}}}"#, distortion)), Err(Error::MissingParameter { .. })));
+    assert!(matches!(parse(&format!(r#"{{"width": 4, "height": 3, "focal_length": [5], "centre": [2, 1], {}}}"#, distortion)), Err(Error::InvalidParameter { .. })));
+    assert!(matches!(parse(r#"{"width": 4, "height": 3, "focal_length": [5, 5], "centre": [2, 1]}"#), Err(Error::MissingParameter { .. })));
+    assert!(matches!(parse(&format!(r#"{{"width": "4", "height": 3, "focal_length": [5, 5], "centre": [2, 1], {}}}"#, distortion)), Err(Error::InvalidParameter { .. })));
+}
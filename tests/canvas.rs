@@ -0,0 +1,139 @@
+use std::path::Path;
+
+use rust_image_processing::canvas::{BlendMode, Canvas, Layer};
+use rust_image_processing::plane::Plane;
+use rust_image_processing::testing::test_image;
+use rust_image_processing::text::text_size;
+use rust_image_processing::value::json;
+use rust_image_processing::{save_image, Error, ImageBuffer, OutputOptions, Pipeline};
+
+const WHITE: image::Rgba<u8> = image::Rgba([255, 255, 255, 255]);
+const GREY: image::Rgba<u8> = image::Rgba([100, 100, 100, 255]);
+
+#[test]
+fn blend_modes_combine_channels() {
+    let (below, layer) = (0.25, 0.5);
+    let expected = [
+        ("normal", 0.5),
+        ("multiply", 0.125),
+        ("screen", 0.625),
+        ("overlay", 0.25),
+        ("darken", 0.25),
+        ("lighten", 0.5),
+        ("add", 0.75),
+        ("difference", 0.25),
+    ];
+    for (name, value) in expected {
+        let mode = BlendMode::from_name(name).unwrap();
+        assert!((mode.blend(below, layer) - value).abs() < 1e-6, "{}", name);
+    }
+    assert_eq!(BlendMode::from_name("dodge"), None);
+    assert!((BlendMode::Overlay.blend(0.75, 0.5) - 0.75).abs() < 1e-6);
+}
+
+#[test]
+fn opaque_layers_cover_what_they_overlap() {
+    let logo = test_image(4, 3, 1);
+    let output = Canvas::new(10, 8).with_background(GREY).with_layer(Layer::image(logo.clone()).with_position(7, -1)).render().unwrap();
+
+    for (x, y, pixel) in output.enumerate_pixels() {
+        let expected = if x >= 7 && y < 2 { *logo.get_pixel(x - 7, y + 1) } else { GREY };
+        assert_eq!(*pixel, expected, "pixel {}, {}", x, y);
+    }
+}
+
+#[test]
+fn opacity_and_masks_mix_with_the_layer_beneath() {
+    let white = ImageBuffer::from_pixel(2, 1, WHITE);
+    let mut mask = Plane::new(2, 1);
+    mask.set(1, 0, 1.);
+
+    let output = Canvas::new(2, 1).with_background(GREY).with_layer(Layer::image(white).with_opacity(0.5).with_mask(mask)).render().unwrap();
+    assert_eq!(*output.get_pixel(0, 0), GREY);
+    assert_eq!(*output.get_pixel(1, 0), image::Rgba([178, 178, 178, 255]));
+
+    let layer = Layer::image(test_image(3, 3, 1)).with_mask(Plane::new(2, 2));
+    assert!(matches!(Canvas::new(4, 4).with_layer(layer).render(), Err(Error::DimensionMismatch { expected: (3, 3), actual: (2, 2) })));
+}
+
+#[test]
+fn adjustment_layers_change_everything_beneath() {
+    let canvas = Canvas::new(6, 4)
+        .with_background(GREY)
+        .with_layer(Layer::image(ImageBuffer::from_pixel(2, 2, WHITE)).with_position(1, 1))
+        .with_layer(Layer::adjustment(Pipeline::new().adjust_brightness(-50)).with_position(100, 100));
+
+    let output = canvas.render().unwrap();
+    assert_eq!(*output.get_pixel(0, 0), image::Rgba([50, 50, 50, 255]));
+    assert_eq!(*output.get_pixel(2, 2), image::Rgba([205, 205, 205, 255]));
+}
+
+#[test]
+fn canvas_files_read_layers_relative_to_the_file() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("canvas");
+    std::fs::create_dir_all(&dir).unwrap();
+    let photo = test_image(12, 8, 1);
+    save_image(&photo, dir.join("photo.png"), &OutputOptions::new(), None).unwrap();
+    std::fs::write(
+        dir.join("canvas.toml"),
+        "[[layers]]\nimage = \"photo.png\"\n\n[[layers]]\ntext = \"Hi\"\nx = 1\ny = 1\ncolour = \"#ff0000\"\nblend = \"screen\"\n",
+    )
+    .unwrap();
+
+    let canvas = Canvas::from_file(dir.join("canvas.toml")).unwrap();
+    assert_eq!(canvas.layers().len(), 2);
+    let expected = Canvas::new(12, 8)
+        .with_layer(Layer::image(photo))
+        .with_layer(Layer::text("Hi", image::Rgba([255, 0, 0, 255]), 1).with_position(1, 1).with_blend_mode(BlendMode::Screen));
+    assert_eq!(canvas, expected);
+    assert_eq!(canvas.render().unwrap(), expected.render().unwrap());
+}
+
+#[test]
+fn malformed_canvases_are_rejected() {
+    let parse = |source: &str| Canvas::from_value(&json::parse(source).unwrap(), Path::new("."));
+
+    assert!(matches!(parse(r#"{"layers": [{"text": "a"}]}"#), Err(Error::MissingParameter { .. })));
+    assert!(matches!(parse(r#"{"width": 4, "height": 4, "layers": [{}]}"#), Err(Error::MissingParameter { .. })));
+    for layer in [r#"{"text": "a", "blend": "dodge"}"#, r#"{"text": "a", "colour": "red-ish"}"#, r#"{"text": 1}"#, r#"{"text": "a", "x": "left"}"#] {
+        let source = format!(r#"{{"width": 4, "height": 4, "layers": [{}]}}"#, layer);
+        assert!(matches!(parse(&source), Err(Error::InvalidParameter { .. })), "{}", layer);
+    }
+    assert!(matches!(parse(r#"{"width": 4, "height": 4, "layers": 1}"#), Err(Error::InvalidParameter { .. })));
+}
+
+#[test]
+fn text_clipped_by_the_canvas_matches_unclipped() {
+    let (width, height) = text_size("AB", 2);
+    let mut mask = Plane::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            mask.set(x, y, x as f32 / width as f32);
+        }
+    }
+    let layer = |x, y| Layer::text("AB", WHITE, 2).with_position(x, y).with_mask(mask.clone());
+
+    let clipped = Canvas::new(20, 10).with_layer(layer(-3, -2)).render().unwrap();
+    let whole = Canvas::new(40, 30).with_layer(layer(7, 8)).render().unwrap();
+    assert_eq!(clipped, image::imageops::crop_imm(&whole, 10, 10, 20, 10).to_image());
+}
+
+#[test]
+fn far_positions_and_huge_scales_render() {
+    let document = json::parse(
+        r#"{"width": 8, "height": 8, "layers": [
+            {"text": "A", "x": 1e30, "y": -1e30, "scale": 1e9},
+            {"text": "B", "x": -1e300, "scale": 1e300},
+            {"text": "C", "scale": 1e12}
+        ]}"#,
+    )
+    .unwrap();
+    let canvas = Canvas::from_value(&document, Path::new(".")).unwrap();
+    assert_eq!(canvas.render().unwrap().dimensions(), (8, 8));
+
+    let canvas = Canvas::new(8, 8)
+        .with_layer(Layer::image(test_image(4, 4, 1)).with_position(i64::MAX, i64::MIN))
+        .with_layer(Layer::text("far", WHITE, u32::MAX).with_position(i64::MIN, i64::MAX))
+        .with_layer(Layer::text("near", WHITE, u32::MAX).with_position(-1, -1));
+    assert_eq!(canvas.render().unwrap().dimensions(), (8, 8));
+}
@@ -0,0 +1,139 @@
+use rust_image_processing::document::{adaptive_threshold, deskew, despeckle, estimate_skew, find_page, rectify_page, scan, ScanOptions};
+use rust_image_processing::text::draw_text;
+use rust_image_processing::warp::rotate;
+use rust_image_processing::ImageBuffer;
+
+const CORNERS: [(f64, f64); 4] = [(30., 20.), (130., 28.), (125., 105.), (25., 98.)];
+const BLACK: image::Rgba<u8> = image::Rgba([0, 0, 0, 255]);
+const WHITE: image::Rgba<u8> = image::Rgba([255, 255, 255, 255]);
+
+/// Whether a point is inside a convex quadrilateral with its corners clockwise, y down
+fn inside(corners: &[(f64, f64); 4], x: f64, y: f64) -> bool {
+    (0..4).all(|i| {
+        let (a, b) = (corners[i], corners[(i + 1) % 4]);
+        (b.0 - a.0) * (y - a.1) - (b.1 - a.1) * (x - a.0) >= 0.
+    })
+}
+
+/// A light page with a dark bar of "text" across it, photographed at an angle on a dark desk
+fn photo() -> ImageBuffer {
+    ImageBuffer::from_fn(160, 120, |x, y| {
+        let (x, y) = (x as f64, y as f64);
+        if !inside(&CORNERS, x, y) {
+            image::Rgba([50, 40, 35, 255])
+        } else if (55. ..60.).contains(&y) && (50. ..100.).contains(&x) {
+            image::Rgba([30, 30, 30, 255])
+        } else {
+            image::Rgba([225, 220, 210, 255])
+        }
+    })
+}
+
+#[test]
+fn pages_are_found_by_their_corners() {
+    let corners = find_page(&photo()).unwrap();
+
+    for (found, expected) in corners.iter().zip(CORNERS) {
+        assert!((found.0 - expected.0).abs() < 2. && (found.1 - expected.1).abs() < 2., "found {:?}, not {:?}", corners, CORNERS);
+    }
+}
+
+#[test]
+fn no_page_is_found_without_a_bright_area() {
+    assert_eq!(find_page(&ImageBuffer::from_pixel(64, 48, image::Rgba([50, 40, 35, 255]))), None);
+
+    // A bright patch too small to be the page
+    let patch = ImageBuffer::from_fn(64, 48, |x, y| if x < 8 && y < 8 { WHITE } else { image::Rgba([50, 40, 35, 255]) });
+    assert_eq!(find_page(&patch), None);
+}
+
+#[test]
+fn rectified_pages_are_square_to_their_longest_sides() {
+    let page = rectify_page(&photo(), &CORNERS);
+    assert_eq!(page.dimensions(), (100, 78));
+
+    // The corners of the page land on the corners of the output
+    for (x, y) in [(2, 2), (97, 2), (97, 75), (2, 75)] {
+        assert_eq!(page.get_pixel(x, y)[0], 225, "pixel {}, {}", x, y);
+    }
+}
+
+#[test]
+fn scans_are_black_text_on_white() {
+    let output = scan(&photo(), &ScanOptions::default());
+
+    assert!(output.width() < 100 && output.width() > 90 && output.height() < 78 && output.height() > 70, "{:?}", output.dimensions());
+    assert!(output.pixels().all(|p| *p == BLACK || *p == WHITE));
+    let black = output.pixels().filter(|p| **p == BLACK).count();
+    assert!(black > 100 && black < 400, "{} black pixels", black);
+
+    let whole = scan(&photo(), &ScanOptions::default().with_page_detection(false));
+    assert_eq!(whole.dimensions(), (160, 120));
+}
+
+#[test]
+fn thresholds_follow_uneven_lighting() {
+    // Shading from dark to light, with a darker line across it
+    let shaded = ImageBuffer::from_fn(60, 20, |x, y| {
+        let light = 80 + 2 * x as u8;
+        let value = if y == 10 { light - 40 } else { light };
+        image::Rgba([value, value, value, 255])
+    });
+
+    let binary = adaptive_threshold(&shaded, 5, 10.);
+    for (x, y, pixel) in binary.enumerate_pixels() {
+        assert_eq!(*pixel, if y == 10 { BLACK } else { WHITE }, "pixel {}, {}", x, y);
+    }
+}
+
+#[test]
+fn specks_are_removed_and_text_kept() {
+    let mut binary = ImageBuffer::from_pixel(20, 10, WHITE);
+    binary.put_pixel(2, 2, BLACK);
+    binary.put_pixel(3, 2, BLACK);
+    for x in 8..18 {
+        binary.put_pixel(x, 6, BLACK);
+    }
+
+    let cleaned = despeckle(&binary, 8);
+    assert_eq!(*cleaned.get_pixel(2, 2), WHITE);
+    assert!((8..18).all(|x| *cleaned.get_pixel(x, 6) == BLACK));
+    assert_eq!(despeckle(&binary, 0), binary);
+}
+
+/// Lines of text on a white page, turned clockwise by an angle with the corners filled white
+fn skewed_text(degrees: f32) -> ImageBuffer {
+    let mut page = ImageBuffer::from_pixel(200, 150, WHITE);
+    for line in 0..8 {
+        draw_text(&mut page, 20, 25 + line * 13, "THE QUICK BROWN FOX JUMPS", BLACK, 1);
+    }
+
+    let mut rotated = rotate(&page, degrees);
+    for pixel in rotated.pixels_mut() {
+        if pixel[3] == 0 {
+            *pixel = WHITE;
+        }
+    }
+    rotated
+}
+
+#[test]
+fn skew_is_measured_from_the_lines_of_text() {
+    for degrees in [-7., -2.5, 0., 4., 11.] {
+        let skew = estimate_skew(&skewed_text(degrees));
+        assert!((skew - degrees).abs() <= 0.3, "skew of {} measured as {}", degrees, skew);
+    }
+    assert_eq!(estimate_skew(&ImageBuffer::from_pixel(40, 30, WHITE)), 0.);
+}
+
+#[test]
+fn deskewing_levels_the_text() {
+    let level = deskew(&skewed_text(6.));
+
+    assert_eq!(level.dimensions(), (200, 150));
+    assert!(estimate_skew(&level).abs() <= 0.3, "skew left of {}", estimate_skew(&level));
+    assert!(level.pixels().all(|p| p[3] == 255), "corners are filled");
+
+    let blank = ImageBuffer::from_pixel(40, 30, WHITE);
+    assert_eq!(deskew(&blank), blank);
+}
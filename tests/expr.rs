@@ -0,0 +1,80 @@
+use rust_image_processing::arithmetic::{image_add, image_sub, linear_blend};
+use rust_image_processing::expr::{evaluate_images, Expression, Variables};
+use rust_image_processing::testing::test_image;
+use rust_image_processing::Error;
+
+fn evaluate(source: &str) -> f32 {
+    let variables = Variables { inputs: &[10., 20.], x: 3, y: 4, width: 8, height: 6, channel: 2 };
+    Expression::parse(source).unwrap().evaluate(&variables)
+}
+
+#[test]
+fn operators_follow_precedence() {
+    assert_eq!(evaluate("1 + 2 * 3"), 7.);
+    assert_eq!(evaluate("(1 + 2) * 3"), 9.);
+    assert_eq!(evaluate("2 ^ 3 * 2"), 16.);
+    assert_eq!(evaluate("10 - 4 - 3"), 3.);
+    assert_eq!(evaluate("7 % 4 + -a"), -7.);
+    assert_eq!(evaluate("1 + 2 < 4"), 1.);
+    assert_eq!(evaluate("a == b"), 0.);
+    assert_eq!(evaluate("a != b"), 1.);
+}
+
+#[test]
+fn variables_and_functions() {
+    assert_eq!(evaluate("a + b"), 30.);
+    assert_eq!(evaluate("x + 10 * y + 100 * channel"), 243.);
+    assert_eq!(evaluate("width * height"), 48.);
+    assert_eq!(evaluate("clamp(a * 100)"), 255.);
+    assert_eq!(evaluate("clamp(a, 12, 15)"), 12.);
+    assert_eq!(evaluate("min(a, b, 5) + max(a, b)"), 25.);
+    assert_eq!(evaluate("abs(a - b) + sqrt(16) + pow(2, 3)"), 22.);
+    assert_eq!(evaluate("floor(2.7) + round(2.5)"), 5.);
+    assert_eq!(evaluate("if(a < b, 1, 2)"), 1.);
+    assert!((evaluate("exp(log(a))") - 10.).abs() < 1e-4);
+    assert!((evaluate("sin(0) + cos(0)") - 1.).abs() < 1e-6);
+}
+
+#[test]
+fn expressions_match_the_arithmetic_functions() {
+    let (a, b) = (test_image(9, 7, 1), test_image(9, 7, 2));
+
+    assert_eq!(evaluate_images("a + b", &[a.clone(), b.clone()]).unwrap(), image_add(&a, &b));
+    assert_eq!(evaluate_images("a - b", &[a.clone(), b.clone()]).unwrap(), image_sub(&a, &b));
+    let blend = evaluate_images("0.5 * a + 0.5 * b", &[a.clone(), b.clone()]).unwrap();
+    for (expression, expected) in blend.pixels().zip(linear_blend(&a, &b, 0.5).pixels()) {
+        assert!(expression.0.iter().zip(expected.0).all(|(&p, q)| p.abs_diff(q) <= 1));
+    }
+}
+
+#[test]
+fn results_keep_the_first_alpha() {
+    let a = test_image(4, 4, 1);
+    let output = evaluate_images("255 - a", std::slice::from_ref(&a)).unwrap();
+    for (pixel, input) in output.pixels().zip(a.pixels()) {
+        assert_eq!(pixel.0, [255 - input[0], 255 - input[1], 255 - input[2], input[3]]);
+    }
+}
+
+#[test]
+fn inputs_count_the_highest_image() {
+    assert_eq!(Expression::parse("1 + x").unwrap().inputs(), 0);
+    assert_eq!(Expression::parse("a + c").unwrap().inputs(), 3);
+}
+
+#[test]
+fn missing_and_mismatched_images_are_errors() {
+    let a = test_image(4, 4, 1);
+    assert!(matches!(evaluate_images("a + b", std::slice::from_ref(&a)), Err(Error::MissingParameter { parameter, .. }) if parameter == "b"));
+    assert!(matches!(evaluate_images("1", &[]), Err(Error::MissingParameter { .. })));
+    assert!(matches!(evaluate_images("a + b", &[a, test_image(3, 4, 1)]), Err(Error::DimensionMismatch { expected: (4, 4), actual: (3, 4) })));
+}
+
+#[test]
+fn malformed_expressions_are_parse_errors() {
+    for source in ["", "1 +", "(1", "1)", "foo(1)", "q", "min(1)", "clamp(1, 2)", "if(1, 2)", "1 $ 2", "a b"] {
+        assert!(matches!(Expression::parse(source), Err(Error::Parse { .. })), "{:?}", source);
+    }
+    assert!(matches!(Expression::parse(&"(".repeat(100_000)), Err(Error::Parse { .. })));
+    assert!(matches!(Expression::parse(&"-".repeat(100_000)), Err(Error::Parse { .. })));
+}
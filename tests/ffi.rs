@@ -0,0 +1,126 @@
+#![cfg(feature = "ffi")]
+
+use std::ffi::{CStr, CString};
+use std::ptr;
+use std::slice;
+
+use rust_image_processing::ffi::{
+    rip_image_apply, rip_image_data, rip_image_data_len, rip_image_free, rip_image_from_rgba, rip_image_height, rip_image_run_pipeline, rip_image_width,
+    rip_last_error, RipImage,
+};
+use rust_image_processing::pipeline::PipelineFormat;
+use rust_image_processing::testing::test_image;
+use rust_image_processing::{edge_detect, median_filter, ImageBuffer, Pipeline};
+
+/// A handle to a copy of the image, freed when dropped
+struct Handle(*mut RipImage);
+
+impl Handle {
+    fn new(input: &ImageBuffer) -> Handle {
+        let handle = unsafe { rip_image_from_rgba(input.as_ptr(), input.width(), input.height()) };
+        assert!(!handle.is_null(), "{}", last_error());
+        Handle(handle)
+    }
+
+    fn image(&self) -> ImageBuffer {
+        unsafe {
+            let data = slice::from_raw_parts(rip_image_data(self.0), rip_image_data_len(self.0));
+            ImageBuffer::from_raw(rip_image_width(self.0), rip_image_height(self.0), data.to_vec()).unwrap()
+        }
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        unsafe { rip_image_free(self.0) };
+    }
+}
+
+fn last_error() -> String {
+    unsafe { CStr::from_ptr(rip_last_error()) }.to_str().unwrap().to_string()
+}
+
+fn c_string(value: &str) -> CString {
+    CString::new(value).unwrap()
+}
+
+#[test]
+fn images_round_trip_through_handles() {
+    let input = test_image(13, 7, 1);
+    let handle = Handle::new(&input);
+
+    unsafe {
+        assert_eq!((rip_image_width(handle.0), rip_image_height(handle.0)), (13, 7));
+        assert_eq!(rip_image_data_len(handle.0), 13 * 7 * 4);
+    }
+    assert_eq!(handle.image(), input);
+
+    // Freeing null is allowed
+    unsafe { rip_image_free(ptr::null_mut()) };
+}
+
+#[test]
+fn operations_are_applied_by_name() {
+    let input = test_image(16, 12, 2);
+
+    let handle = Handle::new(&input);
+    assert_eq!(unsafe { rip_image_apply(handle.0, c_string("median_filter").as_ptr(), c_string(r#"{"window": 2}"#).as_ptr()) }, 0);
+    assert_eq!(handle.image(), median_filter(&input, 2));
+
+    let handle = Handle::new(&input);
+    assert_eq!(unsafe { rip_image_apply(handle.0, c_string("edge_detect").as_ptr(), ptr::null()) }, 0);
+    assert_eq!(handle.image(), edge_detect(&input));
+}
+
+#[test]
+fn pipelines_are_run() {
+    let input = test_image(16, 12, 3);
+    let pipeline = r#"{"steps": [{"op": "gaussian_blur"}, {"op": "adjust_brightness", "value": 20}]}"#;
+
+    let handle = Handle::new(&input);
+    assert_eq!(unsafe { rip_image_run_pipeline(handle.0, c_string(pipeline).as_ptr()) }, 0);
+    assert_eq!(handle.image(), Pipeline::parse(pipeline, PipelineFormat::Json).unwrap().run(&input));
+}
+
+#[test]
+fn failures_set_the_last_error() {
+    let input = test_image(8, 6, 4);
+    let handle = Handle::new(&input);
+
+    let failures: [(&str, &dyn Fn() -> i32); 7] = [
+        ("unknown", &|| unsafe { rip_image_apply(handle.0, c_string("unknown").as_ptr(), ptr::null()) }),
+        ("window", &|| unsafe { rip_image_apply(handle.0, c_string("median_filter").as_ptr(), ptr::null()) }),
+        ("json object", &|| unsafe { rip_image_apply(handle.0, c_string("median_filter").as_ptr(), c_string("[2]").as_ptr()) }),
+        ("name is null", &|| unsafe { rip_image_apply(handle.0, ptr::null(), ptr::null()) }),
+        ("utf-8", &|| unsafe { rip_image_apply(handle.0, CString::new(vec![0xff]).unwrap().as_ptr(), ptr::null()) }),
+        ("", &|| unsafe { rip_image_run_pipeline(handle.0, c_string("{").as_ptr()) }),
+        ("pipeline is null", &|| unsafe { rip_image_run_pipeline(handle.0, ptr::null()) }),
+    ];
+    for (message, call) in failures {
+        assert_eq!(call(), -1, "{}", message);
+        assert!(!last_error().is_empty() && last_error().contains(message), "'{}' doesn't mention '{}'", last_error(), message);
+    }
+
+    // The image is left as it was
+    assert_eq!(handle.image(), input);
+}
+
+#[test]
+fn bad_images_are_null() {
+    assert!(unsafe { rip_image_from_rgba(ptr::null(), 4, 4) }.is_null());
+    assert_eq!(last_error(), "data is null");
+
+    // The size is checked before the data is read
+    let pixel = [0u8; 4];
+    assert!(unsafe { rip_image_from_rgba(pixel.as_ptr(), u32::MAX, u32::MAX) }.is_null());
+    assert!(last_error().contains("too large"), "{}", last_error());
+}
+
+#[test]
+fn last_errors_are_per_thread() {
+    assert!(unsafe { rip_image_from_rgba(ptr::null(), 4, 4) }.is_null());
+
+    let other = std::thread::spawn(last_error).join().unwrap();
+    assert_eq!(other, "");
+    assert_eq!(last_error(), "data is null");
+}
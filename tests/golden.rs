@@ -0,0 +1,127 @@
+//! Golden image checks of the filters and pipelines, against the images in `tests/golden`.
+//! After an intended change in output, rewrite them with `UPDATE_GOLDEN=1 cargo test`.
+
+use std::path::Path;
+
+use ndarray::array;
+use rust_image_processing::arithmetic::{image_sub, linear_blend};
+use rust_image_processing::grading::split_tone;
+use rust_image_processing::greyscale::{greyscale, Greyscale};
+use rust_image_processing::stylize::emboss;
+use rust_image_processing::testing::{test_image, Golden, Tolerance};
+use rust_image_processing::vignette::vignette;
+use rust_image_processing::warp::{rotate, warp_perspective, Homography};
+use rust_image_processing::{
+    adaptive_sharpen, adjust_gamma, apply_matrix, bilateral_filter, bilinear_filter, edge_detect, gaussian_blur, median_filter, sharpen, x_grad, y_grad, Error,
+    ImageBuffer, Pipeline,
+};
+
+const SEED: u64 = 7;
+
+/// Goldens allowing a difference of one in a channel, for float rounding on other platforms
+fn golden() -> Golden {
+    Golden::new(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden")).with_tolerance(Tolerance::exact().with_channel(1))
+}
+
+fn input() -> ImageBuffer {
+    test_image(64, 48, SEED)
+}
+
+#[test]
+fn test_image_is_deterministic() {
+    assert_eq!(test_image(64, 48, SEED), input());
+    golden().with_tolerance(Tolerance::exact()).assert("test_image", &input());
+}
+
+#[test]
+fn smoothing_filters() {
+    let golden = golden();
+
+    golden.assert("gaussian_blur", &gaussian_blur(&input()));
+    golden.assert("bilinear_filter", &bilinear_filter(&input()));
+    golden.assert("median_filter", &median_filter(&input(), 2));
+    golden.assert("bilateral_filter", &bilateral_filter(&input(), 3., 30.));
+}
+
+#[test]
+fn sharpening_filters() {
+    let golden = golden();
+
+    golden.assert("sharpen", &sharpen(&input(), 0.8));
+    golden.assert("adaptive_sharpen", &adaptive_sharpen(&input(), 1.5, 1., 0.2));
+}
+
+#[test]
+fn gradient_filters() {
+    let golden = golden();
+
+    golden.assert("edge_detect", &edge_detect(&input()));
+    golden.assert("x_grad", &x_grad(&input()));
+    golden.assert("y_grad", &y_grad(&input()));
+    golden.assert("apply_matrix", &apply_matrix(&input(), array![[1., 2., 1.], [0., 0., 0.], [-1., -2., -1.]]));
+}
+
+#[test]
+fn colour_operations() {
+    let golden = golden();
+
+    golden.assert("adjust_gamma", &adjust_gamma(&input(), 2.2));
+    golden.assert("greyscale", &greyscale(&input(), Greyscale::Rec709));
+    golden.assert("split_tone", &split_tone(&input(), 220., 40., 0.5));
+    golden.assert("vignette", &vignette(&input(), 0.6, 2.));
+}
+
+#[test]
+fn two_image_operations() {
+    let golden = golden();
+    let other = test_image(64, 48, SEED + 1);
+
+    golden.assert("linear_blend", &linear_blend(&input(), &other, 0.3));
+    golden.assert("image_sub", &image_sub(&input(), &other));
+}
+
+#[test]
+fn warps() {
+    let golden = golden();
+    let homography = Homography::from_points(&[(0., 0.), (63., 0.), (63., 47.), (0., 47.)], &[(6., 3.), (58., 0.), (63., 47.), (0., 40.)]).unwrap();
+
+    golden.assert("rotate", &rotate(&input(), 30.));
+    golden.assert("warp_perspective", &warp_perspective(&input(), &homography, 64, 48));
+    golden.assert("emboss", &emboss(&input(), 45., 1.));
+}
+
+#[test]
+fn pipelines() {
+    let golden = golden();
+    let edges = Pipeline::from_file(concat!(env!("CARGO_MANIFEST_DIR"), "/pipelines/edges.toml")).unwrap();
+    let graded = Pipeline::new().adjust_brightness(15).adjust_contrast(1.2).adjust_gamma(0.9).gaussian_blur().sharpen(0.5);
+
+    golden.assert("pipeline_edges", &edges.run(&input()));
+    golden.assert("pipeline_graded", &graded.run(&input()));
+}
+
+#[test]
+fn differences_beyond_the_tolerance_fail() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    let golden = Golden::new(&dir).with_update(false).with_tolerance(Tolerance::exact().with_channel(2).with_differing_pixels(1));
+    std::fs::remove_file(golden.path("check")).ok();
+
+    let expected = input();
+    golden.assert("check", &expected);
+    assert!(golden.path("check").exists());
+
+    let mut changed = expected.clone();
+    changed.get_pixel_mut(0, 0).0[0] ^= 0xff;
+    changed.get_pixel_mut(1, 0).0[1] = changed.get_pixel(1, 0)[1].wrapping_add(2);
+    assert!(golden.check("check", &changed).unwrap().passed);
+
+    changed.get_pixel_mut(2, 0).0[2] ^= 0xff;
+    assert!(matches!(golden.check("check", &changed), Err(Error::Golden(_))));
+    assert!(dir.join("check.actual.png").exists() && dir.join("check.diff.png").exists());
+
+    assert!(matches!(golden.check("check", &test_image(8, 8, SEED)), Err(Error::DimensionMismatch { .. })));
+
+    // A pass clears the images left by the failure
+    golden.assert("check", &expected);
+    assert!(!dir.join("check.actual.png").exists() && !dir.join("check.diff.png").exists());
+}
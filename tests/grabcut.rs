@@ -0,0 +1,73 @@
+use rust_image_processing::grabcut::{apply_matte, grab_cut, grab_cut_mask};
+use rust_image_processing::plane::Plane;
+use rust_image_processing::{ImageBuffer, Rect};
+
+const SUBJECT: Rect = Rect { x: 10, y: 8, width: 12, height: 9 };
+
+/// A red subject on a mottled green background
+fn photo() -> ImageBuffer {
+    ImageBuffer::from_fn(32, 24, |x, y| {
+        let noise = ((x * 37 + y * 91) % 17) as u8;
+        if SUBJECT.contains(x, y) {
+            image::Rgba([200 + noise, 30 + noise, 40, 255])
+        } else {
+            image::Rgba([40 + noise, 150 + 2 * noise, 60, 255])
+        }
+    })
+}
+
+/// Fraction of pixels labelled as in or out of the subject correctly
+fn accuracy(matte: &Plane) -> f32 {
+    let correct = (0..matte.height).flat_map(|y| (0..matte.width).map(move |x| (x, y))).filter(|&(x, y)| (matte.get(x, y) > 0.5) == SUBJECT.contains(x, y)).count();
+    correct as f32 / matte.data.len() as f32
+}
+
+#[test]
+fn rectangles_cut_out_the_subject() {
+    let matte = grab_cut(&photo(), Rect::new(5, 4, 22, 17), 5);
+
+    assert_eq!((matte.width, matte.height), (32, 24));
+    assert!(matte.data.iter().all(|&v| v == 0. || v == 1.));
+    assert!(accuracy(&matte) > 0.98, "accuracy {}", accuracy(&matte));
+    // Outside the rectangle is always background
+    assert!((0..32).all(|x| matte.get(x, 0) == 0. && matte.get(x, 23) == 0.));
+}
+
+#[test]
+fn strokes_seed_the_cut() {
+    let mut seeds = Plane::new(32, 24);
+    for y in 0..24 {
+        for x in 0..32 {
+            seeds.set(x, y, 0.4);
+        }
+    }
+    // A white stroke through the subject and a black one across the top
+    for x in 12..20 {
+        seeds.set(x, 12, 1.);
+    }
+    for x in 0..32 {
+        seeds.set(x, 1, 0.);
+    }
+
+    let matte = grab_cut_mask(&photo(), &seeds, 5);
+    assert!((12..20).all(|x| matte.get(x, 12) == 1.));
+    assert!((0..32).all(|x| matte.get(x, 1) == 0.));
+    assert!(accuracy(&matte) > 0.98, "accuracy {}", accuracy(&matte));
+}
+
+#[test]
+fn rectangles_off_the_image_leave_only_background() {
+    let matte = grab_cut(&photo(), Rect::new(100, 100, 10, 10), 3);
+    assert!(matte.data.iter().all(|&v| v == 0.));
+}
+
+#[test]
+fn mattes_scale_the_alpha() {
+    let input = ImageBuffer::from_fn(3, 1, |x, _| image::Rgba([10, 20, 30, 100 + 50 * x as u8]));
+    let mut matte = Plane::new(3, 1);
+    matte.set(1, 0, 0.5);
+    matte.set(2, 0, 2.);
+
+    let output = apply_matte(&input, &matte);
+    assert_eq!(output.pixels().map(|p| p.0).collect::<Vec<_>>(), vec![[10, 20, 30, 0], [10, 20, 30, 75], [10, 20, 30, 200]]);
+}
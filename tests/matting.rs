@@ -0,0 +1,67 @@
+use rust_image_processing::matting::{closed_form_matte, trimap_from_matte};
+use rust_image_processing::plane::Plane;
+use rust_image_processing::ImageBuffer;
+
+const WIDTH: u32 = 24;
+
+/// True alpha of the soft edge: background up to x 7, foreground from 16, a ramp between
+fn alpha(x: u32) -> f32 {
+    ((x as f32 - 7.) / 9.).clamp(0., 1.)
+}
+
+/// A red subject over a blue background, mixed by `alpha` along a soft vertical edge. The
+/// colours differ by multiples of 9 so the mixes are whole, as rounding noise would be fitted.
+fn photo() -> ImageBuffer {
+    ImageBuffer::from_fn(WIDTH, 8, |x, _| {
+        let a = alpha(x);
+        let mix = |foreground: f32, background: f32| (a * foreground + (1. - a) * background).round() as u8;
+        image::Rgba([mix(200., 20.), mix(20., 110.), mix(20., 200.), 255])
+    })
+}
+
+fn trimap() -> Plane {
+    let mut trimap = Plane::new(WIDTH, 8);
+    for y in 0..8 {
+        for x in 0..WIDTH {
+            trimap.set(x, y, if x <= 6 { 0. } else if x >= 17 { 1. } else { 0.5 });
+        }
+    }
+    trimap
+}
+
+#[test]
+fn soft_edges_follow_the_colours() {
+    let matte = closed_form_matte(&photo(), &trimap(), 500);
+
+    for y in 0..8 {
+        for x in 0..WIDTH {
+            assert!((matte.get(x, y) - alpha(x)).abs() < 0.05, "alpha at {}, {} is {}, not {}", x, y, matte.get(x, y), alpha(x));
+        }
+    }
+}
+
+#[test]
+fn known_pixels_are_kept() {
+    let matte = closed_form_matte(&photo(), &trimap(), 0);
+
+    for x in 0..WIDTH {
+        let expected = if x <= 6 { 0. } else if x >= 17 { 1. } else { 0.5 };
+        assert_eq!(matte.get(x, 3), expected);
+    }
+}
+
+#[test]
+fn trimaps_band_the_edges_of_hard_mattes() {
+    let mut matte = Plane::new(WIDTH, 8);
+    for y in 0..8 {
+        for x in 12..WIDTH {
+            matte.set(x, y, 0.9);
+        }
+    }
+
+    let trimap = trimap_from_matte(&matte, 2);
+    let row: Vec<f32> = (0..WIDTH).map(|x| trimap.get(x, 4)).collect();
+    assert!(row[..9].iter().all(|&v| v == 0.), "{:?}", row);
+    assert!(row[10..14].iter().all(|&v| v == 0.5), "{:?}", row);
+    assert!(row[15..].iter().all(|&v| v == 1.), "{:?}", row);
+}
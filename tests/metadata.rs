@@ -0,0 +1,199 @@
+use rust_image_processing::io::{encode_image, from_bytes};
+use rust_image_processing::metadata::exif::{self, Exif, ExifValue, Orientation};
+use rust_image_processing::metadata::icc::{Curve, IccProfile};
+use rust_image_processing::metadata::Metadata;
+use rust_image_processing::testing::test_image;
+use rust_image_processing::{OutputFormat, OutputOptions};
+
+/// Little endian exif data with an orientation and a camera make
+fn exif_data(orientation: u16) -> Vec<u8> {
+    let mut data = b"II*\0".to_vec();
+    data.extend_from_slice(&8u32.to_le_bytes());
+
+    data.extend_from_slice(&2u16.to_le_bytes());
+    for (tag, kind, count, value) in [(exif::ORIENTATION, 3u16, 1u32, orientation as u32), (exif::MAKE, 2, 6, 38)] {
+        data.extend_from_slice(&tag.to_le_bytes());
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(&count.to_le_bytes());
+        data.extend_from_slice(&value.to_le_bytes());
+    }
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(b"Canon\0");
+
+    data
+}
+
+fn s15_fixed16(value: f32) -> [u8; 4] {
+    ((value * 65536.).round() as i32).to_be_bytes()
+}
+
+/// A version 2 sRGB matrix profile, its tone curves as the sRGB parametric curve
+fn srgb_profile() -> Vec<u8> {
+    let colorants = [[0.4361, 0.2225, 0.0139], [0.3851, 0.7169, 0.0971], [0.1431, 0.0606, 0.7141]];
+    let xyz = |[x, y, z]: [f32; 3]| [b"XYZ \0\0\0\0".as_slice(), &s15_fixed16(x), &s15_fixed16(y), &s15_fixed16(z)].concat();
+    let mut curve = b"para\0\0\0\0\0\x03\0\0".to_vec();
+    for parameter in [2.4, 1. / 1.055, 0.055 / 1.055, 1. / 12.92, 0.04045] {
+        curve.extend_from_slice(&s15_fixed16(parameter));
+    }
+    let description = [b"desc\0\0\0\0".as_slice(), &5u32.to_be_bytes(), b"sRGB\0"].concat();
+
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"rXYZ", xyz(colorants[0])),
+        (b"gXYZ", xyz(colorants[1])),
+        (b"bXYZ", xyz(colorants[2])),
+        (b"rTRC", curve.clone()),
+        (b"gTRC", curve.clone()),
+        (b"bTRC", curve),
+        (b"desc", description),
+    ];
+    profile(&tags)
+}
+
+/// An rgb display profile with the given tags after its header and tag table
+fn profile(tags: &[(&[u8; 4], Vec<u8>)]) -> Vec<u8> {
+    let mut header = vec![0; 128];
+    header[12..16].copy_from_slice(b"mntr");
+    header[16..20].copy_from_slice(b"RGB ");
+    header[20..24].copy_from_slice(b"XYZ ");
+    header[36..40].copy_from_slice(b"acsp");
+
+    let mut table = (tags.len() as u32).to_be_bytes().to_vec();
+    let mut data = Vec::new();
+    let mut offset = 128 + 4 + tags.len() * 12;
+    for (signature, tag) in tags {
+        table.extend_from_slice(*signature);
+        table.extend_from_slice(&(offset as u32).to_be_bytes());
+        table.extend_from_slice(&(tag.len() as u32).to_be_bytes());
+        data.extend_from_slice(tag);
+        offset += tag.len();
+    }
+
+    let mut profile = [header, table, data].concat();
+    let size = profile.len() as u32;
+    profile[0..4].copy_from_slice(&size.to_be_bytes());
+    profile
+}
+
+fn metadata() -> Metadata {
+    Metadata {
+        exif: Some(exif_data(6)),
+        icc_profile: Some(srgb_profile()),
+        text: vec![("Comment".to_string(), "processed".to_string())],
+    }
+}
+
+#[test]
+fn exif_parses_entries() {
+    let exif = Exif::parse(&exif_data(6)).unwrap();
+
+    assert_eq!(exif.entries().len(), 2);
+    assert_eq!(exif.orientation(), Orientation::Rotate90);
+    assert_eq!(exif.get(exif::MAKE), Some(&ExifValue::Ascii("Canon".to_string())));
+    assert_eq!(exif.get(exif::MODEL), None);
+}
+
+#[test]
+fn exif_rejects_malformed_data() {
+    assert!(Exif::parse(b"").is_err());
+    assert!(Exif::parse(b"XX*\0\x08\0\0\0").is_err());
+    for len in [4, 10, 20, 37] {
+        assert!(Exif::parse(&exif_data(6)[..len]).is_err(), "truncated to {} bytes", len);
+    }
+}
+
+#[test]
+fn marking_upright_resets_the_orientation() {
+    let mut metadata = metadata();
+    metadata.mark_upright();
+
+    assert_eq!(metadata.orientation(), Orientation::Normal);
+    let exif = metadata.parse_exif().unwrap();
+    assert_eq!(exif.get(exif::ORIENTATION), Some(&ExifValue::Short(vec![1])));
+    assert_eq!(exif.get(exif::MAKE), Some(&ExifValue::Ascii("Canon".to_string())));
+}
+
+#[test]
+fn marking_truncated_exif_upright_leaves_it() {
+    for len in [0, 8, 13, 19] {
+        let mut metadata = Metadata { exif: Some(exif_data(6)[..len].to_vec()), ..Metadata::default() };
+        metadata.mark_upright();
+        assert_eq!(metadata.exif.as_deref(), Some(&exif_data(6)[..len]));
+    }
+}
+
+#[test]
+fn icc_parses_matrix_profiles() {
+    let profile = IccProfile::parse(&srgb_profile()).unwrap();
+
+    assert_eq!(&profile.colour_space, b"RGB ");
+    assert_eq!(profile.description.as_deref(), Some("sRGB"));
+    assert!(matches!(profile.curves, Some([Curve::Parametric(3, _), _, _])));
+    assert!(profile.is_convertible());
+    assert!(profile.is_srgb());
+}
+
+#[test]
+fn icc_gamma_profiles_are_not_srgb() {
+    let xyz = |[x, y, z]: [f32; 3]| [b"XYZ \0\0\0\0".as_slice(), &s15_fixed16(x), &s15_fixed16(y), &s15_fixed16(z)].concat();
+    let gamma = [b"curv\0\0\0\0".as_slice(), &1u32.to_be_bytes(), &[1, 0]].concat();
+    let tags: Vec<(&[u8; 4], Vec<u8>)> = vec![
+        (b"rXYZ", xyz([0.6097, 0.3111, 0.0195])),
+        (b"gXYZ", xyz([0.2053, 0.6257, 0.0609])),
+        (b"bXYZ", xyz([0.1492, 0.0632, 0.7446])),
+        (b"rTRC", gamma.clone()),
+        (b"gTRC", gamma.clone()),
+        (b"bTRC", gamma),
+    ];
+
+    let profile = IccProfile::parse(&profile(&tags)).unwrap();
+    assert_eq!(profile.curves, Some([Curve::Gamma(1.), Curve::Gamma(1.), Curve::Gamma(1.)]));
+    assert!(profile.is_convertible() && !profile.is_srgb());
+}
+
+#[test]
+fn icc_rejects_malformed_profiles() {
+    assert!(IccProfile::parse(b"").is_err());
+    assert!(IccProfile::parse(&[0; 200]).is_err());
+
+    let mut truncated = srgb_profile();
+    truncated.truncate(200);
+    assert!(IccProfile::parse(&truncated).is_err());
+}
+
+#[test]
+fn icc_short_text_tags_have_no_description() {
+    for description in [b"text".to_vec(), b"text\0\0".to_vec(), b"desc\0\0\0\0\xff\xff\xff\xff".to_vec(), b"mluc".to_vec()] {
+        let profile = IccProfile::parse(&profile(&[(b"desc", description.clone())])).unwrap();
+        assert_eq!(profile.description, None, "{:?}", description);
+    }
+
+    let profile = IccProfile::parse(&profile(&[(b"desc", b"text\0\0\0\0Display\0".to_vec())])).unwrap();
+    assert_eq!(profile.description.as_deref(), Some("Display"));
+}
+
+#[test]
+fn metadata_round_trips_through_png() {
+    let encoded = encode_image(&test_image(16, 12, 1), OutputFormat::Png, &OutputOptions::new(), Some(&metadata())).unwrap();
+    assert_eq!(Metadata::from_bytes(&encoded), metadata());
+}
+
+#[test]
+fn metadata_round_trips_through_jpeg() {
+    let encoded = encode_image(&test_image(16, 12, 1), OutputFormat::Jpeg, &OutputOptions::new(), Some(&metadata())).unwrap();
+    assert_eq!(Metadata::from_bytes(&encoded), metadata());
+}
+
+#[test]
+fn metadata_is_dropped_from_other_formats() {
+    let encoded = encode_image(&test_image(16, 12, 1), OutputFormat::Bmp, &OutputOptions::new(), Some(&metadata())).unwrap();
+    assert!(Metadata::from_bytes(&encoded).is_empty());
+}
+
+#[test]
+fn loading_turns_images_upright() {
+    let input = test_image(16, 12, 1);
+    let encoded = encode_image(&input, OutputFormat::Png, &OutputOptions::new(), Some(&metadata())).unwrap();
+
+    // Orientation 6 is turned 90 degrees clockwise, and the sRGB profile needs no conversion
+    assert_eq!(from_bytes(&encoded).unwrap(), image::imageops::rotate90(&input));
+}
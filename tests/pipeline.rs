@@ -0,0 +1,128 @@
+use std::path::Path;
+
+use ndarray::array;
+use rust_image_processing::greyscale::Greyscale;
+use rust_image_processing::pipeline::{PipelineFormat, Plan, Stage};
+use rust_image_processing::testing::test_image;
+use rust_image_processing::{CancellationToken, Error, Pipeline, Progress};
+
+/// Point operations around and between neighbourhood ones, so the plan has fused runs to check
+fn mixed() -> Pipeline {
+    Pipeline::new()
+        .adjust_brightness(20)
+        .adjust_contrast(1.3)
+        .adjust_gamma(0.8)
+        .median_filter(1)
+        .adjust_brightness(-35)
+        .adjust_gamma(1.6)
+        .sharpen(0.5)
+        .adjust_contrast(0.7)
+}
+
+/// Neighbourhood operations only, each reading a different distance around its pixel
+fn neighbourhood() -> Pipeline {
+    Pipeline::new()
+        .gaussian_blur()
+        .median_filter(2)
+        .sharpen(0.8)
+        .apply_matrix(array![[0., -1., 0.], [-1., 5., -1.], [0., -1., 0.]])
+        .greyscale(Greyscale::Rec709)
+        .edge_detect()
+}
+
+#[test]
+fn plan_fuses_adjacent_point_operations() {
+    let pipeline = mixed();
+    let plan = Plan::new(pipeline.operations());
+
+    let shape: Vec<usize> = plan
+        .stages()
+        .iter()
+        .map(|stage| match stage {
+            Stage::Fused { operations, .. } => operations.len(),
+            Stage::Single(_) => 1,
+        })
+        .collect();
+    assert_eq!(shape, [3, 1, 2, 1, 1]);
+}
+
+#[test]
+fn fused_plan_matches_unfused_pipeline() {
+    let input = test_image(96, 64, 3);
+
+    for pipeline in [mixed(), Pipeline::new().adjust_brightness(300).adjust_brightness(-300), Pipeline::new().adjust_gamma(0.5).adjust_contrast(2.)] {
+        let unfused = pipeline.run_with(&input, |_, _| {});
+        assert_eq!(pipeline.run(&input), unfused, "{:?}", pipeline.operations());
+    }
+}
+
+#[test]
+fn tiled_matches_whole_image() {
+    let input = test_image(45, 37, 5);
+
+    for pipeline in [mixed(), neighbourhood()] {
+        let whole = pipeline.run(&input);
+        for tile_size in [7, 16, 200] {
+            assert_eq!(pipeline.run_tiled(&input, tile_size), whole, "tile size {}", tile_size);
+        }
+    }
+}
+
+#[test]
+fn streamed_matches_whole_image() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("streaming");
+    std::fs::create_dir_all(&dir).unwrap();
+    let (input_path, output_path) = (dir.join("input.png"), dir.join("output.png"));
+
+    let input = test_image(40, 29, 9);
+    input.save(&input_path).unwrap();
+
+    for pipeline in [mixed(), neighbourhood()] {
+        let whole = pipeline.run(&input);
+        for strip_height in [1, 8, 100] {
+            pipeline.run_file_streaming(&input_path, &output_path, strip_height, &Progress::none()).unwrap();
+            let streamed = image::open(&output_path).unwrap().to_rgba8();
+            assert_eq!(streamed, whole, "strip height {}", strip_height);
+        }
+    }
+}
+
+#[test]
+fn zero_tile_and_strip_sizes_are_rejected() {
+    let input = test_image(20, 16, 4);
+    let pipeline = neighbourhood();
+
+    assert_eq!(pipeline.run_tiled(&input, 0), pipeline.run(&input));
+    assert!(matches!(pipeline.run_tiled_with_progress(&input, 0, &Progress::none()), Err(Error::InvalidParameter { .. })));
+
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("zero_strips");
+    std::fs::create_dir_all(&dir).unwrap();
+    input.save(dir.join("input.png")).unwrap();
+    let result = pipeline.run_file_streaming(&dir.join("input.png"), &dir.join("output.png"), 0, &Progress::none());
+    assert!(matches!(result, Err(Error::InvalidParameter { .. })));
+}
+
+#[test]
+fn cancelled_tiling_stops() {
+    let cancellation = CancellationToken::new();
+    cancellation.cancel();
+    let progress = Progress::none().with_cancellation(cancellation);
+
+    let result = neighbourhood().run_tiled_with_progress(&test_image(32, 32, 1), 8, &progress);
+    assert!(matches!(result, Err(Error::Cancelled)));
+}
+
+#[test]
+fn negative_median_window_is_rejected() {
+    let result = Pipeline::parse(r#"{"steps": [{"op": "median_filter", "window": -1}]}"#, PipelineFormat::Json);
+    assert!(result.is_err());
+}
+
+#[test]
+fn median_window_of_zero_leaves_the_image() {
+    let input = test_image(12, 9, 2);
+
+    assert_eq!(Pipeline::new().median_filter(0).run(&input), input);
+    // Windows larger than the image read its clamped edges
+    assert_eq!(Pipeline::new().median_filter(40).run(&input).dimensions(), (12, 9));
+}
@@ -0,0 +1,195 @@
+#![cfg(feature = "raw")]
+
+use std::path::Path;
+
+use rust_image_processing::raw::{is_raw_path, open_raw, BayerPattern, Demosaic, DevelopOptions, RawImage, WhiteBalance};
+use rust_image_processing::Error;
+
+const WIDTH: usize = 8;
+const HEIGHT: usize = 6;
+const BLACK: u16 = 64;
+/// A range of 1024 above the black level, so the camera values below are exact
+const WHITE: u16 = 1088;
+
+/// Linear camera rgb of a flat scene, green twice red and four times blue
+const SCENE: [f32; 3] = [0.25, 0.5, 0.125];
+
+/// The samples a sensor with the pattern reads of the flat scene
+fn mosaic(pattern: BayerPattern) -> Vec<u16> {
+    (0..WIDTH * HEIGHT).map(|i| BLACK + (SCENE[pattern.colour_at(i % WIDTH, i / WIDTH)] * (WHITE - BLACK) as f32) as u16).collect()
+}
+
+fn raw(pattern: BayerPattern) -> RawImage {
+    RawImage {
+        width: WIDTH,
+        height: HEIGHT,
+        data: mosaic(pattern),
+        pattern,
+        black_level: BLACK as f32,
+        white_level: WHITE as f32,
+        as_shot_white_balance: None,
+    }
+}
+
+/// A little endian dng of the flat scene, its raw data in the first directory, with the given
+/// compression and `CFAPattern`
+fn dng(compression: u16, cfa: [u8; 4]) -> Vec<u8> {
+    let samples: Vec<u8> = mosaic(BayerPattern::Rggb).iter().flat_map(|v| v.to_le_bytes()).collect();
+    let rational = |values: &[(u32, u32)]| values.iter().flat_map(|(n, d)| [n.to_le_bytes(), d.to_le_bytes()].concat()).collect::<Vec<u8>>();
+
+    // The neutral is the camera's reading of white, the inverse of the multipliers
+    let mut entries: Vec<(u16, u16, u32, Vec<u8>)> = vec![
+        (256, 3, 1, (WIDTH as u16).to_le_bytes().to_vec()),
+        (257, 3, 1, (HEIGHT as u16).to_le_bytes().to_vec()),
+        (258, 3, 1, 16u16.to_le_bytes().to_vec()),
+        (259, 3, 1, compression.to_le_bytes().to_vec()),
+        (262, 3, 1, 32803u16.to_le_bytes().to_vec()),
+        (273, 4, 1, Vec::new()),
+        (279, 4, 1, (samples.len() as u32).to_le_bytes().to_vec()),
+        (33422, 1, 4, cfa.to_vec()),
+        (50714, 3, 1, BLACK.to_le_bytes().to_vec()),
+        (50717, 3, 1, WHITE.to_le_bytes().to_vec()),
+        (50728, 5, 3, rational(&[(1, 2), (1, 1), (1, 4)])),
+    ];
+
+    let directory_size = 2 + entries.len() * 12 + 4;
+    let mut extra = Vec::new();
+    let mut values = Vec::new();
+    for (_, _, _, payload) in &entries {
+        match payload.len() > 4 {
+            true => {
+                values.push(((8 + directory_size + extra.len()) as u32).to_le_bytes().to_vec());
+                extra.extend_from_slice(payload);
+            }
+            false => values.push([payload.as_slice(), &[0; 4][payload.len()..]].concat()),
+        }
+    }
+    let strip_offset = (8 + directory_size + extra.len()) as u32;
+    entries[5].3 = strip_offset.to_le_bytes().to_vec();
+    values[5] = entries[5].3.clone();
+
+    let mut data = b"II*\0".to_vec();
+    data.extend_from_slice(&8u32.to_le_bytes());
+    data.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    for ((tag, kind, count, _), value) in entries.iter().zip(&values) {
+        data.extend_from_slice(&tag.to_le_bytes());
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(&count.to_le_bytes());
+        data.extend_from_slice(value);
+    }
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&extra);
+    data.extend_from_slice(&samples);
+
+    data
+}
+
+fn assert_flat(image: &rust_image_processing::ImageBuffer, expected: [u8; 3]) {
+    for (x, y, pixel) in image.enumerate_pixels() {
+        assert_eq!(pixel.0, [expected[0], expected[1], expected[2], 255], "pixel {}, {}", x, y);
+    }
+}
+
+#[test]
+fn patterns_are_found_by_name() {
+    assert_eq!(BayerPattern::from_name("RGGB"), Some(BayerPattern::Rggb));
+    assert_eq!(BayerPattern::from_name("gbrg"), Some(BayerPattern::Gbrg));
+    assert_eq!(BayerPattern::from_name("rgb"), None);
+
+    let block = |pattern: BayerPattern| [(0, 0), (1, 0), (0, 1), (1, 1)].map(|(x, y)| pattern.colour_at(x, y));
+    assert_eq!(block(BayerPattern::Rggb), [0, 1, 1, 2]);
+    assert_eq!(block(BayerPattern::Bggr), [2, 1, 1, 0]);
+    assert_eq!(block(BayerPattern::Grbg), [1, 0, 2, 1]);
+    assert_eq!(block(BayerPattern::Gbrg), [1, 2, 0, 1]);
+    assert_eq!(BayerPattern::Grbg.colour_at(5, 3), BayerPattern::Grbg.colour_at(1, 1));
+}
+
+#[test]
+fn flat_scenes_develop_to_their_colour() {
+    let options = DevelopOptions::new().with_white_balance(WhiteBalance::Custom([1., 1., 1.])).with_gamma(1.);
+
+    for pattern in [BayerPattern::Rggb, BayerPattern::Bggr, BayerPattern::Grbg, BayerPattern::Gbrg] {
+        for demosaic in [Demosaic::Bilinear, Demosaic::Malvar] {
+            // 0.25, 0.5 and 0.125 of 255, rounded
+            assert_flat(&raw(pattern).develop(&options.clone().with_demosaic(demosaic)), [64, 128, 32]);
+        }
+    }
+}
+
+#[test]
+fn white_balance_scales_each_colour() {
+    let raw = raw(BayerPattern::Rggb);
+    let linear = DevelopOptions::new().with_gamma(1.);
+
+    assert_flat(&raw.develop(&linear.clone().with_white_balance(WhiteBalance::Custom([2., 1., 4.]))), [128, 128, 128]);
+    assert_flat(&raw.develop(&linear.clone().with_white_balance(WhiteBalance::GreyWorld)), [128, 128, 128]);
+
+    // Without a recorded white balance, as shot falls back to grey world
+    assert_flat(&raw.develop(&linear.with_white_balance(WhiteBalance::AsShot)), [128, 128, 128]);
+}
+
+#[test]
+fn gamma_brightens_the_linear_values() {
+    let options = DevelopOptions::new().with_white_balance(WhiteBalance::Custom([2., 1., 4.]));
+
+    // 0.5 to the power of 1 / 2.2
+    assert_flat(&raw(BayerPattern::Rggb).develop(&options), [186, 186, 186]);
+}
+
+#[test]
+fn samples_outside_the_levels_are_clipped() {
+    let mut raw = raw(BayerPattern::Rggb);
+    raw.data = (0..WIDTH * HEIGHT).map(|i| if i % 2 == 0 { 0 } else { u16::MAX }).collect();
+
+    let options = DevelopOptions::new().with_white_balance(WhiteBalance::Custom([1., 1., 1.])).with_demosaic(Demosaic::Bilinear);
+    for (x, y, pixel) in raw.develop(&options).enumerate_pixels() {
+        let own = BayerPattern::Rggb.colour_at(x as usize, y as usize);
+        assert_eq!(pixel[own], if x % 2 == 0 { 0 } else { 255 });
+    }
+}
+
+#[test]
+fn dng_raw_data_is_read() {
+    let raw = RawImage::from_dng(&dng(1, [0, 1, 1, 2])).unwrap();
+
+    assert_eq!((raw.width, raw.height), (WIDTH, HEIGHT));
+    assert_eq!(raw.data, mosaic(BayerPattern::Rggb));
+    assert_eq!(raw.pattern, BayerPattern::Rggb);
+    assert_eq!((raw.black_level, raw.white_level), (BLACK as f32, WHITE as f32));
+    assert_eq!(raw.as_shot_white_balance, Some([2., 1., 4.]));
+
+    assert_flat(&raw.develop(&DevelopOptions::new().with_gamma(1.)), [128, 128, 128]);
+}
+
+#[test]
+fn unsupported_dng_files_are_rejected() {
+    assert!(matches!(RawImage::from_dng(&dng(7, [0, 1, 1, 2])), Err(Error::UnsupportedFormat(_))));
+    assert!(matches!(RawImage::from_dng(&dng(1, [0, 1, 2, 3])), Err(Error::UnsupportedFormat(_))));
+    assert!(RawImage::from_dng(b"not a tiff").is_err());
+
+    let whole = dng(1, [0, 1, 1, 2]);
+    assert!(matches!(RawImage::from_dng(&whole[..whole.len() - 10]), Err(Error::Codec(_))));
+}
+
+#[test]
+fn raw_files_are_opened_by_extension() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("raw");
+    std::fs::create_dir_all(&dir).unwrap();
+
+    assert!(is_raw_path(Path::new("photo.DNG")) && !is_raw_path(Path::new("photo.png")) && !is_raw_path(Path::new("dng")));
+
+    std::fs::write(dir.join("flat.dng"), dng(1, [0, 1, 1, 2])).unwrap();
+    assert_flat(&open_raw(dir.join("flat.dng")).unwrap(), [186, 186, 186]);
+
+    // Greyscale mosaics use their full range and are taken to be rggb
+    let samples: Vec<u16> = mosaic(BayerPattern::Rggb).iter().map(|v| (v - BLACK) * 64).collect();
+    image::ImageBuffer::<image::Luma<u16>, _>::from_raw(WIDTH as u32, HEIGHT as u32, samples.clone()).unwrap().save(dir.join("mosaic16.png")).unwrap();
+    let raw = RawImage::open(dir.join("mosaic16.png")).unwrap();
+    assert_eq!((raw.data, raw.pattern, raw.white_level), (samples, BayerPattern::Rggb, 65535.));
+
+    let eight_bit: Vec<u8> = mosaic(BayerPattern::Rggb).iter().map(|v| ((v - BLACK) / 4) as u8).collect();
+    image::GrayImage::from_raw(WIDTH as u32, HEIGHT as u32, eight_bit.clone()).unwrap().save(dir.join("mosaic8.png")).unwrap();
+    let raw = RawImage::open_mosaic(dir.join("mosaic8.png"), BayerPattern::Bggr).unwrap();
+    assert_eq!(raw.data, eight_bit.iter().map(|&v| v as u16).collect::<Vec<_>>());
+    assert_eq!((raw.pattern, raw.white_level), (BayerPattern::Bggr, 255.));
+}
@@ -0,0 +1,65 @@
+use std::fmt::Debug;
+use std::path::PathBuf;
+
+use rust_image_processing::barcode::FinderPattern;
+use rust_image_processing::features::Corner;
+use rust_image_processing::records::{load_records, save_records, Record};
+use rust_image_processing::suppression::{Detection, Peak};
+use rust_image_processing::value::binary;
+use rust_image_processing::{Error, Rect};
+
+fn path(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("records");
+    std::fs::create_dir_all(&dir).unwrap();
+    dir.join(name)
+}
+
+/// Save and load records as json and in the binary encoding
+fn assert_round_trips<T: Record + Debug + PartialEq>(name: &str, records: &[T]) {
+    for extension in ["json", "bin"] {
+        let path = path(&format!("{}.{}", name, extension));
+        save_records(records, &path).unwrap();
+        assert_eq!(load_records::<T>(&path).unwrap(), records, "{}", path.display());
+    }
+}
+
+#[test]
+fn records_round_trip() {
+    assert_round_trips("corners", &[Corner { x: 1.5, y: 2., response: 0.25 }, Corner { x: 0., y: 7., response: 3. }]);
+    assert_round_trips("peaks", &[Peak { x: 3, y: 9, value: 0.5 }]);
+    assert_round_trips("detections", &[Detection { rect: Rect::new(1, 2, 30, 40), score: 0.75 }]);
+    assert_round_trips("rects", &[Rect::new(0, 0, 1, 1), Rect::new(5, 6, 7, 8)]);
+    assert_round_trips("outlines", &[[(0., 0.), (10.5, 0.), (10., 12.), (0., 11.)]]);
+    assert_round_trips("finders", &[FinderPattern { x: 12.5, y: 8., module_size: 3. }]);
+    assert_round_trips::<Corner>("empty", &[]);
+}
+
+#[test]
+fn files_are_json_or_binary_by_extension() {
+    let corners = [Corner { x: 1., y: 2., response: 3. }];
+
+    save_records(&corners, path("corners.JSON")).unwrap();
+    assert!(std::fs::read_to_string(path("corners.JSON")).unwrap().starts_with('['));
+    save_records(&corners, path("corners.dat")).unwrap();
+    assert!(std::fs::read(path("corners.dat")).unwrap().starts_with(binary::MAGIC));
+}
+
+#[test]
+fn malformed_records_are_errors() {
+    let load = |name: &str, contents: &[u8]| {
+        std::fs::write(path(name), contents).unwrap();
+        load_records::<Corner>(path(name))
+    };
+
+    assert!(matches!(load("table.json", br#"{"x": 1}"#), Err(Error::InvalidParameter { .. })));
+    assert!(matches!(load("missing.json", br#"[{"x": 1, "y": 2}]"#), Err(Error::MissingParameter { parameter, .. }) if parameter == "response"));
+    assert!(matches!(load("text.json", br#"[{"x": "1", "y": 2, "response": 3}]"#), Err(Error::InvalidParameter { parameter, .. }) if parameter == "x"));
+    assert!(matches!(load("utf8.json", b"[\xff]"), Err(Error::Codec(_))));
+    assert!(matches!(load("truncated.bin", &binary::MAGIC[..]), Err(Error::Codec(_))));
+    assert!(load_records::<Corner>(path("absent.json")).is_err());
+
+    std::fs::write(path("rects.json"), b"[[1, 2, 3], [1, 2, -3, 4]]").unwrap();
+    assert!(matches!(load_records::<Rect>(path("rects.json")), Err(Error::InvalidParameter { .. })));
+    std::fs::write(path("outline.json"), b"[[[0, 0], [1, 0], [1, 1]]]").unwrap();
+    assert!(matches!(load_records::<[(f64, f64); 4]>(path("outline.json")), Err(Error::InvalidParameter { .. })));
+}
@@ -0,0 +1,176 @@
+//! The `serve` command, driven over tcp against the built binary
+
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rust_image_processing::io::{from_bytes, to_bytes, OutputFormat};
+use rust_image_processing::pipeline::PipelineFormat;
+use rust_image_processing::testing::test_image;
+use rust_image_processing::Pipeline;
+
+const EDGES: &str = r#"[{"op": "gaussian_blur"}, {"op": "edge_detect"}]"#;
+
+/// A running server, stopped when dropped
+struct Server {
+    child: Child,
+    address: String,
+}
+
+impl Server {
+    fn start(options: &[&str]) -> Server {
+        // A port the system has just handed out, so very likely still free
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string();
+        let child = Command::new(env!("CARGO_BIN_EXE_clionRustTest"))
+            .args(["serve", "--quiet", "--address", &address])
+            .args(options)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .unwrap();
+
+        let started = Instant::now();
+        while TcpStream::connect(&address).is_err() {
+            assert!(started.elapsed() < Duration::from_secs(10), "server didn't start listening on {}", address);
+            thread::sleep(Duration::from_millis(20));
+        }
+
+        Server { child, address }
+    }
+
+    /// Send a request, giving the response's status and body
+    fn send(&self, head: &str, body: &[u8]) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect(&self.address).unwrap();
+        stream.write_all(head.as_bytes()).unwrap();
+        stream.write_all(body).unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response.windows(4).position(|window| window == b"\r\n\r\n").expect("response has no end of headers");
+        let status = std::str::from_utf8(&response[..split]).unwrap().split_whitespace().nth(1).unwrap().parse().unwrap();
+
+        (status, response[split + 4..].to_vec())
+    }
+
+    fn process(&self, target: &str, headers: &str, body: &[u8]) -> (u16, Vec<u8>) {
+        self.send(&format!("POST {} HTTP/1.1\r\nHost: test\r\n{}Content-Length: {}\r\n\r\n", target, headers, body.len()), body)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+    }
+}
+
+fn png() -> Vec<u8> {
+    to_bytes(&test_image(24, 16, 3), OutputFormat::Png).unwrap()
+}
+
+fn text(body: &[u8]) -> &str {
+    std::str::from_utf8(body).unwrap().trim_end()
+}
+
+#[test]
+fn health_answers_ok() {
+    let server = Server::start(&[]);
+
+    assert_eq!(server.send("GET /health HTTP/1.1\r\n\r\n", b""), (200, b"ok\n".to_vec()));
+    assert_eq!(server.send("POST /health HTTP/1.1\r\nContent-Length: 0\r\n\r\n", b"").0, 405);
+    assert_eq!(server.send("GET /process HTTP/1.1\r\n\r\n", b"").0, 405);
+    assert_eq!(server.send("GET /elsewhere HTTP/1.1\r\n\r\n", b"").0, 404);
+}
+
+#[test]
+fn process_runs_the_pipeline() {
+    let server = Server::start(&[]);
+    let expected = Pipeline::parse(EDGES, PipelineFormat::Json).unwrap().run(&test_image(24, 16, 3));
+
+    let (status, body) = server.process("/process", &format!("X-Pipeline: {}\r\n", EDGES), &png());
+    assert_eq!(status, 200);
+    assert_eq!(from_bytes(&body).unwrap(), expected);
+
+    let query = "pipeline=%5B%7B%22op%22%3A+%22gaussian_blur%22%7D%2C+%7B%22op%22%3A+%22edge_detect%22%7D%5D";
+    let (status, body) = server.process(&format!("/process?{}", query), "", &png());
+    assert_eq!(status, 200);
+    assert_eq!(from_bytes(&body).unwrap(), expected);
+}
+
+#[test]
+fn bad_requests_are_rejected() {
+    let server = Server::start(&[]);
+    let pipeline = format!("X-Pipeline: {}\r\n", EDGES);
+
+    let (status, body) = server.process("/process", "", &png());
+    assert_eq!(status, 400);
+    assert!(text(&body).starts_with("missing pipeline"), "{}", text(&body));
+
+    let (status, body) = server.process("/process", "X-Pipeline: [{\"op\": \"unknown\"}]\r\n", &png());
+    assert_eq!(status, 400);
+    assert!(text(&body).starts_with("invalid pipeline"), "{}", text(&body));
+
+    let (status, body) = server.process("/process", &pipeline, b"not an image");
+    assert_eq!(status, 400);
+    assert!(text(&body).starts_with("invalid image"), "{}", text(&body));
+
+    assert_eq!(server.send("POST /process HTTP/1.1\r\n\r\n", b"").0, 411);
+    assert_eq!(server.send("POST /process HTTP/1.1\r\nContent-Length: many\r\n\r\n", b"").0, 400);
+    assert_eq!(server.send("POST /process HTTP/1.1\r\nno colon\r\n\r\n", b"").0, 400);
+    assert_eq!(server.send("nonsense\r\n\r\n", b"").0, 400);
+}
+
+#[test]
+fn expensive_pipelines_are_rejected() {
+    let server = Server::start(&[]);
+
+    let too_expensive = [
+        r#"[{"op": "median_filter", "window": 9}]"#.to_string(),
+        r#"[{"op": "adaptive_sharpen", "amount": 1, "radius": 17}]"#.to_string(),
+        format!("[{}]", vec![r#"{"op": "gaussian_blur"}"#; 33].join(", ")),
+    ];
+    for pipeline in &too_expensive {
+        let (status, body) = server.process("/process", &format!("X-Pipeline: {}\r\n", pipeline), &png());
+        assert_eq!(status, 400, "{}", pipeline);
+        assert!(text(&body).starts_with("pipeline is too expensive"), "{}", text(&body));
+    }
+
+    let long = format!("X-Pipeline: [{}]\r\n", vec![r#"{"op": "gaussian_blur"}"#; 400].join(", "));
+    assert_eq!(server.process("/process", &long, &png()).0, 413);
+}
+
+#[test]
+fn over_sized_bodies_are_rejected() {
+    let server = Server::start(&["--max-body-bytes", "1024"]);
+
+    // Refused from the content length, before the body is read
+    let (status, body) = server.send("POST /process HTTP/1.1\r\nX-Pipeline: [{\"op\": \"edge_detect\"}]\r\nContent-Length: 1025\r\n\r\n", b"");
+    assert_eq!(status, 413);
+    assert!(text(&body).contains("1024 byte limit"), "{}", text(&body));
+}
+
+#[test]
+fn connections_beyond_the_limit_are_refused() {
+    let server = Server::start(&["--max-connections", "1", "--timeout", "2"]);
+    // Letting go of the slot taken by the check that the server was listening
+    thread::sleep(Duration::from_millis(200));
+
+    // Holding the only connection open, without finishing its request
+    let mut held = TcpStream::connect(&server.address).unwrap();
+    held.write_all(b"GET /health HTTP/1.1\r\n").unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    // Sending nothing, as the refusal closes the connection without reading the request
+    assert_eq!(server.send("", b"").0, 503);
+
+    held.write_all(b"\r\n").unwrap();
+    let mut response = String::new();
+    held.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200"), "{}", response);
+
+    // The slot is given back once the held connection closes
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(server.send("GET /health HTTP/1.1\r\n\r\n", b"").0, 200);
+}
@@ -0,0 +1,105 @@
+use rust_image_processing::plane::Plane;
+use rust_image_processing::records::Record;
+use rust_image_processing::segmentation::connected_components;
+use rust_image_processing::shape::{convex_hull, describe_regions, polygon_area, thin, ShapeDescriptors};
+use rust_image_processing::Rect;
+
+/// A mask set where `inside` is
+fn mask(width: u32, height: u32, inside: impl Fn(u32, u32) -> bool) -> Plane {
+    let mut mask = Plane::new(width, height);
+    for y in 0..height {
+        for x in 0..width {
+            if inside(x, y) {
+                mask.set(x, y, 1.);
+            }
+        }
+    }
+    mask
+}
+
+fn describe(mask: &Plane) -> Vec<ShapeDescriptors> {
+    describe_regions(&connected_components(mask))
+}
+
+#[test]
+fn rectangles_are_measured() {
+    let shapes = describe(&mask(12, 10, |x, y| (2..8).contains(&x) && (3..7).contains(&y)));
+    assert_eq!(shapes.len(), 1);
+    let shape = &shapes[0];
+
+    assert_eq!(shape.label, 1);
+    assert_eq!(shape.area, 24.);
+    assert_eq!(shape.centroid, (4.5, 4.5));
+    assert_eq!(shape.bounding_box, Rect::new(2, 3, 6, 4));
+    assert_eq!(shape.perimeter, 16.);
+    assert_eq!(shape.contour.len(), 16);
+    assert_eq!(&shape.contour[..3], &[(2, 3), (3, 3), (4, 3)]);
+    assert_eq!(shape.convex_area, 24.);
+    assert_eq!(shape.solidity, 1.);
+    assert!(shape.orientation.abs() < 1e-9, "longer across, {}", shape.orientation);
+    assert!(shape.eccentricity > 0.5 && shape.eccentricity < 1.);
+}
+
+#[test]
+fn discs_are_round_and_dents_lower_the_solidity() {
+    let disc = &describe(&mask(21, 21, |x, y| (x as f64 - 10.).hypot(y as f64 - 10.) <= 8.))[0];
+    assert!(disc.circularity > 0.85 && disc.circularity <= 1.1, "circularity {}", disc.circularity);
+    assert!(disc.eccentricity < 0.1, "eccentricity {}", disc.eccentricity);
+    assert!(disc.solidity > 0.85, "solidity {}", disc.solidity);
+
+    let l_shape = &describe(&mask(12, 12, |x, y| (1..11).contains(&x) && (1..11).contains(&y) && (x < 4 || y > 7)))[0];
+    assert!(l_shape.solidity < 0.7, "solidity {}", l_shape.solidity);
+}
+
+#[test]
+fn hu_moments_are_unchanged_by_moving_and_turning() {
+    let shape = |x0: u32, y0: u32| move |x: u32, y: u32| x >= x0 && y >= y0 && x < x0 + 9 && y < y0 + 9 && (y - y0 < 3 || x - x0 < 3);
+    let a = &describe(&mask(20, 20, shape(1, 2)))[0];
+    let moved = &describe(&mask(20, 20, shape(8, 9)))[0];
+    // The same shape turned a quarter
+    let turned = &describe(&mask(20, 20, |x, y| shape(1, 2)(y, 19 - x)))[0];
+
+    for other in [moved, turned] {
+        for (p, q) in a.hu_moments.iter().zip(&other.hu_moments) {
+            assert!((p - q).abs() <= 1e-9 * p.abs() + 1e-15, "{:?} and {:?}", a.hu_moments, other.hu_moments);
+        }
+    }
+    assert!((a.orientation - turned.orientation).abs() > 1.);
+}
+
+#[test]
+fn separate_regions_are_described_in_label_order() {
+    let shapes = describe(&mask(20, 8, |x, y| (1..4).contains(&x) && (1..4).contains(&y) || (10..18).contains(&x) && (2..7).contains(&y)));
+    assert_eq!(shapes.iter().map(|s| (s.label, s.area)).collect::<Vec<_>>(), vec![(1, 9.), (2, 40.)]);
+    assert!(describe(&Plane::new(5, 5)).is_empty());
+}
+
+#[test]
+fn thinning_leaves_lines_one_pixel_wide() {
+    let skeleton = thin(&mask(20, 9, |x, y| (2..18).contains(&x) && (2..7).contains(&y)));
+
+    for x in 0..20 {
+        let column = (0..9).filter(|&y| skeleton.get(x, y) == 1.).count();
+        assert!(column <= 1, "column {} is {} wide", x, column);
+    }
+    assert!((5..15).all(|x| skeleton.get(x, 4) == 1.), "{:?}", skeleton.data);
+    assert!(skeleton.data.iter().all(|&v| v == 0. || v == 1.));
+}
+
+#[test]
+fn hulls_and_areas_of_points() {
+    let points = vec![(0., 0.), (4., 0.), (2., 1.), (4., 3.), (1., 2.), (0., 3.), (4., 0.)];
+    let hull = convex_hull(points);
+
+    assert_eq!(hull.len(), 4);
+    assert!(hull.iter().all(|p| [(0., 0.), (4., 0.), (4., 3.), (0., 3.)].contains(p)), "{:?}", hull);
+    assert_eq!(polygon_area(&hull), 12.);
+    assert_eq!(polygon_area(&[(0., 0.), (2., 0.), (0., 2.)]), 2.);
+    assert_eq!(convex_hull(vec![(1., 1.), (1., 1.)]), vec![(1., 1.)]);
+}
+
+#[test]
+fn descriptors_round_trip_as_records() {
+    let shape = describe(&mask(10, 10, |x, y| (x + y) < 8))[0].clone();
+    assert_eq!(ShapeDescriptors::from_value(&shape.to_value()).unwrap(), shape);
+}
@@ -0,0 +1,70 @@
+use rust_image_processing::arithmetic::adjust_brightness;
+use rust_image_processing::plane::Plane;
+use rust_image_processing::stereo::{colorize_disparity, depth_from_disparity, disparity_map, MatchCost, StereoOptions, INVALID_DISPARITY};
+use rust_image_processing::testing::test_image;
+use rust_image_processing::ImageBuffer;
+
+const SHIFT: u32 = 5;
+
+/// A textured left view and the right view of a flat scene at a disparity of `SHIFT` pixels
+fn pair() -> (ImageBuffer, ImageBuffer) {
+    let scene = test_image(48 + SHIFT, 20, 3);
+    let left = image::imageops::crop_imm(&scene, 0, 0, 48, 20).to_image();
+    let right = image::imageops::crop_imm(&scene, SHIFT, 0, 48, 20).to_image();
+    (left, right)
+}
+
+/// Fraction of the pixels away from the edges given the shift
+fn fraction_matched(disparities: &Plane) -> f32 {
+    let margin = SHIFT + 4;
+    let pixels: Vec<f32> = (4..disparities.height - 4).flat_map(|y| (margin..disparities.width - 4).map(move |x| (x, y))).map(|(x, y)| disparities.get(x, y)).collect();
+    pixels.iter().filter(|&&disparity| (disparity - SHIFT as f32).abs() < 0.5).count() as f32 / pixels.len() as f32
+}
+
+#[test]
+fn shifted_views_match_at_the_shift() {
+    let (left, right) = pair();
+
+    for cost in [MatchCost::Sad, MatchCost::Census] {
+        let options = StereoOptions::new().with_max_disparity(12).with_block_radius(2).with_cost(cost);
+        let disparities = disparity_map(&left, &right, &options);
+        assert_eq!((disparities.width, disparities.height), left.dimensions());
+        assert!(fraction_matched(&disparities) > 0.9, "{:?} matched {}", cost, fraction_matched(&disparities));
+    }
+}
+
+#[test]
+fn census_ignores_exposure_differences() {
+    let (left, right) = pair();
+    let options = StereoOptions::new().with_max_disparity(12).with_block_radius(2);
+
+    let disparities = disparity_map(&left, &adjust_brightness(&right, 30), &options);
+    assert!(fraction_matched(&disparities) > 0.75, "matched {}", fraction_matched(&disparities));
+}
+
+#[test]
+fn featureless_views_have_no_reliable_match() {
+    let flat = ImageBuffer::from_pixel(24, 12, image::Rgba([90, 90, 90, 255]));
+    let disparities = disparity_map(&flat, &flat, &StereoOptions::new().with_max_disparity(8));
+
+    assert!(disparities.data.iter().all(|&disparity| disparity == INVALID_DISPARITY || disparity == 0.), "{:?}", disparities.data);
+}
+
+#[test]
+fn depths_and_colours_of_disparities() {
+    let mut disparities = Plane::new(3, 1);
+    disparities.set(0, 0, 4.);
+    disparities.set(1, 0, INVALID_DISPARITY);
+    disparities.set(2, 0, 1.);
+
+    let depths = depth_from_disparity(&disparities, 800., 0.1);
+    assert_eq!(depths.data, vec![20., 0., 80.]);
+
+    let colours = colorize_disparity(&disparities, 4.);
+    assert_eq!(*colours.get_pixel(1, 0), image::Rgba([0, 0, 0, 255]));
+    assert!(colours.get_pixel(0, 0)[0] > colours.get_pixel(0, 0)[2], "near is red");
+    assert!(colours.get_pixel(2, 0)[2] > colours.get_pixel(2, 0)[0], "far is blue");
+
+    assert_eq!(MatchCost::from_name("census"), Some(MatchCost::Census));
+    assert_eq!(MatchCost::from_name("ncc"), None);
+}
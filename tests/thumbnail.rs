@@ -0,0 +1,35 @@
+use rust_image_processing::plane::Plane;
+use rust_image_processing::saliency::Saliency;
+use rust_image_processing::testing::test_image;
+use rust_image_processing::thumbnail::{salient_crop, thumbnail};
+use rust_image_processing::{ImageBuffer, Rect};
+
+#[test]
+fn crop_keeps_the_salient_region() {
+    let mut saliency = Plane::new(40, 10);
+    saliency.set(33, 4, 1.);
+
+    assert_eq!(salient_crop(&saliency, 1.), Rect::new(24, 0, 10, 10));
+    assert_eq!(salient_crop(&Plane::new(40, 10), 1.), Rect::new(15, 0, 10, 10));
+    assert_eq!(salient_crop(&Plane::new(10, 40), 2.), Rect::new(0, 17, 10, 5));
+}
+
+#[test]
+fn empty_images_give_empty_crops() {
+    for (width, height) in [(0, 0), (0, 5), (5, 0)] {
+        assert_eq!(salient_crop(&Plane::new(width, height), 1.5), Rect::new(0, 0, width, height));
+
+        let output = thumbnail(&ImageBuffer::new(width, height), 8, 6, Saliency::Edges);
+        assert_eq!(output, ImageBuffer::new(8, 6));
+    }
+    assert_eq!(thumbnail(&test_image(16, 12, 1), 0, 6, Saliency::Edges).dimensions(), (0, 6));
+}
+
+#[test]
+fn thumbnails_are_the_size_asked_for() {
+    let input = test_image(48, 32, 1);
+    for saliency in [Saliency::Edges, Saliency::SpectralResidual] {
+        assert_eq!(thumbnail(&input, 12, 12, saliency).dimensions(), (12, 12));
+        assert_eq!(thumbnail(&input, 20, 5, saliency).dimensions(), (20, 5));
+    }
+}
@@ -0,0 +1,172 @@
+use rust_image_processing::pipeline::PipelineFormat;
+use rust_image_processing::value::{binary, json, toml, yaml, Value, MAX_DEPTH};
+use rust_image_processing::{Error, Operation, Pipeline};
+
+fn table(entries: &[(&str, Value)]) -> Value {
+    Value::Table(entries.iter().map(|(key, value)| (key.to_string(), value.clone())).collect())
+}
+
+/// The document every format's version of the edges pipeline should parse to
+fn edges() -> Value {
+    let steps = vec![
+        table(&[("op", Value::String("median_filter".to_string())), ("window", Value::Number(1.))]),
+        table(&[("op", Value::String("edge_detect".to_string()))]),
+    ];
+    table(&[("steps", Value::Array(steps))])
+}
+
+#[test]
+fn json_parses_values() {
+    let value = json::parse(r#"{"a": [1, -2.5e1, true, false, null], "b": {"c": "d\n\u00e9"}}"#).unwrap();
+
+    let expected = table(&[
+        ("a", Value::Array(vec![Value::Number(1.), Value::Number(-25.), Value::Bool(true), Value::Bool(false), Value::Null])),
+        ("b", table(&[("c", Value::String("d\né".to_string()))])),
+    ]);
+    assert_eq!(value, expected);
+}
+
+#[test]
+fn json_round_trips() {
+    let value = edges();
+    assert_eq!(json::parse(&json::to_string(&value)).unwrap(), value);
+}
+
+#[test]
+fn binary_round_trips() {
+    let value = edges();
+    assert_eq!(binary::from_bytes(&binary::to_bytes(&value)).unwrap(), value);
+}
+
+#[test]
+fn toml_parses_array_of_tables() {
+    let source = "# comment\n[[steps]]\nop = \"median_filter\"\nwindow = 1\n\n[[steps]]\nop = 'edge_detect'\n";
+    assert_eq!(toml::parse(source).unwrap(), edges());
+}
+
+#[test]
+fn toml_parses_inline_values() {
+    let value = toml::parse("[a.b]\nlist = [1, 2, [3]]\ninline = { x = true }\n").unwrap();
+
+    let expected = table(&[(
+        "a",
+        table(&[(
+            "b",
+            table(&[
+                ("list", Value::Array(vec![Value::Number(1.), Value::Number(2.), Value::Array(vec![Value::Number(3.)])])),
+                ("inline", table(&[("x", Value::Bool(true))])),
+            ]),
+        )]),
+    )]);
+    assert_eq!(value, expected);
+}
+
+#[test]
+fn yaml_parses_block_sequences() {
+    let source = "# comment\nsteps:\n  - op: median_filter\n    window: 1\n  - op: \"edge_detect\"\n";
+    assert_eq!(yaml::parse(source).unwrap(), edges());
+}
+
+#[test]
+fn yaml_parses_flow_sequences() {
+    let value = yaml::parse("matrix: [[1, 2], [3, 4]]\n").unwrap();
+
+    let row = |a: f64, b: f64| Value::Array(vec![Value::Number(a), Value::Number(b)]);
+    assert_eq!(value, table(&[("matrix", Value::Array(vec![row(1., 2.), row(3., 4.)]))]));
+}
+
+#[test]
+fn formats_parse_to_the_same_pipeline() {
+    let json = Pipeline::parse(r#"{"steps": [{"op": "median_filter", "window": 1}, {"op": "edge_detect"}]}"#, PipelineFormat::Json).unwrap();
+    let toml = Pipeline::from_file("pipelines/edges.toml").unwrap();
+    let yaml = Pipeline::parse("steps:\n  - op: median_filter\n    window: 1\n  - op: edge_detect\n", PipelineFormat::Yaml).unwrap();
+
+    assert_eq!(json.operations(), [Operation::MedianFilter(1), Operation::EdgeDetect]);
+    assert_eq!(toml.operations(), json.operations());
+    assert_eq!(yaml.operations(), json.operations());
+}
+
+#[test]
+fn malformed_documents_are_parse_errors() {
+    for source in ["", "{", "[1, 2", r#"{"a" 1}"#, r#"{"a": 1,}"#, "[1] 2", r#""unterminated"#, "tru", r#""\x""#] {
+        assert!(matches!(json::parse(source), Err(Error::Parse { .. })), "json {:?}", source);
+    }
+    for source in ["[steps", "key", "key = ", "key = [1, 2", "key = \"open", "a = 1\na = 2"] {
+        assert!(matches!(toml::parse(source), Err(Error::Parse { .. })), "toml {:?}", source);
+    }
+    for source in ["key: [1, 2", "key: \"open", "- a\nb: c"] {
+        assert!(matches!(yaml::parse(source), Err(Error::Parse { .. })), "yaml {:?}", source);
+    }
+}
+
+#[test]
+fn errors_give_the_line() {
+    match json::parse("{\n\"a\": 1,\n\"b\": }") {
+        Err(Error::Parse { line, .. }) => assert_eq!(line, 3),
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn nesting_up_to_the_limit_parses() {
+    let json = format!("{}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+    assert!(json::parse(&json).is_ok());
+
+    let toml = format!("a = {}{}", "[".repeat(MAX_DEPTH), "]".repeat(MAX_DEPTH));
+    assert!(toml::parse(&toml).is_ok());
+
+    let yaml: String = (0..MAX_DEPTH - 1).map(|depth| format!("{}a:\n", "  ".repeat(depth))).chain([format!("{}a: 1\n", "  ".repeat(MAX_DEPTH - 1))]).collect();
+    assert!(yaml::parse(&yaml).is_ok());
+}
+
+#[test]
+fn deep_nesting_is_a_parse_error() {
+    for depth in [MAX_DEPTH + 1, 100_000] {
+        let json = format!("{}{}", "[".repeat(depth), "]".repeat(depth));
+        assert!(matches!(json::parse(&json), Err(Error::Parse { .. })), "json depth {}", depth);
+
+        let json = "{\"a\": ".repeat(depth);
+        assert!(matches!(json::parse(&json), Err(Error::Parse { .. })), "json objects depth {}", depth);
+
+        let toml = format!("a = {}", "[".repeat(depth));
+        assert!(matches!(toml::parse(&toml), Err(Error::Parse { .. })), "toml depth {}", depth);
+
+        let toml = format!("a = {}", "{ b = ".repeat(depth));
+        assert!(matches!(toml::parse(&toml), Err(Error::Parse { .. })), "toml tables depth {}", depth);
+
+        let yaml = format!("a: {}", "[".repeat(depth));
+        assert!(matches!(yaml::parse(&yaml), Err(Error::Parse { .. })), "yaml flow depth {}", depth);
+    }
+
+    let yaml: String = (0..MAX_DEPTH + 1).map(|depth| format!("{}a:\n", "  ".repeat(depth))).collect();
+    assert!(matches!(yaml::parse(&yaml), Err(Error::Parse { .. })));
+
+    let yaml: String = (0..MAX_DEPTH + 1).map(|depth| format!("{}-\n", "  ".repeat(depth))).collect();
+    assert!(matches!(yaml::parse(&yaml), Err(Error::Parse { .. })));
+}
+
+#[test]
+fn deeply_nested_binary_is_an_error() {
+    let nested = |depth: usize| (0..depth).fold(Value::Null, |value, _| Value::Array(vec![value]));
+    assert_eq!(binary::from_bytes(&binary::to_bytes(&nested(MAX_DEPTH))).unwrap(), nested(MAX_DEPTH));
+    assert!(matches!(binary::from_bytes(&binary::to_bytes(&nested(MAX_DEPTH + 1))), Err(Error::Codec(_))));
+
+    // A million one element arrays, built as bytes as dropping the value would recurse as deep
+    let mut bytes = binary::MAGIC.to_vec();
+    for _ in 0..1_000_000 {
+        bytes.push(5);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+    }
+    bytes.push(0);
+    assert!(matches!(binary::from_bytes(&bytes), Err(Error::Codec(_))));
+
+    let mut bytes = binary::MAGIC.to_vec();
+    for _ in 0..1_000_000 {
+        bytes.push(6);
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.push(b'a');
+    }
+    bytes.push(0);
+    assert!(matches!(binary::from_bytes(&bytes), Err(Error::Codec(_))));
+}
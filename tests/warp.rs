@@ -0,0 +1,69 @@
+use rust_image_processing::warp::{warp_perspective, Homography};
+use rust_image_processing::ImageBuffer;
+
+const SQUARE: [(f64, f64); 4] = [(0., 0.), (1., 0.), (1., 1.), (0., 1.)];
+
+fn assert_maps(homography: &Homography, from: &[(f64, f64)], to: &[(f64, f64)]) {
+    for (&(x, y), &(u, v)) in from.iter().zip(to) {
+        let (mapped_x, mapped_y) = homography.apply(x, y);
+        assert!((mapped_x - u).abs() < 1e-6 && (mapped_y - v).abs() < 1e-6, "({}, {}) went to ({}, {}), not ({}, {})", x, y, mapped_x, mapped_y, u, v);
+    }
+}
+
+fn assert_matrix(homography: &Homography, expected: [[f64; 3]; 3]) {
+    let close = homography.matrix.iter().flatten().zip(expected.iter().flatten()).all(|(value, expected)| (value - expected).abs() < 1e-6);
+    assert!(close, "{:?} is not {:?}", homography.matrix, expected);
+}
+
+#[test]
+fn identical_points_give_the_identity() {
+    let homography = Homography::from_points(&SQUARE, &SQUARE).unwrap();
+    assert_matrix(&homography, Homography::identity().matrix);
+}
+
+#[test]
+fn affine_correspondences_are_recovered() {
+    // Scale by 2 and 3, then translate by (5, -4)
+    let to: Vec<(f64, f64)> = SQUARE.iter().map(|&(x, y)| (2. * x + 5., 3. * y - 4.)).collect();
+
+    let homography = Homography::from_points(&SQUARE, &to).unwrap();
+    assert_matrix(&homography, [[2., 0., 5.], [0., 3., -4.], [0., 0., 1.]]);
+}
+
+#[test]
+fn perspective_correspondences_are_recovered() {
+    let expected = Homography { matrix: [[1.2, 0.1, 30.], [-0.2, 0.9, 12.], [0.0005, 0.001, 1.]] };
+    let from = [(0., 0.), (640., 0.), (640., 480.), (0., 480.), (320., 240.), (100., 400.)];
+    let to: Vec<(f64, f64)> = from.iter().map(|&(x, y)| expected.apply(x, y)).collect();
+
+    // Exactly four points, and more than four fitted by least squares
+    for count in [4, 6] {
+        let homography = Homography::from_points(&from[..count], &to[..count]).unwrap();
+        assert_matrix(&homography, expected.matrix);
+        assert_maps(&homography, &from, &to);
+    }
+}
+
+#[test]
+fn inverse_maps_back() {
+    let to = [(10., 20.), (110., 5.), (120., 90.), (0., 100.)];
+    let homography = Homography::from_points(&SQUARE, &to).unwrap();
+
+    assert_maps(&homography.inverse().unwrap(), &to, &SQUARE);
+    assert_maps(&Homography::from_points(&to, &SQUARE).unwrap(), &to, &SQUARE);
+}
+
+#[test]
+fn too_few_or_degenerate_points_are_rejected() {
+    assert!(Homography::from_points(&SQUARE[..3], &SQUARE[..3]).is_none());
+    assert!(Homography::from_points(&SQUARE, &SQUARE[..3]).is_none());
+
+    let line = [(0., 0.), (1., 1.), (2., 2.), (3., 3.)];
+    assert!(Homography::from_points(&line, &SQUARE).is_none());
+}
+
+#[test]
+fn identity_warp_leaves_the_image() {
+    let input = ImageBuffer::from_fn(9, 7, |x, y| image::Rgba([(x * 28) as u8, (y * 36) as u8, 90, 255]));
+    assert_eq!(warp_perspective(&input, &Homography::identity(), 9, 7), input);
+}
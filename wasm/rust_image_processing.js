@@ -0,0 +1,57 @@
+// Javascript side of src/wasm.rs, running pipelines over canvas ImageData in the browser.
+//
+//     const processor = await ImageProcessor.load("rust_image_processing.wasm");
+//     const image = context.getImageData(0, 0, canvas.width, canvas.height);
+//     processor.process(image, [{ op: "gaussian_blur" }, { op: "edge_detect" }]);
+//     context.putImageData(image, 0, 0);
+//
+// The module exports plain functions rather than wasm-bindgen bindings. Buffers come from
+// rip_alloc(len) and must go back through rip_free(ptr, len) with the very same len, as the
+// allocation is rebuilt from it; a different len corrupts the module's heap.
+
+export class ImageProcessor {
+    constructor(instance) {
+        this.exports = instance.exports;
+    }
+
+    static async load(url) {
+        const { instance } = await WebAssembly.instantiateStreaming(fetch(url));
+        return new ImageProcessor(instance);
+    }
+
+    // Run a pipeline, given as the steps array or its json, over the ImageData in place
+    process(imageData, pipeline) {
+        const json = typeof pipeline === "string" ? pipeline : JSON.stringify(pipeline);
+        const pipelineBytes = new TextEncoder().encode(json);
+        const pixels = imageData.data;
+
+        const dataPtr = this.exports.rip_alloc(pixels.length);
+        const pipelinePtr = this.exports.rip_alloc(pipelineBytes.length);
+        try {
+            this.bytes(dataPtr, pixels.length).set(pixels);
+            this.bytes(pipelinePtr, pipelineBytes.length).set(pipelineBytes);
+
+            const status = this.exports.rip_process_rgba(
+                dataPtr, imageData.width, imageData.height, pipelinePtr, pipelineBytes.length);
+            if (status !== 0) {
+                throw new Error(this.lastError());
+            }
+
+            pixels.set(this.bytes(dataPtr, pixels.length));
+        } finally {
+            this.exports.rip_free(dataPtr, pixels.length);
+            this.exports.rip_free(pipelinePtr, pipelineBytes.length);
+        }
+    }
+
+    lastError() {
+        const ptr = this.exports.rip_last_error_ptr();
+        const len = this.exports.rip_last_error_len();
+        return new TextDecoder().decode(this.bytes(ptr, len));
+    }
+
+    // A fresh view each time, since memory.buffer is replaced when the module's memory grows
+    bytes(ptr, len) {
+        return new Uint8Array(this.exports.memory.buffer, ptr, len);
+    }
+}